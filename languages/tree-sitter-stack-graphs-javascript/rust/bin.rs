@@ -9,9 +9,11 @@ use anyhow::anyhow;
 use clap::Parser;
 use tree_sitter_stack_graphs::cli::database::default_user_database_path_for_crate;
 use tree_sitter_stack_graphs::cli::provided_languages::Subcommands;
+use tree_sitter_stack_graphs::cli::util::init_logger;
 use tree_sitter_stack_graphs::NoCancellation;
 
 fn main() -> anyhow::Result<()> {
+    init_logger();
     let lc = match tree_sitter_stack_graphs_javascript::try_language_configuration(&NoCancellation)
     {
         Ok(lc) => lc,
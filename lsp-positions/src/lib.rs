@@ -71,6 +71,25 @@ impl Position {
             column: self.column.utf8_offset,
         }
     }
+
+    /// Clamps all of the UTF-8 byte offsets in this position so that they do not point past the
+    /// end of a file of `len` bytes.  This is useful when the position was calculated from a
+    /// different version of the file than the one it is now applied to.
+    ///
+    /// The UTF-16 and grapheme column offsets cannot be recalculated without the file content.
+    /// They are clamped to the UTF-8 column offset, which is always an upper bound for them.
+    pub fn clamp_to(&mut self, len: usize) {
+        self.containing_line.start = self.containing_line.start.min(len);
+        self.containing_line.end = self.containing_line.end.min(len);
+        self.trimmed_line.start = self.trimmed_line.start.min(len);
+        self.trimmed_line.end = self.trimmed_line.end.min(len);
+        let max_column = len - self.containing_line.start;
+        if self.column.utf8_offset > max_column {
+            self.column.utf8_offset = max_column;
+            self.column.utf16_offset = self.column.utf16_offset.min(max_column);
+            self.column.grapheme_offset = self.column.grapheme_offset.min(max_column);
+        }
+    }
 }
 
 impl Ord for Position {
@@ -124,6 +143,13 @@ impl Span {
     pub fn contains_point(&self, point: &tree_sitter::Point) -> bool {
         &self.start <= point && &self.end > point
     }
+
+    /// Clamps both positions of this span to the bounds of a file of `len` bytes.  See
+    /// [`Position::clamp_to`] for details.
+    pub fn clamp_to(&mut self, len: usize) {
+        self.start.clamp_to(len);
+        self.end.clamp_to(len);
+    }
 }
 
 impl Ord for Span {
@@ -209,6 +235,57 @@ impl Offset {
                 result
             })
     }
+
+    /// Adds another offset to this one, field by field.
+    pub fn add(&self, other: &Offset) -> Offset {
+        Offset {
+            utf8_offset: self.utf8_offset + other.utf8_offset,
+            utf16_offset: self.utf16_offset + other.utf16_offset,
+            grapheme_offset: self.grapheme_offset + other.grapheme_offset,
+        }
+    }
+
+    /// Calculates the display column of the character at a UTF-8 offset within a line, as shown
+    /// by an editor that expands tabs.  Each grapheme advances the column by one, except for a tab,
+    /// which advances it to the next multiple of `tab_width`.  A `tab_width` of 0 is treated as 1.
+    /// The UTF-8 offset should be at a grapheme boundary; a grapheme that contains it is counted.
+    pub fn visual_column(line: &str, utf8_offset: usize, tab_width: usize) -> usize {
+        let tab_width = tab_width.max(1);
+        let mut column = 0;
+        for (grapheme_utf8_offset, grapheme) in line.grapheme_indices(true) {
+            if grapheme_utf8_offset >= utf8_offset {
+                break;
+            }
+            if grapheme == "\t" {
+                column += tab_width - column % tab_width;
+            } else {
+                column += 1;
+            }
+        }
+        column
+    }
+
+    /// Subtracts another offset from this one, field by field.  Returns `None` if any of the
+    /// fields would underflow, which indicates that the offsets are inconsistent with each other.
+    pub fn checked_sub(&self, other: &Offset) -> Option<Offset> {
+        Some(Offset {
+            utf8_offset: self.utf8_offset.checked_sub(other.utf8_offset)?,
+            utf16_offset: self.utf16_offset.checked_sub(other.utf16_offset)?,
+            grapheme_offset: self.grapheme_offset.checked_sub(other.grapheme_offset)?,
+        })
+    }
+}
+
+/// Determines how a newline at the end of a string is treated when iterating over its lines.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TrailingNewline {
+    /// A newline at the end of the string terminates the last line, as in [`str::lines`][].  An
+    /// empty string has no lines.
+    #[default]
+    TerminatesLine,
+    /// A newline at the end of the string starts a final, empty line, which is how LSP treats a
+    /// position after the last newline.  An empty string consists of a single empty line.
+    StartsLine,
 }
 
 /// A substring and information about where that substring occurs in a larger string.  (Most often,
@@ -264,15 +341,40 @@ impl<'a> PositionedSubstring<'a> {
         }
     }
 
-    // Returns an iterator over the lines of the given string.
+    /// Returns an iterator over the lines of the given string.  A newline at the end of the
+    /// string terminates the last line; see [`lines_iter_with`][Self::lines_iter_with].
     pub fn lines_iter(string: &'a str) -> impl Iterator<Item = PositionedSubstring<'a>> + 'a {
-        let mut next_utf8_offset = 0;
+        Self::lines_iter_with(string, TrailingNewline::TerminatesLine)
+    }
+
+    /// Returns an iterator over the lines of the given string, where `trailing_newline` determines
+    /// whether a newline at the end of the string (or an empty string) yields a final empty line.
+    pub fn lines_iter_with(
+        string: &'a str,
+        trailing_newline: TrailingNewline,
+    ) -> impl Iterator<Item = PositionedSubstring<'a>> + 'a {
+        // The start of the next line, or None if there are no more lines.
+        let mut next_utf8_offset = Some(0);
         std::iter::from_fn(move || {
-            if string.len() <= next_utf8_offset {
-                return None;
+            let line_utf8_offset = next_utf8_offset?;
+            if line_utf8_offset == string.len() {
+                // We are either at the start of an empty string, or right after a newline that
+                // ends the string.
+                next_utf8_offset = None;
+                return match trailing_newline {
+                    TrailingNewline::TerminatesLine => None,
+                    TrailingNewline::StartsLine => {
+                        Some(PositionedSubstring::from_line(string, line_utf8_offset))
+                    }
+                };
             }
-            let next = PositionedSubstring::from_line(string, next_utf8_offset);
-            next_utf8_offset = next.utf8_bounds.end + 1;
+            let next = PositionedSubstring::from_line(string, line_utf8_offset);
+            // The line is followed by a newline, unless it extends to the end of the string.
+            next_utf8_offset = if next.utf8_bounds.end < string.len() {
+                Some(next.utf8_bounds.end + 1)
+            } else {
+                None
+            };
             Some(next)
         })
     }
@@ -315,29 +417,46 @@ impl<'a> PositionedSubstring<'a> {
 /// Automates the construction of [`Span`][] instances for content within a string.
 pub struct SpanCalculator<'a> {
     string: &'a str,
-    containing_line: Option<PositionedSubstring<'a>>,
-    trimmed_line: Option<PositionedSubstring<'a>>,
+    /// Recently used lines, with the current line last.
+    lines: Vec<CachedLine<'a>>,
+    computed_line_count: usize,
+}
+
+/// The information that we calculate about a line.
+struct CachedLine<'a> {
+    containing_line: PositionedSubstring<'a>,
+    trimmed_line: PositionedSubstring<'a>,
     columns: Vec<Offset>,
 }
 
+/// The number of recently used lines that a [`SpanCalculator`][] keeps.
+const LINE_CACHE_SIZE: usize = 8;
+
 // Note that each time you calculate the position of a node on a _different line_, we have to
 // calculate some information about line.  You'd think that would mean it would be most efficient
 // to use this type if you made to sure group all of your nodes by their rows before asking for us
 // to create Spans for them.  However, it turns out that sorting your nodes to make sure that
 // they're in row order is just as much work as recalculating the UTF16 column offsets if we ever
-// revisit a line!
+// revisit a line!  We do keep a handful of recently used lines around, though, so that the start
+// and end of a node that spans multiple lines don't keep evicting each other.
 
 impl<'a> SpanCalculator<'a> {
     /// Creates a new span calculator for locations within the given string.
     pub fn new(string: &'a str) -> SpanCalculator<'a> {
         SpanCalculator {
             string,
-            containing_line: None,
-            trimmed_line: None,
-            columns: Vec::new(),
+            lines: Vec::with_capacity(LINE_CACHE_SIZE),
+            computed_line_count: 0,
         }
     }
 
+    /// Returns the number of times that information about a line had to be calculated, because
+    /// the line was not among the recently used ones.  This is useful to evaluate how well the
+    /// calculator's cache works for a particular access pattern.
+    pub fn computed_line_count(&self) -> usize {
+        self.computed_line_count
+    }
+
     /// Constructs a [`Position`][] instance for a particular line and column in the string.
     /// You must provide the 0-indexed line number, the byte offset of the line within the string,
     /// and the UTF-8 byte offset of the character within the line.
@@ -348,11 +467,12 @@ impl<'a> SpanCalculator<'a> {
         column_utf8_offset: usize,
     ) -> Position {
         self.replace_current_line(line_utf8_offset);
+        let current_line = self.current_line();
         Position {
             line: line,
             column: *self.for_utf8_offset(column_utf8_offset),
-            containing_line: self.containing_line.as_ref().unwrap().utf8_bounds.clone(),
-            trimmed_line: self.trimmed_line.as_ref().unwrap().utf8_bounds.clone(),
+            containing_line: current_line.containing_line.utf8_bounds.clone(),
+            trimmed_line: current_line.trimmed_line.utf8_bounds.clone(),
         }
     }
 
@@ -387,53 +507,109 @@ impl<'a> SpanCalculator<'a> {
         column_grapheme_offset: usize,
     ) -> Position {
         self.replace_current_line(line_utf8_offset);
+        let current_line = self.current_line();
         Position {
             line: line,
             column: *self.for_grapheme_offset(column_grapheme_offset),
-            containing_line: self.containing_line.as_ref().unwrap().utf8_bounds.clone(),
-            trimmed_line: self.trimmed_line.as_ref().unwrap().utf8_bounds.clone(),
+            containing_line: current_line.containing_line.utf8_bounds.clone(),
+            trimmed_line: current_line.trimmed_line.utf8_bounds.clone(),
         }
     }
 
+    /// Returns the UTF-8 byte offset within the string of an [LSP position][lsp-position], given
+    /// as a 0-indexed line number and a UTF-16 column within that line.  This is the inverse of
+    /// [`for_line_and_column`][Self::for_line_and_column].  A column that falls inside a surrogate
+    /// pair is clamped to the start of that character, and a column past the end of the line is
+    /// clamped to the end of the line.  Returns `None` if the string has no such line.  A trailing
+    /// newline starts an empty last line, which editors allow the cursor to be on.
+    ///
+    /// [lsp-position]: https://microsoft.github.io/language-server-protocol/specification#position
+    pub fn utf8_offset_for_lsp(&mut self, line: usize, utf16_character: usize) -> Option<usize> {
+        let line_utf8_offset =
+            PositionedSubstring::lines_iter_with(self.string, TrailingNewline::StartsLine)
+                .nth(line)?
+                .utf8_bounds
+                .start;
+        self.replace_current_line(line_utf8_offset);
+        let columns = &self.current_line().columns;
+        let index = match columns.binary_search_by_key(&utf16_character, |pos| pos.utf16_offset) {
+            Ok(index) => index,
+            // Not the start of a character, so use the character that contains it (or the end of
+            // the line, which is the last column).
+            Err(index) => index - 1,
+        };
+        Some(line_utf8_offset + columns[index].utf8_offset)
+    }
+
+    /// Returns the display column of a position within the string, where tabs advance to the next
+    /// multiple of `tab_width`.  See [`Offset::visual_column`][] for details.  The code unit
+    /// offsets of the position are not affected.
+    pub fn visual_column(&self, position: &Position, tab_width: usize) -> usize {
+        let line = &self.string[position.containing_line.clone()];
+        Offset::visual_column(line, position.column.utf8_offset, tab_width)
+    }
+
     /// Updates our internal state to represent the information about the line that starts at a
-    /// particular byte offset within the file.
+    /// particular byte offset within the file.  The line's information is reused if it is among
+    /// the recently used lines; otherwise it is calculated, evicting the least recently used line
+    /// if the cache is full.
     fn replace_current_line(&mut self, line_utf8_offset: usize) {
-        if let Some(containing_line) = &self.containing_line {
-            if containing_line.utf8_bounds.start == line_utf8_offset {
-                return;
-            }
+        if let Some(index) = self
+            .lines
+            .iter()
+            .rposition(|line| line.containing_line.utf8_bounds.start == line_utf8_offset)
+        {
+            let line = self.lines.remove(index);
+            self.lines.push(line);
+            return;
         }
         let line = PositionedSubstring::from_line(self.string, line_utf8_offset);
-        self.columns.clear();
-        self.columns.extend(Offset::all_chars(line.content));
+        // reuse the column buffer of the evicted line, if any
+        let mut columns = if self.lines.len() >= LINE_CACHE_SIZE {
+            self.lines.remove(0).columns
+        } else {
+            Vec::new()
+        };
+        columns.clear();
+        columns.extend(Offset::all_chars(line.content));
         let mut trimmed = line.clone();
         trimmed.trim_whitespace();
-        self.containing_line = Some(line);
-        self.trimmed_line = Some(trimmed);
+        self.lines.push(CachedLine {
+            containing_line: line,
+            trimmed_line: trimmed,
+            columns,
+        });
+        self.computed_line_count += 1;
+    }
+
+    /// Returns the information about the current line.
+    /// Assumes that you've already called `replace_current_line` for the containing line.
+    fn current_line(&self) -> &CachedLine<'a> {
+        self.lines.last().unwrap()
     }
 
     /// Returns the offset of the character at a particular UTF-8 offset in the line.
     /// Assumes that you've already called `replace_current_line` for the containing line.
     fn for_utf8_offset(&self, utf8_offset: usize) -> &Offset {
-        let index = self
-            .columns
+        let columns = &self.current_line().columns;
+        let index = columns
             .binary_search_by_key(&utf8_offset, |pos| pos.utf8_offset)
             .unwrap();
-        &self.columns[index]
+        &columns[index]
     }
 
     /// Returns the offset of the character at a particular grapheme offset in the line.
     /// Assumes that you've already called `replace_current_line` for the containing line.
     fn for_grapheme_offset(&self, grapheme_offset: usize) -> &Offset {
-        let mut index = self
-            .columns
+        let columns = &self.current_line().columns;
+        let mut index = columns
             .binary_search_by_key(&grapheme_offset, |pos| pos.grapheme_offset)
             .unwrap();
         // make sure to return the first offset for this grapheme
-        let mut offset = &self.columns[index];
+        let mut offset = &columns[index];
         while index > 0 {
             index -= 1;
-            let prev_offset = &self.columns[index];
+            let prev_offset = &columns[index];
             if prev_offset.grapheme_offset != offset.grapheme_offset {
                 break;
             }
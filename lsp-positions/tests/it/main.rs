@@ -8,6 +8,10 @@
 use unicode_segmentation::UnicodeSegmentation as _;
 
 use lsp_positions::Offset;
+use lsp_positions::PositionedSubstring;
+use lsp_positions::Span;
+use lsp_positions::SpanCalculator;
+use lsp_positions::TrailingNewline;
 
 fn check_offsets(line: &str) {
     let offsets = Offset::all_chars(line).collect::<Vec<_>>();
@@ -47,3 +51,197 @@ fn can_calculate_column_offsets() {
     check_offsets("print '❤️', b, '👨‍👨‍👧', c");
     check_offsets("print '✨✨✨', d");
 }
+
+#[test]
+fn can_add_and_subtract_offsets() {
+    let line = "print '❤️', b";
+    let offsets = Offset::all_chars(line).collect::<Vec<_>>();
+    let start = offsets[6];
+    let end = *offsets.last().unwrap();
+
+    let length = end
+        .checked_sub(&start)
+        .expect("subtraction should not underflow");
+    assert_eq!(length, Offset::string_length(&line[start.utf8_offset..]));
+    assert_eq!(start.add(&length), end);
+}
+
+#[test]
+fn cannot_subtract_larger_offsets() {
+    let smaller = Offset {
+        utf8_offset: 4,
+        utf16_offset: 2,
+        grapheme_offset: 1,
+    };
+    let larger = Offset {
+        utf8_offset: 4,
+        utf16_offset: 3,
+        grapheme_offset: 1,
+    };
+    assert_eq!(smaller.checked_sub(&larger), None);
+    assert_eq!(
+        larger.checked_sub(&smaller).map(|o| o.utf16_offset),
+        Some(1)
+    );
+}
+
+#[test]
+fn can_map_lsp_positions_to_utf8_offsets() {
+    let source = "a = 1\nb = '❤️👍', c\n";
+    let mut calculator = SpanCalculator::new(source);
+
+    // round-trip every character on the second line
+    let line_utf8_offset = source.find('b').unwrap();
+    let line = &source[line_utf8_offset..source.len() - 1];
+    for (column_utf8_offset, _) in line.char_indices() {
+        let position = calculator.for_line_and_column(1, line_utf8_offset, column_utf8_offset);
+        assert_eq!(
+            Some(line_utf8_offset + column_utf8_offset),
+            calculator.utf8_offset_for_lsp(1, position.column.utf16_offset)
+        );
+    }
+
+    // 👍 takes two UTF-16 code units; the second one is clamped to the start of the character
+    let thumbs_utf8_offset = source.find('👍').unwrap();
+    let thumbs_utf16_column = line[..thumbs_utf8_offset - line_utf8_offset]
+        .encode_utf16()
+        .count();
+    assert_eq!(
+        Some(thumbs_utf8_offset),
+        calculator.utf8_offset_for_lsp(1, thumbs_utf16_column + 1)
+    );
+
+    // columns past the end of a line are clamped, lines past the end are rejected
+    assert_eq!(Some(5), calculator.utf8_offset_for_lsp(0, 100));
+    assert_eq!(None, calculator.utf8_offset_for_lsp(3, 0));
+
+    // the trailing newline starts an empty last line
+    assert_eq!(Some(source.len()), calculator.utf8_offset_for_lsp(2, 0));
+    assert_eq!(Some(source.len()), calculator.utf8_offset_for_lsp(2, 10));
+}
+
+#[test]
+fn can_calculate_visual_columns_with_tabs() {
+    assert_eq!(Offset::visual_column("\tx", 1, 4), 4);
+    assert_eq!(Offset::visual_column("ab\tx", 3, 4), 4);
+    assert_eq!(Offset::visual_column("abcd\tx", 5, 4), 8);
+    assert_eq!(Offset::visual_column("\t\tx", 2, 8), 16);
+    assert_eq!(Offset::visual_column("e\u{301}\tx", 4, 4), 4);
+    assert_eq!(Offset::visual_column("\tx", 1, 0), 1);
+
+    let source = "def f():\n\tif x:\n\t\treturn x\n";
+    let mut calculator = SpanCalculator::new(source);
+    let line_utf8_offset = source.find("\t\treturn").unwrap();
+    let position = calculator.for_line_and_column(2, line_utf8_offset, 2);
+    assert_eq!(position.column.utf8_offset, 2);
+    assert_eq!(position.column.utf16_offset, 2);
+    assert_eq!(position.column.grapheme_offset, 2);
+    assert_eq!(calculator.visual_column(&position, 4), 8);
+    assert_eq!(calculator.visual_column(&position, 2), 4);
+    let position = calculator.for_line_and_column(2, line_utf8_offset, 9);
+    assert_eq!(calculator.visual_column(&position, 4), 15);
+}
+
+#[test]
+fn can_clamp_span_to_file_length() {
+    let source = "a = 1\nbb = 22\n";
+    let mut calculator = SpanCalculator::new(source);
+    let line_utf8_offset = source.find("bb").unwrap();
+    let mut span = Span {
+        start: calculator.for_line_and_column(1, line_utf8_offset, 5),
+        end: calculator.for_line_and_column(1, line_utf8_offset, 7),
+    };
+    assert_eq!(span.start.containing_line, 6..13);
+
+    // the file was truncated to "a = 1\nbb ", which ends within the span's line
+    let truncated = &source[..9];
+    span.clamp_to(truncated.len());
+    assert_eq!(span.start.line, 1);
+    assert_eq!(span.start.containing_line, 6..9);
+    assert_eq!(span.start.trimmed_line, 6..9);
+    assert_eq!(span.start.column.utf8_offset, 3);
+    assert_eq!(span.start.column.utf16_offset, 3);
+    assert_eq!(span.start.column.grapheme_offset, 3);
+    assert_eq!(span.end, span.start);
+    assert_eq!(&truncated[span.start.containing_line.clone()], "bb ");
+
+    // spans that are in bounds are unchanged
+    let mut in_bounds = Span {
+        start: calculator.for_line_and_column(0, 0, 0),
+        end: calculator.for_line_and_column(0, 0, 1),
+    };
+    let expected = in_bounds.clone();
+    in_bounds.clamp_to(source.len());
+    assert_eq!(in_bounds, expected);
+}
+
+fn check_lines(source: &str, trailing_newline: TrailingNewline, expected: &[(&str, usize)]) {
+    let lines = PositionedSubstring::lines_iter_with(source, trailing_newline)
+        .map(|line| (line.content, line.utf8_bounds.start))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        expected,
+        lines.as_slice(),
+        "lines of {:?} with {:?}",
+        source,
+        trailing_newline
+    );
+}
+
+#[test]
+fn can_iterate_lines_of_file_without_trailing_newline() {
+    let expected = [("a", 0), ("bc", 2)];
+    check_lines("a\nbc", TrailingNewline::TerminatesLine, &expected);
+    check_lines("a\nbc", TrailingNewline::StartsLine, &expected);
+    check_lines("a\nbc\n", TrailingNewline::TerminatesLine, &expected);
+    check_lines(
+        "a\nbc\n",
+        TrailingNewline::StartsLine,
+        &[("a", 0), ("bc", 2), ("", 5)],
+    );
+    assert_eq!(
+        2,
+        PositionedSubstring::lines_iter("a\nbc").count(),
+        "default handling terminates lines"
+    );
+}
+
+#[test]
+fn can_iterate_lines_of_empty_file() {
+    check_lines("", TrailingNewline::TerminatesLine, &[]);
+    check_lines("", TrailingNewline::StartsLine, &[("", 0)]);
+}
+
+#[test]
+fn can_iterate_lines_of_file_with_only_newlines() {
+    check_lines("\n", TrailingNewline::TerminatesLine, &[("", 0)]);
+    check_lines("\n", TrailingNewline::StartsLine, &[("", 0), ("", 1)]);
+    check_lines(
+        "\n\n\n",
+        TrailingNewline::TerminatesLine,
+        &[("", 0), ("", 1), ("", 2)],
+    );
+    check_lines(
+        "\n\n\n",
+        TrailingNewline::StartsLine,
+        &[("", 0), ("", 1), ("", 2), ("", 3)],
+    );
+}
+
+#[test]
+fn can_reuse_recently_calculated_lines() {
+    let source = "fn f() {\n  let x = '❤️';\n  x\n}\n";
+    let lines = PositionedSubstring::lines_iter(source)
+        .map(|line| line.utf8_bounds.start)
+        .collect::<Vec<_>>();
+
+    // alternate between the start and end lines of nested multi-line spans
+    let mut calculator = SpanCalculator::new(source);
+    let positions = [(0, 0), (3, 1), (1, 2), (2, 3), (0, 3), (3, 0), (1, 17)];
+    for (line, column) in positions {
+        let position = calculator.for_line_and_column(line, lines[line], column);
+        let expected = SpanCalculator::new(source).for_line_and_column(line, lines[line], column);
+        assert_eq!(expected, position);
+    }
+    assert_eq!(4, calculator.computed_line_count());
+}
@@ -188,6 +188,18 @@ impl<T> Arena<T> {
         self.items.truncate(1);
     }
 
+    /// Returns the number of instances that this arena can hold without reallocating.
+    #[inline(always)]
+    pub fn capacity(&self) -> usize {
+        self.items.capacity()
+    }
+
+    /// Shrinks the capacity of this arena as much as possible, while keeping room for at least
+    /// `min_capacity` instances.  Handles remain valid, but pointers returned by `as_ptr` do not.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.items.shrink_to(min_capacity);
+    }
+
     /// Adds a new instance to this arena, returning a stable handle to it.
     ///
     /// Note that we do not deduplicate instances of `T` in any way.  If you add two instances that
@@ -17,6 +17,7 @@ use crate::graph::StackGraph;
 use crate::graph::Symbol;
 use crate::partial::PartialPath;
 use crate::partial::PartialPaths;
+use crate::stitching::unshadowed_paths;
 use crate::stitching::Database;
 use crate::stitching::DatabaseCandidates;
 use crate::stitching::ForwardPartialPathStitcher;
@@ -191,20 +192,13 @@ impl Assertion {
             ForwardPartialPathStitcher::find_all_complete_partial_paths(
                 &mut DatabaseCandidates::new(graph, partials, db),
                 vec![*reference],
-                stitcher_config,
+                stitcher_config.clone(),
                 cancellation_flag,
                 |_, _, p| {
                     reference_paths.push(p.clone());
                 },
             )?;
-            for reference_path in &reference_paths {
-                if reference_paths
-                    .iter()
-                    .all(|other| !other.shadows(partials, reference_path))
-                {
-                    actual_paths.push(reference_path.clone());
-                }
-            }
+            actual_paths.extend(unshadowed_paths(graph, partials, &reference_paths));
         }
 
         let missing_targets = expected_targets
@@ -5,11 +5,17 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+//! Copious debugging output is emitted at the `trace` level through the [`log`][] facade, so that
+//! consumers decide whether and where it is written, by installing a logger of their choice.
+
+#[cfg(feature = "copious-debugging")]
+#[doc(hidden)]
+pub use log as __log;
+
 #[cfg(feature = "copious-debugging")]
 #[macro_export]
 macro_rules! copious_debugging {
-    ($($arg:tt)*) => {{ ::std::eprintln!($($arg)*); }}
-
+    ($($arg:tt)*) => {{ $crate::__log::trace!($($arg)*); }}
 }
 
 #[cfg(not(feature = "copious-debugging"))]
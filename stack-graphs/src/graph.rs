@@ -59,10 +59,15 @@ use controlled_option::ControlledOption;
 use either::Either;
 use fxhash::FxHashMap;
 use smallvec::SmallVec;
+use thiserror::Error;
 
 use crate::arena::Arena;
 use crate::arena::Handle;
+use crate::arena::HandleSet;
 use crate::arena::SupplementalArena;
+use crate::serde::Filter;
+use crate::serde::NoFilter;
+use crate::stats::SymbolStats;
 
 //-------------------------------------------------------------------------------------------------
 // String content
@@ -184,11 +189,51 @@ impl PartialEq<&str> for Symbol {
 }
 
 impl StackGraph {
+    /// Returns a handle to this stack graph's _wildcard symbol_, creating it if it does not exist
+    /// yet.  A _pop symbol_ node whose symbol is the wildcard symbol pops _any_ symbol from the top
+    /// of the symbol stack, which is useful to model glob imports (`from foo import *`).
+    ///
+    /// The wildcard symbol is displayed as `*`, but it is distinct from the ordinary symbol `*`,
+    /// which [`add_symbol`][Self::add_symbol] returns and which only matches itself.
+    pub fn add_wildcard_symbol(&mut self) -> Handle<Symbol> {
+        if let Some(handle) = self.wildcard_symbol {
+            return handle;
+        }
+        let interned = self.interned_strings.add("*");
+        let handle = self.symbols.add(Symbol { content: interned });
+        self.wildcard_symbol = Some(handle);
+        handle
+    }
+
+    /// Adds a symbol of another stack graph to this stack graph, preserving whether it is the
+    /// wildcard symbol.
+    fn copy_symbol(&mut self, other: &StackGraph, symbol: Handle<Symbol>) -> Handle<Symbol> {
+        if other.is_wildcard_symbol(symbol) {
+            self.add_wildcard_symbol()
+        } else {
+            self.add_symbol(&other[symbol])
+        }
+    }
+
+    /// Returns a handle to this stack graph's [wildcard symbol][Self::add_wildcard_symbol], if it
+    /// has been created.
+    pub fn wildcard_symbol(&self) -> Option<Handle<Symbol>> {
+        self.wildcard_symbol
+    }
+
+    /// Returns whether a symbol is this stack graph's [wildcard
+    /// symbol][Self::add_wildcard_symbol].
+    #[inline(always)]
+    pub fn is_wildcard_symbol(&self, symbol: Handle<Symbol>) -> bool {
+        self.wildcard_symbol == Some(symbol)
+    }
+
     /// Adds a symbol to the stack graph, ensuring that there's only ever one copy of a particular
     /// symbol stored in the graph.
     pub fn add_symbol<S: AsRef<str> + ?Sized>(&mut self, symbol: &S) -> Handle<Symbol> {
         let symbol = symbol.as_ref();
         if let Some(handle) = self.symbol_handles.get(symbol) {
+            self.symbol_stats.symbols_reused += 1;
             return *handle;
         }
 
@@ -196,9 +241,16 @@ impl StackGraph {
         let hash_key = unsafe { interned.as_hash_key() };
         let handle = self.symbols.add(Symbol { content: interned });
         self.symbol_handles.insert(hash_key, handle);
+        self.symbol_stats.symbols_created += 1;
         handle
     }
 
+    /// Returns how many calls to [`add_symbol`][Self::add_symbol] created a new symbol, and how
+    /// many reused an existing one.
+    pub fn symbol_stats(&self) -> SymbolStats {
+        self.symbol_stats
+    }
+
     /// Returns an iterator over all of the handles of all of the symbols in this stack graph.
     /// (Note that because we're only returning _handles_, this iterator does not retain a
     /// reference to the `StackGraph`.)
@@ -354,12 +406,28 @@ impl StackGraph {
 
     /// Adds a file to the stack graph, returning its handle.  There can only ever be one file with
     /// a particular name in the graph, so if you call this multiple times with the same name,
-    /// you'll get the same handle each time.
+    /// you'll get the same handle each time.  This makes it impossible to detect whether a file
+    /// is accidentally indexed into the same graph twice; use [`try_create_file`][] if you need
+    /// to detect that.
+    ///
+    /// [`try_create_file`]: #method.try_create_file
     #[inline(always)]
     pub fn get_or_create_file<S: AsRef<str> + ?Sized>(&mut self, name: &S) -> Handle<File> {
         self.add_file(name).unwrap_or_else(|handle| handle)
     }
 
+    /// Adds a file to the stack graph, returning its handle.  Returns an error if a file with the
+    /// requested name already exists, which lets indexers detect duplicate work.
+    pub fn try_create_file<S: AsRef<str> + ?Sized>(
+        &mut self,
+        name: &S,
+    ) -> Result<Handle<File>, FileExistsError> {
+        self.add_file(name).map_err(|handle| FileExistsError {
+            name: name.as_ref().to_string(),
+            handle,
+        })
+    }
+
     /// Returns the file with a particular name, if it exists.
     pub fn get_file<S: AsRef<str> + ?Sized>(&self, name: &S) -> Option<Handle<File>> {
         let name = name.as_ref();
@@ -367,6 +435,16 @@ impl StackGraph {
     }
 }
 
+/// An error returned when creating a file that already exists in a stack graph.
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+#[error("file {name} already exists")]
+pub struct FileExistsError {
+    /// The name of the file.
+    pub name: String,
+    /// The handle of the existing file.
+    pub handle: Handle<File>,
+}
+
 impl StackGraph {
     /// Returns an iterator of all of the nodes that belong to a particular file.  Note that this
     /// does **_not_** include the singleton _root_ or _jump to scope_ nodes.
@@ -380,6 +458,53 @@ impl StackGraph {
     pub fn iter_files(&self) -> impl Iterator<Item = Handle<File>> + '_ {
         self.files.iter_handles()
     }
+
+    /// Returns the number of files in this stack graph.
+    pub fn file_count(&self) -> usize {
+        self.files.len() - 1
+    }
+
+    /// Returns the name of the file that a node belongs to.  Returns `None` for the singleton
+    /// _root_ and _jump to scope_ nodes, which do not belong to any file.
+    pub fn file_name_for_node(&self, node: Handle<Node>) -> Option<&str> {
+        self[node].id().file().map(|file| self[file].name())
+    }
+
+    /// Moves all of the nodes of file `from` into file `to`, which makes it possible to reuse a
+    /// cached subgraph in a graph where its file has a different handle.  Nodes keep their local
+    /// IDs, and scoped symbols that refer to exported scopes in `from` are updated to refer to
+    /// `to`.  Edges, source info, and debug info are keyed by node handle, so they remain valid.
+    ///
+    /// Returns the conflicting node ID if `to` already contains a node with the same local ID as
+    /// one of the nodes in `from`.  In that case, the graph is not modified.
+    pub fn relocate_file(&mut self, from: Handle<File>, to: Handle<File>) -> Result<(), NodeID> {
+        if from == to {
+            return Ok(());
+        }
+        let nodes = self.nodes_for_file(from).collect::<Vec<_>>();
+        for node in &nodes {
+            let id = NodeID::new_in_file(to, self[*node].id().local_id());
+            if self.node_id_handles.try_handle_for_id(id).is_some() {
+                return Err(id);
+            }
+        }
+        for node in nodes {
+            let id = self[node].id_mut();
+            *id = NodeID::new_in_file(to, id.local_id());
+            let id = *id;
+            self.node_id_handles.handle_for_id(id);
+            self.node_id_handles.set_handle_for_id(id, node);
+        }
+        self.node_id_handles.clear_file(from);
+        for node in self.nodes.iter_handles().collect::<Vec<_>>() {
+            if let Node::PushScopedSymbol(node) = &mut self[node] {
+                if node.scope.file() == Some(from) {
+                    node.scope = NodeID::new_in_file(to, node.scope.local_id());
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Display for File {
@@ -621,6 +746,19 @@ impl Node {
         }
     }
 
+    fn id_mut(&mut self) -> &mut NodeID {
+        match self {
+            Node::DropScopes(node) => &mut node.id,
+            Node::JumpTo(node) => &mut node.id,
+            Node::PushScopedSymbol(node) => &mut node.id,
+            Node::PushSymbol(node) => &mut node.id,
+            Node::PopScopedSymbol(node) => &mut node.id,
+            Node::PopSymbol(node) => &mut node.id,
+            Node::Root(node) => &mut node.id,
+            Node::Scope(node) => &mut node.id,
+        }
+    }
+
     /// Returns the file that this node belongs to.  Returns `None` for the singleton _root_ and
     /// _jump to scope_ nodes, which belong to all files.
     #[inline(always)]
@@ -635,6 +773,9 @@ impl Node {
         self.id().is_in_file(file)
     }
 
+    /// Displays this node, including its [`NodeID`][] as `file(local_id)` so that nodes with the
+    /// same symbol in different files can be told apart.  The alternate form (`{:#}`) displays only
+    /// the node ID.
     pub fn display<'a>(&'a self, graph: &'a StackGraph) -> impl Display + 'a {
         DisplayNode {
             wrapped: self,
@@ -669,6 +810,43 @@ impl StackGraph {
         self.nodes.iter_handles()
     }
 
+    /// Returns all of the definition nodes in the graph, grouped by their symbol.  Within each
+    /// group, the nodes are in the order in which they were added to the graph.  A symbol with more
+    /// than one definition is not necessarily an error, since definitions in different scopes can
+    /// share a name.
+    pub fn definitions_by_symbol(&self) -> HashMap<Handle<Symbol>, Vec<Handle<Node>>> {
+        let mut definitions = HashMap::<_, Vec<_>>::new();
+        for node in self.iter_nodes() {
+            let node_data = &self[node];
+            if !node_data.is_definition() {
+                continue;
+            }
+            if let Some(symbol) = node_data.symbol() {
+                definitions.entry(symbol).or_default().push(node);
+            }
+        }
+        definitions
+    }
+
+    /// Returns the number of nodes of each type in the graph.  (This includes the singleton _root_
+    /// and _jump to scope_ nodes.)
+    pub fn node_type_counts(&self) -> NodeTypeCounts {
+        let mut counts = NodeTypeCounts::default();
+        for node in self.iter_nodes() {
+            match &self[node] {
+                Node::DropScopes(_) => counts.drop_scopes += 1,
+                Node::JumpTo(_) => counts.jump_to += 1,
+                Node::PopScopedSymbol(_) => counts.pop_scoped_symbol += 1,
+                Node::PopSymbol(_) => counts.pop_symbol += 1,
+                Node::PushScopedSymbol(_) => counts.push_scoped_symbol += 1,
+                Node::PushSymbol(_) => counts.push_symbol += 1,
+                Node::Root(_) => counts.root += 1,
+                Node::Scope(_) => counts.scope += 1,
+            }
+        }
+        counts
+    }
+
     /// Returns the handle to the node with a particular ID, if it exists.
     pub fn node_for_id(&self, id: NodeID) -> Option<Handle<Node>> {
         if id.file().is_some() {
@@ -701,6 +879,33 @@ impl StackGraph {
     }
 }
 
+/// The number of nodes of each type in a stack graph.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NodeTypeCounts {
+    pub drop_scopes: usize,
+    pub jump_to: usize,
+    pub pop_scoped_symbol: usize,
+    pub pop_symbol: usize,
+    pub push_scoped_symbol: usize,
+    pub push_symbol: usize,
+    pub root: usize,
+    pub scope: usize,
+}
+
+impl NodeTypeCounts {
+    /// Returns the total number of nodes, across all node types.
+    pub fn total(&self) -> usize {
+        self.drop_scopes
+            + self.jump_to
+            + self.pop_scoped_symbol
+            + self.pop_symbol
+            + self.push_scoped_symbol
+            + self.push_symbol
+            + self.root
+            + self.scope
+    }
+}
+
 #[doc(hidden)]
 pub struct DisplayNode<'a> {
     wrapped: &'a Node,
@@ -1202,6 +1407,12 @@ impl NodeIDHandles {
         NodeID::new_in_file(file, local_id)
     }
 
+    fn clear_file(&mut self, file: Handle<File>) {
+        if let Some(file_entry) = self.files.get_mut(file) {
+            file_entry.clear();
+        }
+    }
+
     fn nodes_for_file(&self, file: Handle<File>) -> impl Iterator<Item = Handle<Node>> + '_ {
         let file_entry = match self.files.get(file) {
             Some(file_entry) => file_entry,
@@ -1285,6 +1496,10 @@ impl<'a> Display for DisplayScopeNode<'a> {
 /// in a stack graph.  (Though not all sequence of edges is a well-formed name binding: the nodes
 /// that you encounter along the path must also satisfy all of the rules for maintaining correct
 /// symbol and scope stacks.)
+///
+/// An edge's precedence is used to [shadow][crate::partial::PartialPath::shadows] other paths.
+/// Precedences are compared as signed integers, and the default precedence is 0, so an edge with a
+/// negative precedence has a lower priority than an edge without an explicit precedence.
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Edge {
     pub source: Handle<Node>,
@@ -1295,15 +1510,37 @@ pub struct Edge {
 pub(crate) struct OutgoingEdge {
     sink: Handle<Node>,
     precedence: i32,
+    kind: u32,
 }
 
 impl StackGraph {
-    /// Adds a new edge to the stack graph.
+    /// Adds a new edge to the stack graph.  If the graph already contains an edge between the same
+    /// nodes, the edge is not added again, and the existing edge keeps its precedence.
     pub fn add_edge(&mut self, source: Handle<Node>, sink: Handle<Node>, precedence: i32) {
         let edges = &mut self.outgoing_edges[source];
         if let Err(index) = edges.binary_search_by_key(&sink, |o| o.sink) {
-            edges.insert(index, OutgoingEdge { sink, precedence });
-            self.incoming_edges[sink] += Degree::One;
+            edges.insert(
+                index,
+                OutgoingEdge {
+                    sink,
+                    precedence,
+                    kind: 0,
+                },
+            );
+            self.incoming_edges[sink] += 1;
+        }
+    }
+
+    /// Adds a new edge to the stack graph.  If the graph already contains an edge between the same
+    /// nodes, the edge is not added again, and the existing edge gets the highest of the two
+    /// precedences.
+    pub fn add_or_merge_edge(&mut self, source: Handle<Node>, sink: Handle<Node>, precedence: i32) {
+        let edges = &mut self.outgoing_edges[source];
+        match edges.binary_search_by_key(&sink, |o| o.sink) {
+            Ok(index) => {
+                edges[index].precedence = edges[index].precedence.max(precedence);
+            }
+            Err(_) => self.add_edge(source, sink, precedence),
         }
     }
 
@@ -1320,6 +1557,147 @@ impl StackGraph {
         }
     }
 
+    /// Sets the kind of the given edge.  Edge kinds are not interpreted by the stack graph itself,
+    /// but allow languages to label edges (e.g. as lexical or import edges), so that custom
+    /// resolution policies can distinguish them.  Edges have kind 0 unless set otherwise.
+    pub fn set_edge_kind(&mut self, source: Handle<Node>, sink: Handle<Node>, kind: u32) {
+        let edges = &mut self.outgoing_edges[source];
+        if let Ok(index) = edges.binary_search_by_key(&sink, |o| o.sink) {
+            edges[index].kind = kind;
+        }
+    }
+
+    /// Returns the kind of the given edge, or `None` if the edge does not exist.
+    pub fn edge_kind(&self, source: Handle<Node>, sink: Handle<Node>) -> Option<u32> {
+        let edges = self.outgoing_edges.get(source)?;
+        edges
+            .binary_search_by_key(&sink, |o| o.sink)
+            .ok()
+            .map(|index| edges[index].kind)
+    }
+
+    /// Rewrites the precedences of the outgoing edges of each node into a dense ranking `0..n`,
+    /// where `n` is the number of distinct precedences among that node's outgoing edges.  The
+    /// relative order of precedences is preserved, so this does not change which paths shadow
+    /// each other, but it does make precedences from different sources comparable.
+    pub fn normalize_precedences(&mut self) {
+        for source in self.nodes.iter_handles() {
+            let edges = match self.outgoing_edges.get_mut(source) {
+                Some(edges) => edges,
+                None => continue,
+            };
+            let mut precedences = edges
+                .iter()
+                .map(|o| o.precedence)
+                .collect::<SmallVec<[i32; 4]>>();
+            precedences.sort_unstable();
+            precedences.dedup();
+            for edge in edges.iter_mut() {
+                let rank = precedences.binary_search(&edge.precedence).unwrap();
+                edge.precedence = rank as i32;
+            }
+        }
+    }
+
+    /// Contracts chains of internal scope nodes, to reduce the number of nodes and edges that
+    /// path finding has to visit.  A scope node is collapsed if it has exactly one incoming and one
+    /// outgoing edge, both with precedence 0, by replacing those edges with a single edge from its
+    /// predecessor to its successor.  This does not change which definitions references resolve
+    /// to.
+    ///
+    /// Exported scopes, scopes with source information, and scopes that are attached to _push
+    /// scoped symbol_ nodes are never collapsed.  Collapsed nodes are not removed from the graph,
+    /// but are left without any edges.  The debug info of the replaced edges is merged onto the
+    /// new edge, in path order.  Returns the number of collapsed nodes.
+    pub fn collapse_scope_chains(&mut self) -> usize {
+        let mut attached_scopes = HandleSet::new();
+        let mut incoming = SupplementalArena::<Node, SmallVec<[Edge; 2]>>::new();
+        for node in self.nodes.iter_handles() {
+            if let Some(scope) = self[node].scope().and_then(|id| self.node_for_id(id)) {
+                attached_scopes.add(scope);
+            }
+            for edge in self.outgoing_edges(node) {
+                incoming[edge.sink].push(edge);
+            }
+        }
+
+        let mut collapsed = 0;
+        for node in self.nodes.iter_handles() {
+            if !matches!(self[node], Node::Scope(_))
+                || self[node].is_exported_scope()
+                || attached_scopes.contains(node)
+                || self.source_info(node).is_some_and(|info| {
+                    info.span != lsp_positions::Span::default() || info.syntax_type.is_some()
+                })
+            {
+                continue;
+            }
+            let in_edge = match incoming.get(node).map(|edges| edges.as_slice()) {
+                Some([edge]) => *edge,
+                _ => continue,
+            };
+            let out_edge = match self.outgoing_edges.get(node).map(|edges| edges.as_slice()) {
+                Some([edge]) => Edge {
+                    source: node,
+                    sink: edge.sink,
+                    precedence: edge.precedence,
+                },
+                _ => continue,
+            };
+            let (source, sink) = (in_edge.source, out_edge.sink);
+            if in_edge.precedence != 0
+                || out_edge.precedence != 0
+                || self.edge_kind(source, node) != Some(0)
+                || self.edge_kind(node, sink) != Some(0)
+                || source == node
+                || sink == node
+                || source == sink
+                || self.outgoing_edges(source).any(|edge| edge.sink == sink)
+            {
+                continue;
+            }
+
+            self.remove_outgoing_edge(source, node);
+            self.remove_outgoing_edge(node, sink);
+            self.add_edge(source, sink, 0);
+            let in_debug_info = self.take_edge_debug_info(source, node);
+            let out_debug_info = self.take_edge_debug_info(node, sink);
+            if in_debug_info.is_some() || out_debug_info.is_some() {
+                let debug_info = self.edge_debug_info_mut(source, sink);
+                for info in in_debug_info.into_iter().chain(out_debug_info) {
+                    debug_info.entries.extend(info.entries);
+                }
+            }
+            incoming[node].clear();
+            let sink_incoming = &mut incoming[sink];
+            for edge in sink_incoming.iter_mut() {
+                if edge.source == node {
+                    edge.source = source;
+                }
+            }
+            collapsed += 1;
+        }
+        collapsed
+    }
+
+    fn take_edge_debug_info(
+        &mut self,
+        source: Handle<Node>,
+        sink: Handle<Node>,
+    ) -> Option<DebugInfo> {
+        let edges = self.edge_debug_info.get_mut(source)?;
+        let index = edges.binary_search_by_key(&sink, |e| e.0).ok()?;
+        Some(edges.remove(index).1)
+    }
+
+    fn remove_outgoing_edge(&mut self, source: Handle<Node>, sink: Handle<Node>) {
+        let edges = &mut self.outgoing_edges[source];
+        if let Ok(index) = edges.binary_search_by_key(&sink, |o| o.sink) {
+            edges.remove(index);
+            self.incoming_edges[sink] -= 1;
+        }
+    }
+
     /// Returns an iterator of all of the edges that begin at a particular source node.
     pub fn outgoing_edges(&self, source: Handle<Node>) -> impl Iterator<Item = Edge> + '_ {
         match self.outgoing_edges.get(source) {
@@ -1334,10 +1712,96 @@ impl StackGraph {
 
     /// Returns the number of edges that end at a particular sink node.
     pub fn incoming_edge_degree(&self, sink: Handle<Node>) -> Degree {
-        self.incoming_edges
-            .get(sink)
-            .cloned()
-            .unwrap_or(Degree::Zero)
+        match self.in_degree(sink) {
+            0 => Degree::Zero,
+            1 => Degree::One,
+            _ => Degree::Multiple,
+        }
+    }
+
+    /// Returns the exact number of edges that begin at a particular source node.
+    pub fn out_degree(&self, source: Handle<Node>) -> usize {
+        self.outgoing_edges
+            .get(source)
+            .map(|edges| edges.len())
+            .unwrap_or(0)
+    }
+
+    /// Returns the exact number of edges that end at a particular sink node.  Scope nodes with a
+    /// high in-degree are often a sign of a TSG rule that funnels many nodes through a single
+    /// node, which can make path stitching expensive.
+    pub fn in_degree(&self, sink: Handle<Node>) -> usize {
+        self.incoming_edges.get(sink).cloned().unwrap_or(0)
+    }
+
+    /// Returns the nodes of a file that have no incoming and no outgoing edges.  Such nodes cannot
+    /// be part of any path, and are usually the result of a TSG rule that creates a node but never
+    /// connects it.  The singleton root and jump to scope nodes do not belong to any file, and are
+    /// never returned.
+    pub fn orphan_nodes(&self, file: Handle<File>) -> Vec<Handle<Node>> {
+        self.nodes_for_file(file)
+            .filter(|node| self.in_degree(*node) == 0 && self.out_degree(*node) == 0)
+            .collect()
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Symbol mismatches
+
+/// A symbol that is pushed but never popped, while a nearly identical symbol is popped.  This is
+/// likely a typo in the TSG rules, which prevents references from resolving.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SymbolMismatch {
+    /// The symbol that is pushed, but never popped.
+    pub pushed: Handle<Symbol>,
+    /// A symbol that is popped, and that only differs from the pushed symbol in case or in
+    /// leading and trailing whitespace.
+    pub popped: Handle<Symbol>,
+}
+
+impl StackGraph {
+    /// Returns the symbols that are pushed but never popped, paired with the popped symbols that
+    /// are equal to them when ignoring case and leading and trailing whitespace.  This is a
+    /// heuristic to find typos in the TSG rules of a language, and does not consider whether the
+    /// push and pop nodes are connected.  Mismatches are returned in the order in which the
+    /// pushed symbols were added to the graph.
+    pub fn symbol_mismatches(&self) -> Vec<SymbolMismatch> {
+        let mut pushed = HandleSet::new();
+        let mut popped = HandleSet::new();
+        for node in self.iter_nodes() {
+            match &self[node] {
+                Node::PushScopedSymbol(node) => pushed.add(node.symbol),
+                Node::PushSymbol(node) => pushed.add(node.symbol),
+                Node::PopScopedSymbol(node) => popped.add(node.symbol),
+                Node::PopSymbol(node) if !self.is_wildcard_symbol(node.symbol) => {
+                    popped.add(node.symbol)
+                }
+                _ => {}
+            }
+        }
+
+        let normalize = |symbol: Handle<Symbol>| self[symbol].trim().to_lowercase();
+        let mut popped_by_normalized = HashMap::<_, Vec<_>>::new();
+        for symbol in self.iter_symbols().filter(|s| popped.contains(*s)) {
+            popped_by_normalized
+                .entry(normalize(symbol))
+                .or_default()
+                .push(symbol);
+        }
+
+        let mut mismatches = Vec::new();
+        for symbol in self.iter_symbols() {
+            if !pushed.contains(symbol) || popped.contains(symbol) {
+                continue;
+            }
+            if let Some(similar) = popped_by_normalized.get(&normalize(symbol)) {
+                mismatches.extend(similar.iter().map(|popped| SymbolMismatch {
+                    pushed: symbol,
+                    popped: *popped,
+                }));
+            }
+        }
+        mismatches
     }
 }
 
@@ -1375,6 +1839,199 @@ impl StackGraph {
     pub fn source_info_mut(&mut self, node: Handle<Node>) -> &mut SourceInfo {
         &mut self.source_info[node]
     }
+
+    /// Returns all of the nodes in a file whose source span matches the given span.  Nodes
+    /// without a source span are never returned.
+    pub fn nodes_for_span(
+        &self,
+        file: Handle<File>,
+        span: &lsp_positions::Span,
+        span_match: SpanMatch,
+    ) -> Vec<Handle<Node>> {
+        let no_span = lsp_positions::Span::default();
+        self.nodes_for_file(file)
+            .filter(|node| match self.source_info(*node) {
+                Some(source_info) if source_info.span.cmp(&no_span).is_ne() => {
+                    span_match.matches(&source_info.span, span)
+                }
+                _ => false,
+            })
+            .collect()
+    }
+}
+
+/// Determines how [`StackGraph::nodes_for_span`][] matches node spans against a query span.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SpanMatch {
+    /// The node's span starts and ends at the same positions as the query span.
+    Exact,
+    /// The node's span contains the query span.
+    Contains,
+}
+
+impl SpanMatch {
+    fn matches(self, node_span: &lsp_positions::Span, span: &lsp_positions::Span) -> bool {
+        match self {
+            SpanMatch::Exact => node_span.cmp(span).is_eq(),
+            SpanMatch::Contains => node_span.start <= span.start && span.end <= node_span.end,
+        }
+    }
+}
+
+/// The source lines around a node's span, as shown when hovering over or peeking at a definition.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourcePreview<'a> {
+    /// The 0-indexed line number of the first line of the preview.
+    pub first_line: usize,
+    /// The lines of the preview, without their line terminators.  This includes all lines of the
+    /// node's span, and up to the requested number of context lines before and after it.
+    pub lines: Vec<&'a str>,
+}
+
+impl StackGraph {
+    /// Returns the lines of `source` covered by a node's span, together with up to
+    /// `context_lines` lines before and after it.  `source` must be the content of the node's
+    /// file.  Returns `None` if the node has no source span, or if the span lies outside of
+    /// `source`.
+    pub fn source_preview<'a>(
+        &self,
+        node: Handle<Node>,
+        source: &'a str,
+        context_lines: usize,
+    ) -> Option<SourcePreview<'a>> {
+        let span = &self.source_info(node)?.span;
+        if span.cmp(&lsp_positions::Span::default()).is_eq() {
+            return None;
+        }
+        let first_line = span.start.line.saturating_sub(context_lines);
+        let last_line = span.end.line.saturating_add(context_lines);
+        let lines = lsp_positions::PositionedSubstring::lines_iter(source)
+            .skip(first_line)
+            .take(last_line - first_line + 1)
+            .map(|line| line.content)
+            .collect::<Vec<_>>();
+        if first_line + lines.len() <= span.end.line {
+            return None;
+        }
+        Some(SourcePreview { first_line, lines })
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Definition ranks
+
+impl StackGraph {
+    /// Returns the rank of a definition node.  Ranks are used to break ties between definitions
+    /// that are not shadowed by each other's precedence, preferring definitions with a higher
+    /// rank.  Definitions have rank 0 unless another rank has been set.
+    pub fn definition_rank(&self, node: Handle<Node>) -> i32 {
+        self.definition_ranks.get(node).copied().unwrap_or(0)
+    }
+
+    /// Returns the rank of a definition node, if one has been set.
+    pub(crate) fn explicit_definition_rank(&self, node: Handle<Node>) -> Option<i32> {
+        self.definition_ranks.get(node).copied()
+    }
+
+    /// Sets the rank of a definition node.  For example, giving builtins a negative rank ensures
+    /// that user definitions of the same name are preferred.
+    pub fn set_definition_rank(&mut self, node: Handle<Node>, rank: i32) {
+        self.definition_ranks[node] = rank;
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Definition sites
+
+/// The spans of a definition that a language server reports in a location link: the full extent
+/// of the definition, for example a whole function, and the part of it that should be selected,
+/// typically the definition's name.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DefinitionSite {
+    /// The full span of the definition, from the node's [source info][SourceInfo::span].  This
+    /// corresponds to the LSP `targetRange`.
+    pub span: lsp_positions::Span,
+    /// The span that is selected when jumping to the definition.  This corresponds to the LSP
+    /// `targetSelectionRange`, and defaults to the full span if no selection span was set.
+    pub selection_span: lsp_positions::Span,
+}
+
+impl StackGraph {
+    /// Returns the selection span of a node, if one has been set.
+    pub fn selection_span(&self, node: Handle<Node>) -> Option<&lsp_positions::Span> {
+        self.selection_spans.get(node)
+    }
+
+    /// Sets the selection span of a node, which is typically the span of a definition's name,
+    /// when the node's source span covers the whole definition.
+    pub fn set_selection_span(&mut self, node: Handle<Node>, span: lsp_positions::Span) {
+        self.selection_spans[node] = span;
+    }
+
+    /// Returns the definition site of a node, or `None` if the node has no source span.
+    pub fn definition_site(&self, node: Handle<Node>) -> Option<DefinitionSite> {
+        let span = &self.source_info(node)?.span;
+        if *span == lsp_positions::Span::default() {
+            return None;
+        }
+        let selection_span = self.selection_span(node).unwrap_or(span);
+        Some(DefinitionSite {
+            span: span.clone(),
+            selection_span: selection_span.clone(),
+        })
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Generated nodes
+
+impl StackGraph {
+    /// Returns whether a node is generated, instead of derived from user source code.  Builtins
+    /// and other synthetic nodes are typically marked as generated, so that consumers can hide
+    /// them from user-facing results.
+    pub fn is_generated(&self, node: Handle<Node>) -> bool {
+        self.generated_nodes.contains(node)
+    }
+
+    /// Sets whether a node is generated.
+    pub fn set_generated(&mut self, node: Handle<Node>, is_generated: bool) {
+        if is_generated {
+            self.generated_nodes.add(node);
+        } else {
+            self.generated_nodes.remove(node);
+        }
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Kept nodes
+
+impl StackGraph {
+    /// Returns whether paths through a node must be kept.  Such paths are never dropped by
+    /// similar path detection during path stitching, which gives language authors an escape
+    /// hatch when the heuristic drops a path that is essential.
+    pub fn keeps_paths(&self, node: Handle<Node>) -> bool {
+        self.kept_nodes.contains(node)
+    }
+
+    /// Sets whether paths through a node must be kept.
+    pub fn set_keeps_paths(&mut self, node: Handle<Node>, keeps_paths: bool) {
+        if keeps_paths == self.kept_nodes.contains(node) {
+            return;
+        }
+        if keeps_paths {
+            self.kept_nodes.add(node);
+            self.kept_node_count += 1;
+        } else {
+            self.kept_nodes.remove(node);
+            self.kept_node_count -= 1;
+        }
+    }
+
+    /// Returns whether any node in this graph keeps paths.
+    pub fn has_kept_nodes(&self) -> bool {
+        self.kept_node_count > 0
+    }
 }
 
 //-------------------------------------------------------------------------------------------------
@@ -1479,15 +2136,22 @@ pub struct StackGraph {
     interned_strings: InternedStringArena,
     pub(crate) symbols: Arena<Symbol>,
     symbol_handles: FxHashMap<&'static str, Handle<Symbol>>,
+    wildcard_symbol: Option<Handle<Symbol>>,
+    symbol_stats: SymbolStats,
     pub(crate) strings: Arena<InternedString>,
     string_handles: FxHashMap<&'static str, Handle<InternedString>>,
     pub(crate) files: Arena<File>,
     file_handles: FxHashMap<&'static str, Handle<File>>,
     pub(crate) nodes: Arena<Node>,
     pub(crate) source_info: SupplementalArena<Node, SourceInfo>,
+    definition_ranks: SupplementalArena<Node, i32>,
+    selection_spans: SupplementalArena<Node, lsp_positions::Span>,
+    generated_nodes: HandleSet<Node>,
+    kept_nodes: HandleSet<Node>,
+    kept_node_count: usize,
     node_id_handles: NodeIDHandles,
     outgoing_edges: SupplementalArena<Node, SmallVec<[OutgoingEdge; 4]>>,
-    incoming_edges: SupplementalArena<Node, Degree>,
+    incoming_edges: SupplementalArena<Node, usize>,
     pub(crate) node_debug_info: SupplementalArena<Node, DebugInfo>,
     pub(crate) edge_debug_info: SupplementalArena<Node, SmallVec<[(Handle<Node>, DebugInfo); 4]>>,
 }
@@ -1503,31 +2167,82 @@ impl StackGraph {
     pub fn add_from_graph(
         &mut self,
         other: &StackGraph,
+    ) -> Result<Vec<Handle<File>>, Handle<File>> {
+        self.add_files_from_graph(other, &other.iter_files().collect::<Vec<_>>(), &NoFilter)
+    }
+
+    /// Copies the nodes and edges of a single file of this stack graph into `dest`, which makes
+    /// it possible to analyze or cache the file in isolation.  The file is created in `dest` with
+    /// the same name, nodes keep their local IDs, and symbols and strings are re-interned.  Edges
+    /// between the file and nodes of other files are not copied.  Push scoped symbol nodes keep
+    /// referring to exported scopes in other files, so those files are created in `dest` if they
+    /// do not exist yet, without any nodes.  Returns the file handle in `dest`, or the handle of
+    /// the existing file if `dest` already contains a file with the same name.
+    pub fn clone_file_into(
+        &self,
+        file: Handle<File>,
+        dest: &mut StackGraph,
+    ) -> Result<Handle<File>, Handle<File>> {
+        dest.add_files_from_graph(self, &[file], &NoFilter)
+            .map(|files| files[0])
+    }
+
+    /// Copies the nodes and edges of the given files of another stack graph into this stack
+    /// graph, keeping all of their attributes.  Nodes and edges that are not included by the
+    /// filter are skipped.  Nodes keep their local IDs in files with the same name.
+    pub(crate) fn add_files_from_graph(
+        &mut self,
+        other: &StackGraph,
+        other_files: &[Handle<File>],
+        filter: &dyn Filter,
     ) -> Result<Vec<Handle<File>>, Handle<File>> {
         let mut files = HashMap::new();
-        for other_file in other.iter_files() {
+        for other_file in other_files.iter().cloned() {
             let file = self.add_file(other[other_file].name())?;
             files.insert(other_file, file);
         }
-        let files = files;
+        // Scoped symbols can refer to exported scopes in files that are not copied, which are
+        // referred to by file name.
+        let mut scope_files = HashMap::new();
+        for other_file in other_files.iter().cloned() {
+            for other_node in other.nodes_for_file(other_file) {
+                if !filter.include_node(other, &other_node) {
+                    continue;
+                }
+                if let Node::PushScopedSymbol(node) = &other[other_node] {
+                    if let Some(scope_file) = node.scope.file() {
+                        if !files.contains_key(&scope_file) {
+                            scope_files.insert(
+                                scope_file,
+                                self.get_or_create_file(other[scope_file].name()),
+                            );
+                        }
+                    }
+                }
+            }
+        }
         let node_id = |other_node_id: NodeID| {
             if other_node_id.is_root() {
                 NodeID::root()
             } else if other_node_id.is_jump_to() {
                 NodeID::jump_to()
             } else {
-                NodeID::new_in_file(
-                    files[&other_node_id.file.into_option().unwrap()],
-                    other_node_id.local_id,
-                )
+                let other_file = other_node_id.file.into_option().unwrap();
+                let file = files
+                    .get(&other_file)
+                    .unwrap_or_else(|| &scope_files[&other_file]);
+                NodeID::new_in_file(*file, other_node_id.local_id)
             }
         };
         let mut nodes = HashMap::new();
         nodes.insert(Self::root_node(), Self::root_node());
         nodes.insert(Self::jump_to_node(), Self::jump_to_node());
-        for other_file in files.keys().cloned() {
+        for other_file in other_files.iter().cloned() {
             let file = files[&other_file];
             for other_node in other.nodes_for_file(other_file) {
+                if !filter.include_node(other, &other_node) {
+                    continue;
+                }
                 let value: Node = match other[other_node] {
                     Node::DropScopes(DropScopesNode { id, .. }) => DropScopesNode {
                         id: NodeID::new_in_file(file, id.local_id),
@@ -1550,7 +2265,7 @@ impl StackGraph {
                         ..
                     }) => PopScopedSymbolNode {
                         id: NodeID::new_in_file(file, id.local_id),
-                        symbol: self.add_symbol(&other[symbol]),
+                        symbol: self.copy_symbol(other, symbol),
                         _scope: NodeID::default(),
                         is_definition: is_definition,
                     }
@@ -1562,7 +2277,7 @@ impl StackGraph {
                         ..
                     }) => PopSymbolNode {
                         id: NodeID::new_in_file(file, id.local_id),
-                        symbol: self.add_symbol(&other[symbol]),
+                        symbol: self.copy_symbol(other, symbol),
                         _scope: NodeID::default(),
                         is_definition: is_definition,
                     }
@@ -1575,7 +2290,7 @@ impl StackGraph {
                         ..
                     }) => PushScopedSymbolNode {
                         id: NodeID::new_in_file(file, id.local_id),
-                        symbol: self.add_symbol(&other[symbol]),
+                        symbol: self.copy_symbol(other, symbol),
                         scope: node_id(scope),
                         is_reference: is_reference,
                         _phantom: (),
@@ -1588,7 +2303,7 @@ impl StackGraph {
                         ..
                     }) => PushSymbolNode {
                         id: NodeID::new_in_file(file, id.local_id),
-                        symbol: self.add_symbol(&other[symbol]),
+                        symbol: self.copy_symbol(other, symbol),
                         _scope: NodeID::default(),
                         is_reference: is_reference,
                     }
@@ -1626,33 +2341,65 @@ impl StackGraph {
                             .map(|cl| self.add_string(&other[cl]))
                             .into(),
                         definiens_span: source_info.definiens_span.clone(),
-                        fully_qualified_name: ControlledOption::default(),
+                        fully_qualified_name: source_info
+                            .fully_qualified_name
+                            .into_option()
+                            .map(|fqn| self.add_string(&other[fqn]))
+                            .into(),
                     };
                 }
+                if let Some(rank) = other.explicit_definition_rank(other_node) {
+                    self.set_definition_rank(node, rank);
+                }
+                if let Some(span) = other.selection_span(other_node) {
+                    self.set_selection_span(node, span.clone());
+                }
+                if other.is_generated(other_node) {
+                    self.set_generated(node, true);
+                }
+                if other.keeps_paths(other_node) {
+                    self.set_keeps_paths(node, true);
+                }
                 if let Some(debug_info) = other.node_debug_info(other_node) {
-                    *self.node_debug_info_mut(node) = DebugInfo {
-                        entries: debug_info
-                            .entries
-                            .iter()
-                            .map(|e| DebugEntry {
-                                key: self.add_string(&other[e.key]),
-                                value: self.add_string(&other[e.value]),
-                            })
-                            .collect::<Vec<_>>(),
-                    };
+                    *self.node_debug_info_mut(node) = self.copy_debug_info(other, debug_info);
                 }
             }
-            for other_node in nodes.keys().cloned() {
-                for other_edge in other.outgoing_edges(other_node) {
-                    self.add_edge(
-                        nodes[&other_edge.source],
-                        nodes[&other_edge.sink],
-                        other_edge.precedence,
-                    );
+        }
+        for other_node in nodes.keys().cloned() {
+            for other_edge in other.outgoing_edges(other_node) {
+                let sink = match nodes.get(&other_edge.sink) {
+                    Some(sink) => *sink,
+                    None => continue,
+                };
+                if !filter.include_edge(other, &other_edge.source, &other_edge.sink) {
+                    continue;
+                }
+                let source = nodes[&other_edge.source];
+                self.add_edge(source, sink, other_edge.precedence);
+                if let Some(kind) = other.edge_kind(other_edge.source, other_edge.sink) {
+                    self.set_edge_kind(source, sink, kind);
+                }
+                if let Some(debug_info) = other.edge_debug_info(other_edge.source, other_edge.sink)
+                {
+                    *self.edge_debug_info_mut(source, sink) =
+                        self.copy_debug_info(other, debug_info);
                 }
             }
         }
-        Ok(files.into_values().collect())
+        Ok(other_files.iter().map(|f| files[f]).collect())
+    }
+
+    fn copy_debug_info(&mut self, other: &StackGraph, debug_info: &DebugInfo) -> DebugInfo {
+        DebugInfo {
+            entries: debug_info
+                .entries
+                .iter()
+                .map(|e| DebugEntry {
+                    key: self.add_string(&other[e.key]),
+                    value: self.add_string(&other[e.value]),
+                })
+                .collect::<Vec<_>>(),
+        }
     }
 }
 
@@ -1666,12 +2413,19 @@ impl Default for StackGraph {
             interned_strings: InternedStringArena::new(),
             symbols: Arena::new(),
             symbol_handles: FxHashMap::default(),
+            wildcard_symbol: None,
+            symbol_stats: SymbolStats::default(),
             strings: Arena::new(),
             string_handles: FxHashMap::default(),
             files: Arena::new(),
             file_handles: FxHashMap::default(),
             nodes,
             source_info: SupplementalArena::new(),
+            definition_ranks: SupplementalArena::new(),
+            selection_spans: SupplementalArena::new(),
+            generated_nodes: HandleSet::new(),
+            kept_nodes: HandleSet::new(),
+            kept_node_count: 0,
             node_id_handles: NodeIDHandles::new(),
             outgoing_edges: SupplementalArena::new(),
             incoming_edges: SupplementalArena::new(),
@@ -65,6 +65,9 @@ pub mod c;
 pub mod cycles;
 #[macro_use]
 mod debugging;
+#[cfg(feature = "copious-debugging")]
+#[doc(hidden)]
+pub use debugging::__log;
 pub mod graph;
 pub mod partial;
 pub mod paths;
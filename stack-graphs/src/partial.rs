@@ -466,11 +466,20 @@ impl PartialSymbolStack {
         self.variable.is_some()
     }
 
+    /// Returns the number of symbols in this partial symbol stack, not counting its symbol stack
+    /// variable.
     #[inline(always)]
     pub fn len(&self) -> usize {
         self.length as usize
     }
 
+    /// Returns whether this partial symbol stack contains no symbols.  It might still have a
+    /// symbol stack variable.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
     /// Returns an empty partial symbol stack.
     pub fn empty() -> PartialSymbolStack {
         PartialSymbolStack {
@@ -489,6 +498,25 @@ impl PartialSymbolStack {
         }
     }
 
+    /// Returns a partial symbol stack containing the given symbols, without attached scopes.  The
+    /// first symbol in the slice is the top of the stack.
+    pub fn from_symbols(
+        partials: &mut PartialPaths,
+        symbols: &[Handle<Symbol>],
+    ) -> PartialSymbolStack {
+        let mut stack = PartialSymbolStack::empty();
+        for symbol in symbols {
+            stack.push_back(
+                partials,
+                PartialScopedSymbol {
+                    symbol: *symbol,
+                    scopes: ControlledOption::none(),
+                },
+            );
+        }
+        stack
+    }
+
     /// Returns whether this partial symbol stack is iterable in both directions without needing
     /// mutable access to the arena.
     pub fn have_reversal(&self, partials: &PartialPaths) -> bool {
@@ -618,6 +646,34 @@ impl PartialSymbolStack {
         Ok(result)
     }
 
+    /// Replaces any [wildcard symbols][StackGraph::add_wildcard_symbol] in this precondition with
+    /// the unscoped symbols at the same position in a postcondition, so that the two stacks unify
+    /// if they only differ in the symbols that the wildcards pop.
+    fn instantiate_wildcards(
+        mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        mut postcondition: PartialSymbolStack,
+    ) -> PartialSymbolStack {
+        let wildcard = match graph.wildcard_symbol() {
+            Some(wildcard) => wildcard,
+            None => return self,
+        };
+        let mut result = match self.variable() {
+            Some(variable) => PartialSymbolStack::from_variable(variable),
+            None => PartialSymbolStack::empty(),
+        };
+        while let Some(mut symbol) = self.pop_front(partials) {
+            if let Some(other) = postcondition.pop_front(partials) {
+                if symbol.symbol == wildcard && other.scopes.is_none() {
+                    symbol.symbol = other.symbol;
+                }
+            }
+            result.push_back(partials, symbol);
+        }
+        result
+    }
+
     /// Given two partial symbol stacks, returns the largest possible partial symbol stack such that
     /// any symbol stack that satisfies the result also satisfies both inputs.  This takes into
     /// account any existing variable assignments, and updates those variable assignments with
@@ -626,11 +682,24 @@ impl PartialSymbolStack {
     /// Note that this operation is commutative.  (Concatenating partial paths, defined in
     /// [`PartialPath::concatenate`][], is not.)
     pub fn unify(
+        self,
+        partials: &mut PartialPaths,
+        rhs: PartialSymbolStack,
+        symbol_bindings: &mut PartialSymbolStackBindings,
+        scope_bindings: &mut PartialScopeStackBindings,
+    ) -> Result<PartialSymbolStack, PathResolutionError> {
+        self.unify_traced(partials, rhs, symbol_bindings, scope_bindings, None)
+    }
+
+    /// Unifies two partial symbol stacks like [`unify`][Self::unify].  If `trace` is given, the
+    /// symbols unified before a failure and the first mismatching symbols are recorded in it.
+    fn unify_traced(
         self,
         partials: &mut PartialPaths,
         mut rhs: PartialSymbolStack,
         symbol_bindings: &mut PartialSymbolStackBindings,
         scope_bindings: &mut PartialScopeStackBindings,
+        mut trace: Option<&mut ConcatenationMismatch>,
     ) -> Result<PartialSymbolStack, PathResolutionError> {
         let mut lhs = self;
 
@@ -639,10 +708,46 @@ impl PartialSymbolStack {
         while lhs.contains_symbols() && rhs.contains_symbols() {
             let mut lhs_front = lhs.pop_front(partials).unwrap();
             let rhs_front = rhs.pop_front(partials).unwrap();
-            lhs_front.unify(partials, rhs_front, scope_bindings)?;
+            let original_lhs_front = lhs_front;
+            if let Err(err) = lhs_front.unify(partials, rhs_front, scope_bindings) {
+                if let Some(trace) = trace {
+                    trace.mismatched_symbols = Some(Mismatch {
+                        left: Some(original_lhs_front),
+                        right: Some(rhs_front),
+                    });
+                }
+                return Err(err);
+            }
+            if let Some(trace) = trace.as_deref_mut() {
+                trace.unified_symbols.push(lhs_front);
+            }
             head.push_back(&mut partials.partial_symbol_stacks, lhs_front);
         }
 
+        let result = lhs.unify_remaining(partials, rhs, head, symbol_bindings, scope_bindings);
+        if result.is_err() {
+            if let Some(trace) = trace {
+                trace.mismatched_symbols = Some(Mismatch {
+                    left: lhs.pop_front(partials),
+                    right: rhs.pop_front(partials),
+                });
+            }
+        }
+        result
+    }
+
+    /// Unifies what remains of two partial symbol stacks after their common prefix `head` has been
+    /// unified, so that at most one of them still contains symbols.
+    fn unify_remaining(
+        self,
+        partials: &mut PartialPaths,
+        mut rhs: PartialSymbolStack,
+        head: Deque<PartialScopedSymbol>,
+        symbol_bindings: &mut PartialSymbolStackBindings,
+        scope_bindings: &mut PartialScopeStackBindings,
+    ) -> Result<PartialSymbolStack, PathResolutionError> {
+        let mut lhs = self;
+
         // Now at most one stack still has symbols.  Zero, one, or both of them have variables.
         // Let's do a case analysis on all of those possibilities.
 
@@ -930,11 +1035,26 @@ impl PartialScopeStack {
         self.variable.is_some()
     }
 
+    /// Returns the number of scopes in this partial scope stack, not counting its scope stack
+    /// variable.
     #[inline(always)]
     pub fn len(&self) -> usize {
         self.length as usize
     }
 
+    /// Returns whether this partial scope stack contains no scopes.  It might still have a
+    /// scope stack variable.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns whether this partial scope stack contains the given scope.  This does not consider
+    /// scopes that might be bound to the scope stack variable.
+    pub fn contains(&self, partials: &PartialPaths, scope: Handle<Node>) -> bool {
+        self.iter_unordered(partials).any(|s| s == scope)
+    }
+
     /// Returns an empty partial scope stack.
     pub fn empty() -> PartialScopeStack {
         PartialScopeStack {
@@ -953,6 +1073,16 @@ impl PartialScopeStack {
         }
     }
 
+    /// Returns a partial scope stack containing the given scopes.  The first scope in the slice is
+    /// the top of the stack.
+    pub fn from_scopes(partials: &mut PartialPaths, scopes: &[Handle<Node>]) -> PartialScopeStack {
+        let mut stack = PartialScopeStack::empty();
+        for scope in scopes {
+            stack.push_back(partials, *scope);
+        }
+        stack
+    }
+
     /// Returns whether this partial scope stack is iterable in both directions without needing
     /// mutable access to the arena.
     pub fn have_reversal(&self, partials: &PartialPaths) -> bool {
@@ -1026,10 +1156,22 @@ impl PartialScopeStack {
     /// Note that this operation is commutative.  (Concatenating partial paths, defined in
     /// [`PartialPath::concatenate`][], is not.)
     pub fn unify(
+        self,
+        partials: &mut PartialPaths,
+        rhs: PartialScopeStack,
+        bindings: &mut PartialScopeStackBindings,
+    ) -> Result<PartialScopeStack, PathResolutionError> {
+        self.unify_traced(partials, rhs, bindings, None)
+    }
+
+    /// Unifies two partial scope stacks like [`unify`][Self::unify].  If `trace` is given, the
+    /// scopes unified before a failure and the first mismatching scopes are recorded in it.
+    fn unify_traced(
         self,
         partials: &mut PartialPaths,
         mut rhs: PartialScopeStack,
         bindings: &mut PartialScopeStackBindings,
+        mut trace: Option<&mut ConcatenationMismatch>,
     ) -> Result<PartialScopeStack, PathResolutionError> {
         let mut lhs = self;
         let original_rhs = rhs;
@@ -1039,10 +1181,42 @@ impl PartialScopeStack {
             let lhs_front = lhs.pop_front(partials).unwrap();
             let rhs_front = rhs.pop_front(partials).unwrap();
             if lhs_front != rhs_front {
+                if let Some(trace) = trace {
+                    trace.mismatched_scopes = Some(Mismatch {
+                        left: Some(lhs_front),
+                        right: Some(rhs_front),
+                    });
+                }
                 return Err(PathResolutionError::ScopeStackUnsatisfied);
             }
+            if let Some(trace) = trace.as_deref_mut() {
+                trace.unified_scopes.push(lhs_front);
+            }
+        }
+
+        let result = self.unify_remaining(partials, lhs, rhs, original_rhs, bindings);
+        if result.is_err() {
+            if let Some(trace) = trace {
+                trace.mismatched_scopes = Some(Mismatch {
+                    left: lhs.pop_front(partials),
+                    right: rhs.pop_front(partials),
+                });
+            }
         }
+        result
+    }
 
+    /// Unifies what remains of two partial scope stacks `lhs` and `rhs` after their common prefix
+    /// has been unified, so that at most one of them still contains scopes.  `self` and
+    /// `original_rhs` are the complete stacks.
+    fn unify_remaining(
+        self,
+        partials: &mut PartialPaths,
+        lhs: PartialScopeStack,
+        rhs: PartialScopeStack,
+        original_rhs: PartialScopeStack,
+        bindings: &mut PartialScopeStackBindings,
+    ) -> Result<PartialScopeStack, PathResolutionError> {
         // Now at most one stack still has scopes.  Zero, one, or both of them have variables.
         // Let's do a case analysis on all of those possibilities.
 
@@ -1208,6 +1382,14 @@ impl PartialScopeStack {
             })
     }
 
+    /// Returns an iterator over the contents of this partial scope stack.
+    pub fn iter<'a>(
+        &self,
+        partials: &'a mut PartialPaths,
+    ) -> impl Iterator<Item = Handle<Node>> + 'a {
+        self.iter_scopes(partials)
+    }
+
     /// Returns an iterator over the scopes in this partial scope stack.
     pub fn iter_scopes<'a>(
         &self,
@@ -1325,6 +1507,9 @@ impl PartialSymbolStackBindings {
         scope_bindings: &mut PartialScopeStackBindings,
     ) -> Result<(), PathResolutionError> {
         let index = variable.as_usize();
+        if index > partials.max_variable_bindings {
+            return Err(PathResolutionError::TooManyVariableBindings);
+        }
         if self.bindings.len() < index {
             self.bindings.resize_with(index, || None);
         }
@@ -1414,6 +1599,9 @@ impl PartialScopeStackBindings {
         mut scopes: PartialScopeStack,
     ) -> Result<(), PathResolutionError> {
         let index = variable.as_usize();
+        if index > partials.max_variable_bindings {
+            return Err(PathResolutionError::TooManyVariableBindings);
+        }
         if self.bindings.len() < index {
             self.bindings.resize_with(index, || None);
         }
@@ -1480,8 +1668,10 @@ pub struct PartialPathEdge {
 }
 
 impl PartialPathEdge {
-    /// Returns whether one edge shadows another.  Note that shadowing is not commutative — if path
-    /// A shadows path B, the reverse is not true.
+    /// Returns whether one edge shadows another, which is the case if both edges leave the same
+    /// node, and this edge has a strictly higher precedence.  Precedences are signed, so an edge
+    /// with a negative precedence is shadowed by an edge with the default precedence of 0.  Note
+    /// that shadowing is not commutative — if path A shadows path B, the reverse is not true.
     pub fn shadows(self, other: PartialPathEdge) -> bool {
         self.source_node_id == other.source_node_id && self.precedence > other.precedence
     }
@@ -1848,6 +2038,70 @@ impl PartialPath {
         graph[self.end_node].is_jump_to()
     }
 
+    /// Returns an iterator over the nodes that this partial path traverses, in order, starting
+    /// with its start node and ending with its end node.  The nodes are reconstructed from the
+    /// source nodes of the path's edges, so the iterator yields one node more than the path has
+    /// edges.  Panics if an edge refers to a node that does not exist in `graph`.
+    pub fn nodes<'a>(
+        &self,
+        graph: &'a StackGraph,
+        partials: &'a mut PartialPaths,
+    ) -> impl Iterator<Item = Handle<Node>> + 'a {
+        self.edges
+            .iter(partials)
+            .map(move |edge| {
+                graph
+                    .node_for_id(edge.source_node_id)
+                    .expect("partial path edge refers to a missing node")
+            })
+            .chain(std::iter::once(self.end_node))
+    }
+
+    /// Returns an owned summary of this partial path's endpoints and its pre- and postconditions,
+    /// which does not refer to any arenas.
+    pub fn to_summary(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+    ) -> PartialPathSummary {
+        let symbols = |stack: &PartialSymbolStack, partials: &mut PartialPaths| {
+            stack
+                .iter(partials)
+                .map(|symbol| graph[symbol.symbol].to_string())
+                .collect()
+        };
+        let scopes = |stack: &PartialScopeStack, partials: &mut PartialPaths| {
+            stack
+                .iter_scopes(partials)
+                .map(|scope| graph[scope].id())
+                .collect()
+        };
+        PartialPathSummary {
+            start_node: graph[self.start_node].id(),
+            end_node: graph[self.end_node].id(),
+            symbol_stack_precondition: symbols(&self.symbol_stack_precondition, partials),
+            symbol_stack_precondition_variable: self
+                .symbol_stack_precondition
+                .variable()
+                .map(Into::into),
+            symbol_stack_postcondition: symbols(&self.symbol_stack_postcondition, partials),
+            symbol_stack_postcondition_variable: self
+                .symbol_stack_postcondition
+                .variable()
+                .map(Into::into),
+            scope_stack_precondition: scopes(&self.scope_stack_precondition, partials),
+            scope_stack_precondition_variable: self
+                .scope_stack_precondition
+                .variable()
+                .map(Into::into),
+            scope_stack_postcondition: scopes(&self.scope_stack_postcondition, partials),
+            scope_stack_postcondition_variable: self
+                .scope_stack_postcondition
+                .variable()
+                .map(Into::into),
+        }
+    }
+
     /// Returns whether a partial path is cyclic---that is, it starts and ends at the same node,
     /// and its postcondition is compatible with its precondition.  If the path is cyclic, a
     /// tuple is returned indicating whether cycle requires strengthening the pre- or postcondition.
@@ -1862,7 +2116,7 @@ impl PartialPath {
         let mut rhs = self.clone();
         rhs.ensure_no_overlapping_variables(partials, lhs);
 
-        let join = match Self::compute_join(graph, partials, lhs, &rhs) {
+        let join = match Self::compute_join(graph, partials, lhs, &rhs, None) {
             Ok(join) => join,
             Err(_) => return None,
         };
@@ -2018,6 +2272,24 @@ pub enum Cyclicity {
     StrengthensPostcondition,
 }
 
+/// An owned summary of a partial path, which can be stored and compared without access to the
+/// arenas that the path was created in.  Stacks are listed from top to bottom.  Symbols are
+/// represented by their names; the scopes attached to scoped symbols are not included.
+/// Variables are represented by their numeric value.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PartialPathSummary {
+    pub start_node: NodeID,
+    pub end_node: NodeID,
+    pub symbol_stack_precondition: Vec<String>,
+    pub symbol_stack_precondition_variable: Option<u32>,
+    pub symbol_stack_postcondition: Vec<String>,
+    pub symbol_stack_postcondition_variable: Option<u32>,
+    pub scope_stack_precondition: Vec<NodeID>,
+    pub scope_stack_precondition_variable: Option<u32>,
+    pub scope_stack_postcondition: Vec<NodeID>,
+    pub scope_stack_postcondition_variable: Option<u32>,
+}
+
 impl<'a> DisplayWithPartialPaths for &'a PartialPath {
     fn prepare(&mut self, graph: &StackGraph, partials: &mut PartialPaths) {
         self.symbol_stack_precondition
@@ -2224,6 +2496,47 @@ impl PartialPath {
 
         Ok(())
     }
+
+    /// Resolves a _jump to scope_ node at the end of a partial path, continuing at the scope that
+    /// is on top of the postcondition scope stack.  If the partial path does not end in a _jump to
+    /// scope_ node, we do nothing.
+    ///
+    /// This is the pattern used by languages that attach scopes to symbols using _push scoped
+    /// symbol_ nodes.  For instance, in
+    ///
+    /// ``` python
+    /// def foo(x):
+    ///   return x
+    /// foo(A).bar
+    /// ```
+    ///
+    /// the reference to `bar` pushes a scoped `()` symbol, which has the caller's exported scope
+    /// attached.  Popping it in `foo` moves that scope onto the scope stack, and when `x` resolves
+    /// to the parameter, a _jump to scope_ node continues at the caller's scope, where `A` is
+    /// found.
+    ///
+    /// If the postcondition scope stack starts with a known scope, the path continues at that
+    /// scope, as in [`resolve_from_postcondition`][Self::resolve_from_postcondition].  If the scope
+    /// stack only consists of a variable, the scope is only known to a caller, and the path is
+    /// resolved to `fallback`, as in [`resolve_to_node`][Self::resolve_to_node].  We return
+    /// [`PathResolutionError::EmptyScopeStack`][] if the scope stack is empty, and
+    /// [`PathResolutionError::ScopeStackUnsatisfied`][] if the scope is unknown and no fallback is
+    /// given.
+    pub fn resolve_jump_to_scope(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        fallback: Option<Handle<Node>>,
+    ) -> Result<(), PathResolutionError> {
+        self.resolve_from_postcondition(graph, partials)?;
+        if !graph[self.end_node].is_jump_to() {
+            return Ok(());
+        }
+        match fallback {
+            Some(node) => self.resolve_to_node(graph, partials, node),
+            None => Err(PathResolutionError::ScopeStackUnsatisfied),
+        }
+    }
 }
 
 impl Node {
@@ -2287,8 +2600,9 @@ impl Node {
             }
             Self::PopSymbol(sink) => {
                 // Ideally we want to pop sink's symbol off from top of the symbol stack postcondition.
+                // A wildcard symbol pops whatever symbol is on top.
                 if let Some(top) = symbol_stack_postcondition.pop_front(partials) {
-                    if top.symbol != sink.symbol {
+                    if !graph.is_wildcard_symbol(sink.symbol) && top.symbol != sink.symbol {
                         return Err(PathResolutionError::IncorrectPoppedSymbol);
                     }
                     if top.scopes.is_some() {
@@ -2297,7 +2611,8 @@ impl Node {
                 } else if symbol_stack_postcondition.has_variable() {
                     // If the symbol stack postcondition is empty but has a variable, then we can update
                     // the _precondition_ to indicate that the symbol stack needs to contain this symbol
-                    // in order to successfully use this partial path.
+                    // in order to successfully use this partial path.  For a wildcard, the precondition
+                    // contains the wildcard symbol, which unifies with any symbol.
                     let precondition_symbol = PartialScopedSymbol {
                         symbol: sink.symbol,
                         scopes: ControlledOption::none(),
@@ -2370,6 +2685,7 @@ impl Node {
     /// in the correct behavior for path concatenation.
     fn halfopen_closed_partial_precondition(
         &self,
+        graph: &StackGraph,
         partials: &mut PartialPaths,
         symbol_stack: &mut PartialSymbolStack,
         scope_stack: &mut PartialScopeStack,
@@ -2390,7 +2706,7 @@ impl Node {
                 let symbol = symbol_stack
                     .pop_front(partials)
                     .ok_or(PathResolutionError::EmptySymbolStack)?;
-                if symbol.symbol != node.symbol {
+                if !graph.is_wildcard_symbol(node.symbol) && symbol.symbol != node.symbol {
                     return Err(PathResolutionError::IncorrectPoppedSymbol);
                 }
             }
@@ -2468,17 +2784,30 @@ impl PartialPath {
     /// common, then we ensure that the variables bind to the same values on both sides.  It's your
     /// responsibility to update the two partial paths so that they have no variables in common, if
     /// that's needed for your use case.
-    #[cfg_attr(not(feature = "copious-debugging"), allow(unused_variables))]
     pub fn concatenate(
         &mut self,
         graph: &StackGraph,
         partials: &mut PartialPaths,
         rhs: &PartialPath,
+    ) -> Result<(), PathResolutionError> {
+        self.concatenate_traced(graph, partials, rhs, None)
+    }
+
+    /// Appends a partial path to this one like [`concatenate`][Self::concatenate].  If `trace` is
+    /// given and the postcondition and precondition do not unify, the first mismatch is recorded
+    /// in it.
+    #[cfg_attr(not(feature = "copious-debugging"), allow(unused_variables))]
+    fn concatenate_traced(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        rhs: &PartialPath,
+        trace: Option<&mut ConcatenationMismatch>,
     ) -> Result<(), PathResolutionError> {
         let lhs = self;
 
         #[cfg_attr(not(feature = "copious-debugging"), allow(unused_mut))]
-        let mut join = Self::compute_join(graph, partials, lhs, rhs)?;
+        let mut join = Self::compute_join(graph, partials, lhs, rhs, trace)?;
         #[cfg(feature = "copious-debugging")]
         {
             let unified_symbol_stack = join
@@ -2530,17 +2859,78 @@ impl PartialPath {
     }
 
     /// Compute the bindings to join to partial paths. It is the caller's responsibility
-    /// to ensure non-overlapping variables, if that is required.
+    /// to ensure non-overlapping variables, if that is required.  If `trace` is given and the
+    /// stacks do not unify, the first mismatch and the bindings computed up to it are recorded in
+    /// it.
     fn compute_join(
         graph: &StackGraph,
         partials: &mut PartialPaths,
         lhs: &PartialPath,
         rhs: &PartialPath,
+        mut trace: Option<&mut ConcatenationMismatch>,
     ) -> Result<Join, PathResolutionError> {
         if lhs.end_node != rhs.start_node {
             return Err(PathResolutionError::IncorrectSourceNode);
         }
 
+        let (
+            lhs_symbol_stack_postcondition,
+            lhs_scope_stack_postcondition,
+            rhs_symbol_stack_precondition,
+            rhs_scope_stack_precondition,
+        ) = Self::halfopen_join_stacks(graph, partials, lhs, rhs);
+
+        let mut symbol_bindings = PartialSymbolStackBindings::new();
+        let mut scope_bindings = PartialScopeStackBindings::new();
+        let unified_stacks = lhs_symbol_stack_postcondition
+            .unify_traced(
+                partials,
+                rhs_symbol_stack_precondition,
+                &mut symbol_bindings,
+                &mut scope_bindings,
+                trace.as_deref_mut(),
+            )
+            .and_then(|unified_symbol_stack| {
+                let unified_scope_stack = lhs_scope_stack_postcondition.unify_traced(
+                    partials,
+                    rhs_scope_stack_precondition,
+                    &mut scope_bindings,
+                    trace.as_deref_mut(),
+                )?;
+                Ok((unified_symbol_stack, unified_scope_stack))
+            });
+        let (unified_symbol_stack, unified_scope_stack) = match unified_stacks {
+            Ok(unified_stacks) => unified_stacks,
+            Err(err) => {
+                if let Some(trace) = trace {
+                    trace.symbol_bindings = symbol_bindings;
+                    trace.scope_bindings = scope_bindings;
+                }
+                return Err(err);
+            }
+        };
+
+        Ok(Join {
+            unified_symbol_stack,
+            unified_scope_stack,
+            symbol_bindings,
+            scope_bindings,
+        })
+    }
+
+    /// Returns the left postcondition and the right precondition of a join, made half-open so
+    /// that they can be unified.
+    fn halfopen_join_stacks(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        lhs: &PartialPath,
+        rhs: &PartialPath,
+    ) -> (
+        PartialSymbolStack,
+        PartialScopeStack,
+        PartialSymbolStack,
+        PartialScopeStack,
+    ) {
         // Ensure the right post- and left precondition are half-open, so we can unify them.
         let mut lhs_symbol_stack_postcondition = lhs.symbol_stack_postcondition;
         let mut lhs_scope_stack_postcondition = lhs.scope_stack_postcondition;
@@ -2561,6 +2951,7 @@ impl PartialPath {
             });
         graph[rhs.start_node]
             .halfopen_closed_partial_precondition(
+                graph,
                 partials,
                 &mut rhs_symbol_stack_precondition,
                 &mut rhs_scope_stack_precondition,
@@ -2573,29 +2964,77 @@ impl PartialPath {
                 );
             });
 
-        let mut symbol_bindings = PartialSymbolStackBindings::new();
-        let mut scope_bindings = PartialScopeStackBindings::new();
-        let unified_symbol_stack = lhs_symbol_stack_postcondition.unify(
+        // Wildcards in the right precondition pop whatever symbol the left postcondition has at
+        // that position.
+        rhs_symbol_stack_precondition = rhs_symbol_stack_precondition.instantiate_wildcards(
+            graph,
             partials,
+            lhs_symbol_stack_postcondition,
+        );
+
+        (
+            lhs_symbol_stack_postcondition,
+            lhs_scope_stack_postcondition,
             rhs_symbol_stack_precondition,
-            &mut symbol_bindings,
-            &mut scope_bindings,
-        )?;
-        let unified_scope_stack = lhs_scope_stack_postcondition.unify(
-            partials,
             rhs_scope_stack_precondition,
-            &mut scope_bindings,
-        )?;
+        )
+    }
 
-        Ok(Join {
-            unified_symbol_stack,
-            unified_scope_stack,
-            symbol_bindings,
-            scope_bindings,
-        })
+    /// Attempts to append a partial path to this one, like [`concatenate`][Self::concatenate].  If
+    /// the partial paths are not compatible, we return a [`ConcatenationMismatch`][] that describes
+    /// where the left postcondition and the right precondition diverge, instead of only the
+    /// reason.
+    pub fn concatenate_checked(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        rhs: &PartialPath,
+    ) -> Result<(), Box<ConcatenationMismatch>> {
+        let mut mismatch = ConcatenationMismatch {
+            error: PathResolutionError::IncorrectSourceNode,
+            unified_symbols: Vec::new(),
+            mismatched_symbols: None,
+            unified_scopes: Vec::new(),
+            mismatched_scopes: None,
+            symbol_bindings: PartialSymbolStackBindings::new(),
+            scope_bindings: PartialScopeStackBindings::new(),
+        };
+        self.concatenate_traced(graph, partials, rhs, Some(&mut mismatch))
+            .map_err(|error| Box::new(ConcatenationMismatch { error, ..mismatch }))
     }
 }
 
+/// Describes why two partial paths could not be concatenated.  Returned by
+/// [`PartialPath::concatenate_checked`][].
+pub struct ConcatenationMismatch {
+    /// The reason the concatenation failed.
+    pub error: PathResolutionError,
+    /// The symbols at the front of the left postcondition and the right precondition that were
+    /// unified before the mismatch.
+    pub unified_symbols: Vec<PartialScopedSymbol>,
+    /// The first symbols that did not unify, if the symbol stacks are incompatible.
+    pub mismatched_symbols: Option<Mismatch<PartialScopedSymbol>>,
+    /// The scopes at the front of the left postcondition and the right precondition that were
+    /// unified before the mismatch.
+    pub unified_scopes: Vec<Handle<Node>>,
+    /// The first scopes that did not unify, if the scope stacks are incompatible.
+    pub mismatched_scopes: Option<Mismatch<Handle<Node>>>,
+    /// The symbol stack variable bindings computed before the mismatch.
+    pub symbol_bindings: PartialSymbolStackBindings,
+    /// The scope stack variable bindings computed before the mismatch.
+    pub scope_bindings: PartialScopeStackBindings,
+}
+
+/// A pair of stack elements that did not unify.  An element is `None` if that side's stack ended
+/// without a variable to absorb the other side's remaining elements.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Mismatch<T> {
+    /// The element of the left partial path's postcondition.
+    pub left: Option<T>,
+    /// The element of the right partial path's precondition.
+    pub right: Option<T>,
+}
+
 struct Join {
     #[cfg_attr(not(feature = "copious-debugging"), allow(dead_code))]
     pub unified_symbol_stack: PartialSymbolStack,
@@ -2614,21 +3053,68 @@ pub struct PartialPaths {
     pub(crate) partial_symbol_stacks: DequeArena<PartialScopedSymbol>,
     pub(crate) partial_scope_stacks: DequeArena<Handle<Node>>,
     pub(crate) partial_path_edges: DequeArena<PartialPathEdge>,
+    max_variable_bindings: usize,
 }
 
+/// The default maximum number of symbol stack or scope stack variable bindings during unification.
+pub const DEFAULT_MAX_VARIABLE_BINDINGS: usize = 1 << 16;
+
 impl PartialPaths {
     pub fn new() -> PartialPaths {
         PartialPaths {
             partial_symbol_stacks: Deque::new_arena(),
             partial_scope_stacks: Deque::new_arena(),
             partial_path_edges: Deque::new_arena(),
+            max_variable_bindings: DEFAULT_MAX_VARIABLE_BINDINGS,
         }
     }
 
-    #[cfg_attr(not(feature = "storage"), allow(dead_code))]
-    pub(crate) fn clear(&mut self) {
+    /// Returns the maximum number of symbol stack or scope stack variable bindings during
+    /// unification.
+    pub fn max_variable_bindings(&self) -> usize {
+        self.max_variable_bindings
+    }
+
+    /// Sets the maximum number of symbol stack or scope stack variable bindings during
+    /// unification.  Bindings are stored densely by variable number, so binding a variable
+    /// numbered higher than this limit fails with [`PathResolutionError::TooManyVariableBindings`][]
+    /// instead of allocating room for all lower-numbered variables.  This protects path stitching
+    /// against pathological graphs, where variable offsets accumulate during concatenation.
+    pub fn set_max_variable_bindings(&mut self, max_variable_bindings: usize) {
+        self.max_variable_bindings = max_variable_bindings;
+    }
+
+    /// Removes all of the partial symbol stacks, scope stacks, and path edge lists from this arena,
+    /// so that it can be reused for another batch of partial paths.
+    ///
+    /// The arena keeps the memory that it has already allocated, so reusing an arena avoids
+    /// reallocating its storage.  Any partial paths, stacks, or edge lists that you created using
+    /// this arena before calling this method become invalid, and must not be used afterwards.
+    pub fn clear(&mut self) {
         self.partial_symbol_stacks.clear();
         self.partial_scope_stacks.clear();
         self.partial_path_edges.clear();
     }
+
+    /// Removes all of the partial symbol stacks, scope stacks, and path edge lists from this arena,
+    /// like [`clear`][Self::clear], and releases allocated memory, keeping room for at least
+    /// `min_capacity` elements in each of the underlying arenas.
+    ///
+    /// Clearing and shrinking happen in one step, because shrinking is only useful once the
+    /// contents are discarded.  This is useful for long-lived arenas that are reused for bursts of
+    /// work, which would otherwise hold on to their high-water mark of memory.
+    pub fn clear_and_shrink_to(&mut self, min_capacity: usize) {
+        self.clear();
+        self.partial_symbol_stacks.shrink_to(min_capacity);
+        self.partial_scope_stacks.shrink_to(min_capacity);
+        self.partial_path_edges.shrink_to(min_capacity);
+    }
+
+    /// Returns the total number of elements that the underlying arenas can hold without
+    /// reallocating.
+    pub fn capacity(&self) -> usize {
+        self.partial_symbol_stacks.capacity()
+            + self.partial_scope_stacks.capacity()
+            + self.partial_path_edges.capacity()
+    }
 }
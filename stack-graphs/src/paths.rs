@@ -17,49 +17,78 @@
 
 use std::collections::VecDeque;
 
+use thiserror::Error;
+
+use crate::arena::Handle;
+use crate::graph::Node;
+use crate::graph::StackGraph;
+use crate::partial::PartialPath;
+use crate::partial::PartialPaths;
+use crate::CancellationError;
+use crate::CancellationFlag;
+
 /// Errors that can occur during the path resolution process.
-#[derive(Debug)]
+#[derive(Debug, Error)]
 pub enum PathResolutionError {
     /// The path is cyclic, and the cycle is disallowed.
+    #[error("disallowed cycle in path")]
     DisallowedCycle,
     /// The path contains a _jump to scope_ node, but there are no scopes on the scope stack to
     /// jump to.
+    #[error("cannot jump to scope: the scope stack is empty")]
     EmptyScopeStack,
     /// The path contains a _pop symbol_ or _pop scoped symbol_ node, but there are no symbols on
     /// the symbol stack to pop off.
+    #[error("cannot pop symbol: the symbol stack is empty")]
     EmptySymbolStack,
     /// The partial path contains multiple references to a scope stack variable, and those
     /// references can't unify on a single scope stack.
+    #[error("incompatible bindings for scope stack variable")]
     IncompatibleScopeStackVariables,
     /// The partial path contains multiple references to a symbol stack variable, and those
     /// references can't unify on a single symbol stack.
+    #[error("incompatible bindings for symbol stack variable")]
     IncompatibleSymbolStackVariables,
     /// The partial path contains edges from multiple files.
+    #[error("partial path contains edges from multiple files")]
     IncorrectFile,
     /// The path contains a _pop symbol_ or _pop scoped symbol_ node, but the symbol at the top of
     /// the symbol stack does not match.
+    #[error("popped symbol does not match the top of the symbol stack")]
     IncorrectPoppedSymbol,
     /// The path contains an edge whose source node does not match the sink node of the preceding
     /// edge.
+    #[error("edge source does not match the end of the path")]
     IncorrectSourceNode,
     /// The path contains a _pop scoped symbol_ node, but the symbol at the top of the symbol stack
     /// does not have an attached scope list to pop off.
+    #[error("popped symbol has no attached scope list")]
     MissingAttachedScopeList,
     /// The path's scope stack does not satisfy the partial path's scope stack precondition.
+    #[error("scope stack does not satisfy the precondition")]
     ScopeStackUnsatisfied,
     /// The path's symbol stack does not satisfy the partial path's symbol stack precondition.
+    #[error("symbol stack does not satisfy the precondition")]
     SymbolStackUnsatisfied,
     /// The partial path's postcondition references a symbol stack variable that isn't present in
     /// the precondition.
+    #[error("unbound symbol stack variable in postcondition")]
     UnboundSymbolStackVariable,
     /// The partial path's postcondition references a scope stack variable that isn't present in
     /// the precondition.
+    #[error("unbound scope stack variable in postcondition")]
     UnboundScopeStackVariable,
     /// The path contains a _pop symbol_ node, but the symbol at the top of the symbol stack has an
     /// attached scope list that we weren't expecting.
+    #[error("popped symbol has an unexpected attached scope list")]
     UnexpectedAttachedScopeList,
     /// A _push scoped symbol_ node referes to an exported scope node that doesn't exist.
+    #[error("attached scope does not exist")]
     UnknownAttachedScope,
+    /// A symbol stack or scope stack variable exceeds the [maximum number of variable
+    /// bindings][crate::partial::PartialPaths::set_max_variable_bindings].
+    #[error("too many variable bindings")]
+    TooManyVariableBindings,
 }
 
 /// A collection that can be used to receive the results of the [`Path::extend`][] method.
@@ -94,3 +123,80 @@ impl<T> Extend<T> for VecDeque<T> {
         self.push_back(item);
     }
 }
+
+/// Finds complete paths by exhaustively extending paths from reference nodes one edge at a time.
+///
+/// This is a simple, but slow, reference implementation of name resolution.  It performs no cycle
+/// detection, and instead bounds the search by a maximum number of extensions.  It is primarily
+/// useful as an oracle when testing the partial path stitching algorithm in the
+/// [`stitching`][crate::stitching] module.
+pub struct Paths {
+    partials: PartialPaths,
+}
+
+impl Paths {
+    pub fn new() -> Paths {
+        Paths {
+            partials: PartialPaths::new(),
+        }
+    }
+
+    /// Returns the arena that stores the contents of the paths found by this instance.
+    pub fn partials(&mut self) -> &mut PartialPaths {
+        &mut self.partials
+    }
+
+    /// Finds all complete paths that start at any of the reference nodes in `start_nodes`.  The
+    /// search stops after `max_steps` paths have been extended with an edge, in which case the
+    /// result might not contain all complete paths.
+    pub fn find_all_complete_paths<I>(
+        &mut self,
+        graph: &StackGraph,
+        start_nodes: I,
+        max_steps: usize,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<PartialPath>, CancellationError>
+    where
+        I: IntoIterator<Item = Handle<Node>>,
+    {
+        let partials = &mut self.partials;
+        let mut queue = start_nodes
+            .into_iter()
+            .filter(|node| graph[*node].is_reference())
+            .map(|node| {
+                let mut path = PartialPath::from_node(graph, partials, node);
+                path.eliminate_precondition_stack_variables(partials);
+                path
+            })
+            .collect::<VecDeque<_>>();
+
+        let mut complete_paths = Vec::new();
+        let mut steps = 0;
+        while let Some(mut path) = queue.pop_front() {
+            cancellation_flag.check("finding complete paths")?;
+            if path.resolve_from_postcondition(graph, partials).is_err() {
+                continue;
+            }
+            if path.is_complete(graph) {
+                complete_paths.push(path.clone());
+            }
+            for edge in graph.outgoing_edges(path.end_node) {
+                if steps == max_steps {
+                    return Ok(complete_paths);
+                }
+                steps += 1;
+                let mut extended_path = path.clone();
+                if extended_path.append(graph, partials, edge).is_ok() {
+                    queue.push_back(extended_path);
+                }
+            }
+        }
+        Ok(complete_paths)
+    }
+}
+
+impl Default for Paths {
+    fn default() -> Paths {
+        Paths::new()
+    }
+}
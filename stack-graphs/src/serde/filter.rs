@@ -34,6 +34,16 @@ pub trait Filter {
         paths: &PartialPaths,
         path: &PartialPath,
     ) -> bool;
+
+    /// Return whether source info must be included for included nodes.
+    fn include_source_info(&self) -> bool {
+        true
+    }
+
+    /// Return whether debug info must be included for included nodes and edges.
+    fn include_debug_info(&self) -> bool {
+        true
+    }
 }
 
 impl<F> Filter for F
@@ -129,6 +139,73 @@ impl Filter for FileFilter {
     }
 }
 
+/// Filter implementation that excludes [generated][crate::graph::StackGraph::is_generated] nodes,
+/// such as builtins, and everything connected to them.
+pub struct NoGeneratedFilter;
+
+impl Filter for NoGeneratedFilter {
+    fn include_file(&self, _graph: &StackGraph, _file: &Handle<File>) -> bool {
+        true
+    }
+
+    fn include_node(&self, graph: &StackGraph, node: &Handle<Node>) -> bool {
+        !graph.is_generated(*node)
+    }
+
+    fn include_edge(
+        &self,
+        _graph: &StackGraph,
+        _source: &Handle<Node>,
+        _sink: &Handle<Node>,
+    ) -> bool {
+        true
+    }
+
+    fn include_partial_path(
+        &self,
+        _graph: &StackGraph,
+        _paths: &PartialPaths,
+        _path: &PartialPath,
+    ) -> bool {
+        true
+    }
+}
+
+/// Filter implementation that includes the same elements as another filter, but omits all source
+/// and debug info. The resulting graph contains only what is needed for name resolution.
+pub struct NoInfoFilter<'a>(pub &'a dyn Filter);
+
+impl Filter for NoInfoFilter<'_> {
+    fn include_file(&self, graph: &StackGraph, file: &Handle<File>) -> bool {
+        self.0.include_file(graph, file)
+    }
+
+    fn include_node(&self, graph: &StackGraph, node: &Handle<Node>) -> bool {
+        self.0.include_node(graph, node)
+    }
+
+    fn include_edge(&self, graph: &StackGraph, source: &Handle<Node>, sink: &Handle<Node>) -> bool {
+        self.0.include_edge(graph, source, sink)
+    }
+
+    fn include_partial_path(
+        &self,
+        graph: &StackGraph,
+        paths: &PartialPaths,
+        path: &PartialPath,
+    ) -> bool {
+        self.0.include_partial_path(graph, paths, path)
+    }
+
+    fn include_source_info(&self) -> bool {
+        false
+    }
+
+    fn include_debug_info(&self) -> bool {
+        false
+    }
+}
+
 /// Filter implementation that enforces all implications of another filter.
 /// For example, that nodes frome excluded files are not included, etc.
 pub(crate) struct ImplicationFilter<'a>(pub &'a dyn Filter);
@@ -174,4 +251,12 @@ impl Filter for ImplicationFilter<'_> {
         }
         true
     }
+
+    fn include_source_info(&self) -> bool {
+        self.0.include_source_info()
+    }
+
+    fn include_debug_info(&self) -> bool {
+        self.0.include_debug_info()
+    }
 }
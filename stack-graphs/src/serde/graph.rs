@@ -8,11 +8,16 @@
 use thiserror::Error;
 
 use crate::arena::Handle;
+use crate::CancellationError;
+use crate::CancellationFlag;
+use crate::NoCancellation;
 
 use super::Filter;
 use super::ImplicationFilter;
 use super::NoFilter;
 
+/// A serializable stack graph.  Symbols and other strings are stored inline by content instead of
+/// by handle, so the serialized form does not depend on the order in which they were interned.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
@@ -34,6 +39,20 @@ pub enum Error {
     InvalidStackVariable(u32),
     #[error("failed to locate node `{0}` in graph")]
     NodeNotFound(NodeID),
+    #[error("cancelled at {0}")]
+    Cancelled(&'static str),
+    #[error("graph has {0} nodes, more than the limit of {1}")]
+    TooManyNodes(usize, usize),
+    #[error("graph has {0} edges, more than the limit of {1}")]
+    TooManyEdges(usize, usize),
+    #[error("graph contains a string of length {0}, more than the limit of {1}")]
+    StringTooLong(usize, usize),
+}
+
+impl From<CancellationError> for Error {
+    fn from(value: CancellationError) -> Self {
+        Self::Cancelled(value.0)
+    }
 }
 
 impl StackGraph {
@@ -42,21 +61,61 @@ impl StackGraph {
     }
 
     pub fn from_graph_filter<'a>(graph: &crate::graph::StackGraph, filter: &'a dyn Filter) -> Self {
+        Self::from_graph_filter_with_progress(graph, filter, &NoCancellation, &mut |_, _| {})
+            .expect("cannot be cancelled")
+    }
+
+    /// Creates a serializable graph like [`from_graph_filter`][Self::from_graph_filter], checking
+    /// the cancellation flag for every node.  The `progress` callback is called for every node
+    /// with the number of nodes processed so far, and the total number of nodes in the graph.
+    pub fn from_graph_filter_with_progress(
+        graph: &crate::graph::StackGraph,
+        filter: &dyn Filter,
+        cancellation_flag: &dyn CancellationFlag,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<Self, Error> {
         let filter = ImplicationFilter(filter);
         let files = graph.filter_files(&filter);
-        let nodes = graph.filter_nodes(&filter);
+        let nodes = graph.filter_nodes(&filter, cancellation_flag, progress)?;
         let edges = graph.filter_edges(&filter);
-        Self {
+        Ok(Self {
             files,
             nodes,
             edges,
-        }
+        })
     }
 
     pub fn load_into(&self, graph: &mut crate::graph::StackGraph) -> Result<(), Error> {
+        self.load_into_with_progress(graph, &NoCancellation, &mut |_, _| {})
+    }
+
+    /// Loads this graph into a stack graph like [`load_into`][Self::load_into], after checking
+    /// that it does not exceed the given limits.  If a limit is exceeded, an error is returned and
+    /// nothing is loaded into the stack graph.  Use this when loading graphs from untrusted sources.
+    pub fn load_into_with_limits(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+        limits: &Limits,
+    ) -> Result<(), Error> {
+        limits.check(self)?;
+        self.load_into(graph)
+    }
+
+    /// Loads this graph into a stack graph like [`load_into`][Self::load_into], checking the
+    /// cancellation flag for every node and edge.  The `progress` callback is called for every
+    /// node with the number of nodes loaded so far, and the total number of nodes to load.
+    ///
+    /// If loading is cancelled, the stack graph contains the files and nodes that were loaded
+    /// before cancellation.
+    pub fn load_into_with_progress(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+        cancellation_flag: &dyn CancellationFlag,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<(), Error> {
         self.load_files(graph)?;
-        self.load_nodes(graph)?;
-        self.load_edges(graph)?;
+        self.load_nodes(graph, cancellation_flag, progress)?;
+        self.load_edges(graph, cancellation_flag)?;
         Ok(())
     }
 
@@ -70,8 +129,15 @@ impl StackGraph {
         Ok(())
     }
 
-    fn load_nodes(&self, graph: &mut crate::graph::StackGraph) -> Result<(), Error> {
-        for node in &self.nodes.data {
+    fn load_nodes(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+        cancellation_flag: &dyn CancellationFlag,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<(), Error> {
+        let total = self.nodes.data.len();
+        for (index, node) in self.nodes.data.iter().enumerate() {
+            cancellation_flag.check("loading nodes")?;
             let handle = match node {
                 Node::DropScopes { id, .. } => {
                     let node_id = id.to_node_id(graph)?;
@@ -81,21 +147,36 @@ impl StackGraph {
                     id,
                     symbol,
                     is_definition,
+                    definition_rank,
                     ..
                 } => {
                     let node_id = id.to_node_id(graph)?;
                     let symbol_handle = graph.add_symbol(&symbol);
-                    graph.add_pop_scoped_symbol_node(node_id, symbol_handle, *is_definition)
+                    let handle =
+                        graph.add_pop_scoped_symbol_node(node_id, symbol_handle, *is_definition);
+                    if let (Some(handle), Some(rank)) = (handle, definition_rank) {
+                        graph.set_definition_rank(handle, *rank);
+                    }
+                    handle
                 }
                 Node::PopSymbol {
                     id,
                     symbol,
+                    is_wildcard,
                     is_definition,
+                    definition_rank,
                     ..
                 } => {
                     let node_id = id.to_node_id(graph)?;
-                    let symbol_handle = graph.add_symbol(&symbol);
-                    graph.add_pop_symbol_node(node_id, symbol_handle, *is_definition)
+                    let symbol_handle = match is_wildcard {
+                        true => graph.add_wildcard_symbol(),
+                        false => graph.add_symbol(&symbol),
+                    };
+                    let handle = graph.add_pop_symbol_node(node_id, symbol_handle, *is_definition);
+                    if let (Some(handle), Some(rank)) = (handle, definition_rank) {
+                        graph.set_definition_rank(handle, *rank);
+                    }
+                    handle
                 }
                 Node::PushScopedSymbol {
                     id,
@@ -143,8 +224,28 @@ impl StackGraph {
                             .as_ref()
                             .map(|st| graph.add_string(&st))
                             .into(),
-                        ..Default::default()
+                        containing_line: source_info
+                            .containing_line
+                            .as_ref()
+                            .map(|line| graph.add_string(&line))
+                            .into(),
+                        definiens_span: source_info.definiens_span.clone().unwrap_or_default(),
+                        fully_qualified_name: source_info
+                            .fully_qualified_name
+                            .as_ref()
+                            .map(|fqn| graph.add_string(&fqn))
+                            .into(),
                     };
+                    if let Some(selection_span) = &source_info.selection_span {
+                        graph.set_selection_span(handle, selection_span.clone());
+                    }
+                }
+
+                if node.keeps_paths() {
+                    graph.set_keeps_paths(handle, true);
+                }
+                if node.is_generated() {
+                    graph.set_generated(handle, true);
                 }
 
                 // load debug-info of each node
@@ -160,19 +261,26 @@ impl StackGraph {
                     );
                 }
             }
+            progress(index + 1, total);
         }
         Ok(())
     }
 
-    fn load_edges(&self, graph: &mut crate::graph::StackGraph) -> Result<(), Error> {
+    fn load_edges(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), Error> {
         // load edges into stack-graph
         for Edge {
             source,
             sink,
             precedence,
+            kind,
             debug_info,
         } in &self.edges.data
         {
+            cancellation_flag.check("loading edges")?;
             let source_id = source.to_node_id(graph)?;
             let sink_id = sink.to_node_id(graph)?;
 
@@ -184,6 +292,9 @@ impl StackGraph {
                 .ok_or(Error::InvalidGlobalNodeID(sink.local_id))?;
 
             graph.add_edge(source_handle, sink_handle, *precedence);
+            if let Some(kind) = kind {
+                graph.set_edge_kind(source_handle, sink_handle, *kind);
+            }
 
             // load debug-info of each node
             if let Some(debug_info) = debug_info {
@@ -202,6 +313,263 @@ impl StackGraph {
     }
 }
 
+/// Limits on the size of a serializable graph, which are checked while the graph is
+/// [deserialized][Self::deserialize], or before it is [loaded][StackGraph::load_into_with_limits]
+/// into a stack graph.  By default, there are no limits.
+///
+/// The serialized representation is flat, so deserializing it does not recurse deeply.  The
+/// limits guard against graphs that are so large that loading them, which interns all strings and
+/// allocates all nodes and edges in the stack graph, exhausts the available memory.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Limits {
+    /// The maximum number of nodes.
+    pub max_nodes: usize,
+    /// The maximum number of edges.
+    pub max_edges: usize,
+    /// The maximum length in bytes of any string, such as file names, symbols, and debug info.
+    pub max_string_length: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_nodes: usize::MAX,
+            max_edges: usize::MAX,
+            max_string_length: usize::MAX,
+        }
+    }
+}
+
+impl Limits {
+    /// Checks that a serializable graph does not exceed these limits.
+    pub fn check(&self, graph: &StackGraph) -> Result<(), Error> {
+        if graph.nodes.data.len() > self.max_nodes {
+            return Err(Error::TooManyNodes(graph.nodes.data.len(), self.max_nodes));
+        }
+        if graph.edges.data.len() > self.max_edges {
+            return Err(Error::TooManyEdges(graph.edges.data.len(), self.max_edges));
+        }
+        for file in &graph.files.data {
+            self.check_string(file)?;
+        }
+        for node in &graph.nodes.data {
+            self.check_node(node)?;
+        }
+        for edge in &graph.edges.data {
+            self.check_edge(edge)?;
+        }
+        Ok(())
+    }
+
+    /// Deserializes a serializable graph, checking these limits while deserializing.  Unlike
+    /// [`check`][Self::check], which requires the whole graph to be deserialized first, this
+    /// stops deserializing files, nodes, and edges as soon as a limit is exceeded.  The rest of
+    /// the input is not read, so the error reports one more node or edge than the limit instead of
+    /// the actual number.  Each element is checked once it has been deserialized, so a single
+    /// oversized string is still read into memory.  To bound that as well, limit the size of the input, for example using
+    /// [`Read::take`][std::io::Read::take].
+    #[cfg(feature = "serde")]
+    pub fn deserialize<'de, D>(&self, deserializer: D) -> Result<StackGraph, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        serde::de::DeserializeSeed::deserialize(self, deserializer)
+    }
+
+    fn check_node(&self, node: &Node) -> Result<(), Error> {
+        self.check_node_id(node.id())?;
+        match node {
+            Node::PopScopedSymbol { symbol, .. }
+            | Node::PopSymbol { symbol, .. }
+            | Node::PushSymbol { symbol, .. } => self.check_string(symbol)?,
+            Node::PushScopedSymbol { symbol, scope, .. } => {
+                self.check_string(symbol)?;
+                self.check_node_id(scope)?;
+            }
+            _ => {}
+        }
+        if let Some(source_info) = node.source_info() {
+            for string in [
+                &source_info.syntax_type,
+                &source_info.containing_line,
+                &source_info.fully_qualified_name,
+            ]
+            .iter()
+            .filter_map(|string| string.as_ref())
+            {
+                self.check_string(string)?;
+            }
+        }
+        self.check_debug_info(node.debug_info())
+    }
+
+    fn check_edge(&self, edge: &Edge) -> Result<(), Error> {
+        self.check_node_id(&edge.source)?;
+        self.check_node_id(&edge.sink)?;
+        self.check_debug_info(edge.debug_info.as_ref())
+    }
+
+    fn check_node_id(&self, id: &NodeID) -> Result<(), Error> {
+        match &id.file {
+            Some(file) => self.check_string(file),
+            None => Ok(()),
+        }
+    }
+
+    fn check_debug_info(&self, debug_info: Option<&DebugInfo>) -> Result<(), Error> {
+        for entry in debug_info.iter().flat_map(|i| i.data.iter()) {
+            self.check_string(&entry.key)?;
+            self.check_string(&entry.value)?;
+        }
+        Ok(())
+    }
+
+    fn check_string(&self, string: &str) -> Result<(), Error> {
+        if string.len() > self.max_string_length {
+            return Err(Error::StringTooLong(string.len(), self.max_string_length));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::DeserializeSeed<'de> for &Limits {
+    type Value = StackGraph;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_struct("StackGraph", &["files", "nodes", "edges"], self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::Visitor<'de> for &Limits {
+    type Value = StackGraph;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a stack graph")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        use serde::de::Error as _;
+        let mut files = None;
+        let mut nodes = None;
+        let mut edges = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "files" => {
+                    if files.is_some() {
+                        return Err(A::Error::duplicate_field("files"));
+                    }
+                    files = Some(map.next_value_seed(LimitedSeq {
+                        limits: self,
+                        max_len: usize::MAX,
+                        too_many: |_, _| unreachable!(),
+                        check: |limits, elem: &String| limits.check_string(elem),
+                    })?);
+                }
+                "nodes" => {
+                    if nodes.is_some() {
+                        return Err(A::Error::duplicate_field("nodes"));
+                    }
+                    nodes = Some(map.next_value_seed(LimitedSeq {
+                        limits: self,
+                        max_len: self.max_nodes,
+                        too_many: Error::TooManyNodes,
+                        check: Limits::check_node,
+                    })?);
+                }
+                "edges" => {
+                    if edges.is_some() {
+                        return Err(A::Error::duplicate_field("edges"));
+                    }
+                    edges = Some(map.next_value_seed(LimitedSeq {
+                        limits: self,
+                        max_len: self.max_edges,
+                        too_many: Error::TooManyEdges,
+                        check: Limits::check_edge,
+                    })?);
+                }
+                _ => {
+                    map.next_value::<serde::de::IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(StackGraph {
+            files: Files {
+                data: files.ok_or_else(|| A::Error::missing_field("files"))?,
+            },
+            nodes: Nodes {
+                data: nodes.ok_or_else(|| A::Error::missing_field("nodes"))?,
+            },
+            edges: Edges {
+                data: edges.ok_or_else(|| A::Error::missing_field("edges"))?,
+            },
+        })
+    }
+}
+
+/// Deserializes a sequence, checking every element as soon as it has been deserialized, and
+/// failing as soon as there are too many.
+#[cfg(feature = "serde")]
+struct LimitedSeq<'a, T> {
+    limits: &'a Limits,
+    max_len: usize,
+    too_many: fn(usize, usize) -> Error,
+    check: fn(&Limits, &T) -> Result<(), Error>,
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::de::DeserializeSeed<'de> for LimitedSeq<'_, T>
+where
+    T: serde::Deserialize<'de>,
+{
+    type Value = Vec<T>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::de::Visitor<'de> for LimitedSeq<'_, T>
+where
+    T: serde::Deserialize<'de>,
+{
+    type Value = Vec<T>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        use serde::de::Error as _;
+        let mut result = Vec::new();
+        while let Some(elem) = seq.next_element::<T>()? {
+            (self.check)(self.limits, &elem).map_err(A::Error::custom)?;
+            if result.len() == self.max_len {
+                return Err(A::Error::custom((self.too_many)(
+                    self.max_len + 1,
+                    self.max_len,
+                )));
+            }
+            result.push(elem);
+        }
+        Ok(result)
+    }
+}
+
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 #[cfg_attr(
     feature = "serde",
@@ -237,6 +605,16 @@ pub enum Node {
         id: NodeID,
         source_info: Option<SourceInfo>,
         debug_info: Option<DebugInfo>,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "std::ops::Not::not")
+        )]
+        keeps_paths: bool,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "std::ops::Not::not")
+        )]
+        is_generated: bool,
     },
 
     JumpToScope {
@@ -249,16 +627,49 @@ pub enum Node {
         id: NodeID,
         symbol: String,
         is_definition: bool,
+        /// The [rank][crate::graph::StackGraph::definition_rank] of the definition, omitted if it
+        /// has not been set.
+        definition_rank: Option<i32>,
         source_info: Option<SourceInfo>,
         debug_info: Option<DebugInfo>,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "std::ops::Not::not")
+        )]
+        keeps_paths: bool,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "std::ops::Not::not")
+        )]
+        is_generated: bool,
     },
 
     PopSymbol {
         id: NodeID,
         symbol: String,
+        /// Whether the symbol is the [wildcard symbol][crate::graph::StackGraph::add_wildcard_symbol],
+        /// omitted if it is not.
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "std::ops::Not::not")
+        )]
+        is_wildcard: bool,
         is_definition: bool,
+        /// The [rank][crate::graph::StackGraph::definition_rank] of the definition, omitted if it
+        /// has not been set.
+        definition_rank: Option<i32>,
         source_info: Option<SourceInfo>,
         debug_info: Option<DebugInfo>,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "std::ops::Not::not")
+        )]
+        keeps_paths: bool,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "std::ops::Not::not")
+        )]
+        is_generated: bool,
     },
 
     PushScopedSymbol {
@@ -268,6 +679,16 @@ pub enum Node {
         is_reference: bool,
         source_info: Option<SourceInfo>,
         debug_info: Option<DebugInfo>,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "std::ops::Not::not")
+        )]
+        keeps_paths: bool,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "std::ops::Not::not")
+        )]
+        is_generated: bool,
     },
 
     PushSymbol {
@@ -276,6 +697,16 @@ pub enum Node {
         is_reference: bool,
         source_info: Option<SourceInfo>,
         debug_info: Option<DebugInfo>,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "std::ops::Not::not")
+        )]
+        keeps_paths: bool,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "std::ops::Not::not")
+        )]
+        is_generated: bool,
     },
 
     Root {
@@ -289,10 +720,33 @@ pub enum Node {
         is_exported: bool,
         source_info: Option<SourceInfo>,
         debug_info: Option<DebugInfo>,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "std::ops::Not::not")
+        )]
+        keeps_paths: bool,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "std::ops::Not::not")
+        )]
+        is_generated: bool,
     },
 }
 
 impl Node {
+    pub fn id(&self) -> &NodeID {
+        match self {
+            Self::DropScopes { id, .. } => id,
+            Self::JumpToScope { id, .. } => id,
+            Self::PopScopedSymbol { id, .. } => id,
+            Self::PopSymbol { id, .. } => id,
+            Self::PushScopedSymbol { id, .. } => id,
+            Self::PushSymbol { id, .. } => id,
+            Self::Root { id, .. } => id,
+            Self::Scope { id, .. } => id,
+        }
+    }
+
     fn source_info(&self) -> Option<&SourceInfo> {
         match self {
             Self::DropScopes { source_info, .. } => source_info,
@@ -307,6 +761,30 @@ impl Node {
         .as_ref()
     }
 
+    fn keeps_paths(&self) -> bool {
+        match self {
+            Self::DropScopes { keeps_paths, .. } => *keeps_paths,
+            Self::PopScopedSymbol { keeps_paths, .. } => *keeps_paths,
+            Self::PopSymbol { keeps_paths, .. } => *keeps_paths,
+            Self::PushScopedSymbol { keeps_paths, .. } => *keeps_paths,
+            Self::PushSymbol { keeps_paths, .. } => *keeps_paths,
+            Self::Scope { keeps_paths, .. } => *keeps_paths,
+            Self::JumpToScope { .. } | Self::Root { .. } => false,
+        }
+    }
+
+    fn is_generated(&self) -> bool {
+        match self {
+            Self::DropScopes { is_generated, .. } => *is_generated,
+            Self::PopScopedSymbol { is_generated, .. } => *is_generated,
+            Self::PopSymbol { is_generated, .. } => *is_generated,
+            Self::PushScopedSymbol { is_generated, .. } => *is_generated,
+            Self::PushSymbol { is_generated, .. } => *is_generated,
+            Self::Scope { is_generated, .. } => *is_generated,
+            Self::JumpToScope { .. } | Self::Root { .. } => false,
+        }
+    }
+
     fn debug_info(&self) -> Option<&DebugInfo> {
         match self {
             Self::DropScopes { debug_info, .. } => debug_info,
@@ -322,6 +800,10 @@ impl Node {
     }
 }
 
+/// The serializable source information of a node.  Span columns are serialized as complete
+/// [`Offset`][lsp_positions::Offset]s, so that consumers can use whichever unit (UTF-8 bytes,
+/// UTF-16 code units, or graphemes) their ecosystem expects, without recomputing it from the
+/// source.
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(
     feature = "serde",
@@ -332,6 +814,16 @@ impl Node {
 pub struct SourceInfo {
     pub span: lsp_positions::Span,
     pub syntax_type: Option<String>,
+    /// The [selection span][crate::graph::StackGraph::selection_span] of the node, omitted if it
+    /// has not been set.
+    pub selection_span: Option<lsp_positions::Span>,
+    /// The content of the line containing the node, omitted if it has not been set.
+    pub containing_line: Option<String>,
+    /// The span of the node's definiens, omitted if it is the default span, which means that the
+    /// node has no definiens.
+    pub definiens_span: Option<lsp_positions::Span>,
+    /// The fully qualified name of the node's symbol, omitted if it has not been set.
+    pub fully_qualified_name: Option<String>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -353,7 +845,7 @@ pub struct DebugEntry {
     pub value: String,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 #[cfg_attr(
     feature = "serde",
     serde_with::skip_serializing_none, // must come before derive
@@ -437,6 +929,8 @@ pub struct Edge {
     pub source: NodeID,
     pub sink: NodeID,
     pub precedence: i32,
+    /// The kind of the edge, omitted if it is the default kind 0.
+    pub kind: Option<u32>,
     pub debug_info: Option<DebugInfo>,
 }
 
@@ -467,20 +961,37 @@ impl crate::graph::StackGraph {
 
     fn filter_source_info<'a>(
         &self,
-        _filter: &'a dyn Filter,
+        filter: &'a dyn Filter,
         handle: Handle<crate::graph::Node>,
     ) -> Option<SourceInfo> {
+        if !filter.include_source_info() {
+            return None;
+        }
         self.source_info(handle).map(|info| SourceInfo {
             span: info.span.clone(),
             syntax_type: info.syntax_type.into_option().map(|ty| self[ty].to_owned()),
+            selection_span: self.selection_span(handle).cloned(),
+            containing_line: info
+                .containing_line
+                .into_option()
+                .map(|line| self[line].to_owned()),
+            definiens_span: Some(info.definiens_span.clone())
+                .filter(|span| *span != lsp_positions::Span::default()),
+            fully_qualified_name: info
+                .fully_qualified_name
+                .into_option()
+                .map(|fqn| self[fqn].to_owned()),
         })
     }
 
     fn filter_node_debug_info<'a>(
         &self,
-        _filter: &'a dyn Filter,
+        filter: &'a dyn Filter,
         handle: Handle<crate::graph::Node>,
     ) -> Option<DebugInfo> {
+        if !filter.include_debug_info() {
+            return None;
+        }
         self.node_debug_info(handle).map(|info| DebugInfo {
             data: info
                 .iter()
@@ -492,71 +1003,98 @@ impl crate::graph::StackGraph {
         })
     }
 
-    fn filter_nodes<'a>(&self, filter: &'a dyn Filter) -> Nodes {
-        Nodes {
-            data: self
-                .iter_nodes()
-                .filter(|n| filter.include_node(self, &n))
-                .map(|handle| {
-                    let node = &self[handle];
-                    let id = self.filter_node(filter, node.id());
-                    let source_info = self.filter_source_info(filter, handle);
-                    let debug_info = self.filter_node_debug_info(filter, handle);
-
-                    match node {
-                        crate::graph::Node::DropScopes(_node) => Node::DropScopes {
-                            id,
-                            source_info,
-                            debug_info,
-                        },
-                        crate::graph::Node::JumpTo(_node) => Node::JumpToScope {
-                            id,
-                            source_info,
-                            debug_info,
-                        },
-                        crate::graph::Node::PopScopedSymbol(node) => Node::PopScopedSymbol {
-                            id,
-                            symbol: self[node.symbol].to_owned(),
-                            is_definition: node.is_definition,
-                            source_info,
-                            debug_info,
-                        },
-                        crate::graph::Node::PopSymbol(node) => Node::PopSymbol {
-                            id,
-                            symbol: self[node.symbol].to_owned(),
-                            is_definition: node.is_definition,
-                            source_info,
-                            debug_info,
-                        },
-                        crate::graph::Node::PushScopedSymbol(node) => Node::PushScopedSymbol {
-                            id,
-                            symbol: self[node.symbol].to_owned(),
-                            scope: self.filter_node(filter, node.scope),
-                            is_reference: node.is_reference,
-                            source_info,
-                            debug_info,
-                        },
-                        crate::graph::Node::PushSymbol(node) => Node::PushSymbol {
-                            id,
-                            symbol: self[node.symbol].to_owned(),
-                            is_reference: node.is_reference,
-                            source_info,
-                            debug_info,
-                        },
-                        crate::graph::Node::Root(_node) => Node::Root {
-                            id,
-                            source_info,
-                            debug_info,
-                        },
-                        crate::graph::Node::Scope(node) => Node::Scope {
-                            id,
-                            is_exported: node.is_exported,
-                            source_info,
-                            debug_info,
-                        },
-                    }
-                })
-                .collect::<Vec<_>>(),
+    fn filter_nodes<'a>(
+        &self,
+        filter: &'a dyn Filter,
+        cancellation_flag: &dyn CancellationFlag,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<Nodes, CancellationError> {
+        let total = self.iter_nodes().count();
+        let mut data = Vec::new();
+        for (index, handle) in self.iter_nodes().enumerate() {
+            cancellation_flag.check("serializing nodes")?;
+            if filter.include_node(self, &handle) {
+                data.push(self.filter_node_data(filter, handle));
+            }
+            progress(index + 1, total);
+        }
+        Ok(Nodes { data })
+    }
+
+    fn filter_node_data(&self, filter: &dyn Filter, handle: Handle<crate::graph::Node>) -> Node {
+        let node = &self[handle];
+        let id = self.filter_node(filter, node.id());
+        let source_info = self.filter_source_info(filter, handle);
+        let debug_info = self.filter_node_debug_info(filter, handle);
+        let keeps_paths = self.keeps_paths(handle);
+        let is_generated = self.is_generated(handle);
+
+        match node {
+            crate::graph::Node::DropScopes(_node) => Node::DropScopes {
+                id,
+                source_info,
+                debug_info,
+                keeps_paths,
+                is_generated,
+            },
+            crate::graph::Node::JumpTo(_node) => Node::JumpToScope {
+                id,
+                source_info,
+                debug_info,
+            },
+            crate::graph::Node::PopScopedSymbol(node) => Node::PopScopedSymbol {
+                id,
+                symbol: self[node.symbol].to_owned(),
+                is_definition: node.is_definition,
+                definition_rank: self.explicit_definition_rank(handle),
+                source_info,
+                debug_info,
+                keeps_paths,
+                is_generated,
+            },
+            crate::graph::Node::PopSymbol(node) => Node::PopSymbol {
+                id,
+                symbol: self[node.symbol].to_owned(),
+                is_wildcard: self.is_wildcard_symbol(node.symbol),
+                is_definition: node.is_definition,
+                definition_rank: self.explicit_definition_rank(handle),
+                source_info,
+                debug_info,
+                keeps_paths,
+                is_generated,
+            },
+            crate::graph::Node::PushScopedSymbol(node) => Node::PushScopedSymbol {
+                id,
+                symbol: self[node.symbol].to_owned(),
+                scope: self.filter_node(filter, node.scope),
+                is_reference: node.is_reference,
+                source_info,
+                debug_info,
+                keeps_paths,
+                is_generated,
+            },
+            crate::graph::Node::PushSymbol(node) => Node::PushSymbol {
+                id,
+                symbol: self[node.symbol].to_owned(),
+                is_reference: node.is_reference,
+                source_info,
+                debug_info,
+                keeps_paths,
+                is_generated,
+            },
+            crate::graph::Node::Root(_node) => Node::Root {
+                id,
+                source_info,
+                debug_info,
+            },
+            crate::graph::Node::Scope(node) => Node::Scope {
+                id,
+                is_exported: node.is_exported,
+                source_info,
+                debug_info,
+                keeps_paths,
+                is_generated,
+            },
         }
     }
 
@@ -571,6 +1109,7 @@ impl crate::graph::StackGraph {
                             source: self.filter_node(filter, self[e.source].id()),
                             sink: self.filter_node(filter, self[e.sink].id()),
                             precedence: e.precedence,
+                            kind: self.edge_kind(e.source, e.sink).filter(|kind| *kind != 0),
                             debug_info: self.filter_edge_debug_info(filter, e.source, e.sink),
                         })
                 })
@@ -581,10 +1120,13 @@ impl crate::graph::StackGraph {
 
     fn filter_edge_debug_info<'a>(
         &self,
-        _filter: &'a dyn Filter,
+        filter: &'a dyn Filter,
         source_handle: Handle<crate::graph::Node>,
         sink_handle: Handle<crate::graph::Node>,
     ) -> Option<DebugInfo> {
+        if !filter.include_debug_info() {
+            return None;
+        }
         self.edge_debug_info(source_handle, sink_handle)
             .map(|info| DebugInfo {
                 data: info
@@ -8,9 +8,11 @@
 mod filter;
 mod graph;
 mod partial;
+mod patch;
 mod stitching;
 
 pub use filter::*;
 pub use graph::*;
 pub use partial::*;
+pub use patch::*;
 pub use stitching::*;
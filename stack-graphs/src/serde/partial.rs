@@ -94,6 +94,53 @@ pub struct PartialScopeStack {
 }
 
 impl PartialScopeStack {
+    /// Returns whether this partial scope stack can match the empty scope stack.
+    pub fn can_match_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    /// Returns whether this partial scope stack can _only_ match the empty scope stack.
+    pub fn can_only_match_empty(&self) -> bool {
+        self.scopes.is_empty() && self.variable.is_none()
+    }
+
+    /// Returns whether this partial scope stack contains any scopes.
+    pub fn contains_scopes(&self) -> bool {
+        !self.scopes.is_empty()
+    }
+
+    /// Returns whether this partial scope stack has a scope stack variable.
+    pub fn has_variable(&self) -> bool {
+        self.variable.is_some()
+    }
+
+    /// Returns the number of scopes in this partial scope stack, not counting its scope stack
+    /// variable.
+    pub fn len(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// Returns whether this partial scope stack contains no scopes.  It might still have a
+    /// scope stack variable.
+    pub fn is_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    /// Returns whether this partial scope stack contains the given scope.
+    pub fn contains(&self, scope: &NodeID) -> bool {
+        self.scopes.contains(scope)
+    }
+
+    /// Returns an iterator over the contents of this partial scope stack.
+    pub fn iter(&self) -> impl Iterator<Item = &NodeID> + '_ {
+        self.scopes.iter()
+    }
+
+    /// Returns the scope stack variable at the end of this partial scope stack, if any.
+    pub fn variable(&self) -> Option<&ScopeStackVariable> {
+        self.variable.as_ref()
+    }
+
     pub fn from_partial_scope_stack(
         graph: &crate::graph::StackGraph,
         partials: &mut PartialPaths,
@@ -164,6 +211,48 @@ pub struct PartialSymbolStack {
 }
 
 impl PartialSymbolStack {
+    /// Returns whether this partial symbol stack can match the empty symbol stack.
+    pub fn can_match_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Returns whether this partial symbol stack can _only_ match the empty symbol stack.
+    pub fn can_only_match_empty(&self) -> bool {
+        self.symbols.is_empty() && self.variable.is_none()
+    }
+
+    /// Returns whether this partial symbol stack contains any symbols.
+    pub fn contains_symbols(&self) -> bool {
+        !self.symbols.is_empty()
+    }
+
+    /// Returns whether this partial symbol stack has a symbol stack variable.
+    pub fn has_variable(&self) -> bool {
+        self.variable.is_some()
+    }
+
+    /// Returns the number of symbols in this partial symbol stack, not counting its symbol stack
+    /// variable.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Returns whether this partial symbol stack contains no symbols.  It might still have a
+    /// symbol stack variable.
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Returns an iterator over the contents of this partial symbol stack.
+    pub fn iter(&self) -> impl Iterator<Item = &PartialScopedSymbol> + '_ {
+        self.symbols.iter()
+    }
+
+    /// Returns the symbol stack variable at the end of this partial symbol stack, if any.
+    pub fn variable(&self) -> Option<&SymbolStackVariable> {
+        self.variable.as_ref()
+    }
+
     pub fn from_partial_symbol_stack(
         graph: &crate::graph::StackGraph,
         partials: &mut PartialPaths,
@@ -232,6 +321,13 @@ impl SymbolStackVariable {
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct PartialScopedSymbol {
     symbol: String,
+    /// Whether the symbol is the [wildcard symbol][crate::graph::StackGraph::add_wildcard_symbol],
+    /// omitted if it is not.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    is_wildcard: bool,
     pub(crate) scopes: Option<PartialScopeStack>,
 }
 
@@ -243,6 +339,7 @@ impl PartialScopedSymbol {
     ) -> Self {
         Self {
             symbol: graph[value.symbol].to_string(),
+            is_wildcard: graph.is_wildcard_symbol(value.symbol),
             scopes: value.scopes.into_option().map(|scopes| {
                 PartialScopeStack::from_partial_scope_stack(graph, partials, &scopes)
             }),
@@ -255,7 +352,10 @@ impl PartialScopedSymbol {
         partials: &mut crate::partial::PartialPaths,
     ) -> Result<crate::partial::PartialScopedSymbol, Error> {
         Ok(crate::partial::PartialScopedSymbol {
-            symbol: graph.add_symbol(&self.symbol),
+            symbol: match self.is_wildcard {
+                true => graph.add_wildcard_symbol(),
+                false => graph.add_symbol(&self.symbol),
+            },
             scopes: self
                 .scopes
                 .as_ref()
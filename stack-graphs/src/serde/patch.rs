@@ -0,0 +1,159 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2026, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use super::Edge;
+use super::Node;
+use super::NodeID;
+use super::StackGraph;
+
+/// The changes between two states of a serialized stack graph, which can be applied to a copy of
+/// the old state to reconstruct the new state.
+///
+/// Nodes are identified by their [`NodeID`][], and edges by the IDs of their source and sink
+/// nodes, so that a patch does not depend on the handles of any particular [`StackGraph`][]
+/// instance.  Nodes and edges whose content changed are both removed and added.
+///
+/// [`StackGraph`]: crate::graph::StackGraph
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+pub struct GraphPatch {
+    pub removed_files: Vec<String>,
+    pub added_files: Vec<String>,
+    pub removed_nodes: Vec<NodeID>,
+    pub added_nodes: Vec<Node>,
+    pub removed_edges: Vec<EdgeID>,
+    pub added_edges: Vec<Edge>,
+}
+
+/// Identifies an edge by the IDs of its source and sink nodes.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+pub struct EdgeID {
+    pub source: NodeID,
+    pub sink: NodeID,
+}
+
+impl Edge {
+    pub fn id(&self) -> EdgeID {
+        EdgeID {
+            source: self.source.clone(),
+            sink: self.sink.clone(),
+        }
+    }
+}
+
+impl GraphPatch {
+    /// Returns whether this patch contains no changes.
+    pub fn is_empty(&self) -> bool {
+        self.removed_files.is_empty()
+            && self.added_files.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.added_nodes.is_empty()
+            && self.removed_edges.is_empty()
+            && self.added_edges.is_empty()
+    }
+}
+
+impl StackGraph {
+    /// Computes the patch that transforms this graph into `new`.  To describe the changes to a
+    /// single file, create both graphs using a [`FileFilter`][super::FileFilter].
+    pub fn diff(&self, new: &StackGraph) -> GraphPatch {
+        let old_files = self.files.data.iter().collect::<HashSet<_>>();
+        let new_files = new.files.data.iter().collect::<HashSet<_>>();
+        let old_nodes = self
+            .nodes
+            .data
+            .iter()
+            .map(|n| (n.id(), n))
+            .collect::<HashMap<_, _>>();
+        let new_nodes = new
+            .nodes
+            .data
+            .iter()
+            .map(|n| (n.id(), n))
+            .collect::<HashMap<_, _>>();
+        let old_edges = self
+            .edges
+            .data
+            .iter()
+            .map(|e| (e.id(), e))
+            .collect::<HashMap<_, _>>();
+        let new_edges = new
+            .edges
+            .data
+            .iter()
+            .map(|e| (e.id(), e))
+            .collect::<HashMap<_, _>>();
+
+        GraphPatch {
+            removed_files: self
+                .files
+                .data
+                .iter()
+                .filter(|f| !new_files.contains(f))
+                .cloned()
+                .collect(),
+            added_files: new
+                .files
+                .data
+                .iter()
+                .filter(|f| !old_files.contains(f))
+                .cloned()
+                .collect(),
+            removed_nodes: self
+                .nodes
+                .data
+                .iter()
+                .filter(|n| new_nodes.get(n.id()) != Some(n))
+                .map(|n| n.id().clone())
+                .collect(),
+            added_nodes: new
+                .nodes
+                .data
+                .iter()
+                .filter(|n| old_nodes.get(n.id()) != Some(n))
+                .cloned()
+                .collect(),
+            removed_edges: self
+                .edges
+                .data
+                .iter()
+                .filter(|e| new_edges.get(&e.id()) != Some(e))
+                .map(|e| e.id())
+                .collect(),
+            added_edges: new
+                .edges
+                .data
+                .iter()
+                .filter(|e| old_edges.get(&e.id()) != Some(e))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Applies a patch to this graph.  If this graph is the old state that the patch was computed
+    /// from, the result contains the same files, nodes, and edges as the new state.  Added
+    /// elements are appended, so their order may differ from the new state.
+    pub fn apply_patch(&mut self, patch: &GraphPatch) {
+        let removed_files = patch.removed_files.iter().collect::<HashSet<_>>();
+        self.files.data.retain(|f| !removed_files.contains(f));
+        self.files.data.extend(patch.added_files.iter().cloned());
+
+        let removed_nodes = patch.removed_nodes.iter().collect::<HashSet<_>>();
+        self.nodes.data.retain(|n| !removed_nodes.contains(n.id()));
+        self.nodes.data.extend(patch.added_nodes.iter().cloned());
+
+        let removed_edges = patch.removed_edges.iter().collect::<HashSet<_>>();
+        self.edges.data.retain(|e| !removed_edges.contains(&e.id()));
+        self.edges.data.extend(patch.added_edges.iter().cloned());
+    }
+}
@@ -5,8 +5,17 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+#[cfg(any(feature = "bincode", feature = "serde"))]
+use thiserror::Error;
+
 use crate::graph::StackGraph;
 use crate::partial::PartialPaths;
+#[cfg(feature = "serde")]
+use crate::stitching::ForwardPartialPathStitcher;
+#[cfg(feature = "serde")]
+use crate::stitching::StitcherConfig;
+#[cfg(feature = "serde")]
+use crate::CancellationFlag;
 
 use super::Error;
 use super::Filter;
@@ -20,6 +29,7 @@ use super::PartialPath;
     derive(serde::Deserialize, serde::Serialize),
     serde(transparent)
 )]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct Database {
     paths: Vec<PartialPath>,
 }
@@ -80,3 +90,153 @@ impl crate::stitching::Database {
         Database::from_database_filter(graph, partials, self, filter)
     }
 }
+
+/// A serializable stack graph together with partial paths in that graph, typically the partial
+/// paths extracted from the graph's files.  This captures the state of an analysis in a single
+/// value, for example to cache the analysis of a file.  The symbol and scope stacks and the edges
+/// of the partial paths are stored inline, so that the serialized paths do not depend on the
+/// [`PartialPaths`][] arena they were created in.
+///
+/// The graph is stored as a [serializable graph][super::StackGraph], which includes definition
+/// ranks, generated and kept nodes, source info, and edge kinds.
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+pub struct GraphWithPaths {
+    pub graph: super::StackGraph,
+    pub paths: Vec<PartialPath>,
+}
+
+impl GraphWithPaths {
+    /// Creates a serializable value from all files in `graph`, and the given partial paths, which
+    /// must be allocated in `partials` and refer to nodes in `graph`.  Graph content is not
+    /// filtered, so the paths can refer to any node in the graph.
+    pub fn from_graph_and_paths<'p, I>(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        paths: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = &'p crate::partial::PartialPath>,
+    {
+        let paths = paths
+            .into_iter()
+            .map(|path| PartialPath::from_partial_path(graph, partials, path))
+            .collect();
+        Self {
+            graph: super::StackGraph::from_graph(graph),
+            paths,
+        }
+    }
+
+    /// Loads the graph into `graph`, and returns the partial paths, which are allocated in
+    /// `partials`.  To restore the complete state, load into an empty graph and a new partial
+    /// path arena.
+    pub fn load_into(
+        &self,
+        graph: &mut StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Result<Vec<crate::partial::PartialPath>, Error> {
+        self.graph.load_into(graph)?;
+        self.paths
+            .iter()
+            .map(|path| path.to_partial_path(graph, partials))
+            .collect()
+    }
+}
+
+/// An error that can occur while saving or loading a [`Database`][crate::stitching::Database].
+#[cfg(feature = "bincode")]
+#[derive(Debug, Error)]
+pub enum DatabaseStoreError {
+    #[error(transparent)]
+    Decode(#[from] bincode::error::DecodeError),
+    #[error(transparent)]
+    Encode(#[from] bincode::error::EncodeError),
+    #[error(transparent)]
+    Serde(#[from] Error),
+}
+
+#[cfg(feature = "bincode")]
+impl crate::stitching::Database {
+    /// Writes all partial paths in this database to `writer`, using a compact binary encoding.
+    /// The database can be restored using [`load`][Self::load].
+    pub fn save<W: std::io::Write>(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        writer: &mut W,
+    ) -> Result<(), DatabaseStoreError> {
+        let database = self.to_serializable(graph, partials);
+        bincode::encode_into_std_write(&database, writer, bincode::config::standard())?;
+        Ok(())
+    }
+
+    /// Reads a database that was written using [`save`][Self::save] from `reader`.  The indices of
+    /// the database are rebuilt from the stored partial paths.  The graph must contain all files
+    /// that the partial paths refer to.
+    pub fn load<R: std::io::Read>(
+        graph: &mut StackGraph,
+        partials: &mut PartialPaths,
+        reader: &mut R,
+    ) -> Result<Self, DatabaseStoreError> {
+        let database: Database =
+            bincode::decode_from_std_read(reader, bincode::config::standard())?;
+        let mut result = Self::new();
+        database.load_into(graph, partials, &mut result)?;
+        Ok(result)
+    }
+}
+
+/// An error that can occur while building a [`Database`][crate::stitching::Database] from
+/// serialized files.
+#[cfg(feature = "serde")]
+#[derive(Debug, Error)]
+pub enum SerializedFilesError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Serde(#[from] Error),
+}
+
+#[cfg(feature = "serde")]
+impl crate::stitching::Database {
+    /// Builds a database from stack graphs that were serialized to JSON independently, typically
+    /// one per file.  Each graph is loaded into `graph`, after which the minimal set of partial
+    /// paths of every file it contains is added to the resulting database.  The serialized graphs
+    /// must not contain the same file more than once.
+    pub fn from_serialized_files<I, R>(
+        graph: &mut StackGraph,
+        partials: &mut PartialPaths,
+        readers: I,
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Self, SerializedFilesError>
+    where
+        I: IntoIterator<Item = R>,
+        R: std::io::Read,
+    {
+        let mut result = Self::new();
+        for reader in readers {
+            let serialized: super::StackGraph = serde_json::from_reader(reader)?;
+            serialized.load_into_with_progress(graph, cancellation_flag, &mut |_, _| {})?;
+            for file in &serialized.files.data {
+                let file = graph
+                    .get_file(file)
+                    .ok_or_else(|| Error::FileNotFound(file.clone()))?;
+                ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+                    graph,
+                    partials,
+                    file,
+                    config.clone(),
+                    cancellation_flag,
+                    |g, ps, p| {
+                        result.add_partial_path(g, ps, p.clone());
+                    },
+                )
+                .map_err(Error::from)?;
+            }
+        }
+        Ok(result)
+    }
+}
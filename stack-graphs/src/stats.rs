@@ -7,6 +7,7 @@
 
 use std::collections::HashMap;
 use std::hash::Hash;
+use std::time::Duration;
 
 use itertools::Itertools;
 
@@ -104,3 +105,47 @@ where
         self.total += rhs.total;
     }
 }
+
+/// Counts how often interning a symbol created a new entry in the symbol table, versus reused an
+/// existing one.  A low reuse count can indicate that symbol strings are constructed
+/// inconsistently.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SymbolStats {
+    pub symbols_created: usize,
+    pub symbols_reused: usize,
+}
+
+impl std::ops::AddAssign<Self> for SymbolStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.symbols_created += rhs.symbols_created;
+        self.symbols_reused += rhs.symbols_reused;
+    }
+}
+
+/// The time spent in the different phases of analyzing source files: parsing the source, executing
+/// the TSG rules to build the stack graph, extracting the file's partial paths, and stitching
+/// partial paths into complete paths.  Timings can be recorded per file and summed to get overall
+/// totals.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PhaseTimings {
+    pub parsing: Duration,
+    pub execution: Duration,
+    pub partial_paths: Duration,
+    pub stitching: Duration,
+}
+
+impl PhaseTimings {
+    /// The total time spent in all phases.
+    pub fn total(&self) -> Duration {
+        self.parsing + self.execution + self.partial_paths + self.stitching
+    }
+}
+
+impl std::ops::AddAssign<Self> for PhaseTimings {
+    fn add_assign(&mut self, rhs: Self) {
+        self.parsing += rhs.parsing;
+        self.execution += rhs.execution;
+        self.partial_paths += rhs.partial_paths;
+        self.stitching += rhs.stitching;
+    }
+}
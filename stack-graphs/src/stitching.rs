@@ -37,12 +37,15 @@
 
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::VecDeque;
 #[cfg(feature = "copious-debugging")]
 use std::fmt::Display;
+use std::sync::Arc;
 
 use itertools::izip;
 use itertools::Itertools;
+use thiserror::Error;
 
 use crate::arena::Arena;
 use crate::arena::Handle;
@@ -55,18 +58,22 @@ use crate::cycles::Appendables;
 use crate::cycles::AppendingCycleDetector;
 use crate::cycles::SimilarPathDetector;
 use crate::cycles::SimilarPathStats;
+use crate::graph::DefinitionSite;
 use crate::graph::Degree;
 use crate::graph::Edge;
 use crate::graph::File;
 use crate::graph::Node;
+use crate::graph::NodeID;
 use crate::graph::StackGraph;
 use crate::graph::Symbol;
 use crate::partial::Cyclicity;
 use crate::partial::PartialPath;
 use crate::partial::PartialPaths;
+use crate::partial::PartialScopeStack;
 use crate::partial::PartialSymbolStack;
 use crate::paths::Extend;
 use crate::paths::PathResolutionError;
+use crate::serde::Filter;
 use crate::stats::FrequencyDistribution;
 use crate::CancellationError;
 use crate::CancellationFlag;
@@ -91,6 +98,28 @@ pub trait Appendable {
     /// Return the end node.
     fn end_node(&self) -> Handle<Node>;
 
+    /// Returns whether this appendable starts at, ends at, or passes through a node whose paths
+    /// must be [kept][StackGraph::keeps_paths].
+    fn passes_through_kept_node(&self, graph: &StackGraph, _partials: &PartialPaths) -> bool {
+        graph.keeps_paths(self.start_node()) || graph.keeps_paths(self.end_node())
+    }
+
+    /// Calls `visit` with the file of every node that this appendable starts at, ends at, or
+    /// passes through, in order.  Nodes that belong to all files, such as the root node, are
+    /// skipped.
+    fn visit_files(
+        &self,
+        graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        visit: &mut dyn FnMut(Handle<File>),
+    ) {
+        for node in [self.start_node(), self.end_node()] {
+            if let Some(file) = graph[node].file() {
+                visit(file);
+            }
+        }
+    }
+
     /// Return a Display implementation.
     fn display<'a>(
         &'a self,
@@ -152,6 +181,26 @@ impl Appendable for PartialPath {
         self.end_node
     }
 
+    fn passes_through_kept_node(&self, graph: &StackGraph, partials: &PartialPaths) -> bool {
+        passes_through_kept_node(graph, partials, self)
+    }
+
+    fn visit_files(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        visit: &mut dyn FnMut(Handle<File>),
+    ) {
+        let start_node = std::iter::once(graph[self.start_node].id());
+        let edges = self.edges.iter(partials).map(|edge| edge.source_node_id);
+        let end_node = std::iter::once(graph[self.end_node].id());
+        for node_id in start_node.chain(edges).chain(end_node) {
+            if let Some(file) = node_id.file() {
+                visit(file);
+            }
+        }
+    }
+
     fn display<'a>(
         &'a self,
         graph: &'a StackGraph,
@@ -292,7 +341,21 @@ pub struct Database {
         SupplementalArena<SymbolStackKeyCell, Vec<Handle<PartialPath>>>,
     root_paths_by_precondition_without_variable:
         SupplementalArena<SymbolStackKeyCell, Vec<Handle<PartialPath>>>,
+    root_paths_with_wildcard_precondition: Vec<Handle<PartialPath>>,
     incoming_paths: SupplementalArena<Node, Degree>,
+    paths_by_end_node: SupplementalArena<Node, Vec<Handle<PartialPath>>>,
+    indexed_paths: usize,
+    memory_limit: Option<usize>,
+}
+
+/// An error returned when adding a partial path to a database that exceeds its memory limit.
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+#[error("database memory estimate of {estimate} bytes exceeds limit of {limit} bytes")]
+pub struct MemoryLimitExceeded {
+    /// The configured memory limit of the database.
+    pub limit: usize,
+    /// The memory estimate of the database at the time of the failed insertion.
+    pub estimate: usize,
 }
 
 impl Database {
@@ -307,7 +370,11 @@ impl Database {
             root_paths_by_precondition_prefix: SupplementalArena::new(),
             root_paths_by_precondition_with_variable: SupplementalArena::new(),
             root_paths_by_precondition_without_variable: SupplementalArena::new(),
+            root_paths_with_wildcard_precondition: Vec::new(),
             incoming_paths: SupplementalArena::new(),
+            paths_by_end_node: SupplementalArena::new(),
+            indexed_paths: 0,
+            memory_limit: None,
         }
     }
 
@@ -323,7 +390,65 @@ impl Database {
         self.root_paths_by_precondition_prefix.clear();
         self.root_paths_by_precondition_with_variable.clear();
         self.root_paths_by_precondition_without_variable.clear();
+        self.root_paths_with_wildcard_precondition.clear();
         self.incoming_paths.clear();
+        self.paths_by_end_node.clear();
+        self.indexed_paths = 0;
+    }
+
+    /// Returns an estimate, in bytes, of the memory used by this database.  This includes the
+    /// partial path arena and all of the indexes, but not the contents of the partial paths'
+    /// symbol and scope stacks, which live in a [`PartialPaths`][] instance.  The estimate is
+    /// based on allocated capacities, and is cheap to compute.
+    pub fn memory_estimate(&self) -> usize {
+        use std::mem::size_of;
+        let index_vecs = self.paths_by_start_node.len()
+            + self.paths_by_end_node.len()
+            + self.root_paths_by_precondition_prefix.len()
+            + self.root_paths_by_precondition_with_variable.len()
+            + self.root_paths_by_precondition_without_variable.len();
+        self.partial_paths.capacity() * size_of::<PartialPath>()
+            + self.symbol_stack_keys.capacity() * size_of::<ListCell<Handle<Symbol>>>()
+            + self.symbol_stack_key_cache.capacity()
+                * size_of::<(SymbolStackCacheKey, SymbolStackKeyHandle)>()
+            + index_vecs * size_of::<Vec<Handle<PartialPath>>>()
+            + self.indexed_paths * size_of::<Handle<PartialPath>>()
+            + self.incoming_paths.len() * size_of::<Degree>()
+    }
+
+    /// Returns the memory limit of this database, if any.
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.memory_limit
+    }
+
+    /// Sets a soft memory limit, in bytes, for this database.  Once the [memory
+    /// estimate][Self::memory_estimate] exceeds the limit, [`try_add_partial_path`][] refuses to add
+    /// more partial paths.  The limit is soft because the insertion that crosses it still succeeds.
+    /// The limit is not enforced by [`add_partial_path`][].
+    ///
+    /// [`add_partial_path`]: #method.add_partial_path
+    /// [`try_add_partial_path`]: #method.try_add_partial_path
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory_limit = limit;
+    }
+
+    /// Adds a partial path to this database, unless the database has already exceeded its
+    /// [memory limit][Self::set_memory_limit].  Otherwise behaves like [`add_partial_path`][].
+    ///
+    /// [`add_partial_path`]: #method.add_partial_path
+    pub fn try_add_partial_path(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        path: PartialPath,
+    ) -> Result<Handle<PartialPath>, MemoryLimitExceeded> {
+        if let Some(limit) = self.memory_limit {
+            let estimate = self.memory_estimate();
+            if estimate > limit {
+                return Err(MemoryLimitExceeded { limit, estimate });
+            }
+        }
+        Ok(self.add_partial_path(graph, partials, path))
     }
 
     /// Adds a partial path to this database.  We do not deduplicate partial paths in any way; it's
@@ -349,7 +474,16 @@ impl Database {
         let handle = self.partial_paths.add(path);
 
         // If the partial path starts at the root node, index it by its symbol stack precondition.
-        if graph[start_node].is_root() {
+        // Paths whose precondition starts with the wildcard symbol are compatible with any symbol
+        // stack, so we keep them separately instead.
+        let starts_with_wildcard = symbol_stack_precondition
+            .iter(partials)
+            .next()
+            .is_some_and(|symbol| graph.is_wildcard_symbol(symbol.symbol));
+        if graph[start_node].is_root() && starts_with_wildcard {
+            self.root_paths_with_wildcard_precondition.push(handle);
+            self.indexed_paths += 1;
+        } else if graph[start_node].is_root() {
             // The join node is root, so there's no need to use half-open symbol stacks here, as we
             // do for [`PartialPath::concatenate`][].
             let mut key = SymbolStackKey::from_partial_symbol_stack(
@@ -364,16 +498,21 @@ impl Database {
                     false => self.root_paths_by_precondition_without_variable[key.back_handle()]
                         .push(handle),
                 }
+                self.indexed_paths += 1;
             }
             while key.pop_back(self).is_some() && !key.is_empty() {
                 self.root_paths_by_precondition_prefix[key.back_handle()].push(handle);
+                self.indexed_paths += 1;
             }
         } else {
             // Otherwise index it by its source node.
             self.paths_by_start_node[start_node].push(handle);
+            self.indexed_paths += 1;
         }
 
         self.incoming_paths[end_node] += Degree::One;
+        self.paths_by_end_node[end_node].push(handle);
+        self.indexed_paths += 1;
         handle
     }
 
@@ -416,7 +555,9 @@ impl Database {
         R: std::iter::Extend<Handle<PartialPath>>,
     {
         // If the path currently ends at the root node, then we need to look up partial paths whose
-        // symbol stack precondition is compatible with the path.
+        // symbol stack precondition is compatible with the path.  Paths whose precondition starts
+        // with a wildcard might be compatible with any of them.
+        result.extend(self.root_paths_with_wildcard_precondition.iter().copied());
         match symbol_stack {
             Some(symbol_stack) => {
                 let mut key =
@@ -538,6 +679,16 @@ impl Database {
         self.incoming_paths[end_node]
     }
 
+    /// Returns all of the partial paths in this database that end at the given node, in the order
+    /// in which they were added.  This is a reverse index over the paths, which is useful to find
+    /// the references that can reach a definition.
+    pub fn partial_paths_ending_at(&self, end_node: Handle<Node>) -> &[Handle<PartialPath>] {
+        self.paths_by_end_node
+            .get(end_node)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
     /// Determines which nodes in the stack graph are “local”, taking into account the partial
     /// paths in this database.
     ///
@@ -621,6 +772,27 @@ impl Database {
         self.partial_paths.iter_handles()
     }
 
+    /// Returns an iterator over all of the partial paths in this database.
+    pub fn partial_paths(&self) -> impl Iterator<Item = &PartialPath> + '_ {
+        self.partial_paths
+            .iter_handles()
+            .map(move |handle| &self[handle])
+    }
+
+    /// Returns an iterator over all of the partial paths in this database that start or end at a
+    /// node in the given file.  (The singleton root and jump to scope nodes do not belong to any
+    /// file.)
+    pub fn partial_paths_in_file<'a>(
+        &'a self,
+        graph: &'a StackGraph,
+        file: Handle<File>,
+    ) -> impl Iterator<Item = &'a PartialPath> + 'a {
+        self.partial_paths().filter(move |path| {
+            graph[path.start_node].id().file() == Some(file)
+                || graph[path.end_node].id().file() == Some(file)
+        })
+    }
+
     pub fn ensure_both_directions(&mut self, partials: &mut PartialPaths) {
         for path in self.partial_paths.iter_handles() {
             self.partial_paths
@@ -797,12 +969,40 @@ impl<'a> Display for DisplaySymbolStackKey<'a> {
 //-------------------------------------------------------------------------------------------------
 // Stitching partial paths together
 
+/// A stitching decision reported to the trace sink of a [`ForwardPartialPathStitcher`][].
+pub enum TraceEvent<'a> {
+    /// The path was taken from the queue to be extended.
+    Dequeued(&'a PartialPath),
+    /// The path is not extended because it is cyclic.
+    Cyclic(&'a PartialPath),
+    /// A candidate could not be concatenated onto the path.
+    ConcatenationFailed {
+        path: &'a PartialPath,
+        error: &'a PathResolutionError,
+    },
+    /// A candidate was concatenated onto the path, resulting in the extension.
+    Extended {
+        path: &'a PartialPath,
+        extension: &'a PartialPath,
+    },
+    /// The extension is dropped because too many similar paths were found.
+    SimilarPathRejected(&'a PartialPath),
+    /// The extension is a complete path, i.e. it goes from a reference to a definition.
+    Completed(&'a PartialPath),
+}
+
+/// A callback that receives the [`TraceEvent`][]s of a [`ForwardPartialPathStitcher`][].
+pub type TraceSink = Box<dyn FnMut(&StackGraph, &mut PartialPaths, &TraceEvent)>;
+
 /// Implements a phased forward partial path stitching algorithm.
 ///
 /// Our overall goal is to start with a set of _seed_ partial paths, and to repeatedly extend each
 /// partial path by concatenating another, compatible partial path onto the end of it.  (If there
 /// are multiple compatible partial paths, we concatenate each of them separately, resulting in
-/// more than one extension for the current path.)
+/// more than one extension for the current path.)  The extensions of a path are queued in a
+/// deterministic order---by end node, then by pre- and postconditions, then by edges---so that
+/// repeated runs on the same graph find paths in the same order, regardless of the order in which
+/// the candidates are provided.
 ///
 /// We perform this processing in _phases_.  At the start of each phase, we have a _current set_ of
 /// partial paths that need to be processed.  As we extend those partial paths, we add the
@@ -825,8 +1025,8 @@ impl<'a> Display for DisplaySymbolStackKey<'a> {
 /// [`find_all_complete_partial_paths`]: #method.find_all_complete_partial_paths
 pub struct ForwardPartialPathStitcher<H> {
     candidates: Vec<H>,
-    extensions: Vec<(PartialPath, AppendingCycleDetector<H>)>,
-    queue: VecDeque<(PartialPath, AppendingCycleDetector<H>, bool)>,
+    extensions: Vec<(PartialPath, AppendingCycleDetector<H>, PathState)>,
+    queue: VecDeque<(PartialPath, AppendingCycleDetector<H>, PathState)>,
     // tracks the number of initial paths in the queue because we do not want call
     // extend_until on those
     initial_paths_in_queue: usize,
@@ -835,25 +1035,53 @@ pub struct ForwardPartialPathStitcher<H> {
     next_iteration: (
         VecDeque<PartialPath>,
         VecDeque<AppendingCycleDetector<H>>,
-        VecDeque<bool>,
+        VecDeque<PathState>,
     ),
     appended_paths: Appendables<H>,
     similar_path_detector: Option<SimilarPathDetector<PartialPath>>,
     check_only_join_nodes: bool,
     max_work_per_phase: usize,
     initial_paths: usize,
+    root_matcher: Option<Arc<dyn RootMatcher>>,
+    max_file_hops: Option<usize>,
     stats: Option<Stats>,
+    trace_sink: Option<TraceSink>,
     #[cfg(feature = "copious-debugging")]
     phase_number: usize,
 }
 
+/// The state of a partial path in the stitcher, which is updated incrementally whenever the path
+/// is extended, so that it does not have to be recomputed from the path's edges.
+#[derive(Clone, Copy, Debug, Default)]
+struct PathState {
+    /// Whether the path, or any path it was extended from, had more than one extension.
+    has_split: bool,
+    /// Whether the path starts at, ends at, or passes through a node whose paths must be
+    /// [kept][StackGraph::keeps_paths].
+    passes_kept_node: bool,
+    /// The number of times the path continued in a different file than the one it was in.
+    file_hops: usize,
+    /// The file of the last node of the path that belongs to a file.
+    last_file: Option<Handle<File>>,
+}
+
+impl PathState {
+    /// Updates the state for a path that continues with a node in the given file.
+    fn enter_file(&mut self, file: Handle<File>) {
+        if self.last_file.is_some_and(|last_file| last_file != file) {
+            self.file_hops += 1;
+        }
+        self.last_file = Some(file);
+    }
+}
+
 impl<H> ForwardPartialPathStitcher<H> {
     /// Creates a new forward partial path stitcher that is "seeded" with a set of initial partial
     /// paths. If the sticher is used to find complete paths, it is the responsibility of the caller
     /// to ensure precondition variables are eliminated by calling [`PartialPath::eliminate_precondition_stack_variables`][].
     pub fn from_partial_paths<I>(
-        _graph: &StackGraph,
-        _partials: &mut PartialPaths,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
         initial_partial_paths: I,
     ) -> Self
     where
@@ -864,7 +1092,14 @@ impl<H> ForwardPartialPathStitcher<H> {
             .into_iter()
             .map(|p| {
                 let c = AppendingCycleDetector::from(&mut appended_paths, p.clone().into());
-                (p, c, false)
+                let mut state = PathState {
+                    has_split: false,
+                    passes_kept_node: passes_through_kept_node(graph, partials, &p),
+                    file_hops: 0,
+                    last_file: None,
+                };
+                p.visit_files(graph, partials, &mut |file| state.enter_file(file));
+                (p, c, state)
             })
             .multiunzip();
         let initial_paths = next_iteration.0.len();
@@ -882,7 +1117,11 @@ impl<H> ForwardPartialPathStitcher<H> {
             // By default, there's no artificial bound on the amount of work done per phase
             max_work_per_phase: usize::MAX,
             initial_paths,
+            // By default, symbol stacks at the root node must match exactly
+            root_matcher: None,
+            max_file_hops: None,
             stats: None,
+            trace_sink: None,
             #[cfg(feature = "copious-debugging")]
             phase_number: 1,
         }
@@ -919,6 +1158,28 @@ impl<H> ForwardPartialPathStitcher<H> {
         self.max_work_per_phase = max_work_per_phase;
     }
 
+    /// Sets the [`RootMatcher`][] that is consulted whenever a partial path reaches the root node.
+    /// If no matcher is set, paths leaving the root node must match the symbol stack exactly.
+    pub fn set_root_matcher(&mut self, root_matcher: Option<Arc<dyn RootMatcher>>) {
+        self.root_matcher = root_matcher;
+    }
+
+    /// Sets the maximum number of file boundaries that a path may cross.  A path crosses a file
+    /// boundary every time it continues in a different file than the one it was in, usually via
+    /// the root node.  Paths that leave a file through the root node and return to the same file
+    /// do not cross a boundary.  Extensions that cross more boundaries than allowed are dropped.
+    /// If no maximum is set, paths can cross any number of file boundaries.
+    pub fn set_max_file_hops(&mut self, max_file_hops: Option<usize>) {
+        self.max_file_hops = max_file_hops;
+    }
+
+    /// Sets a sink that receives a [`TraceEvent`][] for every stitching decision: which paths are
+    /// dequeued, which concatenations succeed or fail, and which extensions are complete.  This is
+    /// useful to diagnose why a reference resolves to an unexpected definition, or not at all.
+    pub fn set_trace_sink(&mut self, trace_sink: Option<TraceSink>) {
+        self.trace_sink = trace_sink;
+    }
+
     /// Sets whether to collect statistics during stitching.
     pub fn set_collect_stats(&mut self, collect_stats: bool) {
         if !collect_stats {
@@ -972,7 +1233,7 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         candidates: &mut C,
         partial_path: &PartialPath,
         cycle_detector: AppendingCycleDetector<H>,
-        has_split: bool,
+        state: PathState,
     ) -> usize
     where
         A: Appendable,
@@ -1009,49 +1270,64 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
             };
             if cyclic {
                 copious_debugging!("      is discontinued: cyclic");
+                if let Some(trace_sink) = &mut self.trace_sink {
+                    trace_sink(graph, partials, &TraceEvent::Cyclic(partial_path));
+                }
                 return 0;
             }
         }
 
-        // find candidates to append
-        self.candidates.clear();
-        candidates.get_forward_candidates(partial_path, &mut self.candidates);
-        let (graph, partials, db) = candidates.get_graph_partials_and_db();
-
-        // try to extend path with candidates
-        let candidate_count = self.candidates.len();
+        // find candidates to append; if the path ends at the root node, let the root matcher
+        // decide which symbol stacks the candidates are matched against
         self.extensions.clear();
-        self.extensions.reserve(candidate_count);
-        for candidate in &self.candidates {
-            let appendable = db.get_appendable(candidate);
-            copious_debugging!("      with {}", appendable.display(graph, partials));
-
-            let mut new_partial_path = partial_path.clone();
-            let mut new_cycle_detector = cycle_detector.clone();
-            // If there are errors concatenating these partial paths, or resolving the resulting
-            // partial path, just skip the extension — it's not a fatal error.
-            #[cfg_attr(not(feature = "copious-debugging"), allow(unused_variables))]
-            {
-                if let Err(err) = appendable.append_to(graph, partials, &mut new_partial_path) {
-                    copious_debugging!("        is invalid: {:?}", err);
-                    continue;
+        let (graph, _, _) = candidates.get_graph_partials_and_db();
+        let candidate_count = match &self.root_matcher {
+            Some(root_matcher) if graph[partial_path.end_node].is_root() => {
+                let root_matcher = root_matcher.clone();
+                let (graph, partials, _) = candidates.get_graph_partials_and_db();
+                let mut symbol_stacks = Vec::new();
+                root_matcher.match_symbol_stack(
+                    graph,
+                    partials,
+                    partial_path.symbol_stack_postcondition,
+                    &mut symbol_stacks,
+                );
+                let mut candidate_count = 0;
+                for symbol_stack in symbol_stacks {
+                    let mut matched_path = partial_path.clone();
+                    matched_path.symbol_stack_postcondition = symbol_stack;
+                    candidate_count += self.extend_with_candidates(
+                        candidates,
+                        &matched_path,
+                        &cycle_detector,
+                        state,
+                    );
                 }
+                candidate_count
             }
-            new_cycle_detector.append(&mut self.appended_paths, candidate.clone());
-            copious_debugging!("        is {}", new_partial_path.display(graph, partials));
-            self.extensions.push((new_partial_path, new_cycle_detector));
-        }
+            _ => self.extend_with_candidates(candidates, partial_path, &cycle_detector, state),
+        };
+
+        // queue the extensions in a deterministic order, so that the order in which paths are
+        // found does not depend on the order in which the candidates were returned
+        let (graph, partials, _) = candidates.get_graph_partials_and_db();
+        self.extensions.sort_by(|(left, _, _), (right, _, _)| {
+            left.cmp(graph, partials, right)
+                .then_with(|| left.edges.cmp(partials, right.edges))
+        });
 
         let extension_count = self.extensions.len();
-        let new_has_split = has_split || self.extensions.len() > 1;
+        let new_has_split = state.has_split || self.extensions.len() > 1;
         self.next_iteration.0.reserve(extension_count);
         self.next_iteration.1.reserve(extension_count);
         self.next_iteration.2.reserve(extension_count);
-        for (new_partial_path, new_cycle_detector) in self.extensions.drain(..) {
+        for (new_partial_path, new_cycle_detector, mut new_state) in self.extensions.drain(..) {
+            new_state.has_split = new_has_split;
             let check_similar_path = new_has_split
                 && (!self.check_only_join_nodes
                     || candidates.get_joining_candidate_degree(&new_partial_path)
                         == Degree::Multiple);
+            let check_similar_path = check_similar_path && !new_state.passes_kept_node;
             let (graph, partials, _) = candidates.get_graph_partials_and_db();
             if check_similar_path {
                 if let Some(similar_path_detector) = &mut self.similar_path_detector {
@@ -1078,14 +1354,26 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
                             new_partial_path.display(graph, partials)
                         );
                         copious_debugging!("        is rejected: too many similar");
+                        if let Some(trace_sink) = &mut self.trace_sink {
+                            trace_sink(
+                                graph,
+                                partials,
+                                &TraceEvent::SimilarPathRejected(&new_partial_path),
+                            );
+                        }
                         continue;
                     }
                 }
             }
 
+            if let Some(trace_sink) = &mut self.trace_sink {
+                if new_partial_path.is_complete(graph) {
+                    trace_sink(graph, partials, &TraceEvent::Completed(&new_partial_path));
+                }
+            }
             self.next_iteration.0.push(new_partial_path);
             self.next_iteration.1.push(new_cycle_detector);
-            self.next_iteration.2.push(new_has_split);
+            self.next_iteration.2.push(new_state);
         }
 
         if let Some(stats) = &mut self.stats {
@@ -1107,6 +1395,77 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         candidate_count
     }
 
+    /// Finds the candidates that can be appended to a partial path, and adds the resulting
+    /// extensions to `self.extensions`.  Returns the number of candidates that were found.
+    fn extend_with_candidates<A, Db, C, Err>(
+        &mut self,
+        candidates: &mut C,
+        partial_path: &PartialPath,
+        cycle_detector: &AppendingCycleDetector<H>,
+        state: PathState,
+    ) -> usize
+    where
+        A: Appendable,
+        Db: ToAppendable<H, A>,
+        C: ForwardCandidates<H, A, Db, Err>,
+    {
+        self.candidates.clear();
+        candidates.get_forward_candidates(partial_path, &mut self.candidates);
+        let (graph, partials, db) = candidates.get_graph_partials_and_db();
+
+        // try to extend path with candidates
+        let candidate_count = self.candidates.len();
+        self.extensions.reserve(candidate_count);
+        for candidate in &self.candidates {
+            let appendable = db.get_appendable(candidate);
+            copious_debugging!("      with {}", appendable.display(graph, partials));
+
+            let mut new_partial_path = partial_path.clone();
+            let mut new_cycle_detector = cycle_detector.clone();
+            // If there are errors concatenating these partial paths, or resolving the resulting
+            // partial path, just skip the extension — it's not a fatal error.
+            if let Err(err) = appendable.append_to(graph, partials, &mut new_partial_path) {
+                copious_debugging!("        is invalid: {:?}", err);
+                if let Some(trace_sink) = &mut self.trace_sink {
+                    trace_sink(
+                        graph,
+                        partials,
+                        &TraceEvent::ConcatenationFailed {
+                            path: partial_path,
+                            error: &err,
+                        },
+                    );
+                }
+                continue;
+            }
+            let mut new_state = state;
+            if let Some(max_file_hops) = self.max_file_hops {
+                appendable.visit_files(graph, partials, &mut |file| new_state.enter_file(file));
+                if new_state.file_hops > max_file_hops {
+                    copious_debugging!("        is dropped: too many file hops");
+                    continue;
+                }
+            }
+            new_cycle_detector.append(&mut self.appended_paths, candidate.clone());
+            copious_debugging!("        is {}", new_partial_path.display(graph, partials));
+            if let Some(trace_sink) = &mut self.trace_sink {
+                trace_sink(
+                    graph,
+                    partials,
+                    &TraceEvent::Extended {
+                        path: partial_path,
+                        extension: &new_partial_path,
+                    },
+                );
+            }
+            new_state.passes_kept_node = state.passes_kept_node
+                || (graph.has_kept_nodes() && appendable.passes_through_kept_node(graph, partials));
+            self.extensions
+                .push((new_partial_path, new_cycle_detector, new_state));
+        }
+        candidate_count
+    }
+
     /// Returns whether the algorithm has completed.
     pub fn is_complete(&self) -> bool {
         self.queue.is_empty() && self.next_iteration.0.is_empty()
@@ -1141,12 +1500,15 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
             stats.queued_paths_per_phase.record(self.queue.len());
         }
         let mut work_performed = 0;
-        while let Some((partial_path, cycle_detector, has_split)) = self.queue.pop_front() {
+        while let Some((partial_path, cycle_detector, state)) = self.queue.pop_front() {
             let (graph, partials, _) = candidates.get_graph_partials_and_db();
             copious_debugging!(
                 "--> Candidate partial path {}",
                 partial_path.display(graph, partials)
             );
+            if let Some(trace_sink) = &mut self.trace_sink {
+                trace_sink(graph, partials, &TraceEvent::Dequeued(&partial_path));
+            }
             if self.initial_paths_in_queue > 0 {
                 self.initial_paths_in_queue -= 1;
             } else if !extend_while(graph, partials, &partial_path) {
@@ -1156,7 +1518,7 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
                 );
                 continue;
             }
-            work_performed += self.extend(candidates, &partial_path, cycle_detector, has_split);
+            work_performed += self.extend(candidates, &partial_path, cycle_detector, state);
             if work_performed >= self.max_work_per_phase {
                 break;
             }
@@ -1202,20 +1564,54 @@ impl ForwardPartialPathStitcher<Edge> {
         file: Handle<File>,
         config: StitcherConfig,
         cancellation_flag: &dyn CancellationFlag,
+        visit: F,
+    ) -> Result<Stats, CancellationError>
+    where
+        F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
+    {
+        Self::find_minimal_partial_path_set_in_file_with_endpoints(
+            graph,
+            partials,
+            file,
+            |graph, node| graph[node].is_endpoint(),
+            config,
+            cancellation_flag,
+            visit,
+        )
+    }
+
+    /// Finds a minimal set of partial paths in a file between a custom set of endpoint nodes,
+    /// calling the `visit` closure for each one.
+    ///
+    /// This behaves like [`find_minimal_partial_path_set_in_file`][], except that the `is_endpoint`
+    /// closure decides which nodes partial paths start and end at, instead of the default
+    /// boundaries (the root node, exported scopes, references, and definitions). Only nodes of
+    /// the file and the root node are considered. Paths ending in a jump to scope node are
+    /// always visited.
+    ///
+    /// [`find_minimal_partial_path_set_in_file`]: #method.find_minimal_partial_path_set_in_file
+    pub fn find_minimal_partial_path_set_in_file_with_endpoints<E, F>(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        file: Handle<File>,
+        is_endpoint: E,
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
         mut visit: F,
     ) -> Result<Stats, CancellationError>
     where
+        E: Fn(&StackGraph, Handle<Node>) -> bool,
         F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
     {
-        fn as_complete_as_necessary(graph: &StackGraph, path: &PartialPath) -> bool {
-            path.starts_at_endpoint(graph)
-                && (path.ends_at_endpoint(graph) || path.ends_in_jump(graph))
-        }
+        let as_complete_as_necessary = |graph: &StackGraph, path: &PartialPath| {
+            is_endpoint(graph, path.start_node)
+                && (is_endpoint(graph, path.end_node) || path.ends_in_jump(graph))
+        };
 
         let initial_paths = graph
             .nodes_for_file(file)
             .chain(std::iter::once(StackGraph::root_node()))
-            .filter(|node| graph[*node].is_endpoint())
+            .filter(|node| is_endpoint(graph, *node))
             .map(|node| PartialPath::from_node(graph, partials, node))
             .collect::<Vec<_>>();
         let mut stitcher =
@@ -1262,7 +1658,7 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         starting_nodes: I,
         config: StitcherConfig,
         cancellation_flag: &dyn CancellationFlag,
-        mut visit: F,
+        visit: F,
     ) -> Result<Stats, Err>
     where
         I: IntoIterator<Item = Handle<Node>>,
@@ -1282,6 +1678,41 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
                 p
             })
             .collect::<Vec<_>>();
+        Self::find_all_complete_partial_paths_from_paths(
+            candidates,
+            initial_paths,
+            config,
+            cancellation_flag,
+            visit,
+        )
+    }
+
+    /// Finds all complete partial paths that extend a set of initial partial paths, building them
+    /// up by stitching together partial paths from this database, and calling the `visit` closure
+    /// on each one.  The initial paths themselves are not visited.
+    ///
+    /// This allows resolution to be continued from the paths of an earlier, partial resolution,
+    /// for instance when more input arrives in an interactive session.  As with
+    /// [`from_partial_paths`][Self::from_partial_paths], the caller decides whether the
+    /// precondition variables of the initial paths are eliminated.  Paths found from initial paths
+    /// that keep their variables can be concatenated onto later, which makes them suitable as the
+    /// saved state for such a continuation.
+    pub fn find_all_complete_partial_paths_from_paths<I, F, A, Db, C, Err>(
+        candidates: &mut C,
+        initial_paths: I,
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+        mut visit: F,
+    ) -> Result<Stats, Err>
+    where
+        I: IntoIterator<Item = PartialPath>,
+        A: Appendable,
+        Db: ToAppendable<H, A>,
+        C: ForwardCandidates<H, A, Db, Err>,
+        F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
+        Err: std::convert::From<CancellationError>,
+    {
+        let (graph, partials, _) = candidates.get_graph_partials_and_db();
         let mut stitcher =
             ForwardPartialPathStitcher::from_partial_paths(graph, partials, initial_paths);
         config.apply(&mut stitcher);
@@ -1296,7 +1727,7 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
             stitcher.process_next_phase(candidates, |_, _, _| true);
             let (graph, partials, _) = candidates.get_graph_partials_and_db();
             for path in stitcher.previous_phase_partial_paths() {
-                if path.is_complete(graph) {
+                if path.is_complete(graph) && config.accepts_definition(graph, path.end_node) {
                     accepted_path_length.record(path.edges.len());
                     visit(graph, partials, path);
                 }
@@ -1310,6 +1741,749 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
     }
 }
 
+/// Returns whether a partial path starts at, ends at, or passes through a node whose paths must be
+/// [kept][StackGraph::keeps_paths].
+fn passes_through_kept_node(
+    graph: &StackGraph,
+    partials: &PartialPaths,
+    path: &PartialPath,
+) -> bool {
+    if !graph.has_kept_nodes() {
+        return false;
+    }
+    graph.keeps_paths(path.start_node)
+        || graph.keeps_paths(path.end_node)
+        || path.edges.iter_unordered(partials).any(|edge| {
+            graph
+                .node_for_id(edge.source_node_id)
+                .is_some_and(|node| graph.keeps_paths(node))
+        })
+}
+
+/// Returns the paths that are not shadowed by any other path in `paths`, which should all start at
+/// the same reference.  Paths are first compared by edge precedence, as in
+/// [`PartialPath::shadows`][].  Of the remaining paths, only those that end in a definition with
+/// the highest [definition rank][StackGraph::definition_rank] are returned.
+pub fn unshadowed_paths(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    paths: &[PartialPath],
+) -> Vec<PartialPath> {
+    let mut result = Vec::new();
+    for path in paths {
+        if paths.iter().all(|other| !other.shadows(partials, path)) {
+            result.push(path.clone());
+        }
+    }
+    if let Some(max_rank) = result
+        .iter()
+        .map(|path| graph.definition_rank(path.end_node))
+        .max()
+    {
+        result.retain(|path| graph.definition_rank(path.end_node) == max_rank);
+    }
+    result
+}
+
+/// Returns the [unshadowed paths][unshadowed_paths] in `paths` that have the fewest edges.  The
+/// paths should all start at the same reference.
+///
+/// This is a heuristic for selecting the _nearest_ definition when several definitions remain
+/// after shadowing, for example in dynamic languages where a name can be defined multiple times
+/// along a scope chain.  The number of edges is only a proxy for lexical nearness, and depends on
+/// how the stack graph is constructed, so this is not guaranteed to select the intended
+/// definition.
+pub fn nearest_paths(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    paths: &[PartialPath],
+) -> Vec<PartialPath> {
+    let mut result = unshadowed_paths(graph, partials, paths);
+    if let Some(min_len) = result.iter().map(|path| path.edges.len()).min() {
+        result.retain(|path| path.edges.len() == min_len);
+    }
+    result
+}
+
+/// Computes the resolution coverage of the references in a file.  Returns a `(resolved, total)`
+/// pair, where `total` is the number of reference nodes in the file, and `resolved` is the number
+/// of those references for which at least one complete path can be found.
+///
+/// Paths are found by stitching together the partial paths in `db`, so the database must already
+/// contain all partial paths that might be needed to resolve the file's references.  Stitching
+/// uses the given configuration, which should be the one the language resolves references with,
+/// so that the coverage matches the actual resolution.
+pub fn resolution_coverage(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    db: &mut Database,
+    file: Handle<File>,
+    config: &StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<(usize, usize), CancellationError> {
+    let references = graph
+        .nodes_for_file(file)
+        .filter(|node| graph[*node].is_reference())
+        .collect::<Vec<_>>();
+    let mut resolved = HandleSet::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(graph, partials, db),
+        references.iter().copied(),
+        config.clone(),
+        cancellation_flag,
+        |_, _, path| resolved.add(path.start_node),
+    )?;
+    let resolved = references
+        .iter()
+        .filter(|node| resolved.contains(**node))
+        .count();
+    Ok((resolved, references.len()))
+}
+
+/// Returns the symbols of all references in the graph that do not resolve to any definition.
+/// Each symbol is returned once, in the order of the first unresolved reference with that symbol.
+/// A symbol is reported if any of its references is unresolved, even if other references with
+/// the same symbol do resolve.
+///
+/// Paths are found by stitching together the partial paths in `db`, so the database must already
+/// contain all partial paths that might be needed to resolve the graph's references.  Stitching
+/// uses the given configuration, which should be the one the language resolves references with.
+pub fn unresolved_symbols(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    db: &mut Database,
+    config: &StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<Handle<Symbol>>, CancellationError> {
+    let references = graph
+        .iter_nodes()
+        .filter(|node| graph[*node].is_reference())
+        .collect::<Vec<_>>();
+    let mut resolved = HandleSet::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(graph, partials, db),
+        references.iter().copied(),
+        config.clone(),
+        cancellation_flag,
+        |_, _, path| resolved.add(path.start_node),
+    )?;
+    let mut seen = HandleSet::new();
+    let mut symbols = Vec::new();
+    for reference in references {
+        if resolved.contains(reference) {
+            continue;
+        }
+        if let Some(symbol) = graph[reference].symbol() {
+            if !seen.contains(symbol) {
+                seen.add(symbol);
+                symbols.push(symbol);
+            }
+        }
+    }
+    Ok(symbols)
+}
+
+/// Finds all complete paths from `reference` to `definition`.  There can be more than one, for
+/// example if the definition is reachable via different intermediate scopes.  This can be used to
+/// explain why (and in how many ways) a reference binds to a particular definition.
+///
+/// Paths are found by stitching together the partial paths in `db`, so the database must already
+/// contain all partial paths that might be needed to resolve the reference.  Stitching uses the
+/// given configuration, which should be the one the language resolves references with, except
+/// that similar path detection is disabled, because it would drop paths that only differ in their
+/// edges.  For the same reason, the partial paths in `db` should be computed without similar path
+/// detection.
+pub fn find_paths_between(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    db: &mut Database,
+    reference: Handle<Node>,
+    definition: Handle<Node>,
+    config: &StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<PartialPath>, CancellationError> {
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(graph, partials, db),
+        std::iter::once(reference),
+        config.clone().with_detect_similar_paths(false),
+        cancellation_flag,
+        |_, _, path| {
+            if path.end_node == definition {
+                paths.push(path.clone());
+            }
+        },
+    )?;
+    Ok(paths)
+}
+
+/// A definition that a reference resolves to, together with the files that the path from the
+/// reference to the definition passes through.
+#[derive(Clone, Debug)]
+pub struct ImportChain {
+    pub definition: Handle<Node>,
+    /// The files that the path passes through, in order, starting with the file of the reference
+    /// and ending with the file of the definition.  Consecutive files are separated by a pass
+    /// through the root node, so every pair of consecutive files is one hop from one file to the
+    /// next.  A path that does not pass through the root node has a single file.
+    pub files: Vec<Handle<File>>,
+}
+
+/// Resolves a reference, and returns for every definition it resolves to the chain of files that
+/// the path to the definition passes through.  This can be used to follow a reference through
+/// re-exports, which would otherwise be hidden in the complete path.  Only the
+/// [unshadowed paths][unshadowed_paths] of the reference are considered.
+///
+/// Paths are found by stitching together the partial paths in `db`, so the database must already
+/// contain all partial paths that might be needed to resolve the reference.  Stitching uses the
+/// given configuration, which should be the one the language resolves references with.
+pub fn resolve_with_import_chains(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    db: &mut Database,
+    reference: Handle<Node>,
+    config: &StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<ImportChain>, CancellationError> {
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(graph, partials, db),
+        std::iter::once(reference),
+        config.clone(),
+        cancellation_flag,
+        |_, _, path| paths.push(path.clone()),
+    )?;
+    let mut chains = Vec::new();
+    for path in unshadowed_paths(graph, partials, &paths) {
+        let mut files = Vec::new();
+        let mut crossed_root = true;
+        for node in path.nodes(graph, partials) {
+            if graph[node].is_root() {
+                crossed_root = true;
+            } else if let Some(file) = graph[node].file() {
+                if crossed_root {
+                    files.push(file);
+                    crossed_root = false;
+                }
+            }
+        }
+        chains.push(ImportChain {
+            definition: path.end_node,
+            files,
+        });
+    }
+    Ok(chains)
+}
+
+/// Finds the definitions that a definition shadows.  These are the other definitions with the
+/// same symbol that some reference would resolve to as well, if the path from that reference to
+/// `definition` did not shadow the path to them.
+///
+/// Paths are found by stitching together the partial paths in `db`, so the database must already
+/// contain all partial paths that might be needed to resolve references to the definition.
+/// Stitching uses the given configuration, which should be the one the language resolves
+/// references with.
+pub fn shadowed_definitions(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    db: &mut Database,
+    definition: Handle<Node>,
+    config: &StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<HandleSet<Node>, CancellationError> {
+    let mut shadowed = HandleSet::new();
+    let symbol = match graph[definition].symbol() {
+        Some(symbol) if graph[definition].is_definition() => symbol,
+        _ => return Ok(shadowed),
+    };
+    let references = graph
+        .iter_nodes()
+        .filter(|node| graph[*node].is_reference() && graph[*node].symbol() == Some(symbol))
+        .collect::<Vec<_>>();
+    let mut paths_by_reference = HashMap::<_, Vec<PartialPath>>::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(graph, partials, db),
+        references,
+        config.clone(),
+        cancellation_flag,
+        |_, _, path| {
+            paths_by_reference
+                .entry(path.start_node)
+                .or_default()
+                .push(path.clone())
+        },
+    )?;
+    for paths in paths_by_reference.values() {
+        for path in paths.iter().filter(|p| p.end_node == definition) {
+            for other in paths.iter() {
+                if other.end_node != definition
+                    && graph[other.end_node].symbol() == Some(symbol)
+                    && path.shadows(partials, other)
+                {
+                    shadowed.add(other.end_node);
+                }
+            }
+        }
+    }
+    Ok(shadowed)
+}
+
+/// Finds the references that resolve to a definition.  Every reference with the same symbol as
+/// the definition is a candidate, but a candidate is only returned if stitching finds a complete
+/// path from it to `definition`, which is not shadowed by any of its other paths.  References in
+/// unrelated scopes that merely share the definition's name are therefore not returned.
+///
+/// Paths are found by stitching together the partial paths in `db`, so the database must already
+/// contain all partial paths that might be needed to resolve the candidate references.  Stitching
+/// uses the given configuration, which should be the one the language resolves references with.
+pub fn find_references(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    db: &mut Database,
+    definition: Handle<Node>,
+    config: &StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<Handle<Node>>, CancellationError> {
+    let symbol = match graph[definition].symbol() {
+        Some(symbol) if graph[definition].is_definition() => symbol,
+        _ => return Ok(Vec::new()),
+    };
+    let candidates = graph
+        .iter_nodes()
+        .filter(|node| graph[*node].is_reference() && graph[*node].symbol() == Some(symbol))
+        .collect::<Vec<_>>();
+    let mut paths_by_reference = HashMap::<_, Vec<PartialPath>>::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(graph, partials, db),
+        candidates.iter().copied(),
+        config.clone(),
+        cancellation_flag,
+        |_, _, path| {
+            paths_by_reference
+                .entry(path.start_node)
+                .or_default()
+                .push(path.clone())
+        },
+    )?;
+    let mut references = Vec::new();
+    for reference in candidates {
+        cancellation_flag.check("finding references")?;
+        let paths = match paths_by_reference.get(&reference) {
+            Some(paths) => paths,
+            None => continue,
+        };
+        if unshadowed_paths(graph, partials, paths)
+            .iter()
+            .any(|path| path.end_node == definition)
+        {
+            references.push(reference);
+        }
+    }
+    Ok(references)
+}
+
+/// Returns the symbols that a file exports, i.e., the symbols of the definitions in the file that
+/// are reachable from the root node.  These are found by computing the file's partial paths, and
+/// keeping those that start at the root node and end at a definition.  Each symbol is returned
+/// once, in the order in which it was first found.
+///
+/// This does not require any cross-file stitching, and can be used to build a lightweight index
+/// of module interfaces.  The partial paths are computed with the given configuration, which
+/// should be the one the language uses for indexing.
+pub fn exported_symbols(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    file: Handle<File>,
+    config: &StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<Handle<Symbol>>, CancellationError> {
+    let paths = partial_paths_in_file_matching(
+        graph,
+        partials,
+        file,
+        config,
+        cancellation_flag,
+        |graph, path| {
+            graph[path.start_node].is_root()
+                && path.ends_at_definition(graph)
+                && graph[path.end_node].is_in_file(file)
+        },
+    )?;
+    let mut seen = HandleSet::new();
+    let mut symbols = Vec::new();
+    for path in paths {
+        if let Some(symbol) = graph[path.end_node].symbol() {
+            if !seen.contains(symbol) {
+                seen.add(symbol);
+                symbols.push(symbol);
+            }
+        }
+    }
+    Ok(symbols)
+}
+
+/// Computes the minimal set of partial paths of a file, and returns the ones that match the
+/// given predicate, in the order in which they were found.
+fn partial_paths_in_file_matching<F>(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    file: Handle<File>,
+    config: &StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+    mut predicate: F,
+) -> Result<Vec<PartialPath>, CancellationError>
+where
+    F: FnMut(&StackGraph, &PartialPath) -> bool,
+{
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        graph,
+        partials,
+        file,
+        config.clone(),
+        cancellation_flag,
+        |graph, _, path| {
+            if predicate(graph, path) {
+                paths.push(path.clone());
+            }
+        },
+    )?;
+    Ok(paths)
+}
+
+/// Returns the partial paths of a file that cross the root node with a non-empty symbol stack.
+/// These are the paths that connect the file to other files: paths that start at the root node
+/// and require symbols on the stack, such as exports, and paths that end at the root node and
+/// leave symbols on the stack, such as imports.  They are exactly the paths of the file that must
+/// be stitched together with paths from other files at query time, provided that the given
+/// configuration is the one the language uses for indexing.
+pub fn root_crossing_partial_paths(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    file: Handle<File>,
+    config: &StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<PartialPath>, CancellationError> {
+    partial_paths_in_file_matching(
+        graph,
+        partials,
+        file,
+        config,
+        cancellation_flag,
+        |graph, path| {
+            let is_export =
+                graph[path.start_node].is_root() && !path.symbol_stack_precondition.is_empty();
+            let is_import =
+                graph[path.end_node].is_root() && !path.symbol_stack_postcondition.is_empty();
+            is_export || is_import
+        },
+    )
+}
+
+/// The scope stacks of a partial path that reaches a node.  Returned by
+/// [`scope_stacks_at_node`][].
+#[derive(Clone, Copy)]
+pub struct ScopeStacksAtNode {
+    /// The node that the partial path starts at.
+    pub start_node: Handle<Node>,
+    /// The scope stack that the partial path requires at its start node.
+    pub scope_stack_precondition: PartialScopeStack,
+    /// The scope stack when the partial path reaches the node.
+    pub scope_stack_postcondition: PartialScopeStack,
+}
+
+/// Returns example scope stacks that are observed when reaching a node, which helps to understand
+/// why references do or do not resolve through the node.  The partial paths of the node's file
+/// are computed with the node as an additional endpoint, and the scope stacks of every partial
+/// path that ends at the node are reported.  Partial paths that cross into other files are not
+/// considered.  Nodes that do not belong to a file have no scope stacks.  The partial paths are
+/// computed with the given configuration, which should be the one the language uses for
+/// indexing, so that the stacks match the ones seen during extraction.
+pub fn scope_stacks_at_node(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    node: Handle<Node>,
+    config: &StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<ScopeStacksAtNode>, CancellationError> {
+    let file = match graph[node].file() {
+        Some(file) => file,
+        None => return Ok(Vec::new()),
+    };
+    let mut scope_stacks = Vec::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file_with_endpoints(
+        graph,
+        partials,
+        file,
+        |graph, n| n == node || graph[n].is_endpoint(),
+        config.clone(),
+        cancellation_flag,
+        |_, _, path| {
+            if path.end_node == node && path.start_node != node {
+                scope_stacks.push(ScopeStacksAtNode {
+                    start_node: path.start_node,
+                    scope_stack_precondition: path.scope_stack_precondition,
+                    scope_stack_postcondition: path.scope_stack_postcondition,
+                });
+            }
+        },
+    )?;
+    Ok(scope_stacks)
+}
+
+/// Reduces a graph to a minimal graph in which a reference still resolves to the same
+/// definitions, which is useful as a reproducer when reporting a wrong resolution.  Starting from
+/// the full graph, nodes and then edges are removed one at a time, and a removal is kept if the
+/// (unshadowed) definitions that the reference resolves to do not change.  This is repeated until
+/// no more nodes or edges can be removed.  The returned graph contains copies of the remaining
+/// nodes and all of their attributes, with the same local IDs as in the original graph, and of
+/// the files that contain them.
+///
+/// The reference is resolved with the given configuration, which should be the one the language
+/// resolves references with, so that wrong resolutions that depend on it can be reproduced.  This
+/// resolves the reference once for every removal that is tried, which makes it expensive for
+/// large graphs.  It is meant to be run offline.
+pub fn minimal_reproducer(
+    graph: &StackGraph,
+    reference: Handle<Node>,
+    config: &StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<StackGraph, CancellationError> {
+    let expected = resolved_definitions(graph, reference, config, cancellation_flag)?;
+    let mut reduction = Reduction::default();
+    loop {
+        let mut reduced = false;
+        for node in graph.iter_nodes() {
+            if node == reference
+                || graph[node].is_root()
+                || graph[node].is_jump_to()
+                || reduction.excluded_nodes.contains(node)
+            {
+                continue;
+            }
+            reduction.excluded_nodes.add(node);
+            if reduction.reproduces(graph, reference, &expected, config, cancellation_flag)? {
+                reduced = true;
+            } else {
+                reduction.excluded_nodes.remove(node);
+            }
+        }
+        for source in graph.iter_nodes() {
+            for edge in graph.outgoing_edges(source) {
+                if !reduction.include_node(graph, &edge.source)
+                    || !reduction.include_node(graph, &edge.sink)
+                    || !reduction.excluded_edges.insert((edge.source, edge.sink))
+                {
+                    continue;
+                }
+                if reduction.reproduces(graph, reference, &expected, config, cancellation_flag)? {
+                    reduced = true;
+                } else {
+                    reduction.excluded_edges.remove(&(edge.source, edge.sink));
+                }
+            }
+        }
+        if !reduced {
+            break;
+        }
+    }
+    Ok(reduction.reduce(graph))
+}
+
+/// Identifies a node across a graph and its reduced copies, in which file handles can differ.
+type ReducedNodeKey = (Option<String>, u32);
+
+fn reduced_node_key(graph: &StackGraph, node: Handle<Node>) -> ReducedNodeKey {
+    let id = graph[node].id();
+    (
+        id.file().map(|file| graph[file].name().to_string()),
+        id.local_id(),
+    )
+}
+
+/// Returns the unshadowed definitions that a reference resolves to, in sorted order.
+fn resolved_definitions(
+    graph: &StackGraph,
+    reference: Handle<Node>,
+    config: &StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<ReducedNodeKey>, CancellationError> {
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(graph, &mut partials, None),
+        std::iter::once(reference),
+        config.clone(),
+        cancellation_flag,
+        |_, _, path| paths.push(path.clone()),
+    )?;
+    let mut definitions = unshadowed_paths(graph, &mut partials, &paths)
+        .into_iter()
+        .map(|path| reduced_node_key(graph, path.end_node))
+        .collect::<Vec<_>>();
+    definitions.sort();
+    definitions.dedup();
+    Ok(definitions)
+}
+
+/// The nodes and edges that are removed from a graph by [`minimal_reproducer`][].
+#[derive(Default)]
+struct Reduction {
+    excluded_nodes: HandleSet<Node>,
+    excluded_edges: HashSet<(Handle<Node>, Handle<Node>)>,
+}
+
+impl Reduction {
+    /// Returns a copy of the graph without the excluded nodes and edges.  All attributes of the
+    /// remaining nodes and edges are copied, because they can influence the resolution.
+    fn reduce(&self, graph: &StackGraph) -> StackGraph {
+        let mut reduced = StackGraph::new();
+        let files = graph
+            .iter_files()
+            .filter(|file| self.include_file(graph, file))
+            .collect::<Vec<_>>();
+        reduced
+            .add_files_from_graph(graph, &files, self)
+            .expect("reduced graph is initially empty");
+        reduced
+    }
+
+    /// Returns whether the reference resolves to the expected definitions in the reduced graph.
+    fn reproduces(
+        &self,
+        graph: &StackGraph,
+        reference: Handle<Node>,
+        expected: &[ReducedNodeKey],
+        config: &StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<bool, CancellationError> {
+        let reduced = self.reduce(graph);
+        let reference = graph[reference].id();
+        let reference = match reference.file() {
+            Some(file) => reduced.get_file(graph[file].name()).and_then(|file| {
+                reduced.node_for_id(NodeID::new_in_file(file, reference.local_id()))
+            }),
+            None => None,
+        };
+        let reference = match reference {
+            Some(reference) => reference,
+            None => return Ok(false),
+        };
+        Ok(resolved_definitions(&reduced, reference, config, cancellation_flag)? == expected)
+    }
+}
+
+impl Filter for Reduction {
+    fn include_file(&self, graph: &StackGraph, file: &Handle<File>) -> bool {
+        graph
+            .nodes_for_file(*file)
+            .any(|node| !self.excluded_nodes.contains(node))
+    }
+
+    fn include_node(&self, _graph: &StackGraph, node: &Handle<Node>) -> bool {
+        !self.excluded_nodes.contains(*node)
+    }
+
+    fn include_edge(
+        &self,
+        _graph: &StackGraph,
+        source: &Handle<Node>,
+        sink: &Handle<Node>,
+    ) -> bool {
+        !self.excluded_edges.contains(&(*source, *sink))
+    }
+
+    fn include_partial_path(
+        &self,
+        _graph: &StackGraph,
+        _paths: &PartialPaths,
+        _path: &PartialPath,
+    ) -> bool {
+        true
+    }
+}
+
+/// A definition that a reference resolves to, as returned by [`StackGraph::resolve_at`][].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ResolvedDefinition {
+    /// The definition node.
+    pub node: Handle<Node>,
+    /// The source location of the definition, or `None` if the definition has no source span.
+    pub site: Option<DefinitionSite>,
+}
+
+impl StackGraph {
+    /// Resolves the reference at a position in a file, as given by an editor.  The position is
+    /// given as a 0-indexed line and a UTF-16 code unit offset within that line, as in the
+    /// Language Server Protocol.  A reference is at the position if its span contains it, where
+    /// a position directly after a reference also counts.  If several references are at the
+    /// position, the one with the smallest span is resolved.
+    ///
+    /// The reference is resolved by stitching together the partial paths in `db`, which must
+    /// already contain all partial paths that might be needed.  Stitching uses the given
+    /// configuration, which should be the one the language resolves references with, so that the
+    /// result agrees with other queries.  Shadowed definitions are not returned, and each
+    /// definition is returned once.  Returns an empty list if there is no reference at the
+    /// position.
+    pub fn resolve_at(
+        &self,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+        file: Handle<File>,
+        line: usize,
+        utf16_column: usize,
+        config: &StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<ResolvedDefinition>, CancellationError> {
+        let position = (line, utf16_column);
+        let reference = self
+            .nodes_for_file(file)
+            .filter(|node| self[*node].is_reference())
+            .filter_map(|node| {
+                let span = &self.source_info(node)?.span;
+                let start = (span.start.line, span.start.column.utf16_offset);
+                let end = (span.end.line, span.end.column.utf16_offset);
+                if start <= position && position <= end {
+                    Some((node, start, end))
+                } else {
+                    None
+                }
+            })
+            // the innermost reference starts last, and ends first
+            .max_by(|(_, left_start, left_end), (_, right_start, right_end)| {
+                left_start
+                    .cmp(right_start)
+                    .then_with(|| right_end.cmp(left_end))
+            });
+        let reference = match reference {
+            Some((reference, _, _)) => reference,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut DatabaseCandidates::new(self, partials, db),
+            std::iter::once(reference),
+            config.clone(),
+            cancellation_flag,
+            |_, _, path| paths.push(path.clone()),
+        )?;
+        let mut seen = HandleSet::new();
+        let mut definitions = Vec::new();
+        for path in unshadowed_paths(self, partials, &paths) {
+            if seen.contains(path.end_node) {
+                continue;
+            }
+            seen.add(path.end_node);
+            definitions.push(ResolvedDefinition {
+                node: path.end_node,
+                site: self.definition_site(path.end_node),
+            });
+        }
+        Ok(definitions)
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Stats {
     /// The distribution of the number of initial paths
@@ -1371,13 +2545,54 @@ impl std::ops::AddAssign<&Self> for Stats {
     }
 }
 
+/// Decides how the symbol stack of a partial path that reaches the root node is matched against
+/// the preconditions of partial paths that leave the root node.
+///
+/// Imports typically leave symbols on the symbol stack when they reach the root node, which are
+/// then matched by the exports of other files.  By default, those symbols must match exactly.  A
+/// root matcher lets you model module systems with other matching rules, such as resolving a
+/// package-qualified module name to the module itself.
+pub trait RootMatcher {
+    /// Adds to `result` each symbol stack that paths leaving the root node can be matched
+    /// against, for a path that reaches the root node with the given symbol stack.  Adding
+    /// `symbol_stack` itself preserves the default exact matching.
+    fn match_symbol_stack(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        symbol_stack: PartialSymbolStack,
+        result: &mut Vec<PartialSymbolStack>,
+    );
+}
+
+/// A [`RootMatcher`][] that requires symbol stacks to match exactly.
+pub struct ExactRootMatcher;
+
+impl RootMatcher for ExactRootMatcher {
+    fn match_symbol_stack(
+        &self,
+        _graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        symbol_stack: PartialSymbolStack,
+        result: &mut Vec<PartialSymbolStack>,
+    ) {
+        result.push(symbol_stack);
+    }
+}
+
 /// Configuration for partial path stitchers.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone)]
 pub struct StitcherConfig {
     /// Enables similar path detection during path stitching.
     detect_similar_paths: bool,
     /// Collect statistics about path stitching.
     collect_stats: bool,
+    /// Decides which paths can continue from paths that reach the root node.
+    root_matcher: Option<Arc<dyn RootMatcher>>,
+    /// Only accept complete paths ending in definitions with one of these syntax types.
+    definition_kinds: Option<Arc<[String]>>,
+    /// The maximum number of file boundaries that paths may cross.
+    max_file_hops: Option<usize>,
 }
 
 impl StitcherConfig {
@@ -1398,12 +2613,85 @@ impl StitcherConfig {
         self.collect_stats = collect_stats;
         self
     }
+
+    /// Returns the [`RootMatcher`][] that decides which paths can continue from paths that reach
+    /// the root node, or `None` if symbol stacks must match exactly.
+    pub fn root_matcher(&self) -> Option<&dyn RootMatcher> {
+        self.root_matcher.as_deref()
+    }
+
+    /// Use the given [`RootMatcher`][] to decide which paths can continue from paths that reach
+    /// the root node.
+    pub fn with_root_matcher(mut self, root_matcher: Arc<dyn RootMatcher>) -> Self {
+        self.root_matcher = Some(root_matcher);
+        self
+    }
+
+    /// Returns the syntax types of the definitions that complete paths may end in, or `None` if
+    /// paths may end in any definition.
+    pub fn definition_kinds(&self) -> Option<&[String]> {
+        self.definition_kinds.as_deref()
+    }
+
+    /// Only accept complete paths that end in a definition whose
+    /// [syntax type][crate::graph::SourceInfo::syntax_type] is one of the given kinds, for
+    /// instance to find the type definition of a reference instead of its definition.  Definitions
+    /// without a syntax type are not accepted.
+    pub fn with_definition_kinds<I, S>(mut self, definition_kinds: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.definition_kinds = Some(definition_kinds.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Returns the maximum number of file boundaries that paths may cross, or `None` if paths
+    /// can cross any number of file boundaries.
+    pub fn max_file_hops(&self) -> Option<usize> {
+        self.max_file_hops
+    }
+
+    /// Drop paths that cross more than the given number of file boundaries, i.e., that continue
+    /// in a different file more often.  Returning to the same file through the root node does
+    /// not count as crossing a boundary.  This bounds the cost of cross-file resolution, and can be
+    /// used to only resolve references within a package.
+    pub fn with_max_file_hops(mut self, max_file_hops: usize) -> Self {
+        self.max_file_hops = Some(max_file_hops);
+        self
+    }
+}
+
+impl std::fmt::Debug for StitcherConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StitcherConfig")
+            .field("detect_similar_paths", &self.detect_similar_paths)
+            .field("collect_stats", &self.collect_stats)
+            .field("root_matcher", &self.root_matcher.is_some())
+            .field("definition_kinds", &self.definition_kinds)
+            .field("max_file_hops", &self.max_file_hops)
+            .finish()
+    }
 }
 
 impl StitcherConfig {
     fn apply<H>(&self, stitcher: &mut ForwardPartialPathStitcher<H>) {
         stitcher.set_similar_path_detection(self.detect_similar_paths);
         stitcher.set_collect_stats(self.collect_stats);
+        stitcher.set_root_matcher(self.root_matcher.clone());
+        stitcher.set_max_file_hops(self.max_file_hops);
+    }
+
+    /// Returns whether a complete path ending at the given definition is accepted.
+    fn accepts_definition(&self, graph: &StackGraph, definition: Handle<Node>) -> bool {
+        let kinds = match &self.definition_kinds {
+            Some(kinds) => kinds,
+            None => return true,
+        };
+        graph
+            .source_info(definition)
+            .and_then(|info| info.syntax_type.into_option())
+            .is_some_and(|kind| kinds.iter().any(|k| k == &graph[kind]))
     }
 }
 
@@ -1412,6 +2700,9 @@ impl Default for StitcherConfig {
         Self {
             detect_similar_paths: true,
             collect_stats: false,
+            root_matcher: None,
+            definition_kinds: None,
+            max_file_hops: None,
         }
     }
 }
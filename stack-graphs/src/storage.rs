@@ -31,14 +31,17 @@ use crate::serde;
 use crate::serde::FileFilter;
 use crate::stitching::Database;
 use crate::stitching::ForwardCandidates;
+use crate::stitching::ForwardPartialPathStitcher;
+use crate::stitching::StitcherConfig;
 use crate::CancellationError;
 use crate::CancellationFlag;
 
-const VERSION: usize = 6;
+const VERSION: usize = 14;
 
 const SCHEMA: &str = r#"
         CREATE TABLE metadata (
-            version INTEGER NOT NULL
+            version          INTEGER NOT NULL,
+            definitions_only INTEGER NOT NULL DEFAULT 0
         ) STRICT;
         CREATE TABLE graphs (
             file   TEXT PRIMARY KEY,
@@ -148,6 +151,7 @@ impl<'a, P: Params + Clone> Files<'a, P> {
 /// Writer to store stack graphs and partial paths in a SQLite database.
 pub struct SQLiteWriter {
     conn: Connection,
+    definitions_only: bool,
 }
 
 impl SQLiteWriter {
@@ -156,7 +160,10 @@ impl SQLiteWriter {
         let mut conn = Connection::open_in_memory()?;
         Self::init(&mut conn)?;
         init_indexes(&mut conn)?;
-        Ok(Self { conn })
+        Ok(Self {
+            conn,
+            definitions_only: false,
+        })
     }
 
     /// Open a file database.  If the file does not exist, it is automatically created.
@@ -171,7 +178,11 @@ impl SQLiteWriter {
             check_version(&conn)?;
         }
         init_indexes(&mut conn)?;
-        Ok(Self { conn })
+        let definitions_only = definitions_only(&conn)?;
+        Ok(Self {
+            conn,
+            definitions_only,
+        })
     }
 
     /// Create database tables and write metadata.
@@ -183,14 +194,39 @@ impl SQLiteWriter {
         Ok(())
     }
 
-    /// Clean all data from the database.
+    /// Clean all data from the database.  This also resets the database to store all partial
+    /// paths, see [`set_definitions_only`][Self::set_definitions_only].
     pub fn clean_all(&mut self) -> Result<usize> {
         let tx = self.conn.transaction()?;
         let count = Self::clean_all_inner(&tx)?;
+        tx.execute("UPDATE metadata SET definitions_only = 0", [])?;
         tx.commit()?;
+        self.definitions_only = false;
         Ok(count)
     }
 
+    /// Returns whether this database is a definitions-only index, see
+    /// [`set_definitions_only`][Self::set_definitions_only].
+    pub fn definitions_only(&self) -> bool {
+        self.definitions_only
+    }
+
+    /// Makes this database a definitions-only index, which leaves out the partial paths that
+    /// start at references, to produce a smaller index that only contains the paths leading to
+    /// definitions.  From now on, [`store_result_for_file`][Self::store_result_for_file] drops
+    /// paths starting at references, and queries must recompute the reference paths of the
+    /// queried file using [`SQLiteReader::compute_reference_paths_for_file`][].
+    ///
+    /// The mode is stored in the database, and remains set until the database is
+    /// [cleaned][Self::clean_all], because files that were stored without reference paths may
+    /// still be present.
+    pub fn set_definitions_only(&mut self) -> Result<()> {
+        self.conn
+            .execute("UPDATE metadata SET definitions_only = 1", [])?;
+        self.definitions_only = true;
+        Ok(())
+    }
+
     /// Clean all data from the database.
     ///
     /// This is an inner method, which does not wrap individual SQL statements in a transaction.
@@ -298,7 +334,9 @@ impl SQLiteWriter {
         Ok(())
     }
 
-    /// Store the result of a successful file index.
+    /// Store the result of a successful file index.  If this database is a
+    /// [definitions-only][Self::set_definitions_only] index, paths starting at references are
+    /// not stored.
     pub fn store_result_for_file<'a, IP>(
         &mut self,
         graph: &StackGraph,
@@ -314,6 +352,10 @@ impl SQLiteWriter {
         let tx = self.conn.transaction()?;
         Self::clean_file_inner(&tx, path)?;
         Self::store_graph_for_file_inner(&tx, graph, file, tag)?;
+        let definitions_only = self.definitions_only;
+        let paths = paths
+            .into_iter()
+            .filter(|path| !(definitions_only && graph[path.start_node].is_reference()));
         Self::store_partial_paths_for_file_inner(&tx, graph, file, partials, paths)?;
         tx.commit()?;
         Ok(())
@@ -576,6 +618,68 @@ impl SQLiteReader {
         Ok(())
     }
 
+    /// Returns whether this database is a definitions-only index, which does not store partial
+    /// paths starting at references.  See [`SQLiteWriter::set_definitions_only`][].
+    pub fn definitions_only(&self) -> Result<bool> {
+        definitions_only(&self.conn)
+    }
+
+    /// Returns whether the database stores any partial paths starting at the given node.  Paths
+    /// starting at the root or jump to scope node are not stored per node, so this returns false
+    /// for those.
+    pub fn has_paths_for_node(&mut self, node: Handle<Node>) -> Result<bool> {
+        let id = self.graph[node].id();
+        let file = match id.file() {
+            Some(file) => file,
+            None => return Ok(false),
+        };
+        let file = self.graph[file].name();
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT EXISTS(SELECT 1 FROM file_paths WHERE file = ? AND local_id = ?)",
+        )?;
+        let exists = stmt.query_row((file, id.local_id()), |row| row.get::<_, bool>(0))?;
+        Ok(exists)
+    }
+
+    /// Computes the partial paths starting at references in the given file from the file's
+    /// graph, and adds them to the loaded database.
+    ///
+    /// Use this to resolve references in files that were indexed without reference paths (see
+    /// [`SQLiteWriter::set_definitions_only`][]).  Any reference paths that _are_ stored for the
+    /// file are not loaded anymore after this call, so that paths are never loaded twice.
+    pub fn compute_reference_paths_for_file(
+        &mut self,
+        file: &str,
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        copious_debugging!("--> Compute reference paths for {}", file);
+        let file = self.load_graph_for_file(file)?;
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &self.graph,
+            &mut self.partials,
+            file,
+            config,
+            cancellation_flag,
+            |graph, _partials, path| {
+                if graph[path.start_node].is_reference() {
+                    paths.push(path.clone());
+                }
+            },
+        )?;
+        for node in self.graph.nodes_for_file(file) {
+            if self.graph[node].is_reference() {
+                self.loaded_node_paths.insert(node);
+            }
+        }
+        for path in paths {
+            self.db
+                .add_partial_path(&self.graph, &mut self.partials, path);
+        }
+        Ok(())
+    }
+
     /// Ensure the paths starting a the given node are loaded.
     fn load_paths_for_node(
         &mut self,
@@ -720,7 +824,8 @@ impl SQLiteReader {
 //
 //     has-var GS ( symbol (US symbol)* )?
 //
-// where has-var is "V" if the symbol stack has a variable, "X" otherwise.
+// where has-var is "V" if the symbol stack has a variable, "X" otherwise.  The wildcard symbol is
+// written as SUB, so that it is distinct from any ordinary symbol.
 impl PartialSymbolStack {
     /// Returns a string representation of this symbol stack for indexing in the database.
     fn storage_key(self, graph: &StackGraph, partials: &mut PartialPaths) -> String {
@@ -731,7 +836,10 @@ impl PartialSymbolStack {
         }
         key += &self
             .iter(partials)
-            .map(|s| &graph[s.symbol])
+            .map(|s| match graph.is_wildcard_symbol(s.symbol) {
+                true => "\u{241A}",
+                false => &graph[s.symbol],
+            })
             .join("\u{241F}");
         key
     }
@@ -763,6 +871,8 @@ impl PartialSymbolStack {
             // patterns for paths for which this stack is a prefix
             key_patterns.push("_\u{241E}".to_string() + &symbols + "\u{241F}%");
         }
+        // pattern for paths whose stack starts with a wildcard, which may match any stack
+        key_patterns.push("_\u{241E}\u{241A}%".to_string());
         (key_patterns, "\\".to_string())
     }
 }
@@ -817,6 +927,15 @@ impl Stats {
     }
 }
 
+/// Returns whether the database is a definitions-only index.
+fn definitions_only(conn: &Connection) -> Result<bool> {
+    Ok(
+        conn.query_row("SELECT definitions_only FROM metadata", [], |r| {
+            r.get::<_, bool>(0)
+        })?,
+    )
+}
+
 /// Check if the database has the version supported by this library version.
 fn check_version(conn: &Connection) -> Result<()> {
     let version = conn.query_row("SELECT version FROM metadata", [], |r| r.get::<_, usize>(0))?;
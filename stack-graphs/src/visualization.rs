@@ -5,7 +5,11 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::cell::Cell;
+use std::cell::RefCell;
+
 use serde_json::Error;
+use thiserror::Error;
 
 use crate::arena::Handle;
 use crate::graph::File;
@@ -15,6 +19,9 @@ use crate::partial::PartialPath;
 use crate::partial::PartialPaths;
 use crate::serde::Filter;
 use crate::stitching::Database;
+use crate::CancellationError;
+use crate::CancellationFlag;
+use crate::NoCancellation;
 
 static CSS: &'static str = include_str!("visualization/visualization.css");
 static D3: &'static str = include_str!("visualization/d3.min.js");
@@ -24,6 +31,18 @@ static JS: &'static str = include_str!("visualization/visualization.js");
 static PKG: &'static str = env!("CARGO_PKG_NAME");
 static VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
+/// The number of files, nodes, edges, and paths visited between cancellation checks.
+const CANCELLATION_CHECK_INTERVAL: usize = 1024;
+
+/// An error that can occur while generating a visualization.
+#[derive(Debug, Error)]
+pub enum VisualizationError {
+    #[error(transparent)]
+    Cancelled(#[from] CancellationError),
+    #[error(transparent)]
+    Serialization(#[from] Error),
+}
+
 //-----------------------------------------------------------------------------
 // StackGraph
 
@@ -35,9 +54,32 @@ impl StackGraph {
         db: &mut Database,
         filter: &dyn Filter,
     ) -> Result<String, Error> {
-        let filter = VisualizationFilter(filter);
-        let graph = serde_json::to_string(&self.to_serializable_filter(&filter))?;
-        let paths = serde_json::to_string(&db.to_serializable_filter(self, partials, &filter))?;
+        match self.to_html_string_with_cancellation(title, partials, db, filter, &NoCancellation) {
+            Ok(html) => Ok(html),
+            Err(VisualizationError::Serialization(err)) => Err(err),
+            Err(VisualizationError::Cancelled(_)) => unreachable!("NoCancellation never cancels"),
+        }
+    }
+
+    /// Generates an HTML visualization of this graph and the paths in the given database.
+    /// Nodes, edges, and paths are emitted in chunks, and the cancellation flag is checked
+    /// after every chunk, so that rendering a large graph can be aborted.
+    pub fn to_html_string_with_cancellation(
+        &self,
+        title: &str,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+        filter: &dyn Filter,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<String, VisualizationError> {
+        cancellation_flag.check("visualizing graph")?;
+        let filter = VisualizationFilter::new(filter, cancellation_flag);
+        let graph = self.to_serializable_filter(&filter);
+        filter.check()?;
+        let graph = serde_json::to_string(&graph)?;
+        let paths = db.to_serializable_filter(self, partials, &filter);
+        filter.check()?;
+        let paths = serde_json::to_string(&paths)?;
         let html = format!(
             r#"
 <!DOCTYPE html>
@@ -100,19 +142,63 @@ impl StackGraph {
     }
 }
 
-struct VisualizationFilter<'a>(&'a dyn Filter);
+/// A filter that restricts paths to those that are useful to visualize, and that checks the
+/// cancellation flag periodically.  Once cancelled, the filter rejects everything, so that the
+/// remaining serialization finishes quickly.
+struct VisualizationFilter<'a> {
+    inner: &'a dyn Filter,
+    cancellation_flag: &'a dyn CancellationFlag,
+    visited: Cell<usize>,
+    error: RefCell<Option<CancellationError>>,
+}
+
+impl<'a> VisualizationFilter<'a> {
+    fn new(inner: &'a dyn Filter, cancellation_flag: &'a dyn CancellationFlag) -> Self {
+        Self {
+            inner,
+            cancellation_flag,
+            visited: Cell::new(0),
+            error: RefCell::new(None),
+        }
+    }
+
+    /// Returns whether rendering has been cancelled, checking the cancellation flag at the end
+    /// of every chunk.
+    fn is_cancelled(&self) -> bool {
+        if self.error.borrow().is_some() {
+            return true;
+        }
+        let visited = self.visited.get() + 1;
+        self.visited.set(visited);
+        if visited.is_multiple_of(CANCELLATION_CHECK_INTERVAL) {
+            if let Err(err) = self.cancellation_flag.check("visualizing graph") {
+                *self.error.borrow_mut() = Some(err);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns the cancellation error, if rendering was cancelled.
+    fn check(&self) -> Result<(), CancellationError> {
+        match self.error.borrow().as_ref() {
+            Some(err) => Err(err.clone()),
+            None => Ok(()),
+        }
+    }
+}
 
 impl Filter for VisualizationFilter<'_> {
     fn include_file(&self, graph: &StackGraph, file: &Handle<File>) -> bool {
-        self.0.include_file(graph, file)
+        !self.is_cancelled() && self.inner.include_file(graph, file)
     }
 
     fn include_node(&self, graph: &StackGraph, node: &Handle<Node>) -> bool {
-        self.0.include_node(graph, node)
+        !self.is_cancelled() && self.inner.include_node(graph, node)
     }
 
     fn include_edge(&self, graph: &StackGraph, source: &Handle<Node>, sink: &Handle<Node>) -> bool {
-        self.0.include_edge(graph, source, sink)
+        !self.is_cancelled() && self.inner.include_edge(graph, source, sink)
     }
 
     fn include_partial_path(
@@ -121,9 +207,18 @@ impl Filter for VisualizationFilter<'_> {
         paths: &PartialPaths,
         path: &PartialPath,
     ) -> bool {
-        self.0.include_partial_path(graph, paths, path)
+        !self.is_cancelled()
+            && self.inner.include_partial_path(graph, paths, path)
             && !path.edges.is_empty()
             && path.starts_at_reference(graph)
             && (path.ends_at_definition(graph) || path.ends_in_jump(graph))
     }
+
+    fn include_source_info(&self) -> bool {
+        self.inner.include_source_info()
+    }
+
+    fn include_debug_info(&self) -> bool {
+        self.inner.include_debug_info()
+    }
 }
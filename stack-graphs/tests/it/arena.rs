@@ -241,6 +241,22 @@ fn can_use_arena_after_clear() {
     assert_eq!(7, *a.get(h));
 }
 
+#[test]
+fn can_shrink_arena_after_clear() {
+    let mut a = Arena::new();
+    for i in 0..1000 {
+        a.add(i as u32);
+    }
+    let capacity = a.capacity();
+    a.clear();
+    assert_eq!(capacity, a.capacity());
+    a.shrink_to(10);
+    assert!(a.capacity() < capacity);
+    assert!(a.capacity() >= 10);
+    let h = a.add(7);
+    assert_eq!(7, *a.get(h));
+}
+
 #[test]
 fn can_use_supplemental_arena_after_clear() {
     let mut a = Arena::new();
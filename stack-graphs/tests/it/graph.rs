@@ -7,8 +7,22 @@
 
 use std::collections::HashSet;
 
+use lsp_positions::Offset;
+use lsp_positions::Position;
+use lsp_positions::Span;
 use maplit::hashset;
-use stack_graphs::graph::{Degree, StackGraph};
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::{
+    Degree, FileExistsError, Node, NodeID, NodeTypeCounts, SourcePreview, SpanMatch, StackGraph,
+    SymbolMismatch,
+};
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::Database;
+use stack_graphs::stitching::DatabaseCandidates;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
 
 use crate::test_graphs;
 use crate::test_graphs::CreateStackGraph;
@@ -123,6 +137,46 @@ fn can_display_strings() {
     assert_eq!(strings, vec!["a", "b", "c"]);
 }
 
+#[test]
+fn can_iterate_files() {
+    let mut graph = StackGraph::new();
+    assert_eq!(graph.file_count(), 0);
+    assert_eq!(graph.iter_files().count(), 0);
+    let f1 = graph.get_or_create_file("a.py");
+    let f2 = graph.get_or_create_file("b.py");
+    let f3 = graph.get_or_create_file("c.py");
+    assert_eq!(graph.get_or_create_file("b.py"), f2);
+    let files = graph.iter_files().collect::<HashSet<_>>();
+    assert_eq!(files, hashset! {f1, f2, f3});
+    assert_eq!(graph.file_count(), 3);
+}
+
+#[test]
+fn cannot_create_existing_file() {
+    let mut graph = StackGraph::new();
+    let file = graph
+        .try_create_file("a.py")
+        .expect("file should not exist");
+    assert_eq!(
+        Err(FileExistsError {
+            name: "a.py".to_string(),
+            handle: file,
+        }),
+        graph.try_create_file("a.py")
+    );
+    assert_eq!(graph.file_count(), 1);
+}
+
+#[test]
+fn can_get_file_name_for_node() {
+    let mut graph = StackGraph::new();
+    let file = graph.file("a.py");
+    let scope = graph.internal_scope(file, 0);
+    assert_eq!(Some("a.py"), graph.file_name_for_node(scope));
+    assert_eq!(None, graph.file_name_for_node(StackGraph::root_node()));
+    assert_eq!(None, graph.file_name_for_node(StackGraph::jump_to_node()));
+}
+
 #[test]
 fn can_iterate_nodes() {
     let mut graph = StackGraph::new();
@@ -137,6 +191,37 @@ fn can_iterate_nodes() {
     );
 }
 
+#[test]
+fn can_count_node_types() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let sym = graph.symbol("a");
+    graph.internal_scope(file, 0);
+    graph.exported_scope(file, 1);
+    graph.drop_scopes(file, 2);
+    graph.push_symbol(file, 3, sym);
+    graph.reference(file, 4, sym);
+    graph.pop_symbol(file, 5, sym);
+    graph.definition(file, 6, sym);
+    graph.pop_symbol(file, 7, sym);
+    graph.pop_scoped_symbol(file, 8, sym);
+    graph.push_scoped_symbol(file, 9, sym, file, 1);
+    assert_eq!(
+        graph.node_type_counts(),
+        NodeTypeCounts {
+            drop_scopes: 1,
+            jump_to: 1,
+            pop_scoped_symbol: 1,
+            pop_symbol: 3,
+            push_scoped_symbol: 1,
+            push_symbol: 2,
+            root: 1,
+            scope: 2,
+        }
+    );
+    assert_eq!(graph.node_type_counts().total(), 12);
+}
+
 #[test]
 fn can_add_and_remove_edges() {
     let mut graph = StackGraph::new();
@@ -159,6 +244,37 @@ fn can_add_and_remove_edges() {
     );
 }
 
+#[test]
+fn can_normalize_precedences() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let h1 = graph.internal_scope(file, 0);
+    let h2 = graph.internal_scope(file, 1);
+    let h3 = graph.internal_scope(file, 2);
+    let h4 = graph.internal_scope(file, 3);
+    let h5 = graph.internal_scope(file, 4);
+    graph.add_edge(h1, h2, -100);
+    graph.add_edge(h1, h3, 7);
+    graph.add_edge(h1, h4, 1000);
+    graph.add_edge(h1, h5, 7);
+    graph.add_edge(h2, h3, 42);
+    graph.normalize_precedences();
+    assert_eq!(
+        graph
+            .outgoing_edges(h1)
+            .map(|edge| (edge.sink, edge.precedence))
+            .collect::<HashSet<_>>(),
+        hashset! { (h2, 0), (h3, 1), (h4, 2), (h5, 1) }
+    );
+    assert_eq!(
+        graph
+            .outgoing_edges(h2)
+            .map(|edge| (edge.sink, edge.precedence))
+            .collect::<HashSet<_>>(),
+        hashset! { (h3, 0) }
+    );
+}
+
 #[test]
 fn singleton_nodes_have_correct_ids() {
     let graph = StackGraph::new();
@@ -197,6 +313,102 @@ fn can_add_graph_to_empty_graph() {
     }
 }
 
+#[test]
+fn can_clone_file_into_new_graph() {
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+    let sym_x = graph.symbol("x");
+    let sym_y = graph.symbol("y");
+
+    let file_a = graph.file("a.py");
+    let scope_a = graph.internal_scope(file_a, 0);
+    let def_x = graph.definition(file_a, 1, sym_x);
+    let ref_x = graph.reference(file_a, 2, sym_x);
+    let ref_y = graph.reference(file_a, 3, sym_y);
+    graph.edge(scope_a, def_x);
+    graph.edge(ref_x, scope_a);
+    graph.edge(ref_y, root);
+
+    let file_b = graph.file("b.py");
+    let def_y = graph.definition(file_b, 0, sym_y);
+    graph.edge(root, def_y);
+
+    let mut copy = StackGraph::new();
+    let copied_file = graph
+        .clone_file_into(file_a, &mut copy)
+        .expect("Expected cloning to succeed");
+    assert_eq!(vec![copied_file], copy.iter_files().collect::<Vec<_>>());
+    assert_eq!("a.py", copy[copied_file].name());
+    assert_eq!(4, copy.nodes_for_file(copied_file).count());
+    assert_eq!(0, copy.outgoing_edges(StackGraph::root_node()).count());
+
+    let copied_ref_x = copy
+        .node_for_id(NodeID::new_in_file(
+            copied_file,
+            graph[ref_x].id().local_id(),
+        ))
+        .expect("Expected reference to be copied");
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&copy, &mut partials, None),
+        vec![copied_ref_x],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| paths.push(path.end_node),
+    )
+    .expect("should never be cancelled");
+    assert_eq!(1, paths.len());
+    assert_eq!(graph[def_x].id().local_id(), copy[paths[0]].id().local_id());
+    assert_eq!("x", &copy[copy[paths[0]].symbol().unwrap()]);
+
+    assert_eq!(
+        Err(copied_file),
+        graph.clone_file_into(file_a, &mut copy),
+        "Expected cloning into a graph with the same file to fail"
+    );
+}
+
+#[test]
+fn can_clone_file_with_scoped_symbols_into_new_graph() {
+    let mut graph = StackGraph::new();
+    let sym_x = graph.symbol("x");
+
+    let file_a = graph.file("a.py");
+    let file_b = graph.file("b.py");
+    let scope_b = graph.exported_scope(file_b, 0);
+    let push_x = graph.push_scoped_symbol(file_a, 0, sym_x, file_b, 0);
+
+    let mut copy = StackGraph::new();
+    let copied_file = graph
+        .clone_file_into(file_a, &mut copy)
+        .expect("Expected cloning to succeed");
+
+    // the file of the attached scope is created, but its nodes are not copied
+    let scope_file = copy.get_file("b.py").expect("Expected scope file to exist");
+    assert_eq!(0, copy.nodes_for_file(scope_file).count());
+    let copied_push_x = copy
+        .node_for_id(NodeID::new_in_file(
+            copied_file,
+            graph[push_x].id().local_id(),
+        ))
+        .expect("Expected push scoped symbol node to be copied");
+    assert_eq!(
+        Some(NodeID::new_in_file(
+            scope_file,
+            graph[scope_b].id().local_id()
+        )),
+        copy[copied_push_x].scope()
+    );
+
+    // the scope file can no longer be cloned into the same graph
+    assert_eq!(
+        Err(scope_file),
+        graph.clone_file_into(file_b, &mut copy),
+        "Expected cloning the scope file to fail"
+    );
+}
+
 #[test]
 fn can_get_incoming_edges() {
     let mut graph = StackGraph::new();
@@ -215,3 +427,363 @@ fn can_get_incoming_edges() {
     graph.add_edge(h3, h1, 0);
     assert_eq!(Degree::One, graph.incoming_edge_degree(h1));
 }
+
+#[test]
+fn can_count_node_degrees() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let hub = graph.internal_scope(file, 0);
+    let spokes = (1..=5)
+        .map(|id| graph.internal_scope(file, id))
+        .collect::<Vec<_>>();
+    for spoke in &spokes[..3] {
+        graph.add_edge(*spoke, hub, 0);
+    }
+    for spoke in &spokes[3..] {
+        graph.add_edge(hub, *spoke, 0);
+    }
+    // Adding an existing edge again does not change the degrees.
+    graph.add_edge(spokes[0], hub, 1);
+
+    assert_eq!(3, graph.in_degree(hub));
+    assert_eq!(2, graph.out_degree(hub));
+    for spoke in &spokes[..3] {
+        assert_eq!(0, graph.in_degree(*spoke));
+        assert_eq!(1, graph.out_degree(*spoke));
+    }
+    for spoke in &spokes[3..] {
+        assert_eq!(1, graph.in_degree(*spoke));
+        assert_eq!(0, graph.out_degree(*spoke));
+    }
+}
+
+#[test]
+fn can_find_orphan_nodes() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let other_file = graph.get_or_create_file("other.py");
+    let root = StackGraph::root_node();
+    let sym = graph.symbol("x");
+    let def = graph.definition(file, 0, sym);
+    let scope = graph.internal_scope(file, 1);
+    let orphan = graph.internal_scope(file, 2);
+    let other_orphan = graph.internal_scope(other_file, 0);
+    graph.add_edge(root, def, 0);
+    graph.add_edge(scope, def, 0);
+
+    assert_eq!(vec![orphan], graph.orphan_nodes(file));
+    assert_eq!(vec![other_orphan], graph.orphan_nodes(other_file));
+}
+
+#[test]
+fn can_find_symbol_mismatches() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let root = StackGraph::root_node();
+    // `Foo` is pushed, but only `foo` is popped
+    let foo_upper = graph.symbol("Foo");
+    let foo_lower = graph.symbol("foo");
+    let foo_ref = graph.reference(file, 1, foo_upper);
+    let foo_def = graph.definition(file, 2, foo_lower);
+    graph.edge(foo_ref, root);
+    graph.edge(root, foo_def);
+    // `bar` is pushed and popped, so the similar ` bar ` is not reported
+    let bar = graph.symbol("bar");
+    let bar_padded = graph.symbol(" bar ");
+    let bar_ref = graph.reference(file, 3, bar);
+    let bar_def = graph.definition(file, 4, bar);
+    let bar_padded_def = graph.definition(file, 5, bar_padded);
+    graph.edge(bar_ref, root);
+    graph.edge(root, bar_def);
+    graph.edge(root, bar_padded_def);
+    // `baz` is pushed, but nothing similar is popped
+    let baz = graph.symbol("baz");
+    let baz_ref = graph.reference(file, 6, baz);
+    graph.edge(baz_ref, root);
+
+    assert_eq!(
+        vec![SymbolMismatch {
+            pushed: foo_upper,
+            popped: foo_lower,
+        }],
+        graph.symbol_mismatches()
+    );
+}
+
+#[test]
+fn can_group_definitions_by_symbol() {
+    let mut graph = StackGraph::new();
+    let file1 = graph.add_file("test1").unwrap();
+    let file2 = graph.add_file("test2").unwrap();
+    let foo = graph.symbol("foo");
+    let bar = graph.symbol("bar");
+    let foo_def1 = graph.definition(file1, 1, foo);
+    let bar_def = graph.definition(file1, 2, bar);
+    let foo_def2 = graph.definition(file2, 1, foo);
+    // references are not definitions
+    graph.reference(file2, 2, bar);
+
+    let definitions = graph.definitions_by_symbol();
+    assert_eq!(2, definitions.len());
+    assert_eq!(Some(&vec![foo_def1, foo_def2]), definitions.get(&foo));
+    assert_eq!(Some(&vec![bar_def]), definitions.get(&bar));
+}
+
+fn span(line: usize, start: usize, end: usize) -> Span {
+    let position = |column| Position {
+        line,
+        column: Offset {
+            utf8_offset: column,
+            utf16_offset: column,
+            grapheme_offset: column,
+        },
+        ..Default::default()
+    };
+    Span {
+        start: position(start),
+        end: position(end),
+    }
+}
+
+#[test]
+fn can_find_nodes_for_span() {
+    let graph = test_graphs::simple::new();
+    let file = graph.get_file("test.py").unwrap();
+    let def_x = graph
+        .nodes_for_file(file)
+        .find(|n| graph[*n].is_definition())
+        .unwrap();
+
+    assert_eq!(
+        graph.nodes_for_span(file, &span(0, 0, 1), SpanMatch::Exact),
+        vec![def_x]
+    );
+    assert_eq!(
+        graph.nodes_for_span(file, &span(0, 0, 0), SpanMatch::Exact),
+        vec![]
+    );
+    assert_eq!(
+        graph.nodes_for_span(file, &span(0, 0, 0), SpanMatch::Contains),
+        vec![def_x]
+    );
+    assert_eq!(
+        graph.nodes_for_span(file, &span(0, 0, 2), SpanMatch::Contains),
+        vec![]
+    );
+}
+
+#[test]
+fn can_preview_source_around_definitions() {
+    let source = "import a\n\ndef f():\n  return a\n\nx = f()\n";
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test.py").unwrap();
+    let f = graph.symbol("f");
+    let def = graph.definition(file, 1, f);
+    let x = graph.symbol("x");
+    let no_span = graph.definition(file, 2, x);
+    let y = graph.symbol("y");
+    let out_of_bounds = graph.definition(file, 3, y);
+    graph.source_info_mut(def).span = Span {
+        end: span(3, 0, 10).end,
+        ..span(2, 4, 5)
+    };
+    graph.source_info_mut(out_of_bounds).span = span(17, 0, 1);
+
+    assert_eq!(
+        Some(SourcePreview {
+            first_line: 2,
+            lines: vec!["def f():", "  return a"],
+        }),
+        graph.source_preview(def, source, 0)
+    );
+    assert_eq!(
+        Some(SourcePreview {
+            first_line: 1,
+            lines: vec!["", "def f():", "  return a", ""],
+        }),
+        graph.source_preview(def, source, 1)
+    );
+    assert_eq!(
+        Some(SourcePreview {
+            first_line: 0,
+            lines: vec!["import a", "", "def f():", "  return a", "", "x = f()"],
+        }),
+        graph.source_preview(def, source, 5)
+    );
+    assert_eq!(None, graph.source_preview(no_span, source, 1));
+    assert_eq!(None, graph.source_preview(out_of_bounds, source, 1));
+}
+
+#[test]
+fn can_count_symbol_interning() {
+    let mut graph = StackGraph::new();
+    let first = graph.add_symbol("foo");
+    let second = graph.add_symbol("foo");
+    assert_eq!(first, second);
+    let stats = graph.symbol_stats();
+    assert_eq!(1, stats.symbols_created);
+    assert_eq!(1, stats.symbols_reused);
+}
+
+#[test]
+fn can_collapse_scope_chains() {
+    fn resolve(graph: &StackGraph) -> Vec<(Handle<Node>, Handle<Node>)> {
+        let mut partials = PartialPaths::new();
+        let mut db = Database::new();
+        for file in graph.iter_files() {
+            ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+                graph,
+                &mut partials,
+                file,
+                StitcherConfig::default(),
+                &NoCancellation,
+                |g, ps, p| {
+                    db.add_partial_path(g, ps, p.clone());
+                },
+            )
+            .expect("should never be cancelled");
+        }
+        let mut results = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut DatabaseCandidates::new(graph, &mut partials, &mut db),
+            graph.iter_nodes(),
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, p| results.push((p.start_node, p.end_node)),
+        )
+        .expect("should never be cancelled");
+        results.sort();
+        results
+    }
+
+    fn connected_nodes(graph: &StackGraph) -> usize {
+        graph
+            .iter_nodes()
+            .filter(|n| {
+                graph.outgoing_edges(*n).count() > 0
+                    || graph.incoming_edge_degree(*n) != Degree::Zero
+            })
+            .count()
+    }
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let x = graph.symbol("x");
+    let reference = graph.reference(file, 0, x);
+    let s1 = graph.internal_scope(file, 1);
+    let s2 = graph.internal_scope(file, 2);
+    let s3 = graph.internal_scope(file, 3);
+    let exported = graph.exported_scope(file, 4);
+    let definition = graph.definition(file, 5, x);
+    graph.edge(reference, s1);
+    graph.edge(s1, s2);
+    graph.edge(s2, s3);
+    graph.edge(s3, exported);
+    graph.edge(exported, definition);
+    let key = graph.add_string("tsg_location");
+    for (source, sink, location) in [(reference, s1, "(1, 1)"), (s2, s3, "(3, 1)")] {
+        let value = graph.add_string(location);
+        graph.edge_debug_info_mut(source, sink).add(key, value);
+    }
+
+    let expected = resolve(&graph);
+    assert_eq!(vec![(reference, definition)], expected);
+    assert_eq!(6, connected_nodes(&graph));
+
+    assert_eq!(3, graph.collapse_scope_chains());
+    assert_eq!(
+        vec![exported],
+        graph
+            .outgoing_edges(reference)
+            .map(|e| e.sink)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(Degree::One, graph.incoming_edge_degree(exported));
+    assert_eq!(3, connected_nodes(&graph));
+    assert_eq!(expected, resolve(&graph));
+    assert_eq!(
+        vec![("tsg_location", "(1, 1)"), ("tsg_location", "(3, 1)")],
+        graph
+            .edge_debug_info(reference, exported)
+            .expect("debug info should be merged")
+            .iter()
+            .map(|e| (&graph[e.key], &graph[e.value]))
+            .collect::<Vec<_>>()
+    );
+    assert!(graph.edge_debug_info(s2, s3).is_none());
+}
+
+#[test]
+fn node_display_includes_node_id() {
+    let mut graph = StackGraph::new();
+    let x = graph.symbol("x");
+    let a = graph.get_or_create_file("a.py");
+    let b = graph.get_or_create_file("b.py");
+    let a_def = graph.definition(a, 3, x);
+    let b_def = graph.definition(b, 3, x);
+    assert_eq!(a_def.display(&graph).to_string(), "[a.py(3) definition x]");
+    assert_eq!(b_def.display(&graph).to_string(), "[b.py(3) definition x]");
+    assert_eq!(format!("{:#}", a_def.display(&graph)), "[a.py(3)]");
+}
+
+#[test]
+fn can_relocate_file() {
+    let mut graph = StackGraph::new();
+    let cached = graph.file("cached.py");
+    let lib = graph.file("lib.py");
+    let root = StackGraph::root_node();
+    let sym = graph.symbol("x");
+    let reference = graph.reference(cached, 1, sym);
+    graph.edge(reference, root);
+    let definition = graph.definition(lib, 1, sym);
+    graph.edge(root, definition);
+
+    let main = graph.file("main.py");
+    graph
+        .relocate_file(cached, main)
+        .expect("Expected relocating file to succeed");
+    assert_eq!(0, graph.nodes_for_file(cached).count());
+    assert_eq!(
+        vec![reference],
+        graph.nodes_for_file(main).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        Some(reference),
+        graph.node_for_id(NodeID::new_in_file(main, 1))
+    );
+    assert_eq!(None, graph.node_for_id(NodeID::new_in_file(cached, 1)));
+    assert_eq!(Some("main.py"), graph.file_name_for_node(reference));
+
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in [main, lib] {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |g, ps, p| {
+                db.add_partial_path(g, ps, p.clone());
+            },
+        )
+        .expect("Expected path finding to succeed");
+    }
+    let mut ends = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, p| ends.push(p.end_node),
+    )
+    .expect("Expected path finding to succeed");
+    assert_eq!(vec![definition], ends);
+
+    // Relocating into a file that already contains a node with the same local ID fails.
+    assert_eq!(
+        Err(NodeID::new_in_file(lib, 1)),
+        graph.relocate_file(main, lib)
+    );
+    assert_eq!(Some("main.py"), graph.file_name_for_node(reference));
+}
@@ -22,6 +22,7 @@ mod can_jump_to_definition_with_forward_partial_path_stitching;
 mod cycles;
 mod graph;
 mod partial;
+mod paths;
 #[cfg(feature = "serde")]
 mod serde;
 mod stats;
@@ -29,3 +30,5 @@ mod stitching;
 #[cfg(feature = "storage")]
 mod storage;
 mod util;
+#[cfg(feature = "visualization")]
+mod visualization;
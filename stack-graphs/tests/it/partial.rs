@@ -5,21 +5,34 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use controlled_option::ControlledOption;
 use stack_graphs::arena::Handle;
+use stack_graphs::c::sg_partial_path_arena;
+use stack_graphs::c::sg_partial_path_arena_partial_symbol_stack_cells;
+use stack_graphs::graph::Edge;
 use stack_graphs::graph::Node;
 use stack_graphs::graph::NodeID;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::partial::PartialPath;
 use stack_graphs::partial::PartialPathEdgeList;
+use stack_graphs::partial::PartialPathSummary;
 use stack_graphs::partial::PartialPaths;
 use stack_graphs::partial::PartialScopeStack;
 use stack_graphs::partial::PartialScopeStackBindings;
+use stack_graphs::partial::PartialScopedSymbol;
+use stack_graphs::partial::PartialSymbolStack;
 use stack_graphs::partial::PartialSymbolStackBindings;
 use stack_graphs::partial::ScopeStackVariable;
 use stack_graphs::partial::SymbolStackVariable;
 use stack_graphs::paths::PathResolutionError;
 use stack_graphs::stitching::Database;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
 
+use crate::test_graphs;
+use crate::test_graphs::CreateStackGraph;
 use crate::util::*;
 
 #[test]
@@ -847,6 +860,71 @@ fn can_append_partial_paths() -> Result<(), PathResolutionError> {
     Ok(())
 }
 
+#[test]
+fn can_report_concatenation_mismatches() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").expect("");
+    let scope0 = create_scope_node(&mut graph, file, false);
+    let scope1 = create_scope_node(&mut graph, file, false);
+    let foo_ref = create_push_symbol_node(&mut graph, file, "foo", false);
+    let bar_ref = create_push_symbol_node(&mut graph, file, "bar", false);
+    let bar_def = create_pop_symbol_node(&mut graph, file, "bar", false);
+    let baz_def = create_pop_symbol_node(&mut graph, file, "baz", false);
+
+    let mut ps = PartialPaths::new();
+
+    // <bar,foo,%1> does not unify with <bar,baz,%2> after the first symbol
+    let mut l =
+        create_partial_path_and_edges(&mut graph, &mut ps, &[foo_ref, bar_ref, scope0]).expect("");
+    let mut r =
+        create_partial_path_and_edges(&mut graph, &mut ps, &[scope0, bar_def, scope1, baz_def])
+            .expect("");
+    r.ensure_no_overlapping_variables(&mut ps, &l);
+    let mismatch = l
+        .concatenate_checked(&graph, &mut ps, &r)
+        .expect_err("Expected concatenation to fail");
+    assert!(matches!(
+        mismatch.error,
+        PathResolutionError::SymbolStackUnsatisfied
+    ));
+    let unified_symbols = mismatch
+        .unified_symbols
+        .iter()
+        .map(|symbol| symbol.display(&graph, &mut ps).to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["bar"], unified_symbols);
+    let mismatched_symbols = mismatch
+        .mismatched_symbols
+        .expect("Expected mismatched symbols");
+    assert_eq!(
+        Some("foo".to_string()),
+        mismatched_symbols
+            .left
+            .map(|symbol| symbol.display(&graph, &mut ps).to_string())
+    );
+    assert_eq!(
+        Some("baz".to_string()),
+        mismatched_symbols
+            .right
+            .map(|symbol| symbol.display(&graph, &mut ps).to_string())
+    );
+    assert!(mismatch.mismatched_scopes.is_none());
+
+    // paths that do not meet have no mismatching stack elements
+    let mut l = create_partial_path_and_edges(&mut graph, &mut ps, &[scope0]).expect("");
+    let r = create_partial_path_and_edges(&mut graph, &mut ps, &[scope1]).expect("");
+    let mismatch = l
+        .concatenate_checked(&graph, &mut ps, &r)
+        .expect_err("Expected concatenation to fail");
+    assert!(matches!(
+        mismatch.error,
+        PathResolutionError::IncorrectSourceNode
+    ));
+    assert!(mismatch.unified_symbols.is_empty());
+    assert!(mismatch.mismatched_symbols.is_none());
+    assert!(mismatch.mismatched_scopes.is_none());
+}
+
 #[test]
 fn can_append_partial_paths_without_precondition_variables() -> Result<(), PathResolutionError> {
     let mut graph = StackGraph::new();
@@ -964,3 +1042,382 @@ fn can_resolve_to_node() -> Result<(), PathResolutionError> {
 
     Ok(())
 }
+
+#[test]
+fn can_reuse_cleared_partial_paths_arena() {
+    let mut graph = StackGraph::new();
+    let mut arena = sg_partial_path_arena {
+        inner: PartialPaths::new(),
+    };
+    let contents = (&[("a", None), ("b", None), ("c", None)][..], None);
+
+    let stack = create_symbol_stack(&mut graph, &mut arena.inner, contents);
+    let expected = stack.display(&graph, &mut arena.inner).to_string();
+    let before = sg_partial_path_arena_partial_symbol_stack_cells(&arena);
+
+    arena.inner.clear();
+    let cleared = sg_partial_path_arena_partial_symbol_stack_cells(&arena);
+    assert!(cleared.count < before.count);
+
+    let stack = create_symbol_stack(&mut graph, &mut arena.inner, contents);
+    let actual = stack.display(&graph, &mut arena.inner).to_string();
+    let after = sg_partial_path_arena_partial_symbol_stack_cells(&arena);
+    assert_eq!(expected, actual);
+    assert_eq!(before.count, after.count);
+    assert_eq!(before.cells, after.cells);
+}
+
+#[test]
+fn can_shrink_cleared_partial_paths_arena() {
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let initial_capacity = partials.capacity();
+    let symbols = ["a", "b", "c", "d", "e", "f", "g", "h"];
+    let contents = (
+        &symbols.iter().map(|s| (*s, None)).collect::<Vec<_>>()[..],
+        None,
+    );
+    for _ in 0..100 {
+        create_symbol_stack(&mut graph, &mut partials, contents);
+    }
+    let grown_capacity = partials.capacity();
+    assert!(grown_capacity > initial_capacity);
+
+    partials.clear();
+    assert_eq!(grown_capacity, partials.capacity());
+
+    partials.clear_and_shrink_to(16);
+    let shrunk_capacity = partials.capacity();
+    assert!(shrunk_capacity < grown_capacity);
+
+    let stack = create_symbol_stack(&mut graph, &mut partials, contents);
+    assert_eq!("abcdefgh", stack.display(&graph, &mut partials).to_string());
+}
+
+#[test]
+fn can_create_stacks_from_slices() {
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let file = graph.get_or_create_file("test");
+
+    let a = graph.add_symbol("a");
+    let b = graph.add_symbol("b");
+    let from_slice = PartialSymbolStack::from_symbols(&mut partials, &[a, b]);
+    let mut manual = PartialSymbolStack::empty();
+    for symbol in [b, a] {
+        manual.push_front(
+            &mut partials,
+            PartialScopedSymbol {
+                symbol,
+                scopes: ControlledOption::none(),
+            },
+        );
+    }
+    assert!(from_slice.equals(&mut partials, manual));
+    let mut from_slice = from_slice;
+    assert_eq!(
+        Some(a),
+        from_slice.pop_front(&mut partials).map(|s| s.symbol)
+    );
+
+    let s1 = graph
+        .add_scope_node(NodeID::new_in_file(file, 1), true)
+        .unwrap();
+    let s2 = graph
+        .add_scope_node(NodeID::new_in_file(file, 2), true)
+        .unwrap();
+    let from_slice = PartialScopeStack::from_scopes(&mut partials, &[s1, s2]);
+    let mut manual = PartialScopeStack::empty();
+    manual.push_front(&mut partials, s2);
+    manual.push_front(&mut partials, s1);
+    assert!(from_slice.equals(&mut partials, manual));
+    let mut from_slice = from_slice;
+    assert_eq!(Some(s1), from_slice.pop_front(&mut partials));
+}
+
+#[test]
+fn can_limit_variable_bindings() {
+    let mut partials = PartialPaths::new();
+    let mut symbol_bindings = PartialSymbolStackBindings::new();
+    let mut scope_bindings = PartialScopeStackBindings::new();
+
+    // a variable far beyond the default limit is rejected instead of allocated
+    let huge = SymbolStackVariable::new(u32::MAX).unwrap();
+    assert!(matches!(
+        symbol_bindings.add(
+            &mut partials,
+            huge,
+            PartialSymbolStack::empty(),
+            &mut scope_bindings
+        ),
+        Err(PathResolutionError::TooManyVariableBindings)
+    ));
+    assert!(symbol_bindings.get(huge).is_none());
+
+    partials.set_max_variable_bindings(8);
+    assert_eq!(8, partials.max_variable_bindings());
+    let last = SymbolStackVariable::new(8).unwrap();
+    symbol_bindings
+        .add(
+            &mut partials,
+            last,
+            PartialSymbolStack::empty(),
+            &mut scope_bindings,
+        )
+        .expect("variable within the limit should bind");
+    assert!(symbol_bindings.get(last).is_some());
+    assert!(matches!(
+        symbol_bindings.add(
+            &mut partials,
+            SymbolStackVariable::new(9).unwrap(),
+            PartialSymbolStack::empty(),
+            &mut scope_bindings
+        ),
+        Err(PathResolutionError::TooManyVariableBindings)
+    ));
+
+    scope_bindings
+        .add(
+            &mut partials,
+            ScopeStackVariable::new(8).unwrap(),
+            PartialScopeStack::empty(),
+        )
+        .expect("variable within the limit should bind");
+    assert!(matches!(
+        scope_bindings.add(
+            &mut partials,
+            ScopeStackVariable::new(9).unwrap(),
+            PartialScopeStack::empty(),
+        ),
+        Err(PathResolutionError::TooManyVariableBindings)
+    ));
+}
+
+#[test]
+fn can_inspect_partial_stacks() {
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+
+    let scopes = create_scope_stack(
+        &mut graph,
+        &mut partials,
+        (&[1, 2][..], ScopeStackVariable::new(1)),
+    );
+    let file = graph.get_file("file").unwrap();
+    let first = graph.node_for_id(NodeID::new_in_file(file, 1)).unwrap();
+    let other = create_scope_node(&mut graph, file, false);
+    assert_eq!(2, scopes.len());
+    assert!(scopes.has_variable());
+    assert!(!scopes.can_match_empty());
+    assert!(scopes.contains(&partials, first));
+    assert!(!scopes.contains(&partials, other));
+    assert_eq!(
+        scopes.iter_scopes(&mut partials).collect::<Vec<_>>(),
+        scopes.iter(&mut partials).collect::<Vec<_>>()
+    );
+    assert_eq!(first, scopes.iter(&mut partials).next().unwrap());
+
+    let empty = PartialScopeStack::from_variable(ScopeStackVariable::new(2).unwrap());
+    assert_eq!(0, empty.len());
+    assert!(empty.is_empty());
+    assert!(empty.can_match_empty());
+    assert!(!empty.can_only_match_empty());
+
+    let symbols = create_symbol_stack(
+        &mut graph,
+        &mut partials,
+        (&[("a", None), ("b", None)][..], None),
+    );
+    assert_eq!(2, symbols.len());
+    assert!(!symbols.has_variable());
+    assert!(!symbols.can_match_empty());
+    assert_eq!(2, symbols.iter(&mut partials).count());
+}
+
+#[test]
+fn can_resolve_jump_to_scope() {
+    let graph: StackGraph = test_graphs::class_field_through_function_parameter::new();
+    let main_file = graph.get_file("main.py").unwrap();
+    let a_file = graph.get_file("a.py").unwrap();
+    let b_file = graph.get_file("b.py").unwrap();
+    let node = |file, local_id| {
+        graph
+            .node_for_id(NodeID::new_in_file(file, local_id))
+            .unwrap()
+    };
+    let main_exported = node(main_file, 7);
+    let main_bar = node(main_file, 10);
+    let a_x_15 = node(a_file, 15);
+    let a_zero_16 = node(a_file, 16);
+    let b_bar = node(b_file, 8);
+    let jump_to = StackGraph::jump_to_node();
+
+    // Within a.py, the scope that the parameter jumps to is only known to the caller.
+    let mut partials = PartialPaths::new();
+    let mut path = PartialPath::from_node(&graph, &mut partials, a_x_15);
+    for (source, sink) in [(a_x_15, a_zero_16), (a_zero_16, jump_to)] {
+        path.append(
+            &graph,
+            &mut partials,
+            Edge {
+                source,
+                sink,
+                precedence: 0,
+            },
+        )
+        .expect("Expected appending edge to succeed");
+    }
+    assert_eq!(jump_to, path.end_node);
+    let err = path
+        .clone()
+        .resolve_jump_to_scope(&graph, &mut partials, None)
+        .expect_err("Expected unknown scope to fail");
+    assert!(matches!(err, PathResolutionError::ScopeStackUnsatisfied));
+    path.resolve_jump_to_scope(&graph, &mut partials, Some(main_exported))
+        .expect("Expected resolving to caller scope to succeed");
+    assert_eq!(main_exported, path.end_node);
+    assert_eq!(
+        Some(main_exported),
+        path.scope_stack_precondition.pop_front(&mut partials)
+    );
+
+    // End to end, the attached scope is jumped to, and the field reference resolves.
+    let mut ends = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![main_bar],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| ends.push(path.end_node),
+    )
+    .expect("Expected path finding to succeed");
+    assert_eq!(vec![b_bar], ends);
+}
+
+#[test]
+fn jumping_to_empty_scope_stack_is_reported() {
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let drop_scopes = graph.drop_scopes(file, 1);
+    let jump_to = StackGraph::jump_to_node();
+
+    let mut partials = PartialPaths::new();
+    let mut path = PartialPath::from_node(&graph, &mut partials, drop_scopes);
+    let err = path
+        .append(
+            &graph,
+            &mut partials,
+            Edge {
+                source: drop_scopes,
+                sink: jump_to,
+                precedence: 0,
+            },
+        )
+        .expect_err("Expected jumping to empty scope stack to fail");
+    assert!(matches!(err, PathResolutionError::EmptyScopeStack));
+    assert_eq!(
+        "cannot jump to scope: the scope stack is empty",
+        err.to_string()
+    );
+}
+
+#[test]
+fn can_iterate_nodes_of_partial_path() {
+    let graph: StackGraph = test_graphs::class_field_through_function_parameter::new();
+    let main_file = graph.get_file("main.py").unwrap();
+    let main_bar = graph
+        .node_for_id(NodeID::new_in_file(main_file, 10))
+        .unwrap();
+
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![main_bar],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| paths.push(path.clone()),
+    )
+    .expect("Expected path finding to succeed");
+    assert_eq!(1, paths.len());
+    let path = &paths[0];
+
+    let nodes = path.nodes(&graph, &mut partials).collect::<Vec<_>>();
+    assert_eq!(path.edges.len() + 1, nodes.len());
+    assert_eq!(Some(&path.start_node), nodes.first());
+    assert_eq!(Some(&path.end_node), nodes.last());
+    let ids = nodes
+        .iter()
+        .map(|node| graph[*node].display(&graph).to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            "[main.py(10) reference bar]",
+            "[main.py(11) push .]",
+            "[main.py(12) push scoped () main.py(7)]",
+            "[main.py(13) reference foo]",
+            "[main.py(3) scope]",
+        ],
+        ids[..5].to_vec()
+    );
+    assert_eq!("[b.py(8) definition bar]", ids[ids.len() - 1]);
+
+    // a path without edges consists of its start node only
+    let single = PartialPath::from_node(&graph, &mut partials, main_bar);
+    assert_eq!(
+        vec![main_bar],
+        single.nodes(&graph, &mut partials).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn can_summarize_partial_path() {
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let file = graph.get_or_create_file("file");
+    let symbol_var = SymbolStackVariable::new(1).unwrap();
+    let scope_var = ScopeStackVariable::new(2).unwrap();
+    let symbol_stack_precondition = create_symbol_stack(
+        &mut graph,
+        &mut partials,
+        (&[("a", None), ("b", Some((&[10], None)))], Some(symbol_var)),
+    );
+    let symbol_stack_postcondition = create_symbol_stack(
+        &mut graph,
+        &mut partials,
+        (&[("c", None)], Some(symbol_var)),
+    );
+    let scope_stack_precondition = create_scope_stack(&mut graph, &mut partials, (&[11, 12], None));
+    let scope_stack_postcondition =
+        create_scope_stack(&mut graph, &mut partials, (&[13], Some(scope_var)));
+    let path = PartialPath {
+        start_node: StackGraph::root_node(),
+        end_node: StackGraph::jump_to_node(),
+        symbol_stack_precondition,
+        symbol_stack_postcondition,
+        scope_stack_precondition,
+        scope_stack_postcondition,
+        edges: PartialPathEdgeList::empty(),
+    };
+
+    let summary = path.to_summary(&graph, &mut partials);
+    assert_eq!(
+        PartialPathSummary {
+            start_node: NodeID::root(),
+            end_node: NodeID::jump_to(),
+            symbol_stack_precondition: vec!["a".to_string(), "b".to_string()],
+            symbol_stack_precondition_variable: Some(1),
+            symbol_stack_postcondition: vec!["c".to_string()],
+            symbol_stack_postcondition_variable: Some(1),
+            scope_stack_precondition: vec![
+                NodeID::new_in_file(file, 11),
+                NodeID::new_in_file(file, 12),
+            ],
+            scope_stack_precondition_variable: None,
+            scope_stack_postcondition: vec![NodeID::new_in_file(file, 13)],
+            scope_stack_postcondition_variable: Some(2),
+        },
+        summary
+    );
+    assert_eq!(summary, path.to_summary(&graph, &mut partials));
+}
@@ -0,0 +1,82 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2026, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::collections::BTreeSet;
+
+use pretty_assertions::assert_eq;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::paths::Paths;
+use stack_graphs::stitching::Database;
+use stack_graphs::stitching::DatabaseCandidates;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
+
+use crate::test_graphs;
+
+fn stitched_paths(graph: &StackGraph) -> BTreeSet<String> {
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+
+    let mut complete_paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(graph, &mut partials, &mut db),
+        graph.iter_nodes(),
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| complete_paths.push(path.clone()),
+    )
+    .expect("should never be cancelled");
+    complete_paths
+        .into_iter()
+        .map(|path| path.display(graph, &mut partials).to_string())
+        .collect()
+}
+
+fn full_paths(graph: &StackGraph, max_steps: usize) -> BTreeSet<String> {
+    let mut paths = Paths::new();
+    let complete_paths = paths
+        .find_all_complete_paths(graph, graph.iter_nodes(), max_steps, &NoCancellation)
+        .expect("should never be cancelled");
+    complete_paths
+        .into_iter()
+        .map(|path| path.display(graph, paths.partials()).to_string())
+        .collect()
+}
+
+#[test]
+fn full_paths_agree_with_stitched_paths() {
+    for graph in [
+        test_graphs::simple::new(),
+        test_graphs::class_field_through_function_parameter::new(),
+        test_graphs::sequenced_import_star::new(),
+    ] {
+        let expected = stitched_paths(&graph);
+        assert!(!expected.is_empty());
+        assert_eq!(expected, full_paths(&graph, 10_000));
+    }
+}
+
+#[test]
+fn full_path_search_stops_when_out_of_fuel() {
+    let graph = test_graphs::simple::new();
+    assert!(full_paths(&graph, 0).is_empty());
+}
@@ -13,7 +13,10 @@ use stack_graphs::graph::StackGraph;
 use stack_graphs::partial::PartialPaths;
 use stack_graphs::serde;
 use stack_graphs::stitching::{Database, ForwardPartialPathStitcher, StitcherConfig};
+use stack_graphs::CancellationError;
+use stack_graphs::CancellationFlag;
 use stack_graphs::NoCancellation;
+use std::cell::Cell;
 
 use crate::test_graphs;
 
@@ -53,6 +56,10 @@ fn serde_json_stack_graph() {
                         },
                     },
                     syntax_type: None,
+                    selection_span: None,
+                    containing_line: None,
+                    definiens_span: None,
+                    fully_qualified_name: None,
                 }),
                 debug_info: Some(serde::DebugInfo { data: vec![] }),
             }],
@@ -68,6 +75,7 @@ fn serde_json_stack_graph() {
                     local_id: 0,
                 },
                 precedence: 0,
+                kind: None,
                 debug_info: Some(serde::DebugInfo { data: vec![] }),
             }],
         },
@@ -580,6 +588,7 @@ fn can_serialize_graph() {
                                 }
                             }
                         },
+                        "containing_line" : "print(x)",
                         "syntax_type" : "variable"
                     },
                     "symbol" : "x",
@@ -963,6 +972,7 @@ fn can_serialize_graph() {
                                 }
                             }
                         },
+                        "containing_line" : "x = 42",
                         "syntax_type" : "variable"
                     },
                     "symbol" : "x",
@@ -1276,3 +1286,848 @@ fn can_serialize_partial_paths() {
     );
     assert_json_eq!(expected, actual);
 }
+
+/// Cancels after a fixed number of checks.
+struct CancelAfterChecks(Cell<usize>);
+
+impl CancellationFlag for CancelAfterChecks {
+    fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+        if self.0.get() == 0 {
+            return Err(CancellationError(at));
+        }
+        self.0.set(self.0.get() - 1);
+        Ok(())
+    }
+}
+
+#[test]
+fn can_cancel_loading_serialized_graph() {
+    let source = test_graphs::simple::new();
+    let serialized = source.to_serializable();
+    let total = serialized.nodes.data.len();
+
+    let mut reports = Vec::new();
+    let mut graph = StackGraph::new();
+    let result = serialized.load_into_with_progress(
+        &mut graph,
+        &CancelAfterChecks(Cell::new(3)),
+        &mut |done, total| reports.push((done, total)),
+    );
+    assert_eq!(Err(serde::Error::Cancelled("loading nodes")), result);
+    assert_eq!(vec![(1, total), (2, total), (3, total)], reports);
+
+    let mut reports = Vec::new();
+    let mut graph = StackGraph::new();
+    serialized
+        .load_into_with_progress(&mut graph, &NoCancellation, &mut |done, total| {
+            reports.push((done, total))
+        })
+        .expect("loading should succeed");
+    assert_eq!(Some(&(total, total)), reports.last());
+    assert_eq!(total, reports.len());
+}
+
+#[test]
+fn can_reject_oversized_serialized_graphs() {
+    let source = test_graphs::simple::new();
+    let serialized = source.to_serializable();
+    let nodes = serialized.nodes.data.len();
+    let edges = serialized.edges.data.len();
+
+    let load = |serialized: &serde::StackGraph, limits: serde::Limits| {
+        let mut graph = StackGraph::new();
+        let result = serialized.load_into_with_limits(&mut graph, &limits);
+        if result.is_err() {
+            assert_eq!(0, graph.iter_files().count());
+            assert_eq!(2, graph.iter_nodes().count()); // only the root and jump to scope nodes
+        }
+        result
+    };
+
+    let limits = serde::Limits {
+        max_nodes: nodes,
+        max_edges: edges,
+        max_string_length: 64,
+    };
+    assert_eq!(Ok(()), load(&serialized, limits));
+    assert_eq!(
+        Err(serde::Error::TooManyNodes(nodes, nodes - 1)),
+        load(
+            &serialized,
+            serde::Limits {
+                max_nodes: nodes - 1,
+                ..limits
+            }
+        )
+    );
+    assert_eq!(
+        Err(serde::Error::TooManyEdges(edges, edges - 1)),
+        load(
+            &serialized,
+            serde::Limits {
+                max_edges: edges - 1,
+                ..limits
+            }
+        )
+    );
+
+    let json = json!({
+        "files": ["test.py"],
+        "nodes": [{
+            "type": "pop_symbol",
+            "id": { "file": "test.py", "local_id": 0 },
+            "symbol": "x".repeat(1024),
+            "is_definition": true,
+        }],
+        "edges": [],
+    });
+    let oversized: serde::StackGraph =
+        serde_json::from_value(json).expect("deserializing should succeed");
+    assert_eq!(
+        Err(serde::Error::StringTooLong(1024, 64)),
+        load(&oversized, limits)
+    );
+    assert_eq!(Ok(()), load(&oversized, serde::Limits::default()));
+}
+
+#[test]
+fn can_reject_oversized_graphs_while_deserializing() {
+    let source = test_graphs::simple::new();
+    let serialized = source.to_serializable();
+    let nodes = serialized.nodes.data.len();
+    let edges = serialized.edges.data.len();
+    let json = serde_json::to_vec(&serialized).expect("serializing should succeed");
+
+    let deserialize = |json: &[u8], limits: serde::Limits| {
+        limits
+            .deserialize(&mut serde_json::Deserializer::from_reader(json))
+            .map_err(|err| err.to_string())
+    };
+
+    let limits = serde::Limits {
+        max_nodes: nodes,
+        max_edges: edges,
+        max_string_length: 64,
+    };
+    assert_eq!(Ok(serialized), deserialize(&json, limits));
+
+    let err = deserialize(
+        &json,
+        serde::Limits {
+            max_nodes: nodes - 1,
+            ..limits
+        },
+    )
+    .expect_err("deserializing should fail");
+    assert!(
+        err.starts_with(&serde::Error::TooManyNodes(nodes, nodes - 1).to_string()),
+        "unexpected error: {}",
+        err
+    );
+    let err = deserialize(
+        &json,
+        serde::Limits {
+            max_edges: edges - 1,
+            ..limits
+        },
+    )
+    .expect_err("deserializing should fail");
+    assert!(
+        err.starts_with(&serde::Error::TooManyEdges(edges, edges - 1).to_string()),
+        "unexpected error: {}",
+        err
+    );
+    // deserializing stops at the first node over the limit
+    let err = deserialize(
+        &json,
+        serde::Limits {
+            max_nodes: 1,
+            ..limits
+        },
+    )
+    .expect_err("deserializing should fail");
+    assert!(
+        err.starts_with(&serde::Error::TooManyNodes(2, 1).to_string()),
+        "unexpected error: {}",
+        err
+    );
+
+    let missing = serde_json::to_vec(&json!({ "files": [], "nodes": [] })).unwrap();
+    let err = deserialize(&missing, limits).expect_err("deserializing should fail");
+    assert!(
+        err.starts_with("missing field `edges`"),
+        "unexpected error: {}",
+        err
+    );
+    let duplicate = br#"{ "files": [], "nodes": [], "edges": [], "files": [] }"#;
+    let err = deserialize(duplicate, limits).expect_err("deserializing should fail");
+    assert!(
+        err.starts_with("duplicate field `files`"),
+        "unexpected error: {}",
+        err
+    );
+
+    let oversized = serde_json::to_vec(&json!({
+        "files": ["test.py"],
+        "nodes": [{
+            "type": "pop_symbol",
+            "id": { "file": "test.py", "local_id": 0 },
+            "symbol": "x".repeat(1024),
+            "is_definition": true,
+        }],
+        "edges": [],
+    }))
+    .unwrap();
+    let err = deserialize(&oversized, limits).expect_err("deserializing should fail");
+    assert!(
+        err.starts_with(&serde::Error::StringTooLong(1024, 64).to_string()),
+        "unexpected error: {}",
+        err
+    );
+    assert!(deserialize(&oversized, serde::Limits::default()).is_ok());
+}
+
+#[test]
+fn can_cancel_serializing_graph() {
+    let graph = test_graphs::simple::new();
+    let total = graph.iter_nodes().count();
+
+    let mut reports = Vec::new();
+    let result = serde::StackGraph::from_graph_filter_with_progress(
+        &graph,
+        &serde::NoFilter,
+        &CancelAfterChecks(Cell::new(2)),
+        &mut |done, total| reports.push((done, total)),
+    );
+    assert_eq!(Err(serde::Error::Cancelled("serializing nodes")), result);
+    assert_eq!(vec![(1, total), (2, total)], reports);
+}
+
+#[test]
+fn can_apply_patch_between_graph_states() {
+    use test_graphs::CreateStackGraph;
+
+    let mut old_graph = StackGraph::new();
+    let file = old_graph.file("test.py");
+    let root = old_graph.root_node();
+    let scope = old_graph.exported_scope(file, 1);
+    let x = old_graph.symbol("x");
+    let def_x = old_graph.definition(file, 2, x);
+    let y = old_graph.symbol("y");
+    let def_y = old_graph.definition(file, 3, y);
+    old_graph.edge(root, scope);
+    old_graph.edge(scope, def_x);
+    old_graph.edge(scope, def_y);
+
+    let mut new_graph = StackGraph::new();
+    let file = new_graph.file("test.py");
+    let root = new_graph.root_node();
+    let scope = new_graph.exported_scope(file, 1);
+    let x = new_graph.symbol("x");
+    let def_x = new_graph.definition(file, 2, x);
+    let z = new_graph.symbol("z");
+    let def_z = new_graph.definition(file, 4, z);
+    new_graph.edge(root, scope);
+    new_graph.add_edge(scope, def_x, 1);
+    new_graph.edge(scope, def_z);
+
+    let old_file = old_graph.get_file("test.py").unwrap();
+    let new_file = new_graph.get_file("test.py").unwrap();
+    let old = serde::StackGraph::from_graph_filter(&old_graph, &serde::FileFilter(old_file));
+    let new = serde::StackGraph::from_graph_filter(&new_graph, &serde::FileFilter(new_file));
+
+    let patch = old.diff(&new);
+    assert!(patch.added_files.is_empty());
+    assert!(patch.removed_files.is_empty());
+    assert_eq!(1, patch.removed_nodes.len());
+    assert_eq!(1, patch.added_nodes.len());
+    assert_eq!(2, patch.removed_edges.len());
+    assert_eq!(2, patch.added_edges.len());
+    assert!(old.diff(&old).is_empty());
+
+    let json = serde_json::to_string(&patch).expect("serialization should succeed");
+    let patch: serde::GraphPatch =
+        serde_json::from_str(&json).expect("deserialization should succeed");
+
+    let mut patched = old.clone();
+    patched.apply_patch(&patch);
+    assert!(patched.diff(&new).is_empty());
+    assert!(new.diff(&patched).is_empty());
+}
+
+#[cfg(feature = "bincode")]
+#[test]
+fn can_save_and_load_database() {
+    fn complete_paths(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+    ) -> Vec<String> {
+        let references = graph
+            .iter_nodes()
+            .filter(|n| graph[*n].is_reference())
+            .collect::<Vec<_>>();
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut stack_graphs::stitching::DatabaseCandidates::new(graph, partials, db),
+            references,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, p| paths.push(p.clone()),
+        )
+        .expect("should never be cancelled");
+        let mut paths = paths
+            .into_iter()
+            .map(|p| p.display(graph, partials).to_string())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    let mut graph: StackGraph = test_graphs::class_field_through_function_parameter::new();
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |g, ps, p| {
+                db.add_partial_path(g, ps, p.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+    let expected = complete_paths(&graph, &mut partials, &mut db);
+    assert!(!expected.is_empty());
+
+    let mut buffer = Vec::new();
+    db.save(&graph, &mut partials, &mut buffer)
+        .expect("saving should succeed");
+
+    let mut partials = PartialPaths::new();
+    let mut loaded = Database::load(&mut graph, &mut partials, &mut buffer.as_slice())
+        .expect("loading should succeed");
+    assert_eq!(
+        db.iter_partial_paths().count(),
+        loaded.iter_partial_paths().count()
+    );
+    assert_eq!(expected, complete_paths(&graph, &mut partials, &mut loaded));
+}
+
+#[test]
+fn can_build_database_from_serialized_files() {
+    fn complete_paths(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+    ) -> Vec<String> {
+        let references = graph
+            .iter_nodes()
+            .filter(|n| graph[*n].is_reference())
+            .collect::<Vec<_>>();
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut stack_graphs::stitching::DatabaseCandidates::new(graph, partials, db),
+            references,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, p| paths.push(p.clone()),
+        )
+        .expect("should never be cancelled");
+        let mut paths = paths
+            .into_iter()
+            .map(|p| p.display(graph, partials).to_string())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    let graph: StackGraph = test_graphs::class_field_through_function_parameter::new();
+    let files = graph
+        .iter_files()
+        .map(|file| {
+            serde_json::to_vec(&graph.to_serializable_filter(&serde::FileFilter(file)))
+                .expect("serializing should succeed")
+        })
+        .collect::<Vec<_>>();
+    assert!(files.len() > 1);
+
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |g, ps, p| {
+                db.add_partial_path(g, ps, p.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+    let expected = complete_paths(&graph, &mut partials, &mut db);
+    // at least one reference resolves to a definition in another file
+    assert!(expected
+        .iter()
+        .any(|path| path.contains("[main.py") && path.contains("[b.py")));
+
+    let mut loaded_graph = StackGraph::new();
+    let mut loaded_partials = PartialPaths::new();
+    let mut loaded = Database::from_serialized_files(
+        &mut loaded_graph,
+        &mut loaded_partials,
+        files.iter().map(|file| file.as_slice()),
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("building database should succeed");
+    assert_eq!(
+        graph.iter_files().count(),
+        loaded_graph.iter_files().count()
+    );
+    assert_eq!(
+        expected,
+        complete_paths(&loaded_graph, &mut loaded_partials, &mut loaded)
+    );
+}
+
+#[test]
+fn can_round_trip_graph_with_partial_paths() {
+    fn complete_paths(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        paths: &[stack_graphs::partial::PartialPath],
+    ) -> Vec<String> {
+        let mut db = Database::new();
+        for path in paths {
+            db.add_partial_path(graph, partials, path.clone());
+        }
+        let references = graph
+            .iter_nodes()
+            .filter(|n| graph[*n].is_reference())
+            .collect::<Vec<_>>();
+        let mut complete_paths = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut stack_graphs::stitching::DatabaseCandidates::new(graph, partials, &mut db),
+            references,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |g, ps, p| complete_paths.push(p.display(g, ps).to_string()),
+        )
+        .expect("should never be cancelled");
+        complete_paths.sort();
+        complete_paths
+    }
+
+    let mut graph: StackGraph = test_graphs::class_field_through_function_parameter::new();
+    let definition = graph
+        .iter_nodes()
+        .find(|n| graph[*n].is_definition())
+        .unwrap();
+    graph.set_definition_rank(definition, -1);
+    graph.set_generated(definition, true);
+    graph.set_keeps_paths(definition, true);
+    let definiens_span = lsp_positions::Span {
+        start: lsp_positions::Position {
+            line: 1,
+            ..Default::default()
+        },
+        end: lsp_positions::Position {
+            line: 3,
+            ..Default::default()
+        },
+    };
+    let containing_line = graph.add_string("class A:");
+    let fully_qualified_name = graph.add_string("module.A");
+    let source_info = graph.source_info_mut(definition);
+    source_info.containing_line = containing_line.into();
+    source_info.definiens_span = definiens_span.clone();
+    source_info.fully_qualified_name = fully_qualified_name.into();
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, p| paths.push(p.clone()),
+        )
+        .expect("should never be cancelled");
+    }
+    let expected = complete_paths(&graph, &mut partials, &paths);
+    assert!(!expected.is_empty());
+
+    let state = serde::GraphWithPaths::from_graph_and_paths(&graph, &mut partials, &paths);
+    let json = serde_json::to_string(&state).expect("serializing should succeed");
+    let restored_state: serde::GraphWithPaths =
+        serde_json::from_str(&json).expect("deserializing should succeed");
+    assert_eq!(state, restored_state);
+
+    let mut restored_graph = StackGraph::new();
+    let mut restored_partials = PartialPaths::new();
+    let restored_paths = restored_state
+        .load_into(&mut restored_graph, &mut restored_partials)
+        .expect("loading should succeed");
+    assert_eq!(paths.len(), restored_paths.len());
+    assert_eq!(
+        expected,
+        complete_paths(&restored_graph, &mut restored_partials, &restored_paths)
+    );
+
+    let id = graph[definition].id();
+    let file = restored_graph
+        .get_file(graph[id.file().unwrap()].name())
+        .unwrap();
+    let restored_definition = restored_graph
+        .node_for_id(graph::NodeID::new_in_file(file, id.local_id()))
+        .unwrap();
+    assert_eq!(-1, restored_graph.definition_rank(restored_definition));
+    assert!(restored_graph.is_generated(restored_definition));
+    assert!(restored_graph.keeps_paths(restored_definition));
+    let restored_source_info = restored_graph.source_info(restored_definition).unwrap();
+    assert_eq!(
+        Some("class A:"),
+        restored_source_info
+            .containing_line
+            .into_option()
+            .map(|line| &restored_graph[line])
+    );
+    assert_eq!(definiens_span, restored_source_info.definiens_span);
+    assert_eq!(
+        Some("module.A"),
+        restored_source_info
+            .fully_qualified_name
+            .into_option()
+            .map(|fqn| &restored_graph[fqn])
+    );
+}
+
+#[test]
+fn can_round_trip_all_span_units() {
+    let line = "let 👍🏽 = 1";
+    let position = |prefix: &str| lsp_positions::Position {
+        line: 0,
+        column: lsp_positions::Offset::string_length(prefix),
+        containing_line: 0..line.len(),
+        trimmed_line: 0..line.len(),
+    };
+    let span = lsp_positions::Span {
+        start: position("let "),
+        end: position("let 👍🏽"),
+    };
+    assert_ne!(span.end.column.utf8_offset, span.end.column.utf16_offset);
+    assert_ne!(
+        span.end.column.utf16_offset,
+        span.end.column.grapheme_offset
+    );
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let id = graph.new_node_id(file);
+    let node = graph.add_scope_node(id, false).unwrap();
+    graph.source_info_mut(node).span = span.clone();
+
+    let json = serde_json::to_value(graph.to_serializable()).expect("serialization should succeed");
+    let node_json = json["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|n| n["id"]["file"] == json!("test.py"))
+        .unwrap();
+    let column = &node_json["source_info"]["span"]["end"]["column"];
+    assert_eq!(json!(12), column["utf8_offset"]);
+    assert_eq!(json!(8), column["utf16_offset"]);
+    assert_eq!(json!(5), column["grapheme_offset"]);
+
+    let serialized: serde::StackGraph =
+        serde_json::from_value(json).expect("deserialization should succeed");
+    let mut loaded = StackGraph::new();
+    serialized
+        .load_into(&mut loaded)
+        .expect("loading should succeed");
+    let file = loaded.get_file("test.py").unwrap();
+    let node = loaded.nodes_for_file(file).next().unwrap();
+    assert_eq!(span, loaded.source_info(node).unwrap().span);
+}
+
+#[test]
+fn can_exclude_generated_nodes() {
+    use test_graphs::CreateStackGraph;
+
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let scope = graph.internal_scope(file, 0);
+    let print = graph.symbol("print");
+    let builtin = graph.definition(file, 1, print);
+    let user = graph.definition(file, 2, print);
+    graph.edge(scope, builtin);
+    graph.edge(scope, user);
+    graph.set_generated(builtin, true);
+
+    let serialized = serde::StackGraph::from_graph_filter(&graph, &serde::NoGeneratedFilter);
+    let local_ids = serialized
+        .nodes
+        .data
+        .iter()
+        .filter(|n| n.id().file.is_some())
+        .map(|n| n.id().local_id)
+        .collect::<Vec<_>>();
+    assert!(!local_ids.contains(&graph[builtin].id().local_id()));
+    assert!(local_ids.contains(&graph[user].id().local_id()));
+    assert_eq!(1, serialized.edges.data.len());
+    assert_eq!(
+        graph[user].id().local_id(),
+        serialized.edges.data[0].sink.local_id
+    );
+}
+
+#[test]
+fn serialization_does_not_depend_on_symbol_interning_order() {
+    fn build(symbol_order: &[&str]) -> String {
+        let mut graph = StackGraph::new();
+        for symbol in symbol_order {
+            graph.add_symbol(symbol);
+        }
+        let file = graph.get_or_create_file("test.py");
+        let scope = graph
+            .add_scope_node(graph::NodeID::new_in_file(file, 0), true)
+            .unwrap();
+        let a = graph.add_symbol("a");
+        let reference = graph
+            .add_push_symbol_node(graph::NodeID::new_in_file(file, 1), a, true)
+            .unwrap();
+        let b = graph.add_symbol("b");
+        let definition = graph
+            .add_pop_symbol_node(graph::NodeID::new_in_file(file, 2), b, true)
+            .unwrap();
+        graph.add_edge(reference, scope, 0);
+        graph.add_edge(scope, definition, 0);
+        serde_json::to_string(&graph.to_serializable()).expect("Cannot serialize graph")
+    }
+
+    assert_eq!(build(&["a", "b", "c"]), build(&["c", "b", "a"]));
+}
+
+#[test]
+fn can_serialize_definition_ranks() {
+    use test_graphs::CreateStackGraph;
+
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let x = graph.symbol("x");
+    let builtin = graph.definition(file, 0, x);
+    let user = graph.definition(file, 1, x);
+    graph.set_definition_rank(builtin, -1);
+
+    let json = serde_json::to_string(&graph.to_serializable()).expect("Cannot serialize graph");
+    let deserialized: serde::StackGraph =
+        serde_json::from_str(&json).expect("Cannot deserialize graph");
+    let mut loaded = StackGraph::new();
+    deserialized
+        .load_into(&mut loaded)
+        .expect("Cannot load graph");
+    let file = loaded.get_file("test.py").unwrap();
+    let ranks = [builtin, user]
+        .iter()
+        .map(|node| {
+            let id = graph::NodeID::new_in_file(file, graph[*node].id().local_id());
+            loaded.definition_rank(loaded.node_for_id(id).unwrap())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(vec![-1, 0], ranks);
+}
+
+#[test]
+fn can_serialize_generated_nodes() {
+    use test_graphs::CreateStackGraph;
+
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let x = graph.symbol("x");
+    let builtin = graph.definition(file, 0, x);
+    let user = graph.definition(file, 1, x);
+    graph.set_generated(builtin, true);
+
+    let json = serde_json::to_string(&graph.to_serializable()).expect("Cannot serialize graph");
+    let deserialized: serde::StackGraph =
+        serde_json::from_str(&json).expect("Cannot deserialize graph");
+    let mut loaded = StackGraph::new();
+    deserialized
+        .load_into(&mut loaded)
+        .expect("Cannot load graph");
+    let file = loaded.get_file("test.py").unwrap();
+    let generated = [builtin, user]
+        .iter()
+        .map(|node| {
+            let id = graph::NodeID::new_in_file(file, graph[*node].id().local_id());
+            loaded.is_generated(loaded.node_for_id(id).unwrap())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(vec![true, false], generated);
+}
+
+#[test]
+fn can_serialize_edge_kinds() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let scope = graph
+        .add_scope_node(graph::NodeID::new_in_file(file, 0), true)
+        .unwrap();
+    let other = graph
+        .add_scope_node(graph::NodeID::new_in_file(file, 1), false)
+        .unwrap();
+    graph.add_edge(scope, other, 0);
+    graph.add_edge(other, scope, 0);
+    graph.set_edge_kind(scope, other, 2);
+    assert_eq!(Some(2), graph.edge_kind(scope, other));
+    assert_eq!(Some(0), graph.edge_kind(other, scope));
+    assert_eq!(None, graph.edge_kind(scope, scope));
+
+    let serialized = graph.to_serializable();
+    let kinds = serialized
+        .edges
+        .data
+        .iter()
+        .map(|e| (e.source.local_id, e.kind))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![(0, Some(2)), (1, None)], kinds);
+
+    let json = serde_json::to_string(&serialized).expect("Cannot serialize graph");
+    let deserialized: serde::StackGraph =
+        serde_json::from_str(&json).expect("Cannot deserialize graph");
+    let mut loaded = StackGraph::new();
+    deserialized
+        .load_into(&mut loaded)
+        .expect("Cannot load graph");
+    let file = loaded.get_file("test.py").unwrap();
+    let scope = loaded
+        .node_for_id(graph::NodeID::new_in_file(file, 0))
+        .unwrap();
+    let other = loaded
+        .node_for_id(graph::NodeID::new_in_file(file, 1))
+        .unwrap();
+    assert_eq!(Some(2), loaded.edge_kind(scope, other));
+    assert_eq!(Some(0), loaded.edge_kind(other, scope));
+}
+
+#[test]
+fn can_serialize_minimal_graph_without_info() {
+    use stack_graphs::stitching::GraphEdgeCandidates;
+    use test_graphs::CreateStackGraph;
+
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let x = graph.symbol("x");
+    let reference = graph.reference(file, 0, x);
+    let definition = graph.definition(file, 1, x);
+    let root = StackGraph::root_node();
+    graph.edge(reference, root);
+    graph.edge(root, definition);
+    let key = graph.add_string("key");
+    let value = graph.add_string("value");
+    let syntax_type = graph.add_string("function");
+    for node in [reference, definition] {
+        graph.source_info_mut(node).syntax_type = syntax_type.into();
+        graph.node_debug_info_mut(node).add(key, value);
+    }
+    graph.edge_debug_info_mut(reference, root).add(key, value);
+
+    let full = serde_json::to_string(&graph.to_serializable()).expect("Cannot serialize graph");
+    let minimal = graph.to_serializable_filter(&serde::NoInfoFilter(&serde::NoFilter));
+    let json = serde_json::to_string(&minimal).expect("Cannot serialize graph");
+    assert!(!json.contains("source_info"));
+    assert!(!json.contains("debug_info"));
+    assert!(json.len() < full.len());
+
+    let deserialized: serde::StackGraph =
+        serde_json::from_str(&json).expect("Cannot deserialize graph");
+    let mut loaded = StackGraph::new();
+    deserialized
+        .load_into(&mut loaded)
+        .expect("Cannot load graph");
+    let file = loaded.get_file("test.py").unwrap();
+    let reference = loaded
+        .node_for_id(graph::NodeID::new_in_file(file, 0))
+        .unwrap();
+    let definition = loaded
+        .node_for_id(graph::NodeID::new_in_file(file, 1))
+        .unwrap();
+    assert!(loaded.source_info(reference).is_none());
+    assert!(loaded.node_debug_info(reference).is_none());
+
+    let mut partials = PartialPaths::new();
+    let mut ends = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&loaded, &mut partials, None),
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| ends.push(path.end_node),
+    )
+    .expect("should never be cancelled");
+    assert_eq!(vec![definition], ends);
+}
+
+#[test]
+fn can_inspect_serialized_partial_stacks() {
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let file = graph.get_or_create_file("test.py");
+    let scope = graph
+        .add_scope_node(graph::NodeID::new_in_file(file, 0), true)
+        .unwrap();
+    let mut scopes = stack_graphs::partial::PartialScopeStack::from_variable(
+        stack_graphs::partial::ScopeStackVariable::new(1).unwrap(),
+    );
+    scopes.push_back(&mut partials, scope);
+    let mut symbols = stack_graphs::partial::PartialSymbolStack::empty();
+    let x = graph.add_symbol("x");
+    symbols.push_back(
+        &mut partials,
+        stack_graphs::partial::PartialScopedSymbol {
+            symbol: x,
+            scopes: controlled_option::ControlledOption::none(),
+        },
+    );
+
+    let serialized_scopes =
+        serde::PartialScopeStack::from_partial_scope_stack(&graph, &mut partials, &scopes);
+    assert_eq!(scopes.len(), serialized_scopes.len());
+    assert_eq!(scopes.is_empty(), serialized_scopes.is_empty());
+    assert_eq!(scopes.has_variable(), serialized_scopes.has_variable());
+    assert_eq!(
+        scopes.can_match_empty(),
+        serialized_scopes.can_match_empty()
+    );
+    assert_eq!(
+        scopes.can_only_match_empty(),
+        serialized_scopes.can_only_match_empty()
+    );
+    let scope_id = serde::NodeID::from_node(&graph, scope);
+    assert!(serialized_scopes.contains(&scope_id));
+    assert_eq!(
+        vec![&scope_id],
+        serialized_scopes.iter().collect::<Vec<_>>()
+    );
+    assert!(serialized_scopes.variable().is_some());
+
+    let serialized_symbols =
+        serde::PartialSymbolStack::from_partial_symbol_stack(&graph, &mut partials, &symbols);
+    assert_eq!(symbols.len(), serialized_symbols.len());
+    assert_eq!(symbols.is_empty(), serialized_symbols.is_empty());
+    assert_eq!(symbols.has_variable(), serialized_symbols.has_variable());
+    assert_eq!(
+        symbols.can_match_empty(),
+        serialized_symbols.can_match_empty()
+    );
+    assert!(serialized_symbols.contains_symbols());
+    assert_eq!(1, serialized_symbols.iter().count());
+    assert!(serialized_symbols.variable().is_none());
+}
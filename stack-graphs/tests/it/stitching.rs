@@ -6,13 +6,49 @@
 // ------------------------------------------------------------------------------------------------
 
 use itertools::Itertools;
+use lsp_positions::Offset;
+use lsp_positions::Position;
+use lsp_positions::Span;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::NodeID;
 use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPath;
 use stack_graphs::partial::PartialPaths;
+use stack_graphs::partial::PartialScopedSymbol;
+use stack_graphs::partial::PartialSymbolStack;
+use stack_graphs::stitching::exported_symbols;
+use stack_graphs::stitching::find_paths_between;
+use stack_graphs::stitching::find_references;
+use stack_graphs::stitching::minimal_reproducer;
+use stack_graphs::stitching::nearest_paths;
+use stack_graphs::stitching::resolution_coverage;
+use stack_graphs::stitching::resolve_with_import_chains;
+use stack_graphs::stitching::root_crossing_partial_paths;
+use stack_graphs::stitching::scope_stacks_at_node;
+use stack_graphs::stitching::shadowed_definitions;
+use stack_graphs::stitching::unresolved_symbols;
+use stack_graphs::stitching::unshadowed_paths;
 use stack_graphs::stitching::Database;
+use stack_graphs::stitching::DatabaseCandidates;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::MemoryLimitExceeded;
+use stack_graphs::stitching::RootMatcher;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::stitching::TraceEvent;
+use stack_graphs::NoCancellation;
+use std::sync::Arc;
 
+use crate::test_graphs;
+use crate::test_graphs::CreateStackGraph;
+use crate::util::create_edge;
 use crate::util::create_partial_path_and_edges;
+use crate::util::create_pop_scoped_symbol_node;
 use crate::util::create_pop_symbol_node;
+use crate::util::create_push_scoped_symbol_node;
 use crate::util::create_push_symbol_node;
+use crate::util::create_scope_node;
 
 fn test_foo_bar_root_candidate_paths(symbols: &[&str], variable: bool) -> usize {
     let mut graph = StackGraph::new();
@@ -102,3 +138,1509 @@ fn find_candidates_for_shorter_symbol_stack_without_variable() {
     let results = test_foo_bar_root_candidate_paths(&["foo"], false);
     assert_eq!(0, results);
 }
+
+#[test]
+fn can_compute_resolution_coverage() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let foo_def = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let foo_ref = create_push_symbol_node(&mut graph, file, "foo", true);
+    let bar_ref = create_push_symbol_node(&mut graph, file, "bar", true);
+    create_edge(&mut graph, (foo_ref, foo_def));
+    create_edge(&mut graph, (bar_ref, foo_def));
+
+    let mut db = Database::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |graph, partials, path| {
+            db.add_partial_path(graph, partials, path.clone());
+        },
+    )
+    .expect("should never be cancelled");
+
+    let coverage = resolution_coverage(
+        &graph,
+        &mut partials,
+        &mut db,
+        file,
+        &StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    assert_eq!((1, 2), coverage);
+
+    // the definition has no syntax type, so it is not accepted as a class definition
+    let coverage = resolution_coverage(
+        &graph,
+        &mut partials,
+        &mut db,
+        file,
+        &StitcherConfig::default().with_definition_kinds(["class"]),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    assert_eq!((0, 2), coverage);
+}
+
+#[test]
+fn can_find_unresolved_symbols() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let scope = create_scope_node(&mut graph, file, false);
+    let foo_def = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let foo_ref = create_push_symbol_node(&mut graph, file, "foo", true);
+    let bar_ref = create_push_symbol_node(&mut graph, file, "bar", true);
+    create_edge(&mut graph, (scope, foo_def));
+    create_edge(&mut graph, (foo_ref, scope));
+    create_edge(&mut graph, (bar_ref, scope));
+
+    let mut db = Database::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |graph, partials, path| {
+            db.add_partial_path(graph, partials, path.clone());
+        },
+    )
+    .expect("should never be cancelled");
+
+    let mut unresolved = |config: StitcherConfig| {
+        unresolved_symbols(&graph, &mut partials, &mut db, &config, &NoCancellation)
+            .expect("should never be cancelled")
+            .into_iter()
+            .map(|symbol| graph[symbol].to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(vec!["bar"], unresolved(StitcherConfig::default()));
+    // the definition has no syntax type, so it is not accepted as a class definition
+    assert_eq!(
+        vec!["foo", "bar"],
+        unresolved(StitcherConfig::default().with_definition_kinds(["class"]))
+    );
+}
+
+#[test]
+fn can_resolve_reference_at_position() {
+    // café = 1
+    // x = 2
+    // print(café, x)
+    let span = |line, utf8: (usize, usize), utf16: (usize, usize)| {
+        let position = |utf8_offset, utf16_offset| Position {
+            line,
+            column: Offset {
+                utf8_offset,
+                utf16_offset,
+                grapheme_offset: utf16_offset,
+            },
+            ..Default::default()
+        };
+        Span {
+            start: position(utf8.0, utf16.0),
+            end: position(utf8.1, utf16.1),
+        }
+    };
+
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test.py").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let scope = create_scope_node(&mut graph, file, false);
+    let cafe_def = create_pop_symbol_node(&mut graph, file, "café", true);
+    let x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    let cafe_ref = create_push_symbol_node(&mut graph, file, "café", true);
+    let x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (scope, cafe_def));
+    create_edge(&mut graph, (scope, x_def));
+    create_edge(&mut graph, (cafe_ref, scope));
+    create_edge(&mut graph, (x_ref, scope));
+    graph.source_info_mut(cafe_def).span = span(0, (0, 5), (0, 4));
+    graph.source_info_mut(x_def).span = span(1, (0, 1), (0, 1));
+    graph.source_info_mut(cafe_ref).span = span(2, (6, 11), (6, 10));
+    graph.source_info_mut(x_ref).span = span(2, (13, 14), (12, 13));
+
+    let mut db = Database::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |graph, partials, path| {
+            db.add_partial_path(graph, partials, path.clone());
+        },
+    )
+    .expect("should never be cancelled");
+
+    let mut resolve_at = |line, utf16_column| {
+        graph
+            .resolve_at(
+                &mut partials,
+                &mut db,
+                file,
+                line,
+                utf16_column,
+                &StitcherConfig::default(),
+                &NoCancellation,
+            )
+            .expect("should never be cancelled")
+    };
+
+    let definitions = resolve_at(2, 8);
+    assert_eq!(
+        vec![cafe_def],
+        definitions.iter().map(|d| d.node).collect_vec()
+    );
+    assert_eq!(
+        Some(span(0, (0, 5), (0, 4))),
+        definitions[0].site.as_ref().map(|s| s.span.clone())
+    );
+    // the position directly after a reference is part of it
+    assert_eq!(
+        vec![cafe_def],
+        resolve_at(2, 10).iter().map(|d| d.node).collect_vec()
+    );
+    // columns are UTF-16 offsets, so this is between the references
+    assert!(resolve_at(2, 11).is_empty());
+    assert_eq!(
+        vec![x_def],
+        resolve_at(2, 12).iter().map(|d| d.node).collect_vec()
+    );
+    assert!(resolve_at(1, 0).is_empty());
+}
+
+#[test]
+fn can_compute_minimal_reproducer() {
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+    let sym_x = graph.symbol("x");
+    let sym_y = graph.symbol("y");
+    // the other file comes first, so that file handles differ in the reduced graph
+    let other_file = graph.file("other.py");
+    let file = graph.file("test.py");
+    let x_ref = graph.reference(file, 1, sym_x);
+    let long_scope1 = graph.internal_scope(file, 2);
+    let long_scope2 = graph.internal_scope(file, 3);
+    let short_scope = graph.internal_scope(file, 4);
+    let x_def = graph.definition(file, 5, sym_x);
+    let y_def = graph.definition(file, 6, sym_y);
+    let y_ref = graph.reference(file, 7, sym_y);
+    let shadowed_x_def = graph.definition(file, 8, sym_x);
+    // x resolves through a long and a short chain of scopes
+    graph.edge(x_ref, long_scope1);
+    graph.edge(long_scope1, long_scope2);
+    graph.edge(long_scope2, x_def);
+    graph.edge(x_ref, short_scope);
+    graph.edge(short_scope, x_def);
+    // the other definition of x is shadowed because of its lower rank
+    graph.edge(short_scope, shadowed_x_def);
+    graph.set_definition_rank(x_def, 1);
+    // y is unrelated to the resolution of x
+    graph.edge(long_scope2, y_def);
+    graph.edge(y_ref, short_scope);
+    graph.edge(root, x_def);
+    let other_ref = graph.reference(other_file, 1, sym_x);
+    graph.edge(other_ref, root);
+
+    let reduced = minimal_reproducer(&graph, x_ref, &StitcherConfig::default(), &NoCancellation)
+        .expect("should never be cancelled");
+
+    let reduced_file = reduced.get_file("test.py").expect("file should be kept");
+    let reduced_node = |node: Handle<Node>| {
+        reduced.node_for_id(NodeID::new_in_file(
+            reduced_file,
+            graph[node].id().local_id(),
+        ))
+    };
+    let reduced_x_ref = reduced_node(x_ref).expect("reference should be kept");
+    let reduced_x_def = reduced_node(x_def).expect("definition should be kept");
+    assert_eq!(1, reduced.definition_rank(reduced_x_def));
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&reduced, &mut partials, None),
+        vec![reduced_x_ref],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| paths.push(path.clone()),
+    )
+    .expect("should never be cancelled");
+    let definitions = unshadowed_paths(&reduced, &mut partials, &paths)
+        .into_iter()
+        .map(|path| path.end_node)
+        .collect_vec();
+    assert_eq!(vec![reduced_x_def], definitions);
+
+    let mut nodes = reduced
+        .iter_nodes()
+        .filter(|n| !reduced[*n].is_root() && !reduced[*n].is_jump_to())
+        .map(|n| reduced[n].id().local_id())
+        .collect::<Vec<_>>();
+    nodes.sort();
+    let mut expected = vec![
+        graph[x_ref].id().local_id(),
+        graph[short_scope].id().local_id(),
+        graph[x_def].id().local_id(),
+    ];
+    expected.sort();
+    assert_eq!(expected, nodes);
+    assert_eq!(
+        vec!["test.py"],
+        reduced
+            .iter_files()
+            .map(|f| reduced[f].name())
+            .collect_vec()
+    );
+}
+
+#[test]
+fn can_find_shadowed_definitions() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let outer_scope = create_scope_node(&mut graph, file, false);
+    let outer_x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (outer_scope, outer_x_def));
+
+    let inner_scope = create_scope_node(&mut graph, file, false);
+    let inner_x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    let inner_y_def = create_pop_symbol_node(&mut graph, file, "y", true);
+    graph.add_edge(inner_scope, inner_x_def, 1);
+    graph.add_edge(inner_scope, inner_y_def, 1);
+    graph.add_edge(inner_scope, outer_scope, 0);
+
+    let x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (x_ref, inner_scope));
+
+    let mut db = Database::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |graph, partials, path| {
+            db.add_partial_path(graph, partials, path.clone());
+        },
+    )
+    .expect("should never be cancelled");
+
+    let shadowed = shadowed_definitions(
+        &graph,
+        &mut partials,
+        &mut db,
+        inner_x_def,
+        &StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    assert_eq!(vec![outer_x_def], shadowed.iter().collect_vec());
+
+    let shadowed = shadowed_definitions(
+        &graph,
+        &mut partials,
+        &mut db,
+        outer_x_def,
+        &StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    assert_eq!(0, shadowed.iter().count());
+
+    let shadowed = shadowed_definitions(
+        &graph,
+        &mut partials,
+        &mut db,
+        inner_y_def,
+        &StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    assert_eq!(0, shadowed.iter().count());
+}
+
+/// Also matches package-qualified module names `<prefix><name>` against exports of `<name>`.
+struct PackageRootMatcher {
+    prefix: String,
+}
+
+impl RootMatcher for PackageRootMatcher {
+    fn match_symbol_stack(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        symbol_stack: PartialSymbolStack,
+        result: &mut Vec<PartialSymbolStack>,
+    ) {
+        result.push(symbol_stack);
+        let mut rest = symbol_stack;
+        let top = match rest.pop_front(partials) {
+            Some(top) => top,
+            None => return,
+        };
+        let name = match graph[top.symbol].strip_prefix(self.prefix.as_str()) {
+            Some(name) => name,
+            None => return,
+        };
+        if let Some(symbol) = graph.iter_symbols().find(|s| &graph[*s] == name) {
+            rest.push_front(
+                partials,
+                PartialScopedSymbol {
+                    symbol,
+                    scopes: top.scopes,
+                },
+            );
+            result.push(rest);
+        }
+    }
+}
+
+fn count_root_matched_paths(config: StitcherConfig) -> usize {
+    let mut graph = StackGraph::new();
+    let main_file = graph.add_file("main").unwrap();
+    let lib_file = graph.add_file("lib").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let mod_ref = create_push_symbol_node(&mut graph, main_file, "pkg/mod", true);
+    create_edge(&mut graph, (mod_ref, r));
+    let mod_def = create_pop_symbol_node(&mut graph, lib_file, "mod", true);
+    create_edge(&mut graph, (r, mod_def));
+
+    let mut db = Database::new();
+    for file in [main_file, lib_file] {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+
+    let mut results = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+        vec![mod_ref],
+        config,
+        &NoCancellation,
+        |_, _, path| results.push(path.clone()),
+    )
+    .expect("should never be cancelled");
+    assert!(results
+        .iter()
+        .all(|path| path.start_node == mod_ref && path.end_node == mod_def));
+    results.len()
+}
+
+#[test]
+fn can_match_root_symbol_stacks_with_custom_matcher() {
+    assert_eq!(0, count_root_matched_paths(StitcherConfig::default()));
+    assert_eq!(
+        1,
+        count_root_matched_paths(StitcherConfig::default().with_root_matcher(Arc::new(
+            PackageRootMatcher {
+                prefix: "pkg/".to_string(),
+            }
+        )))
+    );
+}
+
+#[test]
+fn higher_ranked_definitions_win_at_equal_precedence() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let scope = create_scope_node(&mut graph, file, false);
+    let builtin_x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    let user_x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (scope, builtin_x_def));
+    create_edge(&mut graph, (scope, user_x_def));
+    graph.set_definition_rank(builtin_x_def, -1);
+
+    let x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (x_ref, scope));
+
+    let mut db = Database::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |graph, partials, path| {
+            db.add_partial_path(graph, partials, path.clone());
+        },
+    )
+    .expect("should never be cancelled");
+
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+        vec![x_ref],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| paths.push(path.clone()),
+    )
+    .expect("should never be cancelled");
+    assert_eq!(2, paths.len());
+
+    let definitions = unshadowed_paths(&graph, &mut partials, &paths)
+        .into_iter()
+        .map(|path| path.end_node)
+        .collect_vec();
+    assert_eq!(vec![user_x_def], definitions);
+}
+
+#[test]
+fn precedence_determines_shadowing() {
+    let unshadowed_precedences = |precedences: &[i32]| {
+        let mut graph = StackGraph::new();
+        let file = graph.add_file("test").unwrap();
+        let mut partials = PartialPaths::new();
+        let scope = create_scope_node(&mut graph, file, false);
+        let x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+        create_edge(&mut graph, (x_ref, scope));
+        let mut definitions = Vec::new();
+        for precedence in precedences {
+            let x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+            graph.add_edge(scope, x_def, *precedence);
+            definitions.push((x_def, *precedence));
+        }
+
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+            vec![x_ref],
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, path| paths.push(path.clone()),
+        )
+        .expect("should never be cancelled");
+        assert_eq!(precedences.len(), paths.len());
+        unshadowed_paths(&graph, &mut partials, &paths)
+            .into_iter()
+            .map(|path| {
+                definitions
+                    .iter()
+                    .find(|(def, _)| *def == path.end_node)
+                    .unwrap()
+                    .1
+            })
+            .sorted()
+            .collect_vec()
+    };
+
+    assert_eq!(vec![0], unshadowed_precedences(&[-1, 0]));
+    assert_eq!(vec![1], unshadowed_precedences(&[0, 1]));
+    assert_eq!(vec![1], unshadowed_precedences(&[-1, 0, 1]));
+    assert_eq!(vec![-2], unshadowed_precedences(&[-5, -2]));
+    assert_eq!(vec![-1, -1], unshadowed_precedences(&[-1, -1]));
+    assert_eq!(vec![0, 0], unshadowed_precedences(&[0, 0]));
+}
+
+#[test]
+fn nearest_definitions_win_among_unshadowed_paths() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    // an inner scope defining `x`, nested in an outer scope that also defines `x`
+    let outer_scope = create_scope_node(&mut graph, file, false);
+    let outer_x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (outer_scope, outer_x_def));
+    let inner_scope = create_scope_node(&mut graph, file, false);
+    let inner_x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (inner_scope, inner_x_def));
+    create_edge(&mut graph, (inner_scope, outer_scope));
+
+    let x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (x_ref, inner_scope));
+
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![x_ref],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| paths.push(path.clone()),
+    )
+    .expect("should never be cancelled");
+    assert_eq!(2, paths.len());
+    assert_eq!(2, unshadowed_paths(&graph, &mut partials, &paths).len());
+
+    let definitions = nearest_paths(&graph, &mut partials, &paths)
+        .into_iter()
+        .map(|path| path.end_node)
+        .collect_vec();
+    assert_eq!(vec![inner_x_def], definitions);
+}
+
+#[test]
+fn can_find_references_in_lexical_scope() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    // two functions, each with a local `x` and a reference to it
+    let f_scope = create_scope_node(&mut graph, file, false);
+    let f_x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    let f_x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (f_scope, f_x_def));
+    create_edge(&mut graph, (f_x_ref, f_scope));
+
+    let g_scope = create_scope_node(&mut graph, file, false);
+    let g_x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    let g_x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+    let g_other_x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (g_scope, g_x_def));
+    create_edge(&mut graph, (g_x_ref, g_scope));
+    create_edge(&mut graph, (g_other_x_ref, g_scope));
+
+    let mut db = Database::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |graph, partials, path| {
+            db.add_partial_path(graph, partials, path.clone());
+        },
+    )
+    .expect("should never be cancelled");
+
+    let references = find_references(
+        &graph,
+        &mut partials,
+        &mut db,
+        f_x_def,
+        &StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    assert_eq!(vec![f_x_ref], references);
+
+    let references = find_references(
+        &graph,
+        &mut partials,
+        &mut db,
+        g_x_def,
+        &StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    assert_eq!(vec![g_x_ref, g_other_x_ref], references);
+}
+
+#[test]
+fn can_resolve_through_wildcard_import() {
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+
+    // stubs: `declare module "*" { export const x }`, which provides the members of any module
+    let stubs = graph.add_file("stubs").unwrap();
+    let wildcard = graph.add_wildcard_symbol();
+    let any_module_id = graph.new_node_id(stubs);
+    let any_module = graph
+        .add_pop_symbol_node(any_module_id, wildcard, false)
+        .unwrap();
+    let stubs_dot = create_pop_symbol_node(&mut graph, stubs, ".", false);
+    let x_def = create_pop_symbol_node(&mut graph, stubs, "x", true);
+    create_edge(&mut graph, (StackGraph::root_node(), any_module));
+    create_edge(&mut graph, (any_module, stubs_dot));
+    create_edge(&mut graph, (stubs_dot, x_def));
+
+    // star: a module that is literally named `*`, which is not a wildcard
+    let star = graph.add_file("star").unwrap();
+    let star_module = create_pop_symbol_node(&mut graph, star, "*", false);
+    let star_dot = create_pop_symbol_node(&mut graph, star, ".", false);
+    let y_def = create_pop_symbol_node(&mut graph, star, "y", true);
+    create_edge(&mut graph, (StackGraph::root_node(), star_module));
+    create_edge(&mut graph, (star_module, star_dot));
+    create_edge(&mut graph, (star_dot, y_def));
+    assert!(!graph.is_wildcard_symbol(graph[star_module].symbol().unwrap()));
+
+    // main: `import { x, y } from "lib"`
+    let main = graph.add_file("main").unwrap();
+    let x_ref = create_push_symbol_node(&mut graph, main, "x", true);
+    let y_ref = create_push_symbol_node(&mut graph, main, "y", true);
+    let main_dot = create_push_symbol_node(&mut graph, main, ".", false);
+    let import_lib = create_push_symbol_node(&mut graph, main, "lib", false);
+    create_edge(&mut graph, (x_ref, main_dot));
+    create_edge(&mut graph, (y_ref, main_dot));
+    create_edge(&mut graph, (main_dot, import_lib));
+    create_edge(&mut graph, (import_lib, StackGraph::root_node()));
+
+    let mut db = Database::new();
+    for file in [stubs, star, main] {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+
+    let mut results = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+        vec![x_ref, y_ref],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| results.push((path.start_node, path.end_node)),
+    )
+    .expect("should never be cancelled");
+    assert_eq!(vec![(x_ref, x_def)], results);
+}
+
+#[test]
+fn can_iterate_database_partial_paths() {
+    let graph: StackGraph = test_graphs::sequenced_import_star::new();
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    let mut inserted = 0;
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+                inserted += 1;
+            },
+        )
+        .expect("should never be cancelled");
+    }
+    assert!(inserted > 0);
+    assert_eq!(inserted, db.partial_paths().count());
+
+    let per_file = graph
+        .iter_files()
+        .map(|file| db.partial_paths_in_file(&graph, file).count())
+        .sum::<usize>();
+    assert_eq!(inserted, per_file);
+}
+
+#[test]
+fn can_continue_resolution_from_earlier_paths() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+
+    // definitions for `a.b.c`
+    let scope = create_scope_node(&mut graph, file, false);
+    let def_a = create_pop_symbol_node(&mut graph, file, "a", true);
+    let pop_dot_a = create_pop_symbol_node(&mut graph, file, ".", false);
+    let def_b = create_pop_symbol_node(&mut graph, file, "b", true);
+    let pop_dot_b = create_pop_symbol_node(&mut graph, file, ".", false);
+    let def_c = create_pop_symbol_node(&mut graph, file, "c", true);
+    create_edge(&mut graph, (scope, def_a));
+    create_edge(&mut graph, (def_a, pop_dot_a));
+    create_edge(&mut graph, (pop_dot_a, def_b));
+    create_edge(&mut graph, (def_b, pop_dot_b));
+    create_edge(&mut graph, (pop_dot_b, def_c));
+
+    // references for `a.b`
+    let ref_a = create_push_symbol_node(&mut graph, file, "a", true);
+    let push_dot_a = create_push_symbol_node(&mut graph, file, ".", false);
+    let ref_b = create_push_symbol_node(&mut graph, file, "b", true);
+    create_edge(&mut graph, (ref_a, scope));
+    create_edge(&mut graph, (push_dot_a, ref_a));
+    create_edge(&mut graph, (ref_b, push_dot_a));
+
+    // keep the precondition variables, so that the results can be continued later
+    let mut partials = PartialPaths::new();
+    let mut saved = Vec::new();
+    let start = PartialPath::from_node(&graph, &mut partials, ref_b);
+    ForwardPartialPathStitcher::find_all_complete_partial_paths_from_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![start],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| saved.push(path.clone()),
+    )
+    .expect("should never be cancelled");
+    assert_eq!(1, saved.len());
+    assert_eq!(def_b, saved[0].end_node);
+
+    // more input arrives, continuing to `a.b.c`
+    let ref_c = create_push_symbol_node(&mut graph, file, "c", true);
+    let push_dot_b = create_push_symbol_node(&mut graph, file, ".", false);
+    create_edge(&mut graph, (ref_c, push_dot_b));
+    create_edge(&mut graph, (push_dot_b, ref_b));
+
+    let mut seed = PartialPath::from_node(&graph, &mut partials, ref_c);
+    seed.eliminate_precondition_stack_variables(&mut partials);
+    for (source, sink) in [(ref_c, push_dot_b), (push_dot_b, ref_b)] {
+        let edge = graph
+            .outgoing_edges(source)
+            .find(|edge| edge.sink == sink)
+            .unwrap();
+        seed.append(&graph, &mut partials, edge).unwrap();
+    }
+    seed.concatenate(&graph, &mut partials, &saved[0]).unwrap();
+
+    let mut results = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths_from_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![seed],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| results.push((path.start_node, path.end_node)),
+    )
+    .expect("should never be cancelled");
+    assert_eq!(vec![(ref_c, def_c)], results);
+}
+
+#[test]
+fn can_filter_complete_paths_by_definition_kind() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+
+    // `class T` and `x: T`, where the type of `x` is reachable from its definition
+    let scope = create_scope_node(&mut graph, file, false);
+    let def_t = create_pop_symbol_node(&mut graph, file, "T", true);
+    let def_x = create_pop_symbol_node(&mut graph, file, "x", true);
+    let ref_t = create_push_symbol_node(&mut graph, file, "T", true);
+    let ref_x = create_push_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (scope, def_t));
+    create_edge(&mut graph, (scope, def_x));
+    create_edge(&mut graph, (def_x, ref_t));
+    create_edge(&mut graph, (ref_t, scope));
+    create_edge(&mut graph, (ref_x, scope));
+    for (node, kind) in [(def_t, "class"), (def_x, "variable")] {
+        let kind = graph.add_string(kind);
+        graph.source_info_mut(node).syntax_type = kind.into();
+    }
+
+    let find_definitions = |config: StitcherConfig| {
+        let mut partials = PartialPaths::new();
+        let mut results = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+            vec![ref_x],
+            config,
+            &NoCancellation,
+            |_, _, path| results.push(path.end_node),
+        )
+        .expect("should never be cancelled");
+        results.sort();
+        results
+    };
+
+    let mut all = vec![def_t, def_x];
+    all.sort();
+    assert_eq!(all, find_definitions(StitcherConfig::default()));
+    assert_eq!(
+        vec![def_t],
+        find_definitions(StitcherConfig::default().with_definition_kinds(["class"]))
+    );
+
+    // the configuration owns the kinds, so they do not have to be static
+    let kinds = vec!["class".to_string()];
+    let config = StitcherConfig::default().with_definition_kinds(kinds);
+    assert_eq!(Some(&["class".to_string()][..]), config.definition_kinds());
+    assert_eq!(vec![def_t], find_definitions(config));
+}
+
+#[test]
+fn can_trace_stitching_decisions() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let scope = create_scope_node(&mut graph, file, false);
+    let x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    let y_def = create_pop_symbol_node(&mut graph, file, "y", true);
+    create_edge(&mut graph, (scope, x_def));
+    create_edge(&mut graph, (scope, y_def));
+    let x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (x_ref, scope));
+
+    let mut seed = PartialPath::from_node(&graph, &mut partials, x_ref);
+    seed.eliminate_precondition_stack_variables(&mut partials);
+    let mut stitcher =
+        ForwardPartialPathStitcher::from_partial_paths(&graph, &mut partials, vec![seed]);
+    let events = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let sink_events = events.clone();
+    stitcher.set_trace_sink(Some(Box::new(move |graph, _partials, event| {
+        let description = match event {
+            TraceEvent::Dequeued(path) => {
+                format!("dequeued {}", graph[path.end_node].id().local_id())
+            }
+            TraceEvent::Cyclic(_) => "cyclic".to_string(),
+            TraceEvent::ConcatenationFailed { path, .. } => {
+                format!("failed {}", graph[path.end_node].id().local_id())
+            }
+            TraceEvent::Extended { extension, .. } => {
+                format!("extended {}", graph[extension.end_node].id().local_id())
+            }
+            TraceEvent::SimilarPathRejected(_) => "similar".to_string(),
+            TraceEvent::Completed(path) => {
+                format!("completed {}", graph[path.end_node].id().local_id())
+            }
+        };
+        sink_events.borrow_mut().push(description);
+    })));
+
+    let mut candidates = GraphEdgeCandidates::new(&graph, &mut partials, None);
+    while !stitcher.is_complete() {
+        stitcher.process_next_phase(&mut candidates, |_, _, _| true);
+    }
+
+    let (scope_id, x_def_id, x_ref_id) = (
+        graph[scope].id().local_id(),
+        graph[x_def].id().local_id(),
+        graph[x_ref].id().local_id(),
+    );
+    let events = events.borrow();
+    assert!(events.contains(&format!("dequeued {}", x_ref_id)));
+    assert!(events.contains(&format!("extended {}", scope_id)));
+    assert!(events.contains(&format!("dequeued {}", scope_id)));
+    assert!(events.contains(&format!("extended {}", x_def_id)));
+    assert!(events.contains(&format!("failed {}", scope_id)));
+    assert!(events.contains(&format!("completed {}", x_def_id)));
+    assert_eq!(
+        1,
+        events.iter().filter(|e| e.starts_with("completed")).count()
+    );
+}
+
+#[test]
+fn can_find_all_paths_between_reference_and_definition() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    // the reference reaches `x_def` via two different scopes, and `other_x_def` via one of them
+    let scope1 = create_scope_node(&mut graph, file, false);
+    let scope2 = create_scope_node(&mut graph, file, false);
+    let x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    let other_x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    let x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (x_ref, scope1));
+    create_edge(&mut graph, (x_ref, scope2));
+    create_edge(&mut graph, (scope1, x_def));
+    create_edge(&mut graph, (scope2, x_def));
+    create_edge(&mut graph, (scope1, other_x_def));
+
+    let mut db = Database::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default().with_detect_similar_paths(false),
+        &NoCancellation,
+        |graph, partials, path| {
+            db.add_partial_path(graph, partials, path.clone());
+        },
+    )
+    .expect("should never be cancelled");
+
+    let paths = find_paths_between(
+        &graph,
+        &mut partials,
+        &mut db,
+        x_ref,
+        x_def,
+        &StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    assert_eq!(2, paths.len());
+    assert!(paths
+        .iter()
+        .all(|p| p.start_node == x_ref && p.end_node == x_def));
+    assert!(!paths[0].edges.equals(&mut partials, paths[1].edges));
+
+    let paths = find_paths_between(
+        &graph,
+        &mut partials,
+        &mut db,
+        x_ref,
+        other_x_def,
+        &StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    assert_eq!(1, paths.len());
+}
+
+#[test]
+fn can_resolve_through_reexport_chains() {
+    const BARRELS: u32 = 24;
+
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+    let sym_foo = graph.symbol("foo");
+    let module_symbols = (0..=BARRELS)
+        .map(|i| graph.symbol(&format!("m{}", i)))
+        .collect::<Vec<_>>();
+
+    // m0 defines `foo`
+    let file = graph.file("m0.ts");
+    let module_def = graph.pop_symbol(file, 0, module_symbols[0]);
+    let exports = graph.exported_scope(file, 1);
+    let foo_def = graph.definition(file, 2, sym_foo);
+    graph.edge(root, module_def);
+    graph.edge(module_def, exports);
+    graph.edge(exports, foo_def);
+
+    // every barrel mi re-exports from both m(i-1) and m(i-2), alternating between
+    // `export * from ...` and `export { foo } from ...`
+    for i in 1..=BARRELS {
+        let file = graph.file(&format!("m{}.ts", i));
+        let module_def = graph.pop_symbol(file, 0, module_symbols[i as usize]);
+        let exports = graph.exported_scope(file, 1);
+        graph.edge(root, module_def);
+        graph.edge(module_def, exports);
+        for (j, source) in [i - 1, i.saturating_sub(2)].iter().copied().enumerate() {
+            let local_id = 2 + 3 * j as u32;
+            let source_module = graph.push_symbol(file, local_id, module_symbols[source as usize]);
+            graph.edge(source_module, root);
+            if (i + j as u32) % 2 == 0 {
+                graph.edge(exports, source_module);
+            } else {
+                let pop_foo = graph.pop_symbol(file, local_id + 1, sym_foo);
+                let push_foo = graph.push_symbol(file, local_id + 2, sym_foo);
+                graph.edge(exports, pop_foo);
+                graph.edge(pop_foo, push_foo);
+                graph.edge(push_foo, source_module);
+            }
+        }
+    }
+
+    // main imports `foo` from the last barrel
+    let file = graph.file("main.ts");
+    let foo_ref = graph.reference(file, 0, sym_foo);
+    let barrel_module = graph.push_symbol(file, 1, module_symbols[BARRELS as usize]);
+    graph.edge(foo_ref, barrel_module);
+    graph.edge(barrel_module, root);
+
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+
+    let mut definitions = Vec::new();
+    let stats = ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+        vec![foo_ref],
+        StitcherConfig::default().with_collect_stats(true),
+        &NoCancellation,
+        |_, _, path| definitions.push(path.end_node),
+    )
+    .expect("should never be cancelled");
+
+    // The number of routes through the barrels is exponential in the number of barrels, but
+    // similar paths are merged at the root node, so the work done only grows linearly.
+    assert_eq!(vec![foo_def], definitions);
+    assert!(stats.root_visits <= 2 * (BARRELS as usize + 1));
+}
+
+#[test]
+fn can_find_partial_paths_to_custom_endpoints() {
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let x = graph.symbol("x");
+    let reference = graph.reference(file, 0, x);
+    let scope = graph.internal_scope(file, 1);
+    let definition = graph.definition(file, 2, x);
+    graph.edge(reference, scope);
+    graph.edge(scope, definition);
+
+    let find = |is_endpoint: &dyn Fn(&StackGraph, _) -> bool| {
+        let mut partials = PartialPaths::new();
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file_with_endpoints(
+            &graph,
+            &mut partials,
+            file,
+            is_endpoint,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, p| paths.push((p.start_node, p.end_node)),
+        )
+        .expect("should never be cancelled");
+        paths.sort();
+        paths
+    };
+
+    assert_eq!(
+        vec![(reference, definition)],
+        find(&|graph, node| graph[node].is_endpoint())
+    );
+    assert_eq!(
+        vec![(reference, scope)],
+        find(&|graph, node| graph[node].is_reference() || node == scope)
+    );
+    assert_eq!(
+        vec![(reference, scope), (scope, definition)],
+        find(&|graph, node| graph[node].is_endpoint() || node == scope)
+    );
+}
+
+#[test]
+fn can_estimate_and_limit_database_memory() {
+    let graph: StackGraph = test_graphs::class_field_through_function_parameter::new();
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, p| paths.push(p.clone()),
+        )
+        .expect("should never be cancelled");
+    }
+    assert!(paths.len() > 2);
+
+    let mut db = Database::new();
+    let empty_estimate = db.memory_estimate();
+    let mut previous_estimate = empty_estimate;
+    for path in &paths {
+        db.add_partial_path(&graph, &mut partials, path.clone());
+        assert!(db.memory_estimate() >= previous_estimate);
+        previous_estimate = db.memory_estimate();
+    }
+    assert!(db.memory_estimate() > empty_estimate);
+
+    let mut db = Database::new();
+    db.set_memory_limit(Some(empty_estimate));
+    assert_eq!(Some(empty_estimate), db.memory_limit());
+    db.try_add_partial_path(&graph, &mut partials, paths[0].clone())
+        .expect("first path should fit");
+    let result = db.try_add_partial_path(&graph, &mut partials, paths[1].clone());
+    assert_eq!(
+        Err(MemoryLimitExceeded {
+            limit: empty_estimate,
+            estimate: db.memory_estimate(),
+        }),
+        result
+    );
+    assert_eq!(1, db.iter_partial_paths().count());
+
+    db.set_memory_limit(None);
+    db.try_add_partial_path(&graph, &mut partials, paths[1].clone())
+        .expect("unlimited database should accept paths");
+}
+
+#[test]
+fn can_find_exported_symbols() {
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let root = StackGraph::root_node();
+    let sym_a = graph.symbol("a");
+    let sym_b = graph.symbol("b");
+    let sym_private = graph.symbol("private");
+    let def_a = graph.definition(file, 1, sym_a);
+    let def_b = graph.definition(file, 2, sym_b);
+    let scope = graph.internal_scope(file, 3);
+    let def_private = graph.definition(file, 4, sym_private);
+    graph.edge(root, def_a);
+    graph.edge(root, def_b);
+    graph.edge(scope, def_private);
+
+    let mut partials = PartialPaths::new();
+    let symbols = exported_symbols(
+        &graph,
+        &mut partials,
+        file,
+        &StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("Expected finding exported symbols to succeed");
+    let mut symbols = symbols
+        .into_iter()
+        .map(|symbol| graph[symbol].to_string())
+        .collect::<Vec<_>>();
+    symbols.sort();
+    assert_eq!(vec!["a", "b"], symbols);
+}
+
+#[test]
+fn can_find_root_crossing_partial_paths() {
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let root = StackGraph::root_node();
+    let sym_a = graph.symbol("a");
+    let sym_b = graph.symbol("b");
+    let sym_local = graph.symbol("local");
+    // import: a reference to `a` that is resolved in another file
+    let ref_a = graph.reference(file, 1, sym_a);
+    graph.edge(ref_a, root);
+    // export: a definition of `b` that is visible to other files
+    let def_b = graph.definition(file, 2, sym_b);
+    graph.edge(root, def_b);
+    // a local reference that is resolved within the file
+    let ref_local = graph.reference(file, 3, sym_local);
+    let scope = graph.internal_scope(file, 4);
+    let def_local = graph.definition(file, 5, sym_local);
+    graph.edge(ref_local, scope);
+    graph.edge(scope, def_local);
+
+    let mut partials = PartialPaths::new();
+    let paths = root_crossing_partial_paths(
+        &graph,
+        &mut partials,
+        file,
+        &StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    let mut endpoints = paths
+        .iter()
+        .map(|path| (path.start_node, path.end_node))
+        .collect::<Vec<_>>();
+    endpoints.sort();
+    let mut expected = vec![(ref_a, root), (root, def_b)];
+    expected.sort();
+    assert_eq!(expected, endpoints);
+}
+
+#[test]
+fn can_report_scope_stacks_at_node() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    // a call `foo(x)` pushes the scope of its arguments, which the callee pops into its scope
+    // stack before reaching the body
+    let args = create_scope_node(&mut graph, file, true);
+    let args_id = graph[args].id();
+    let call = create_push_scoped_symbol_node(&mut graph, file, "foo", args_id, true);
+    let scope = create_scope_node(&mut graph, file, false);
+    let callee = create_pop_scoped_symbol_node(&mut graph, file, "foo", false);
+    let body = create_scope_node(&mut graph, file, false);
+    create_edge(&mut graph, (call, scope));
+    create_edge(&mut graph, (scope, callee));
+    create_edge(&mut graph, (callee, body));
+
+    let mut partials = PartialPaths::new();
+    let scope_stacks = scope_stacks_at_node(
+        &graph,
+        &mut partials,
+        body,
+        &StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    let actual = scope_stacks
+        .iter()
+        .map(|stacks| {
+            let precondition = stacks
+                .scope_stack_precondition
+                .display(&graph, &mut partials)
+                .to_string();
+            let postcondition = stacks
+                .scope_stack_postcondition
+                .display(&graph, &mut partials)
+                .to_string();
+            (stacks.start_node, precondition, postcondition)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![(call, "$1".to_string(), "[test(0)],$1".to_string())],
+        actual
+    );
+}
+
+#[test]
+fn can_limit_file_hops() {
+    // a.py references x, which is re-exported by b.py and c.py, and defined in d.py
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+    let sym_w = graph.symbol("w");
+    let sym_x = graph.symbol("x");
+    let sym_y = graph.symbol("y");
+    let sym_z = graph.symbol("z");
+    let a = graph.file("a.py");
+    let ref_x = graph.reference(a, 1, sym_x);
+    graph.edge(ref_x, root);
+    // a.py also references w, which it defines itself and reaches through the root node
+    let ref_w = graph.reference(a, 2, sym_w);
+    let def_w = graph.definition(a, 3, sym_w);
+    graph.edge(ref_w, root);
+    graph.edge(root, def_w);
+    let b = graph.file("b.py");
+    let pop_x = graph.pop_symbol(b, 1, sym_x);
+    let push_y = graph.push_symbol(b, 2, sym_y);
+    graph.edge(root, pop_x);
+    graph.edge(pop_x, push_y);
+    graph.edge(push_y, root);
+    let c = graph.file("c.py");
+    let pop_y = graph.pop_symbol(c, 1, sym_y);
+    let push_z = graph.push_symbol(c, 2, sym_z);
+    graph.edge(root, pop_y);
+    graph.edge(pop_y, push_z);
+    graph.edge(push_z, root);
+    let d = graph.file("d.py");
+    let def_z = graph.definition(d, 1, sym_z);
+    graph.edge(root, def_z);
+
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |g, ps, p| {
+                db.add_partial_path(g, ps, p.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+
+    let mut resolve_from = |reference, config: StitcherConfig| {
+        let mut definitions = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+            vec![reference],
+            config,
+            &NoCancellation,
+            |_, _, path| definitions.push(path.end_node),
+        )
+        .expect("should never be cancelled");
+        definitions
+    };
+
+    let mut resolve = |config: StitcherConfig| resolve_from(ref_x, config);
+    assert_eq!(vec![def_z], resolve(StitcherConfig::default()));
+    assert_eq!(
+        vec![def_z],
+        resolve(StitcherConfig::default().with_max_file_hops(3))
+    );
+    assert!(resolve(StitcherConfig::default().with_max_file_hops(2)).is_empty());
+    assert_eq!(
+        vec![def_w],
+        resolve_from(ref_w, StitcherConfig::default().with_max_file_hops(0))
+    );
+}
+
+#[test]
+fn paths_through_kept_nodes_are_not_dropped_as_similar() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+
+    // the reference reaches `x_def` via two different scopes, which yields similar paths
+    let scope1 = create_scope_node(&mut graph, file, false);
+    let scope2 = create_scope_node(&mut graph, file, false);
+    let x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    let x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (x_ref, scope1));
+    create_edge(&mut graph, (x_ref, scope2));
+    create_edge(&mut graph, (scope1, x_def));
+    create_edge(&mut graph, (scope2, x_def));
+
+    let count_paths = |graph: &StackGraph| {
+        let mut partials = PartialPaths::new();
+        let mut count = 0;
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut GraphEdgeCandidates::new(graph, &mut partials, None),
+            vec![x_ref],
+            StitcherConfig::default().with_detect_similar_paths(true),
+            &NoCancellation,
+            |_, _, _| count += 1,
+        )
+        .expect("should never be cancelled");
+        count
+    };
+
+    assert!(!graph.has_kept_nodes());
+    assert_eq!(1, count_paths(&graph));
+
+    graph.set_keeps_paths(scope2, true);
+    assert!(graph.has_kept_nodes());
+    assert_eq!(2, count_paths(&graph));
+
+    graph.set_keeps_paths(scope2, false);
+    assert!(!graph.has_kept_nodes());
+    assert_eq!(1, count_paths(&graph));
+}
+
+#[test]
+fn can_find_partial_paths_ending_at_node() {
+    let graph: StackGraph = test_graphs::class_field_through_function_parameter::new();
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |g, ps, p| {
+                db.add_partial_path(g, ps, p.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+
+    let b_file = graph.get_file("b.py").unwrap();
+    let b_bar = graph.node_for_id(NodeID::new_in_file(b_file, 8)).unwrap();
+    let expected = db
+        .iter_partial_paths()
+        .filter(|path| db[*path].end_node == b_bar)
+        .collect::<Vec<_>>();
+    assert!(!expected.is_empty());
+    assert_eq!(expected, db.partial_paths_ending_at(b_bar));
+
+    for node in graph.iter_nodes() {
+        let paths = db.partial_paths_ending_at(node);
+        assert!(paths.iter().all(|path| db[*path].end_node == node));
+        assert_eq!(
+            db.iter_partial_paths()
+                .filter(|path| db[*path].end_node == node)
+                .count(),
+            paths.len()
+        );
+    }
+}
+
+#[test]
+fn can_resolve_with_import_chains() {
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+    let sym_a = graph.symbol("a");
+    let sym_b = graph.symbol("b");
+    let sym_c = graph.symbol("c");
+    let sym_x = graph.symbol("x");
+
+    // a.py: from b import x; x
+    let a = graph.file("a.py");
+    let a_ref = graph.reference(a, 1, sym_x);
+    let a_import = graph.push_symbol(a, 2, sym_b);
+    graph.edge(a_ref, a_import);
+    graph.edge(a_import, root);
+    let a_def = graph.definition(a, 3, sym_a);
+    graph.edge(root, a_def);
+
+    // b.py: from c import x
+    let b = graph.file("b.py");
+    let b_def = graph.definition(b, 1, sym_b);
+    let b_import = graph.push_symbol(b, 2, sym_c);
+    graph.edge(root, b_def);
+    graph.edge(b_def, b_import);
+    graph.edge(b_import, root);
+
+    // c.py: x = 1
+    let c = graph.file("c.py");
+    let c_def = graph.definition(c, 1, sym_c);
+    let c_x_def = graph.definition(c, 2, sym_x);
+    graph.edge(root, c_def);
+    graph.edge(c_def, c_x_def);
+
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in [a, b, c] {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+
+    let chains = resolve_with_import_chains(
+        &graph,
+        &mut partials,
+        &mut db,
+        a_ref,
+        &StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    assert_eq!(1, chains.len());
+    assert_eq!(c_x_def, chains[0].definition);
+    assert_eq!(vec![a, b, c], chains[0].files);
+}
+
+#[test]
+fn stitching_order_does_not_depend_on_candidate_order() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let scope = create_scope_node(&mut graph, file, false);
+    let x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (x_ref, scope));
+    for _ in 0..3 {
+        let x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+        create_edge(&mut graph, (scope, x_def));
+    }
+
+    let mut partials = PartialPaths::new();
+    let mut file_paths = Vec::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| file_paths.push(path.clone()),
+    )
+    .expect("should never be cancelled");
+
+    let mut find_definitions = |paths: Vec<PartialPath>| {
+        let mut db = Database::new();
+        for path in paths {
+            db.add_partial_path(&graph, &mut partials, path);
+        }
+        let mut definitions = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+            vec![x_ref],
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, path| definitions.push(path.end_node),
+        )
+        .expect("should never be cancelled");
+        definitions
+    };
+    let forward = find_definitions(file_paths.clone());
+    let backward = find_definitions(file_paths.into_iter().rev().collect());
+    assert_eq!(3, forward.len());
+    assert_eq!(forward, backward);
+}
@@ -8,12 +8,16 @@
 use itertools::Itertools;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::unshadowed_paths;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::StitcherConfig;
 use stack_graphs::storage::SQLiteWriter;
 use stack_graphs::NoCancellation;
 
 use crate::util::create_partial_path_and_edges;
 use crate::util::create_pop_symbol_node;
 use crate::util::create_push_symbol_node;
+use crate::util::create_scope_node;
 
 fn test_foo_bar_root_candidate_paths(symbols: &[&str], variable: bool) -> usize {
     let mut reader = {
@@ -125,3 +129,288 @@ fn find_candidates_for_shorter_symbol_stack_without_variable() {
     let results = test_foo_bar_root_candidate_paths(&["foo"], false);
     assert_eq!(0, results);
 }
+
+#[test]
+fn can_resolve_references_without_stored_reference_paths() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    assert!(!writer.definitions_only());
+    writer.set_definitions_only().unwrap();
+    let mut graph = StackGraph::new();
+    let r = StackGraph::root_node();
+
+    let def_file = graph.add_file("def").unwrap();
+    let def = create_pop_symbol_node(&mut graph, def_file, "x", true);
+    graph.add_edge(r, def, 0);
+
+    let ref_file = graph.add_file("ref").unwrap();
+    let reference = create_push_symbol_node(&mut graph, ref_file, "x", true);
+    graph.add_edge(reference, r, 0);
+
+    for file in [def_file, ref_file] {
+        let mut partials = PartialPaths::new();
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_g, _ps, p| paths.push(p.clone()),
+        )
+        .unwrap();
+        writer
+            .store_result_for_file(&graph, file, "", &mut partials, &paths)
+            .unwrap();
+    }
+
+    let mut reader = writer.into_reader();
+    reader.load_graph_for_file("ref").unwrap();
+    let (graph, _, _) = reader.get();
+    let reference = graph
+        .iter_nodes()
+        .find(|n| graph[*n].is_reference())
+        .unwrap();
+    assert!(reader.definitions_only().unwrap());
+    assert!(!reader.has_paths_for_node(reference).unwrap());
+    assert!(!reader.has_paths_for_node(StackGraph::root_node()).unwrap());
+
+    reader
+        .compute_reference_paths_for_file("ref", StitcherConfig::default(), &NoCancellation)
+        .unwrap();
+    let mut definitions = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut reader,
+        std::iter::once(reference),
+        StitcherConfig::default(),
+        &NoCancellation,
+        |g, _ps, p| {
+            definitions.push(g[p.end_node].id().display(g).to_string());
+        },
+    )
+    .unwrap();
+    assert_eq!(vec!["def(0)".to_string()], definitions);
+}
+
+#[test]
+fn can_resolve_references_through_stored_wildcard_paths() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut graph = StackGraph::new();
+    let r = StackGraph::root_node();
+
+    let def_file = graph.add_file("def").unwrap();
+    let wildcard = graph.add_wildcard_symbol();
+    let any_module_id = graph.new_node_id(def_file);
+    let any_module = graph
+        .add_pop_symbol_node(any_module_id, wildcard, false)
+        .unwrap();
+    let def = create_pop_symbol_node(&mut graph, def_file, "x", true);
+    graph.add_edge(r, any_module, 0);
+    graph.add_edge(any_module, def, 0);
+
+    let ref_file = graph.add_file("ref").unwrap();
+    let reference = create_push_symbol_node(&mut graph, ref_file, "x", true);
+    let module = create_push_symbol_node(&mut graph, ref_file, "lib", false);
+    graph.add_edge(reference, module, 0);
+    graph.add_edge(module, r, 0);
+
+    for file in [def_file, ref_file] {
+        let mut partials = PartialPaths::new();
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_g, _ps, p| paths.push(p.clone()),
+        )
+        .unwrap();
+        writer
+            .store_result_for_file(&graph, file, "", &mut partials, &paths)
+            .unwrap();
+    }
+
+    let mut reader = writer.into_reader();
+    reader.load_graph_for_file("ref").unwrap();
+    let (graph, _, _) = reader.get();
+    let reference = graph
+        .iter_nodes()
+        .find(|n| graph[*n].is_reference())
+        .unwrap();
+    let mut definitions = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut reader,
+        std::iter::once(reference),
+        StitcherConfig::default(),
+        &NoCancellation,
+        |g, _ps, p| {
+            definitions.push(g[p.end_node].id().display(g).to_string());
+        },
+    )
+    .unwrap();
+    assert_eq!(vec!["def(1)".to_string()], definitions);
+}
+
+#[test]
+fn can_round_trip_definition_ranks() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut graph = StackGraph::new();
+    let r = StackGraph::root_node();
+
+    let builtins_file = graph.add_file("builtins").unwrap();
+    let builtin_def = create_pop_symbol_node(&mut graph, builtins_file, "x", true);
+    graph.add_edge(r, builtin_def, 0);
+    graph.set_definition_rank(builtin_def, -1);
+
+    let def_file = graph.add_file("def").unwrap();
+    let def = create_pop_symbol_node(&mut graph, def_file, "x", true);
+    graph.add_edge(r, def, 0);
+
+    let ref_file = graph.add_file("ref").unwrap();
+    let reference = create_push_symbol_node(&mut graph, ref_file, "x", true);
+    graph.add_edge(reference, r, 0);
+
+    for file in [builtins_file, def_file, ref_file] {
+        let mut partials = PartialPaths::new();
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_g, _ps, p| paths.push(p.clone()),
+        )
+        .unwrap();
+        writer
+            .store_result_for_file(&graph, file, "", &mut partials, &paths)
+            .unwrap();
+    }
+
+    let mut reader = writer.into_reader();
+    reader.load_graph_for_file("ref").unwrap();
+    let (graph, _, _) = reader.get();
+    let reference = graph
+        .iter_nodes()
+        .find(|n| graph[*n].is_reference())
+        .unwrap();
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut reader,
+        std::iter::once(reference),
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_g, _ps, p| paths.push(p.clone()),
+    )
+    .unwrap();
+    assert_eq!(2, paths.len());
+
+    let (graph, partials, _) = reader.get();
+    let ranks = paths
+        .iter()
+        .map(|p| graph.definition_rank(p.end_node))
+        .sorted()
+        .collect::<Vec<_>>();
+    assert_eq!(vec![-1, 0], ranks);
+    let definitions = unshadowed_paths(graph, partials, &paths)
+        .into_iter()
+        .map(|p| graph[p.end_node].id().display(graph).to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["def(0)".to_string()], definitions);
+}
+
+#[test]
+fn can_round_trip_selection_spans() {
+    let line_span = |line: usize| lsp_positions::Span {
+        start: lsp_positions::Position {
+            line,
+            ..Default::default()
+        },
+        end: lsp_positions::Position {
+            line: line + 1,
+            ..Default::default()
+        },
+    };
+
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("def").unwrap();
+    let def = create_pop_symbol_node(&mut graph, file, "x", true);
+    graph.add_edge(StackGraph::root_node(), def, 0);
+    graph.source_info_mut(def).span = line_span(1);
+    graph.set_selection_span(def, line_span(2));
+
+    let mut partials = PartialPaths::new();
+    writer
+        .store_result_for_file(&graph, file, "", &mut partials, &Vec::new())
+        .unwrap();
+
+    let mut reader = writer.into_reader();
+    reader.load_graph_for_file("def").unwrap();
+    let (graph, _, _) = reader.get();
+    let def = graph
+        .iter_nodes()
+        .find(|n| graph[*n].is_definition())
+        .unwrap();
+    let site = graph.definition_site(def).unwrap();
+    assert_eq!(line_span(1), site.span);
+    assert_eq!(line_span(2), site.selection_span);
+}
+
+#[test]
+fn can_round_trip_kept_nodes() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+
+    // the reference reaches `x_def` via two different scopes, which yields similar paths
+    let scope1 = create_scope_node(&mut graph, file, false);
+    let scope2 = create_scope_node(&mut graph, file, false);
+    let x_def = create_pop_symbol_node(&mut graph, file, "x", true);
+    let x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+    graph.add_edge(x_ref, scope1, 0);
+    graph.add_edge(x_ref, scope2, 0);
+    graph.add_edge(scope1, x_def, 0);
+    graph.add_edge(scope2, x_def, 0);
+    graph.set_keeps_paths(scope2, true);
+
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default().with_detect_similar_paths(true),
+        &NoCancellation,
+        |_g, _ps, p| paths.push(p.clone()),
+    )
+    .unwrap();
+    writer
+        .store_result_for_file(&graph, file, "", &mut partials, &paths)
+        .unwrap();
+
+    let mut reader = writer.into_reader();
+    reader.load_graph_for_file("test").unwrap();
+    let (graph, _, _) = reader.get();
+    let kept = graph
+        .iter_nodes()
+        .filter(|n| graph.keeps_paths(*n))
+        .map(|n| graph[n].id().display(graph).to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["test(1)".to_string()], kept);
+
+    let reference = graph
+        .iter_nodes()
+        .find(|n| graph[*n].is_reference())
+        .unwrap();
+    let mut count = 0;
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut reader,
+        std::iter::once(reference),
+        StitcherConfig::default().with_detect_similar_paths(true),
+        &NoCancellation,
+        |_g, _ps, _p| count += 1,
+    )
+    .unwrap();
+    assert_eq!(2, count);
+}
@@ -0,0 +1,95 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2026, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::cell::Cell;
+
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::serde::NoFilter;
+use stack_graphs::stitching::Database;
+use stack_graphs::visualization::VisualizationError;
+use stack_graphs::CancellationError;
+use stack_graphs::CancellationFlag;
+use stack_graphs::NoCancellation;
+
+use crate::test_graphs::CreateStackGraph;
+
+/// A cancellation flag that cancels after it has been checked a fixed number of times.
+struct CancelAfterChecks {
+    remaining: Cell<usize>,
+    checked: Cell<usize>,
+}
+
+impl CancelAfterChecks {
+    fn new(checks: usize) -> Self {
+        Self {
+            remaining: Cell::new(checks),
+            checked: Cell::new(0),
+        }
+    }
+}
+
+impl CancellationFlag for CancelAfterChecks {
+    fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+        self.checked.set(self.checked.get() + 1);
+        if self.remaining.get() == 0 {
+            return Err(CancellationError(at));
+        }
+        self.remaining.set(self.remaining.get() - 1);
+        Ok(())
+    }
+}
+
+fn large_graph() -> StackGraph {
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let root = StackGraph::root_node();
+    let x = graph.symbol("x");
+    for i in 0..10_000 {
+        let def = graph.definition(file, i * 2 + 1, x);
+        let r#ref = graph.reference(file, i * 2 + 2, x);
+        graph.edge(root, def);
+        graph.edge(r#ref, root);
+    }
+    graph
+}
+
+#[test]
+fn can_render_visualization_with_cancellation() {
+    let graph = large_graph();
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    let html = graph
+        .to_html_string_with_cancellation(
+            "test",
+            &mut partials,
+            &mut db,
+            &NoFilter,
+            &NoCancellation,
+        )
+        .expect("Expected visualization to succeed");
+    assert!(html.contains("<title>test</title>"));
+}
+
+#[test]
+fn can_cancel_visualization_mid_render() {
+    let graph = large_graph();
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    let cancellation_flag = CancelAfterChecks::new(2);
+    let result = graph.to_html_string_with_cancellation(
+        "test",
+        &mut partials,
+        &mut db,
+        &NoFilter,
+        &cancellation_flag,
+    );
+    assert!(matches!(result, Err(VisualizationError::Cancelled(_))));
+    // The flag is checked once up front and then once per chunk; after cancellation, no
+    // further checks are made.
+    assert_eq!(3, cancellation_flag.checked.get());
+}
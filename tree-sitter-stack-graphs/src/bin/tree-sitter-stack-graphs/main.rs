@@ -8,6 +8,7 @@
 use clap::Parser;
 use tree_sitter_stack_graphs::cli::database::default_user_database_path_for_crate;
 use tree_sitter_stack_graphs::cli::path_loading::Subcommands;
+use tree_sitter_stack_graphs::cli::util::init_logger;
 
 #[derive(Parser)]
 #[clap(about, version)]
@@ -17,6 +18,7 @@ pub struct Cli {
 }
 
 fn main() -> anyhow::Result<()> {
+    init_logger();
     let cli = Cli::parse();
     let default_db_path = default_user_database_path_for_crate(env!("CARGO_PKG_NAME"))?;
     cli.subcommand.run(default_db_path)
@@ -17,6 +17,7 @@
 //! use clap::Parser;
 //! use tree_sitter_stack_graphs::cli::database::default_user_database_path_for_crate;
 //! use tree_sitter_stack_graphs::cli::path_loading::Subcommands;
+//! use tree_sitter_stack_graphs::cli::util::init_logger;
 //!
 //! #[derive(Parser)]
 //! #[clap(about, version)]
@@ -26,6 +27,7 @@
 //! }
 //!
 //! fn main() -> anyhow::Result<()> {
+//!     init_logger();
 //!     let cli = Cli::parse();
 //!     let default_db_path = default_user_database_path_for_crate(env!("CARGO_PKG_NAME"))?;
 //!     cli.subcommand.run(default_db_path)
@@ -41,6 +43,7 @@
 //! use clap::Parser;
 //! use tree_sitter_stack_graphs::cli::database::default_user_database_path_for_crate;
 //! use tree_sitter_stack_graphs::cli::provided_languages::Subcommands;
+//! use tree_sitter_stack_graphs::cli::util::init_logger;
 //!
 //! #[derive(Parser)]
 //! #[clap(about, version)]
@@ -50,6 +53,7 @@
 //! }
 //!
 //! fn main() -> anyhow::Result<()> {
+//!     init_logger();
 //!     let cli = Cli::parse();
 //!     let language_configurations = vec![/* add your language configurations here */];
 //!     let default_db_path = default_user_database_path_for_crate(env!("CARGO_PKG_NAME"))?;
@@ -276,6 +280,8 @@ pub mod path_loading {
     /// Visualize command
     #[derive(clap::Parser)]
     pub struct Visualize {
+        #[clap(flatten)]
+        load_args: PathLoaderArgs,
         #[clap(flatten)]
         db_args: DatabaseArgs,
         #[clap(flatten)]
@@ -284,6 +290,10 @@ pub mod path_loading {
 
     impl Visualize {
         pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            if self.visualize_args.from_source {
+                let loader = self.load_args.get()?;
+                return self.visualize_args.run_from_source(loader);
+            }
             let db_path = self.db_args.get_or(default_db_path);
             self.visualize_args.run(&db_path)
         }
@@ -342,7 +352,7 @@ pub mod provided_languages {
                 Self::Query(cmd) => cmd.run(default_db_path),
                 Self::Status(cmd) => cmd.run(default_db_path),
                 Self::Test(cmd) => cmd.run(configurations),
-                Self::Visualize(cmd) => cmd.run(default_db_path),
+                Self::Visualize(cmd) => cmd.run(default_db_path, configurations),
             }
         }
     }
@@ -507,6 +517,8 @@ pub mod provided_languages {
     /// Visualize command
     #[derive(clap::Parser)]
     pub struct Visualize {
+        #[clap(flatten)]
+        load_args: LanguageConfigurationsLoaderArgs,
         #[clap(flatten)]
         db_args: DatabaseArgs,
         #[clap(flatten)]
@@ -514,7 +526,15 @@ pub mod provided_languages {
     }
 
     impl Visualize {
-        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+        pub fn run(
+            self,
+            default_db_path: PathBuf,
+            configurations: Vec<LanguageConfiguration>,
+        ) -> anyhow::Result<()> {
+            if self.visualize_args.from_source {
+                let loader = self.load_args.get(configurations)?;
+                return self.visualize_args.run_from_source(loader);
+            }
             let db_path = self.db_args.get_or(default_db_path);
             self.visualize_args.run(&db_path)
         }
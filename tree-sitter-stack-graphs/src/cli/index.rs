@@ -12,6 +12,7 @@ use stack_graphs::graph::File;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::partial::PartialPaths;
 use stack_graphs::stats::FrequencyDistribution;
+use stack_graphs::stats::PhaseTimings;
 use stack_graphs::stitching::ForwardPartialPathStitcher;
 use stack_graphs::stitching::Stats as StitchingStats;
 use stack_graphs::stitching::StitcherConfig;
@@ -21,6 +22,7 @@ use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
 use std::time::Duration;
+use std::time::Instant;
 use thiserror::Error;
 use tree_sitter_graph::Variables;
 
@@ -83,6 +85,13 @@ pub struct IndexArgs {
     )]
     pub max_file_time: Option<Duration>,
 
+    /// Only store the partial paths needed to resolve references to definitions, and leave out
+    /// the paths starting at references. This produces a smaller index that is faster to build,
+    /// but queries must recompute the reference paths of the queried file. Once used, the mode
+    /// is kept for the database until it is cleaned.
+    #[clap(long)]
+    pub definitions_only: bool,
+
     #[clap(long)]
     pub stats: bool,
 
@@ -101,6 +110,7 @@ impl IndexArgs {
             hide_error_details: false,
             max_file_time: None,
             wait_at_start: false,
+            definitions_only: false,
             stats: false,
         }
     }
@@ -110,6 +120,9 @@ impl IndexArgs {
             wait_for_input()?;
         }
         let mut db = SQLiteWriter::open(&db_path)?;
+        if self.definitions_only {
+            db.set_definitions_only()?;
+        }
         let reporter = self.get_reporter();
         let mut indexer = Indexer::new(&mut db, &mut loader, &reporter);
         indexer.force = self.force;
@@ -308,7 +321,8 @@ impl<'a> Indexer<'a> {
             .with_detect_similar_paths(!lcs.no_similar_paths_in_file())
             .with_collect_stats(self.stats.is_some());
 
-        let source = file_reader.get(source_path)?;
+        let (source, utf8_diagnostic) = file_reader.get_with_diagnostic(source_path)?;
+        let utf8_diagnostic = utf8_diagnostic.cloned();
         let tag = sha1(source);
 
         let success_status = match self
@@ -353,8 +367,9 @@ impl<'a> Indexer<'a> {
             lcs,
             &cancellation_flag,
         );
-        if let Err(err) = result {
-            match err.inner {
+        let mut timings = match result {
+            Ok(timings) => timings,
+            Err(err) => match err.inner {
                 BuildError::Cancelled(_) => {
                     file_status.warning("timed out", None);
                     self.db
@@ -366,7 +381,7 @@ impl<'a> Indexer<'a> {
                     self.db.store_error_for_file(source_path, &tag, "failed")?;
                     return Ok(());
                 }
-            }
+            },
         };
         if let Some(stats) = &mut self.stats {
             stats.total_graph_nodes.record(graph.iter_nodes().count());
@@ -385,6 +400,7 @@ impl<'a> Indexer<'a> {
 
         let mut partials = PartialPaths::new();
         let mut paths = Vec::new();
+        let partial_paths_start = Instant::now();
         match ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
             &graph,
             &mut partials,
@@ -396,8 +412,11 @@ impl<'a> Indexer<'a> {
             },
         ) {
             Ok(stitching_stats) => {
+                timings.partial_paths += partial_paths_start.elapsed();
                 if let Some(stats) = &mut self.stats {
                     stats.stitching_stats += stitching_stats;
+                    stats.phase_timings += timings;
+                    stats.file_millis.record(timings.total().as_millis());
                 }
             }
             Err(_) => {
@@ -414,7 +433,12 @@ impl<'a> Indexer<'a> {
         self.db
             .store_result_for_file(&graph, file, &tag, &mut partials, &paths)?;
 
-        file_status.success(success_status, None);
+        file_status.success(
+            success_status,
+            utf8_diagnostic
+                .as_ref()
+                .map(|d| d as &dyn std::fmt::Display),
+        );
 
         Ok(())
     }
@@ -427,7 +451,8 @@ impl<'a> Indexer<'a> {
         source: &'b str,
         lcs: FileLanguageConfigurations<'b>,
         cancellation_flag: &dyn CancellationFlag,
-    ) -> std::result::Result<(), BuildErrorWithSource<'b>> {
+    ) -> std::result::Result<PhaseTimings, BuildErrorWithSource<'b>> {
+        let mut timings = PhaseTimings::default();
         let relative_source_path = source_path.strip_prefix(source_root).unwrap();
         if let Some(lc) = lcs.primary {
             let mut globals = Variables::new();
@@ -440,16 +465,20 @@ impl<'a> Indexer<'a> {
                 .add(ROOT_PATH_VAR.into(), source_root.to_str().unwrap().into())
                 .expect("failed to add root path variable");
 
-            lc.sgl
-                .build_stack_graph_into(graph, file, source, &globals, cancellation_flag)
+            let mut builder = lc.sgl.builder_into_stack_graph(graph, file, source);
+            builder.collect_timings(&mut timings);
+            builder
+                .build(&globals, cancellation_flag)
                 .map_err(|inner| BuildErrorWithSource {
                     inner,
                     source_path: source_path.to_path_buf(),
                     source_str: source,
                     tsg_path: lc.sgl.tsg_path().to_path_buf(),
                     tsg_str: &lc.sgl.tsg_source(),
+                    tsg_includes: lc.sgl.tsg_includes(),
                 })?;
         }
+        let execution_start = Instant::now();
         for (_, fa) in lcs.secondary {
             fa.build_stack_graph_into(
                 graph,
@@ -466,9 +495,11 @@ impl<'a> Indexer<'a> {
                 source_str: &source,
                 tsg_path: PathBuf::new(),
                 tsg_str: "",
+                tsg_includes: None,
             })?;
         }
-        Ok(())
+        timings.execution += execution_start.elapsed();
+        Ok(timings)
     }
 
     /// Determines if a path should be skipped because we have not seen the
@@ -526,4 +557,8 @@ pub struct IndexingStats {
     pub root_out_degree: usize,
     // The stitching statistics.
     pub stitching_stats: StitchingStats,
+    // The time spent in each phase, summed over all files.
+    pub phase_timings: PhaseTimings,
+    // The distribution of the total time spent per file, in milliseconds.
+    pub file_millis: FrequencyDistribution<u128>,
 }
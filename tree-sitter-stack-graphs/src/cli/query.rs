@@ -7,11 +7,14 @@
 
 use std::path::Path;
 use std::path::PathBuf;
+use std::time::Instant;
 
 use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
 use clap::ValueHint;
+use stack_graphs::stats::PhaseTimings;
+use stack_graphs::stitching::unshadowed_paths;
 use stack_graphs::stitching::ForwardPartialPathStitcher;
 use stack_graphs::stitching::Stats as StitchingStats;
 use stack_graphs::stitching::StitcherConfig;
@@ -21,6 +24,8 @@ use thiserror::Error;
 use tree_sitter_graph::parse_error::Excerpt;
 
 use crate::cli::util::print_database_stats;
+use crate::cli::util::print_phase_timings;
+use crate::cli::util::print_quartiles_header;
 use crate::cli::util::print_stitching_stats;
 use crate::cli::util::reporter::ConsoleReporter;
 use crate::cli::util::reporter::Reporter;
@@ -51,12 +56,15 @@ impl QueryArgs {
             wait_for_input()?;
         }
         let mut db = SQLiteReader::open(&db_path)?;
-        let stitching_stats = self.target.run(&mut db, self.stats)?;
+        let (stitching_stats, timings) = self.target.run(&mut db, self.stats)?;
         if self.stats {
             println!();
             print_stitching_stats(stitching_stats);
             println!();
             print_database_stats(db.stats());
+            println!();
+            print_quartiles_header("timings (ms)");
+            print_phase_timings(timings);
         }
         Ok(())
     }
@@ -68,14 +76,19 @@ pub enum Target {
 }
 
 impl Target {
-    fn run(self, db: &mut SQLiteReader, collect_stats: bool) -> anyhow::Result<StitchingStats> {
+    fn run(
+        self,
+        db: &mut SQLiteReader,
+        collect_stats: bool,
+    ) -> anyhow::Result<(StitchingStats, PhaseTimings)> {
         let reporter = ConsoleReporter::details();
         let mut querier = Querier::new(db, &reporter);
         querier.set_collect_stats(collect_stats);
         match self {
             Self::Definition(cmd) => cmd.run(&mut querier)?,
         }
-        Ok(querier.into_stats())
+        let timings = querier.timings();
+        Ok((querier.into_stats(), timings))
     }
 }
 
@@ -154,6 +167,7 @@ pub struct Querier<'a> {
     db: &'a mut SQLiteReader,
     reporter: &'a dyn Reporter,
     stats: Option<StitchingStats>,
+    timings: PhaseTimings,
 }
 
 impl<'a> Querier<'a> {
@@ -162,6 +176,7 @@ impl<'a> Querier<'a> {
             db,
             reporter,
             stats: None,
+            timings: PhaseTimings::default(),
         }
     }
 
@@ -207,6 +222,24 @@ impl<'a> Querier<'a> {
             return Ok(Default::default());
         }
 
+        // Definitions-only indexes do not store paths starting at references, so we compute
+        // those from the file's graph instead.
+        let mut has_reference_paths = true;
+        if self.db.definitions_only()? {
+            for (node, _) in &starting_nodes {
+                has_reference_paths &= self.db.has_paths_for_node(*node)?;
+            }
+        }
+        if !has_reference_paths {
+            let partial_paths_start = Instant::now();
+            self.db.compute_reference_paths_for_file(
+                &reference.path.to_string_lossy(),
+                StitcherConfig::default(),
+                &cancellation_flag,
+            )?;
+            self.timings.partial_paths += partial_paths_start.elapsed();
+        }
+
         let mut result = Vec::new();
         for (node, span) in starting_nodes {
             let reference_span = SourceSpan {
@@ -219,6 +252,7 @@ impl<'a> Querier<'a> {
                 // always detect similar paths, we don't know the language configurations for the data in the database
                 .with_detect_similar_paths(true)
                 .with_collect_stats(self.stats.is_some());
+            let stitching_start = Instant::now();
             let ref_result = ForwardPartialPathStitcher::find_all_complete_partial_paths(
                 self.db,
                 std::iter::once(node),
@@ -228,6 +262,7 @@ impl<'a> Querier<'a> {
                     reference_paths.push(p.clone());
                 },
             );
+            self.timings.stitching += stitching_start.elapsed();
             match ref_result {
                 Ok(ref_stats) => {
                     if let Some(stats) = &mut self.stats {
@@ -241,19 +276,11 @@ impl<'a> Querier<'a> {
             }
 
             let (graph, partials, _) = self.db.get();
-            let mut actual_paths = Vec::new();
-            for reference_path in &reference_paths {
-                if let Err(err) = cancellation_flag.check("shadowing") {
-                    self.reporter.failed(&log_path, "query timed out", None);
-                    return Err(err.into());
-                }
-                if reference_paths
-                    .iter()
-                    .all(|other| !other.shadows(partials, reference_path))
-                {
-                    actual_paths.push(reference_path.clone());
-                }
+            if let Err(err) = cancellation_flag.check("shadowing") {
+                self.reporter.failed(&log_path, "query timed out", None);
+                return Err(err.into());
             }
+            let actual_paths = unshadowed_paths(graph, partials, &reference_paths);
 
             let definitions = actual_paths
                 .into_iter()
@@ -290,6 +317,11 @@ impl<'a> Querier<'a> {
         Ok(result)
     }
 
+    /// Returns the time spent computing partial paths and stitching them, for all queries so far.
+    pub fn timings(&self) -> PhaseTimings {
+        self.timings
+    }
+
     pub fn into_stats(self) -> StitchingStats {
         self.stats.unwrap_or_default()
     }
@@ -10,6 +10,8 @@ use clap::Args;
 use clap::ValueEnum;
 use clap::ValueHint;
 use itertools::Itertools;
+use sha1::Digest;
+use sha1::Sha1;
 use stack_graphs::arena::Handle;
 use stack_graphs::graph::File;
 use stack_graphs::graph::StackGraph;
@@ -45,6 +47,10 @@ use crate::CancelAfterDuration;
 use crate::CancellationFlag;
 use crate::FILE_PATH_VAR;
 
+/// Version of the format of cached test graphs. Bump this whenever the serialized graph format
+/// changes, so that stale cache entries are not reused.
+const GRAPH_CACHE_VERSION: usize = 1;
+
 #[derive(Args)]
 #[clap(after_help = r#"PATH SPECIFICATIONS:
     Output filenames can be specified using placeholders based on the input file.
@@ -144,6 +150,15 @@ pub struct TestArgs {
         value_parser = duration_from_seconds_str,
     )]
     pub max_test_time: Option<Duration>,
+
+    /// Write a JUnit XML report with a test case for every assertion to the given file.
+    #[clap(long, value_name = "PATH", value_hint = ValueHint::FilePath)]
+    pub junit: Option<PathBuf>,
+
+    /// Cache built test graphs in the given directory. Graphs are reused for tests whose
+    /// source and stack graph rules are unchanged, and only assertions are checked again.
+    #[clap(long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    pub graph_cache: Option<PathBuf>,
 }
 
 /// Flag to control output
@@ -175,6 +190,8 @@ impl TestArgs {
             output_mode: OutputMode::OnFailure,
             no_builtins: false,
             max_test_time: None,
+            junit: None,
+            graph_cache: None,
         }
     }
 
@@ -194,6 +211,10 @@ impl TestArgs {
             file_status.assert_reported();
             total_result.absorb(test_result);
         }
+        if let Some(junit) = &self.junit {
+            let mut file = std::fs::File::create(junit)?;
+            total_result.write_junit_xml("stack-graphs", &mut file)?;
+        }
         if total_result.failure_count() > 0 {
             return Err(anyhow!(total_result.to_string()));
         }
@@ -283,81 +304,107 @@ impl TestArgs {
 
         let source = file_reader.get(test_path)?;
         let default_fragment_path = test_path.strip_prefix(test_root).unwrap();
-        let mut test = Test::from_source(test_path, source, default_fragment_path)?;
+        // without the rules' source, changes to the rules cannot be detected, so nothing is cached
+        let cache_path = self
+            .graph_cache
+            .as_ref()
+            .filter(|_| !lc.sgl.tsg_source().is_empty())
+            .map(|dir| {
+                dir.join(Self::graph_cache_file_name(
+                    default_fragment_path,
+                    source,
+                    &lc,
+                ))
+            });
+        let cached_graph = cache_path
+            .as_ref()
+            .and_then(|path| Self::load_cached_graph(path));
+        let is_cached = cached_graph.is_some();
+        let mut test = Test::from_source_with_graph(
+            test_path,
+            source,
+            default_fragment_path,
+            cached_graph.unwrap_or_default(),
+        )?;
         if !self.no_builtins {
             self.load_builtins_into(
                 &lc,
                 &mut test.graph,
                 &mut partials,
                 &mut db,
-                stitcher_config,
+                stitcher_config.clone(),
                 cancellation_flag.as_ref(),
                 cache,
             )?;
         }
-        let mut globals = Variables::new();
-        for test_fragment in &test.fragments {
-            let result = if let Some(fa) = test_fragment
-                .path
-                .file_name()
-                .and_then(|file_name| lc.special_files.get(&file_name.to_string_lossy()))
-            {
-                let mut all_paths = test.fragments.iter().map(|f| f.path.as_path());
-                fa.build_stack_graph_into(
-                    &mut test.graph,
-                    test_fragment.file,
+        if !is_cached {
+            let mut globals = Variables::new();
+            for test_fragment in &test.fragments {
+                let result = if let Some(fa) = test_fragment
+                    .path
+                    .file_name()
+                    .and_then(|file_name| lc.special_files.get(&file_name.to_string_lossy()))
+                {
+                    let mut all_paths = test.fragments.iter().map(|f| f.path.as_path());
+                    fa.build_stack_graph_into(
+                        &mut test.graph,
+                        test_fragment.file,
+                        &test_fragment.path,
+                        &test_fragment.source,
+                        &mut all_paths,
+                        &test_fragment.globals,
+                        cancellation_flag.as_ref(),
+                    )
+                } else if lc.matches_file(
                     &test_fragment.path,
-                    &test_fragment.source,
-                    &mut all_paths,
-                    &test_fragment.globals,
-                    cancellation_flag.as_ref(),
-                )
-            } else if lc.matches_file(
-                &test_fragment.path,
-                &mut Some(test_fragment.source.as_ref()),
-            )? {
-                globals.clear();
-
-                test_fragment.add_globals_to(&mut globals);
-
-                globals
-                    .add(
-                        FILE_PATH_VAR.into(),
-                        test_fragment.path.to_str().unwrap().into(),
+                    &mut Some(test_fragment.source.as_ref()),
+                )? {
+                    globals.clear();
+
+                    test_fragment.add_globals_to(&mut globals);
+
+                    globals
+                        .add(
+                            FILE_PATH_VAR.into(),
+                            test_fragment.path.to_str().unwrap().into(),
+                        )
+                        .unwrap_or_default();
+
+                    lc.sgl.build_stack_graph_into(
+                        &mut test.graph,
+                        test_fragment.file,
+                        &test_fragment.source,
+                        &globals,
+                        cancellation_flag.as_ref(),
                     )
-                    .unwrap_or_default();
-
-                lc.sgl.build_stack_graph_into(
-                    &mut test.graph,
-                    test_fragment.file,
-                    &test_fragment.source,
-                    &globals,
-                    cancellation_flag.as_ref(),
-                )
-            } else {
-                return Err(anyhow!(
-                    "Test fragment {} not supported by language of test file {}",
-                    test_fragment.path.display(),
-                    test.path.display()
-                ));
-            };
-            match result {
-                Err(err) => {
-                    file_status.failure(
-                        "failed to build stack graph",
-                        Some(&format!(
-                            "{}",
-                            err.display_pretty(
-                                &test.path,
-                                source,
-                                lc.sgl.tsg_path(),
-                                lc.sgl.tsg_source(),
-                            )
-                        )),
-                    );
-                    return Err(anyhow!("Failed to build graph for {}", test_path.display()));
+                } else {
+                    return Err(anyhow!(
+                        "Test fragment {} not supported by language of test file {}",
+                        test_fragment.path.display(),
+                        test.path.display()
+                    ));
+                };
+                match result {
+                    Err(err) => {
+                        file_status.failure(
+                            "failed to build stack graph",
+                            Some(&format!(
+                                "{}",
+                                err.display_pretty_for_language(&test.path, source, &lc.sgl)
+                            )),
+                        );
+                        return Err(anyhow!("Failed to build graph for {}", test_path.display()));
+                    }
+                    Ok(_) => {}
                 }
-                Ok(_) => {}
+            }
+            if let Some(cache_path) = &cache_path {
+                let files = test.fragments.iter().map(|f| f.file).collect::<Vec<_>>();
+                self.save_graph(
+                    cache_path,
+                    &test.graph,
+                    &|_: &StackGraph, h: &Handle<File>| files.contains(h),
+                )?;
             }
         }
         for fragment in &test.fragments {
@@ -365,7 +412,7 @@ impl TestArgs {
                 &test.graph,
                 &mut partials,
                 fragment.file,
-                stitcher_config,
+                stitcher_config.clone(),
                 &cancellation_flag.as_ref(),
                 |g, ps, p| {
                     db.add_partial_path(g, ps, p.clone());
@@ -375,7 +422,7 @@ impl TestArgs {
         let result = test.run(
             &mut partials,
             &mut db,
-            stitcher_config,
+            stitcher_config.clone(),
             cancellation_flag.as_ref(),
         )?;
         let success = result.failure_count() == 0;
@@ -418,6 +465,68 @@ impl TestArgs {
         Ok(result)
     }
 
+    /// Returns the file name of the cached graph for a test, which depends on the test path and
+    /// source, on the language, and on the cache format version.
+    fn graph_cache_file_name(test_path: &Path, source: &str, lc: &LanguageConfiguration) -> String {
+        let mut hasher = Sha1::new();
+        hasher.update(test_path.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(source);
+        let source_hash = hasher.finalize();
+        format!(
+            "{:x}-{:x}.v{}.graph.json",
+            source_hash,
+            Self::language_hash(lc),
+            GRAPH_CACHE_VERSION
+        )
+    }
+
+    /// Returns a hash of the parts of a language configuration that test graphs are built from:
+    /// the grammar, the stack graph rules, the builtins, and the special files.  Special file
+    /// analyzers are code, so they are only identified by their file names and by the version of
+    /// this crate, which provides the common analyzers.
+    fn language_hash(lc: &LanguageConfiguration) -> impl std::fmt::LowerHex {
+        let mut hasher = Sha1::new();
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update([0]);
+        hasher.update(lc.scope.as_deref().unwrap_or_default());
+        hasher.update([0]);
+
+        let language = &lc.language;
+        hasher.update(language.version().to_le_bytes());
+        hasher.update(language.parse_state_count().to_le_bytes());
+        for id in 0..language.node_kind_count() as u16 {
+            hasher.update(language.node_kind_for_id(id).unwrap_or_default());
+            hasher.update([0, language.node_kind_is_named(id) as u8]);
+        }
+        for id in 1..=language.field_count() as u16 {
+            hasher.update(language.field_name_for_id(id).unwrap_or_default());
+            hasher.update([0]);
+        }
+
+        hasher.update(lc.sgl.tsg_source().as_bytes());
+        hasher.update([0]);
+        if let Ok(builtins) = serde_json::to_vec(&lc.builtins.to_serializable()) {
+            hasher.update(builtins);
+        }
+        hasher.update([0]);
+        for file_name in lc.special_files.file_names().sorted() {
+            hasher.update(file_name);
+            hasher.update([0]);
+        }
+        hasher.finalize()
+    }
+
+    /// Loads a cached graph. Missing or unreadable cache entries are ignored, and cause the
+    /// graph to be built again.
+    fn load_cached_graph(path: &Path) -> Option<StackGraph> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        let serialized = serde_json::from_str::<stack_graphs::serde::StackGraph>(&contents).ok()?;
+        let mut graph = StackGraph::new();
+        serialized.load_into(&mut graph).ok()?;
+        Some(graph)
+    }
+
     fn load_builtins_into<'a>(
         &self,
         lc: &'a LanguageConfiguration,
@@ -442,7 +551,7 @@ impl TestArgs {
                         graph,
                         partials,
                         *file,
-                        stitcher_config,
+                        stitcher_config.clone(),
                         &cancellation_flag,
                         |g, ps, p| {
                             db.add_partial_path(g, ps, p.clone());
@@ -19,6 +19,7 @@ use stack_graphs::arena::Handle;
 use stack_graphs::graph::Node;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::stats::FrequencyDistribution;
+use stack_graphs::stats::PhaseTimings;
 use stack_graphs::stitching::Stats as StitchingStats;
 use stack_graphs::storage::Stats as StorageStats;
 use std::ffi::OsStr;
@@ -34,6 +35,7 @@ use walkdir::WalkDir;
 
 use crate::cli::index::IndexingStats;
 use crate::cli::util::reporter::Reporter;
+use crate::loader::TsgSource;
 
 pub mod reporter;
 
@@ -334,6 +336,13 @@ pub(crate) fn duration_from_milliseconds_str(s: &str) -> Result<Duration, anyhow
     Ok(Duration::new(seconds, nano_seconds))
 }
 
+/// Initializes logging to standard error, which shows warnings and errors by default.  The level
+/// can be changed using the `RUST_LOG` environment variable.  Call this at the start of a CLI's
+/// `main` function, so that warnings emitted while loading languages are shown as well.
+pub fn init_logger() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+}
+
 pub fn iter_files_and_directories<'a, P, IP>(
     paths: IP,
 ) -> impl Iterator<Item = (PathBuf, PathBuf, bool)> + 'a
@@ -477,6 +486,7 @@ pub(crate) struct BuildErrorWithSource<'a> {
     pub source_str: &'a str,
     pub tsg_path: PathBuf,
     pub tsg_str: &'a str,
+    pub tsg_includes: Option<&'a TsgSource>,
 }
 
 impl<'a> BuildErrorWithSource<'a> {
@@ -492,11 +502,12 @@ impl std::fmt::Display for DisplayBuildErrorPretty<'_> {
         write!(
             f,
             "{}",
-            self.0.inner.display_pretty(
+            self.0.inner.display_pretty_with_includes(
                 &self.0.source_path,
                 self.0.source_str,
                 &self.0.tsg_path,
                 self.0.tsg_str,
+                self.0.tsg_includes,
             )
         )
     }
@@ -510,6 +521,26 @@ pub(super) fn print_indexing_stats(stats: IndexingStats) {
     print_value_row("root out degree", stats.root_out_degree);
     println!();
     print_stitching_stats(stats.stitching_stats);
+    println!();
+    print_quartiles_header("timings (ms)");
+    print_quartiles_row("time per file", stats.file_millis);
+    print_phase_timings(stats.phase_timings);
+}
+
+/// Prints the total time of every phase, skipping phases that did not run.
+pub(super) fn print_phase_timings(timings: PhaseTimings) {
+    let phases = [
+        ("total parsing", timings.parsing),
+        ("total execution", timings.execution),
+        ("total partial paths", timings.partial_paths),
+        ("total stitching", timings.stitching),
+    ];
+    for (name, duration) in phases {
+        if !duration.is_zero() {
+            print_value_row(name, duration.as_millis());
+        }
+    }
+    print_value_row("total", timings.total().as_millis());
 }
 
 pub(super) fn print_stitching_stats(stats: StitchingStats) {
@@ -555,7 +586,7 @@ pub(super) fn print_database_stats(stats: StorageStats) {
     );
 }
 
-fn print_quartiles_header(title: &str) {
+pub(super) fn print_quartiles_header(title: &str) {
     println!(
         "| {:^29} | {:^9} | {:^9} | {:^9} | {:^9} | {:^9} | {:^9} |",
         title, "min", "p25", "p50", "p75", "max", "count",
@@ -5,16 +5,28 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use anyhow::anyhow;
 use clap::Args;
 use clap::ValueHint;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
 use stack_graphs::serde::NoFilter;
 use stack_graphs::stitching::Database;
+use stack_graphs::stitching::DatabaseCandidates;
 use stack_graphs::stitching::ForwardPartialPathStitcher;
 use stack_graphs::stitching::StitcherConfig;
 use stack_graphs::storage::SQLiteReader;
 use stack_graphs::NoCancellation;
+use std::io::Read;
 use std::path::Path;
 use std::path::PathBuf;
+use tree_sitter_graph::Variables;
+
+use crate::loader::FileReader;
+use crate::loader::Loader;
+use crate::CancellationFlag;
+use crate::FILE_PATH_VAR;
+use crate::ROOT_PATH_VAR;
 
 /// Visualize database
 #[derive(Args)]
@@ -39,6 +51,20 @@ pub struct VisualizeArgs {
         default_value = "stack-graph.html",
     )]
     pub output: PathBuf,
+
+    /// Build the stack graphs of the source files, instead of loading them from the database.
+    /// Source paths must be files in this case.
+    #[clap(long)]
+    pub from_source: bool,
+
+    /// Read the source of a single file from standard input. Requires --from-source and --path.
+    #[clap(long, requires_all = &["from_source", "path"])]
+    pub stdin: bool,
+
+    /// Logical path of the source read from standard input. It is used to select the language
+    /// and as the FILE_PATH of the built stack graph, and need not exist on disk.
+    #[clap(long, value_name = "PATH", value_hint = ValueHint::FilePath, requires = "stdin")]
+    pub path: Option<PathBuf>,
 }
 
 impl VisualizeArgs {
@@ -68,8 +94,117 @@ impl VisualizeArgs {
             },
         )?;
         let (graph, partials, _) = db.get();
-        let html =
-            graph.to_html_string("stack-graph", partials, &mut complete_paths_db, &NoFilter)?;
+        self.write_html(graph, partials, &mut complete_paths_db)
+    }
+
+    /// Builds the stack graphs of the source files with the given loader, and visualizes them
+    /// without using the database.
+    pub fn run_from_source(self, loader: Loader) -> anyhow::Result<()> {
+        self.run_from_source_with_stdin(loader, &mut std::io::stdin())
+    }
+
+    /// Builds the stack graphs of the source files with the given loader, and visualizes them
+    /// without using the database. If `--stdin` is set, the source of the file at `--path` is
+    /// read from the given reader instead of from disk.
+    pub fn run_from_source_with_stdin(
+        self,
+        mut loader: Loader,
+        stdin: &mut dyn Read,
+    ) -> anyhow::Result<()> {
+        let cancellation_flag: &dyn CancellationFlag = &crate::NoCancellation;
+        let mut graph = StackGraph::new();
+        let mut partials = PartialPaths::new();
+        let mut db = Database::new();
+
+        let mut inputs = Vec::new();
+        if self.stdin {
+            let path = self
+                .path
+                .clone()
+                .ok_or_else(|| anyhow!("--stdin requires --path"))?;
+            let mut source = String::new();
+            stdin.read_to_string(&mut source)?;
+            inputs.push((path, Some(source)));
+        }
+        for source_path in &self.source_paths {
+            let source_path = source_path.canonicalize()?;
+            if !source_path.is_file() {
+                return Err(anyhow!("{} is not a file", source_path.display()));
+            }
+            inputs.push((source_path, None));
+        }
+
+        for (source_path, stdin_source) in &inputs {
+            let mut file_reader = FileReader::new();
+            let source = match stdin_source {
+                Some(source) => source.as_str(),
+                None => file_reader.get(source_path)?,
+            };
+            let lc = match loader
+                .load_for_file(source_path, &mut Some(source), cancellation_flag)?
+                .primary
+            {
+                Some(lc) => lc,
+                None => {
+                    return Err(anyhow!(
+                        "No stack graph language found for {}",
+                        source_path.display()
+                    ))
+                }
+            };
+            let stitcher_config =
+                StitcherConfig::default().with_detect_similar_paths(!lc.no_similar_paths_in_file);
+
+            let mut globals = Variables::new();
+            globals
+                .add(FILE_PATH_VAR.into(), source_path.to_str().unwrap().into())
+                .expect("failed to add file path variable");
+            if let Some(source_root) = source_path.parent() {
+                globals
+                    .add(ROOT_PATH_VAR.into(), source_root.to_str().unwrap().into())
+                    .expect("failed to add root path variable");
+            }
+
+            let file = graph.get_or_create_file(&source_path.to_string_lossy());
+            lc.sgl
+                .build_stack_graph_into(&mut graph, file, source, &globals, cancellation_flag)
+                .map_err(|err| anyhow!("Failed to build {}: {}", source_path.display(), err))?;
+            ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+                &graph,
+                &mut partials,
+                file,
+                stitcher_config,
+                &cancellation_flag,
+                |g, ps, p| {
+                    db.add_partial_path(g, ps, p.clone());
+                },
+            )?;
+        }
+
+        let starting_nodes = graph
+            .iter_nodes()
+            .filter(|n| graph[*n].is_reference())
+            .collect::<Vec<_>>();
+        let mut complete_paths_db = Database::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+            starting_nodes,
+            StitcherConfig::default().with_detect_similar_paths(true),
+            &cancellation_flag,
+            |g, ps, p| {
+                complete_paths_db.add_partial_path(g, ps, p.clone());
+            },
+        )?;
+        self.write_html(&graph, &mut partials, &mut complete_paths_db)
+    }
+
+    fn write_html(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+    ) -> anyhow::Result<()> {
+        let html = graph.to_html_string("stack-graph", partials, db, &NoFilter)?;
         if let Some(dir) = self.output.parent() {
             std::fs::create_dir_all(dir)?;
         }
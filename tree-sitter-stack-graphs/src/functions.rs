@@ -8,6 +8,7 @@
 //! Define tree-sitter-graph functions
 
 pub use path::add_path_functions;
+pub use string::add_string_functions;
 
 pub mod path {
     use std::path::Component;
@@ -66,10 +67,13 @@ pub mod path {
             _source: &str,
             parameters: &mut dyn Parameters,
         ) -> Result<Value, ExecutionError> {
-            let path = PathBuf::from(parameters.param()?.into_string()?);
+            let path = parameters.param()?;
             parameters.finish()?;
+            if let Value::Null = path {
+                return Ok(Value::Null);
+            }
 
-            let path = self.0(&path);
+            let path = self.0(&PathBuf::from(path.into_string()?));
             Ok(path
                 .map(|s| {
                     s.into_string()
@@ -89,12 +93,21 @@ pub mod path {
             _source: &str,
             parameters: &mut dyn Parameters,
         ) -> Result<Value, ExecutionError> {
-            let mut path = PathBuf::new();
+            let mut path = Some(PathBuf::new());
             while let Ok(component) = parameters.param() {
-                path = path.join(component.into_string()?);
+                // Keep consuming parameters after a #null, so that all of them are type checked.
+                path = match component {
+                    Value::Null => None,
+                    component => {
+                        let component = component.into_string()?;
+                        path.map(|path| path.join(component))
+                    }
+                };
             }
 
-            Ok(path.to_str().unwrap().into())
+            Ok(path
+                .map(|path| path.to_str().unwrap().into())
+                .unwrap_or(Value::Null))
         }
     }
 
@@ -107,10 +120,13 @@ pub mod path {
             _source: &str,
             parameters: &mut dyn Parameters,
         ) -> Result<Value, ExecutionError> {
-            let path = PathBuf::from(parameters.param()?.into_string()?);
+            let path = parameters.param()?;
             parameters.finish()?;
+            if let Value::Null = path {
+                return Ok(Value::Null);
+            }
 
-            let components = path
+            let components = PathBuf::from(path.into_string()?)
                 .components()
                 .map(|c| c.as_os_str().to_str().unwrap().into())
                 .collect::<Vec<_>>();
@@ -160,3 +176,121 @@ pub mod path {
         Some(ret)
     }
 }
+
+pub mod string {
+    use tree_sitter_graph::functions::Function;
+    use tree_sitter_graph::functions::Functions;
+    use tree_sitter_graph::functions::Parameters;
+    use tree_sitter_graph::graph::Graph;
+    use tree_sitter_graph::graph::Value;
+    use tree_sitter_graph::ExecutionError;
+
+    pub fn add_string_functions(functions: &mut Functions) {
+        functions.add("dedent".into(), string_fn(dedent));
+        functions.add("trim".into(), string_fn(|s| s.trim().to_string()));
+        functions.add("url-decode".into(), string_fn(url_decode));
+        functions.add("url-encode".into(), string_fn(url_encode));
+    }
+
+    pub fn string_fn<F>(f: F) -> impl Function
+    where
+        F: Fn(&str) -> String,
+    {
+        StringFn(f)
+    }
+
+    struct StringFn<F>(F)
+    where
+        F: Fn(&str) -> String;
+
+    impl<F> Function for StringFn<F>
+    where
+        F: Fn(&str) -> String,
+    {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let string = parameters.param()?.into_string()?;
+            parameters.finish()?;
+
+            Ok(self.0(&string).into())
+        }
+    }
+
+    /// Remove the leading whitespace that all lines have in common.  Lines consisting only of
+    /// whitespace are ignored when computing the common whitespace, and are emptied in the result.
+    pub fn dedent(text: &str) -> String {
+        fn indent(line: &str) -> &str {
+            &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+        }
+
+        let mut common: Option<&str> = None;
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            let indent = indent(line);
+            common = Some(match common {
+                None => indent,
+                Some(common) => {
+                    let len = common
+                        .bytes()
+                        .zip(indent.bytes())
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    &common[..len]
+                }
+            });
+        }
+        let common = common.unwrap_or("");
+
+        text.split('\n')
+            .map(|line| {
+                if line.trim().is_empty() {
+                    ""
+                } else {
+                    &line[common.len()..]
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Decode percent-encoded bytes in a URL component.  Malformed escapes are kept as they are,
+    /// and decoded bytes that are not valid UTF-8 are replaced by the replacement character.
+    pub fn url_decode(text: &str) -> String {
+        fn hex(byte: u8) -> Option<u8> {
+            (byte as char).to_digit(16).map(|d| d as u8)
+        }
+
+        let bytes = text.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'%' && i + 2 < bytes.len() {
+                if let (Some(hi), Some(lo)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                    decoded.push(hi << 4 | lo);
+                    i += 3;
+                    continue;
+                }
+            }
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+        String::from_utf8_lossy(&decoded).to_string()
+    }
+
+    /// Percent-encode all bytes of a URL component, except for the unreserved characters
+    /// `A-Z`, `a-z`, `0-9`, `-`, `.`, `_`, and `~`.
+    pub fn url_encode(text: &str) -> String {
+        let mut encoded = String::with_capacity(text.len());
+        for byte in text.bytes() {
+            if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+                encoded.push(byte as char);
+            } else {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        }
+        encoded
+    }
+}
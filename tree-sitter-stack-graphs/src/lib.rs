@@ -78,7 +78,9 @@
 //! - `push_scoped_symbol`: a _push scoped symbol_ node
 //! - `scope`: a _scope_ node
 //!
-//! A node without an explicit `type` attribute is assumed to be of type `scope`.
+//! A node without an explicit `type` attribute is assumed to be of type `scope`.  A node with any
+//! other `type` is an error, unless the [`Builder`][] is made lenient with
+//! [`Builder::on_unknown_node_type`][], in which case the node is loaded as a `scope` node.
 //!
 //! Certain node types — `pop_symbol`, `pop_scoped_symbol`, `push_symbol` and `push_scoped_symbol` —
 //! also require you to provide a `symbol` attribute.  Its value must be a string, but will typically
@@ -94,6 +96,23 @@
 //! }
 //! ```
 //!
+//! Empty symbols are accepted, and are treated like any other symbol: an empty pushed symbol can
+//! only be popped by a node with an empty symbol (or a wildcard).  Since empty symbols are almost
+//! always the result of a mistake in the stack graph rules, the [`Builder`][] can be configured to
+//! reject them with [`Builder::reject_empty_symbols`][].
+//!
+//! A `pop_symbol` node with the `is_wildcard` attribute is a _wildcard_, which pops any symbol.  This
+//! can be used to model glob imports, such as Python's `from foo import *`.  Wildcard nodes do not
+//! need a `symbol` attribute.  Note that a `pop_symbol` node with the symbol `"*"` is not a
+//! wildcard, and only pops the symbol `"*"`.
+//!
+//! ``` skip
+//! (import_from_statement (wildcard_import)) @import {
+//!   node @import.wildcard
+//!   attr (@import.wildcard) type = "pop_symbol", is_wildcard
+//! }
+//! ```
+//!
 //! Node types `pop_symbol` and `pop_scoped_symbol` allow an optional `is_definition` attribute, which
 //! marks that node as a proper definition.  Node types `push_symbol` and `push_scoped_symbol` allow
 //! an optional `is_reference` attribute, which marks the node as a proper reference.  When `is_definition`
@@ -122,6 +141,20 @@
 //! }
 //! ```
 //!
+//! The `scope` attribute can also refer to an exported scope node in another file of the stack graph,
+//! such as a node in the builtins.  In that case, its value must be a list containing the file name and
+//! the local ID of the node in that file.  The file must already exist in the stack graph:
+//!
+//! ``` skip
+//! (identifier) @id {
+//!   node new_push_scoped_symbol_node
+//!   attr (new_push_scoped_symbol_node)
+//!     type = "push_scoped_symbol",
+//!     symbol = (source-text @id),
+//!     scope = ["<builtins>", 1]
+//! }
+//! ```
+//!
 //! Nodes of type `scope` allow an optional `is_exported` attribute, that is required to use the scope
 //! in a `push_scoped_symbol` node.
 //!
@@ -173,6 +206,34 @@
 //! }
 //! ```
 //!
+//! ### Marking generated nodes
+//!
+//! Nodes that do not correspond to user source code, such as the definitions of builtins, can be
+//! marked by adding the `is_generated` attribute.  Consumers can use this to hide such nodes from
+//! user-facing results, for example by serializing graphs with a
+//! [`NoGeneratedFilter`][stack_graphs::serde::NoGeneratedFilter].
+//!
+//! ``` skip
+//! (module) @mod {
+//!   node builtin_def
+//!   attr (builtin_def) type = "pop_symbol", symbol = "print", is_definition, is_generated
+//! }
+//! ```
+//!
+//! ### Keeping essential paths
+//!
+//! When similar path detection is enabled, partial paths that are similar to others are dropped
+//! during path finding.  If this heuristic drops a path that is essential for your language, you
+//! can add the `keep_path` attribute to a node.  Paths that start at, end at, or pass through
+//! such a node are never dropped as similar paths.
+//!
+//! ``` skip
+//! (import_statement) @import {
+//!   node import
+//!   attr (import) type = "scope", keep_path
+//! }
+//! ```
+//!
 //! ### Annotating definitions with definiens information
 //!
 //! You cannot annotate definitions with a definiens, which is the thing the definition covers. For example, for
@@ -189,6 +250,43 @@
 //!
 //! Definiens are optional and setting them to `#null` explicitly is allowed.
 //!
+//! ### Annotating definitions with selection spans
+//!
+//! Language servers report both the full extent of a definition, such as a whole function, and
+//! the part of it that is selected when jumping to it, typically the definition's name.  If the
+//! `source_node` of a definition spans the whole definition, add a `selection_node` attribute,
+//! whose value is the syntax node of the name.  Both spans are available from
+//! [`StackGraph::definition_site`][stack_graphs::graph::StackGraph::definition_site].
+//!
+//! ``` skip
+//! (function_definition name: (identifier) @id) @func {
+//!   node def
+//!   attr (def) type = "pop_symbol", symbol = (source-text @id), source_node = @func, is_definition
+//!   attr (def) selection_node = @id
+//! }
+//! ```
+//!
+//! Selection nodes are optional and setting them to `#null` explicitly is allowed.
+//!
+//! ### Ranking definitions
+//!
+//! When a reference resolves to several definitions that do not shadow each other, you can prefer
+//! some of them by adding a `definition_rank` attribute to the definitions.  Only the definitions
+//! with the highest rank are kept.  For example, user definitions can be given a higher rank than
+//! builtins, so that they are preferred over builtins of the same name:
+//!
+//! ``` skip
+//! (function_definition name: (identifier) @id) @func {
+//!   node def
+//!   attr (def) type = "pop_symbol", symbol = (source-text @id), source_node = @func, is_definition
+//!   attr (def) definition_rank = 1
+//! }
+//! ```
+//!
+//! (If you don't specify a `definition_rank`, the default is 0.)  Builtins can instead be given a
+//! negative rank.  Because TSG has no negative integer literals, a negative rank is given as a
+//! string, e.g. `definition_rank = "-1"`.  Ranks must fit in a 32-bit signed integer.
+//!
 //! ### Connecting stack graph nodes with edges
 //!
 //! To connect two stack graph nodes, use the `edge` statement to add an edge between them:
@@ -217,6 +315,38 @@
 //!
 //! (If you don't specify a `precedence`, the default is 0.)
 //!
+//! Precedences are signed: a path is preferred over another if it takes an edge with a higher
+//! precedence where the other takes an edge with a lower one, so a negative precedence has a lower
+//! priority than the default.  Since TSG integer literals cannot be negative, a negative
+//! precedence is given as a string, e.g. `precedence = "-1"`.  Precedences must fit in a 32-bit
+//! signed integer, and can be limited to a smaller range with [`Builder::clamp_precedence`][].
+//!
+//! Instead of adding the same precedence to every edge that leads into a definition, you can add a
+//! `precedence` attribute to the `pop_symbol` or `pop_scoped_symbol` node itself.  Every edge into
+//! that node that doesn't have its own `precedence` attribute gets the node's precedence.  An
+//! explicit `precedence` on an edge always takes priority over the precedence of its sink node:
+//!
+//! ``` skip
+//! (function_definition name: (identifier) @id) @func {
+//!   node def
+//!   attr (def) type = "pop_symbol", symbol = (source-text @id), source_node = @func, is_definition
+//!   attr (def) precedence = 1
+//! }
+//! ```
+//!
+//! Edges can also be labeled with an `edge_kind`, a non-negative integer that is not interpreted by
+//! the stack graph, but can be read by custom resolution policies, for example to distinguish
+//! lexical edges from import edges:
+//!
+//! ``` skip
+//! (import_statement) @import {
+//!   edge @import.lexical_scope -> @import.imported_scope
+//!   attr (@import.lexical_scope -> @import.imported_scope) edge_kind = 1
+//! }
+//! ```
+//!
+//! (If you don't specify an `edge_kind`, the default is 0.)
+//!
 //! ### Referring to the singleton nodes
 //!
 //! The _root node_ and _jump to scope node_ are singleton nodes that always exist for all stack
@@ -262,6 +392,11 @@
 //! - `path-normalize`: normalize the argument path by eliminating `.` and `..` components where possible
 //! - `path-split`: split the argument path into a list of its components
 //!
+//! All path functions propagate `#null`: if any argument is `#null`, the result is `#null` instead
+//! of an error.  This means that a chain of path functions, such as
+//! `(path-filename (path-dir (path-dir FILE_PATH)))`, results in `#null` as soon as one of
+//! its intermediate results is `#null`, and only the final result needs to be checked.
+//!
 //! The following example computes a module name from a file path:
 //!
 //! ``` skip
@@ -293,6 +428,43 @@
 //! }
 //! ```
 //!
+//! ### Working with source text
+//!
+//! Text captured with [`source-text`][] often contains indentation or surrounding whitespace, for
+//! example when capturing docstrings or heredocs. Built-in string functions are available to clean
+//! up such text before it is used in symbols or debug info.
+//!
+//! The following string functions are available:
+//! - `dedent`: remove the leading whitespace that all non-blank lines of the argument string have in common
+//! - `trim`: remove leading and trailing whitespace from the argument string
+//! - `url-decode`: decode percent-encoded characters (e.g. `%20`) in the argument string
+//! - `url-encode`: percent-encode all characters of the argument string, except `A-Z`, `a-z`, `0-9`, `-`, `.`, `_`, and `~`
+//!
+//! The following example attaches the cleaned up text of a docstring to a definition:
+//!
+//! ``` skip
+//! (function_definition name:(_)@name body:(block . (expression_statement (string (string_content)@doc)))) {
+//!   ; ...
+//!   node def
+//!   attr (def) type = "pop_symbol", symbol = (source-text @name), is_definition, source_node = @name
+//!   attr (def) debug_doc = (trim (dedent (source-text @doc)))
+//!   ; ...
+//! }
+//! ```
+//!
+//! Any syntax node can be the `source_node` of a stack graph node, including comments, which
+//! tree-sitter grammars usually mark as extras. This allows references to be created for names
+//! mentioned in documentation comments, so that they can be navigated like references in code.
+//! The following example creates a reference for a `@see` tag in a comment:
+//!
+//! ``` skip
+//! (comment)@comment {
+//!   node ref
+//!   attr (ref) type = "push_symbol", symbol = (replace (source-text @comment) "^#\\s*@see\\s+" ""), is_reference, source_node = @comment
+//!   ; ...
+//! }
+//! ```
+//!
 //! ## Using this crate from Rust
 //!
 //! If you need very fine-grained control over how to use the resulting stack graphs, you can
@@ -334,6 +506,10 @@
 //! # Ok(())
 //! # }
 //! ```
+//!
+//! Diagnostics, such as warnings about unexpected node attributes, are reported through the
+//! [`log`][] facade, and are not written anywhere unless a logger is installed.  Use the logger
+//! and level filter of your choice to control whether and where they are written.
 
 use controlled_option::ControlledOption;
 use lsp_positions::SpanCalculator;
@@ -343,16 +519,20 @@ use stack_graphs::graph::File;
 use stack_graphs::graph::Node;
 use stack_graphs::graph::NodeID;
 use stack_graphs::graph::StackGraph;
+use stack_graphs::stats::PhaseTimings;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::mem::transmute;
 use std::ops::BitOr;
+use std::ops::RangeInclusive;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
 use std::time::Instant;
 use thiserror::Error;
@@ -363,6 +543,7 @@ use tree_sitter_graph::graph::Graph;
 use tree_sitter_graph::graph::GraphNode;
 use tree_sitter_graph::graph::GraphNodeRef;
 use tree_sitter_graph::graph::Value;
+use tree_sitter_graph::parse_error::Excerpt;
 use tree_sitter_graph::parse_error::ParseError;
 use tree_sitter_graph::parse_error::TreeWithParseErrorVec;
 use tree_sitter_graph::ExecutionConfig;
@@ -394,12 +575,17 @@ static SCOPE_TYPE: &'static str = "scope";
 // Node attribute names
 static DEBUG_ATTR_PREFIX: &'static str = "debug_";
 static DEFINIENS_NODE_ATTR: &'static str = "definiens_node";
+static DEFINITION_RANK_ATTR: &str = "definition_rank";
 static EMPTY_SOURCE_SPAN_ATTR: &'static str = "empty_source_span";
 static IS_DEFINITION_ATTR: &'static str = "is_definition";
 static IS_ENDPOINT_ATTR: &'static str = "is_endpoint";
 static IS_EXPORTED_ATTR: &'static str = "is_exported";
+static IS_GENERATED_ATTR: &str = "is_generated";
 static IS_REFERENCE_ATTR: &'static str = "is_reference";
+static IS_WILDCARD_ATTR: &str = "is_wildcard";
+static KEEP_PATH_ATTR: &str = "keep_path";
 static SCOPE_ATTR: &'static str = "scope";
+static SELECTION_NODE_ATTR: &str = "selection_node";
 static SOURCE_NODE_ATTR: &'static str = "source_node";
 static SYMBOL_ATTR: &'static str = "symbol";
 static SYNTAX_TYPE_ATTR: &'static str = "syntax_type";
@@ -412,6 +598,9 @@ static POP_SCOPED_SYMBOL_ATTRS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
         SYMBOL_ATTR,
         IS_DEFINITION_ATTR,
         DEFINIENS_NODE_ATTR,
+        DEFINITION_RANK_ATTR,
+        PRECEDENCE_ATTR,
+        SELECTION_NODE_ATTR,
         SYNTAX_TYPE_ATTR,
     ])
 });
@@ -420,7 +609,11 @@ static POP_SYMBOL_ATTRS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
         TYPE_ATTR,
         SYMBOL_ATTR,
         IS_DEFINITION_ATTR,
+        IS_WILDCARD_ATTR,
         DEFINIENS_NODE_ATTR,
+        DEFINITION_RANK_ATTR,
+        PRECEDENCE_ATTR,
+        SELECTION_NODE_ATTR,
         SYNTAX_TYPE_ATTR,
     ])
 });
@@ -431,8 +624,9 @@ static PUSH_SYMBOL_ATTRS: Lazy<HashSet<&'static str>> =
 static SCOPE_ATTRS: Lazy<HashSet<&'static str>> =
     Lazy::new(|| HashSet::from([TYPE_ATTR, IS_EXPORTED_ATTR, IS_ENDPOINT_ATTR]));
 
-// Edge attribute names
+// Edge attribute names (precedence is also allowed on pop nodes)
 static PRECEDENCE_ATTR: &'static str = "precedence";
+static EDGE_KIND_ATTR: &str = "edge_kind";
 
 // Global variables
 /// Name of the variable used to pass the root node.
@@ -452,7 +646,12 @@ pub struct StackGraphLanguage {
     tsg: tree_sitter_graph::ast::File,
     tsg_path: PathBuf,
     tsg_source: std::borrow::Cow<'static, str>,
+    /// The TSG source with includes that the rules were loaded from, which maps rows of the
+    /// expanded source to the files they came from
+    tsg_includes: Option<crate::loader::TsgSource>,
     functions: Functions,
+    /// Unexpected attributes that have been reported, as pairs of attribute name and node type
+    reported_attributes: Mutex<HashSet<(String, String)>>,
 }
 
 impl StackGraphLanguage {
@@ -468,7 +667,9 @@ impl StackGraphLanguage {
             tsg,
             tsg_path: PathBuf::from("<tsg>"),
             tsg_source: Cow::from(String::new()),
+            tsg_includes: None,
             functions: Self::default_functions(),
+            reported_attributes: Mutex::new(HashSet::new()),
         }
     }
 
@@ -479,13 +680,22 @@ impl StackGraphLanguage {
         language: tree_sitter::Language,
         tsg_source: &str,
     ) -> Result<StackGraphLanguage, LanguageError> {
+        // includes can only be resolved relative to a path, see [`crate::loader::TsgSource`][]
+        if let Some(row) = tsg_source
+            .lines()
+            .position(|line| crate::loader::INCLUDE_DIRECTIVE.is_match(line))
+        {
+            return Err(LanguageError::UnsupportedInclude(row + 1));
+        }
         let tsg = tree_sitter_graph::ast::File::from_str(language.clone(), tsg_source)?;
         Ok(StackGraphLanguage {
             language,
             tsg,
             tsg_path: PathBuf::from("<missing tsg path>"),
             tsg_source: Cow::from(tsg_source.to_string()),
+            tsg_includes: None,
             functions: Self::default_functions(),
+            reported_attributes: Mutex::new(HashSet::new()),
         })
     }
 
@@ -503,14 +713,54 @@ impl StackGraphLanguage {
         Ok(sgl)
     }
 
+    /// Like [`new`][Self::new], but accepts anything that converts into a
+    /// [`tree_sitter::Language`][], such as a grammar's `LANGUAGE` constant.
+    pub fn from_grammar(
+        grammar: impl Into<tree_sitter::Language>,
+        tsg: tree_sitter_graph::ast::File,
+    ) -> StackGraphLanguage {
+        Self::new(grammar.into(), tsg)
+    }
+
+    /// Like [`from_str`][Self::from_str], but accepts anything that converts into a
+    /// [`tree_sitter::Language`][], such as a grammar's `LANGUAGE` constant.
+    pub fn from_grammar_str(
+        grammar: impl Into<tree_sitter::Language>,
+        tsg_source: &str,
+    ) -> Result<StackGraphLanguage, LanguageError> {
+        Self::from_str(grammar.into(), tsg_source)
+    }
+
+    /// Like [`from_source`][Self::from_source], but accepts anything that converts into a
+    /// [`tree_sitter::Language`][], such as a grammar's `LANGUAGE` constant.
+    pub fn from_grammar_source(
+        grammar: impl Into<tree_sitter::Language>,
+        tsg_path: PathBuf,
+        tsg_source: &str,
+    ) -> Result<StackGraphLanguage, LanguageError> {
+        Self::from_source(grammar.into(), tsg_path, tsg_source)
+    }
+
     pub fn set_tsg_info(&mut self, path: PathBuf, source: Cow<'static, str>) {
         self.tsg_path = path;
         self.tsg_source = source;
+        self.tsg_includes = None;
+    }
+
+    /// Sets the TSG source that the rules were parsed from, when it may contain includes. The
+    /// path of the main file and the expanded source are used as the TSG path and source, and
+    /// [`BuildError::display_pretty_for_language`][] reports locations in the rules relative to
+    /// the file they occur in.
+    pub fn set_tsg_source(&mut self, tsg: crate::loader::TsgSource) {
+        self.tsg_path = tsg.path().to_path_buf();
+        self.tsg_source = Cow::from(tsg.source().to_string());
+        self.tsg_includes = Some(tsg);
     }
 
     fn default_functions() -> tree_sitter_graph::functions::Functions {
         let mut functions = tree_sitter_graph::functions::Functions::stdlib();
         crate::functions::add_path_functions(&mut functions);
+        crate::functions::add_string_functions(&mut functions);
         functions
     }
 
@@ -533,6 +783,11 @@ impl StackGraphLanguage {
     pub fn tsg_source(&self) -> &Cow<'static, str> {
         &self.tsg_source
     }
+
+    /// Returns the TSG source with includes, if it was set with [`set_tsg_source`][].
+    pub fn tsg_includes(&self) -> Option<&crate::loader::TsgSource> {
+        self.tsg_includes.as_ref()
+    }
 }
 
 /// An error that can occur while loading in the TSG stack graph construction rules for a language
@@ -540,6 +795,8 @@ impl StackGraphLanguage {
 pub enum LanguageError {
     #[error(transparent)]
     ParseError(#[from] tree_sitter_graph::ParseError),
+    #[error("Unsupported TSG include at line {0}, includes are only expanded when loading TSG files from paths")]
+    UnsupportedInclude(usize),
 }
 
 impl LanguageError {
@@ -548,8 +805,34 @@ impl LanguageError {
         path: &'a Path,
         source: &'a str,
     ) -> impl std::fmt::Display + 'a {
-        match self {
-            Self::ParseError(err) => err.display_pretty(path, source),
+        DisplayLanguageErrorPretty {
+            error: self,
+            path,
+            source,
+        }
+    }
+}
+
+struct DisplayLanguageErrorPretty<'a> {
+    error: &'a LanguageError,
+    path: &'a Path,
+    source: &'a str,
+}
+
+impl std::fmt::Display for DisplayLanguageErrorPretty<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.error {
+            LanguageError::ParseError(err) => {
+                write!(f, "{}", err.display_pretty(self.path, self.source))
+            }
+            LanguageError::UnsupportedInclude(line) => {
+                writeln!(f, "{}", self.error)?;
+                write!(
+                    f,
+                    "{}",
+                    Excerpt::from_source(self.path, self.source, line - 1, 0..1, 0)
+                )
+            }
         }
     }
 }
@@ -594,8 +877,23 @@ pub struct Builder<'a> {
     remapped_nodes: HashMap<usize, NodeID>,
     injected_node_count: usize,
     span_calculator: SpanCalculator<'a>,
+    on_node_created: Option<NodeCreatedCallback<'a>>,
+    on_unpopped_symbol: Option<UnpoppedSymbolCallback<'a>>,
+    on_unknown_node_type: Option<UnknownNodeTypeCallback<'a>>,
+    record_tsg_node_index: bool,
+    reject_empty_symbols: bool,
+    deduplicate_edges: bool,
+    precedence_range: Option<RangeInclusive<i32>>,
+    file_path: Option<String>,
+    old_tree: Option<&'a tree_sitter::Tree>,
+    tree: Option<&'a mut Option<tree_sitter::Tree>>,
+    timings: Option<&'a mut PhaseTimings>,
 }
 
+type NodeCreatedCallback<'a> = Box<dyn FnMut(Handle<Node>, GraphNodeRef) + 'a>;
+pub type UnpoppedSymbolCallback<'a> = Box<dyn FnMut(&StackGraph, Handle<Node>) + 'a>;
+pub type UnknownNodeTypeCallback<'a> = Box<dyn FnMut(&StackGraph, Handle<Node>, &str) + 'a>;
+
 impl<'a> Builder<'a> {
     fn new(
         sgl: &'a StackGraphLanguage,
@@ -613,15 +911,117 @@ impl<'a> Builder<'a> {
             remapped_nodes: HashMap::new(),
             injected_node_count: 0,
             span_calculator,
+            on_node_created: None,
+            on_unpopped_symbol: None,
+            on_unknown_node_type: None,
+            record_tsg_node_index: false,
+            reject_empty_symbols: false,
+            deduplicate_edges: false,
+            precedence_range: None,
+            file_path: None,
+            old_tree: None,
+            tree: None,
+            timings: None,
         }
     }
 
+    /// Sets a callback that is invoked for every stack graph node created while loading the
+    /// executed graph. The callback receives the handle of the new stack graph node, and a
+    /// reference to the TSG graph node it was created from.
+    pub fn on_node_created(&mut self, callback: Box<dyn FnMut(Handle<Node>, GraphNodeRef) + 'a>) {
+        self.on_node_created = Some(callback);
+    }
+
+    /// Sets a callback that enables a diagnostic pass after the stack graph has been built.  The
+    /// callback is invoked for every push symbol node in the file whose symbol is not popped by
+    /// any pop symbol node in the same file.  This is a heuristic: such references may still
+    /// resolve to definitions in other files or in builtins, but if they are reported for a file
+    /// that defines the symbol, it is likely that a TSG rule is missing or wrong.
+    pub fn on_unpopped_symbol(&mut self, callback: UnpoppedSymbolCallback<'a>) {
+        self.on_unpopped_symbol = Some(callback);
+    }
+
+    /// Sets a callback that makes the builder lenient about unknown node types.  By default, a TSG
+    /// node with an unknown `type` attribute fails the build with
+    /// [`BuildError::UnknownNodeType`][].  When this callback is set, such nodes are loaded as scope
+    /// nodes instead, and the callback is invoked with the new scope node and the unknown type, so
+    /// that the rest of the file still builds.
+    pub fn on_unknown_node_type(&mut self, callback: UnknownNodeTypeCallback<'a>) {
+        self.on_unknown_node_type = Some(callback);
+    }
+
+    /// Sets whether the index of the TSG graph node that a stack graph node was created from is
+    /// recorded in the node's debug info, under the `tsg_node_index` key.  Stack graph nodes do not
+    /// necessarily get the TSG node index as their local ID, so this helps to correlate the two
+    /// graphs when debugging.
+    pub fn record_tsg_node_index(&mut self, record: bool) {
+        self.record_tsg_node_index = record;
+    }
+
+    /// Sets whether push and pop nodes with an empty symbol are rejected with a
+    /// [`BuildError::EmptySymbol`][] error.  Empty symbols are allowed by default.
+    pub fn reject_empty_symbols(&mut self, reject: bool) {
+        self.reject_empty_symbols = reject;
+    }
+
+    /// Sets whether an edge that is added more than once keeps the highest precedence.  Duplicate
+    /// edges can occur when several TSG nodes refer to the same stack graph node, for example
+    /// through [injected nodes][Self::inject_node], or when the stack graph already contains the
+    /// edge.  An edge is never stored twice, but by default the existing edge keeps its
+    /// precedence, and the precedence of the duplicate is ignored.
+    pub fn deduplicate_edges(&mut self, deduplicate: bool) {
+        self.deduplicate_edges = deduplicate;
+    }
+
+    /// Sets the range that edge precedences are clamped to.  Precedences outside of the range are
+    /// replaced by the nearest bound of the range.  By default, precedences are not clamped.
+    pub fn clamp_precedence(&mut self, range: RangeInclusive<i32>) {
+        self.precedence_range = Some(range);
+    }
+
+    /// Sets the value of the `FILE_PATH` global variable, which the path functions use to compute
+    /// module names and the like.  This allows the logical path of a file to differ from the name
+    /// of the file in the stack graph, for example for files in a virtual file system.  The value
+    /// takes precedence over a `FILE_PATH` in the globals passed to [`build`][Self::build].  By
+    /// default, the globals' `FILE_PATH` is used, or the file name if the globals do not set it.
+    pub fn set_file_path(&mut self, file_path: String) {
+        self.file_path = Some(file_path);
+    }
+
+    /// Sets the syntax tree of a previous version of the source, which the parser reuses to parse
+    /// the source incrementally.  The tree must have been edited with [`Tree::edit`][] to describe
+    /// the changes from the previous version of the source to the current one.
+    ///
+    /// Only parsing is incremental.  The TSG rules are executed for the whole file, and all of the
+    /// file's nodes and edges are created again.  Because files cannot be removed from a stack
+    /// graph, the rebuilt file should be built into a fresh stack graph.
+    ///
+    /// [`Tree::edit`]: tree_sitter::Tree::edit
+    pub fn set_old_tree(&mut self, old_tree: &'a tree_sitter::Tree) {
+        self.old_tree = Some(old_tree);
+    }
+
+    /// Sets a location that the syntax tree of the source is stored in after parsing, so that it
+    /// can be edited and passed to [`set_old_tree`][Self::set_old_tree] when the source changes.
+    /// The tree is stored even if it contains parse errors.
+    pub fn save_tree(&mut self, tree: &'a mut Option<tree_sitter::Tree>) {
+        self.tree = Some(tree);
+    }
+
+    /// Sets the phase timings that the time spent parsing the source and executing the TSG rules
+    /// is added to.  Execution includes loading the executed graph into the stack graph.
+    pub fn collect_timings(&mut self, timings: &'a mut PhaseTimings) {
+        self.timings = Some(timings);
+    }
+
     /// Executes this builder.
     pub fn build(
         mut self,
         globals: &'a Variables<'a>,
         cancellation_flag: &dyn CancellationFlag,
     ) -> Result<(), BuildError> {
+        let timings = self.timings.take();
+        let parsing_start = Instant::now();
         let tree = {
             let mut parser = Parser::new();
             parser.set_language(&self.sgl.language)?;
@@ -632,14 +1032,19 @@ impl<'a> Builder<'a> {
             // it is put into its own block here, instead of extending to the end of the method.
             unsafe { parser.set_cancellation_flag(Some(ts_cancellation_flag.as_ref())) };
             parser
-                .parse(self.source, None)
+                .parse(self.source, self.old_tree)
                 .ok_or(BuildError::ParseError)?
         };
+        if let Some(saved_tree) = self.tree.take() {
+            *saved_tree = Some(tree.clone());
+        }
         let parse_errors = ParseError::into_all(tree);
         if parse_errors.errors().len() > 0 {
             return Err(BuildError::ParseErrors(parse_errors));
         }
         let tree = parse_errors.into_tree();
+        let parsing = parsing_start.elapsed();
+        let execution_start = Instant::now();
 
         let mut globals = Variables::nested(globals);
 
@@ -653,7 +1058,11 @@ impl<'a> Builder<'a> {
             .add(JUMP_TO_SCOPE_NODE_VAR.into(), jump_to_scope_node.into())
             .expect("Failed to set JUMP_TO_SCOPE_NODE");
 
-        if globals.get(&FILE_PATH_VAR.into()).is_none() {
+        if let Some(file_path) = self.file_path.take() {
+            globals
+                .add(FILE_PATH_VAR.into(), file_path.into())
+                .expect("Failed to set FILE_PATH");
+        } else if globals.get(&FILE_PATH_VAR.into()).is_none() {
             let file_name = self.stack_graph[self.file].to_string();
             globals
                 .add(FILE_PATH_VAR.into(), file_name.into())
@@ -689,7 +1098,12 @@ impl<'a> Builder<'a> {
             &(cancellation_flag as &dyn CancellationFlag),
         )?;
 
-        self.load(cancellation_flag)
+        self.load(cancellation_flag)?;
+        if let Some(timings) = timings {
+            timings.parsing += parsing;
+            timings.execution += execution_start.elapsed();
+        }
+        Ok(())
     }
 
     /// Create a graph node to represent the stack graph node. It is the callers responsibility to
@@ -814,8 +1228,12 @@ pub enum BuildError {
     MissingNodeType(GraphNodeRef),
     #[error("Missing ‘symbol’ attribute on graph node")]
     MissingSymbol(GraphNodeRef),
+    #[error("Empty ‘symbol’ attribute on graph node")]
+    EmptySymbol(GraphNodeRef),
     #[error("Missing ‘scope’ attribute on graph node")]
     MissingScope(GraphNodeRef),
+    #[error("Missing global variable ‘{0}’, which must be provided when building the stack graph")]
+    MissingGlobal(String),
     #[error("Unknown ‘{0}’ flag type {1}")]
     UnknownFlagType(String, String),
     #[error("Unknown node type {0}")]
@@ -834,6 +1252,16 @@ pub enum BuildError {
     LanguageError(#[from] tree_sitter::LanguageError),
     #[error("Expected exported symbol scope in {0}, got {1}")]
     SymbolScopeError(String, String),
+    #[error("Invalid node ID {0}, expected a list of a file name and a local ID")]
+    InvalidNodeID(String),
+    #[error("Invalid precedence {0}, expected a 32-bit signed integer")]
+    InvalidPrecedence(String),
+    #[error("Invalid definition rank {0}, expected a 32-bit signed integer")]
+    InvalidDefinitionRank(String),
+    #[error("Unknown file ‘{0}’")]
+    UnknownFile(String),
+    #[error("Unknown symbol scope {1} in {0}")]
+    UnknownSymbolScope(String, String),
 }
 
 impl From<stack_graphs::CancellationError> for BuildError {
@@ -846,6 +1274,9 @@ impl From<tree_sitter_graph::ExecutionError> for BuildError {
     fn from(value: tree_sitter_graph::ExecutionError) -> Self {
         match value {
             tree_sitter_graph::ExecutionError::Cancelled(err) => Self::Cancelled(err.0),
+            tree_sitter_graph::ExecutionError::MissingGlobalVariable(name) => {
+                Self::MissingGlobal(name)
+            }
             err => Self::ExecutionError(err),
         }
     }
@@ -858,6 +1289,35 @@ impl BuildError {
         source: &'a str,
         tsg_path: &'a Path,
         tsg: &'a str,
+    ) -> impl std::fmt::Display + 'a {
+        self.display_pretty_with_includes(source_path, source, tsg_path, tsg, None)
+    }
+
+    /// Like [`display_pretty`][Self::display_pretty], but uses the TSG path and source of the
+    /// given language. If the language was loaded from TSG files with includes, locations in the
+    /// rules are reported relative to the file they occur in.
+    pub fn display_pretty_for_language<'a>(
+        &'a self,
+        source_path: &'a Path,
+        source: &'a str,
+        sgl: &'a StackGraphLanguage,
+    ) -> impl std::fmt::Display + 'a {
+        self.display_pretty_with_includes(
+            source_path,
+            source,
+            sgl.tsg_path(),
+            sgl.tsg_source(),
+            sgl.tsg_includes(),
+        )
+    }
+
+    pub(crate) fn display_pretty_with_includes<'a>(
+        &'a self,
+        source_path: &'a Path,
+        source: &'a str,
+        tsg_path: &'a Path,
+        tsg: &'a str,
+        tsg_includes: Option<&'a crate::loader::TsgSource>,
     ) -> impl std::fmt::Display + 'a {
         DisplayBuildErrorPretty {
             error: self,
@@ -865,6 +1325,7 @@ impl BuildError {
             source,
             tsg_path,
             tsg,
+            tsg_includes,
         }
     }
 }
@@ -875,16 +1336,24 @@ struct DisplayBuildErrorPretty<'a> {
     source: &'a str,
     tsg_path: &'a Path,
     tsg: &'a str,
+    tsg_includes: Option<&'a crate::loader::TsgSource>,
 }
 
 impl std::fmt::Display for DisplayBuildErrorPretty<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.error {
-            BuildError::ExecutionError(err) => write!(
-                f,
-                "{}",
-                err.display_pretty(self.source_path, self.source, self.tsg_path, self.tsg)
-            ),
+            BuildError::ExecutionError(err) => match self.tsg_includes {
+                Some(tsg_includes) => write!(
+                    f,
+                    "{}",
+                    tsg_includes.display_execution_error(err, self.source_path, self.source)
+                ),
+                None => write!(
+                    f,
+                    "{}",
+                    err.display_pretty(self.source_path, self.source, self.tsg_path, self.tsg)
+                ),
+            },
             BuildError::ParseErrors(parse_errors) => write!(
                 f,
                 "{}",
@@ -929,7 +1398,16 @@ impl<'a> Builder<'a> {
         // DSL nodes that we create are the proxies for the injected stack graph nodes.)
         for node_ref in self.graph.iter_nodes().skip(self.injected_node_count) {
             cancellation_flag.check("loading graph nodes")?;
-            let node_type = self.get_node_type(node_ref)?;
+            let mut unknown_node_type = None;
+            let node_type = match self.get_node_type(node_ref) {
+                Err(BuildError::UnknownNodeType(node_type))
+                    if self.on_unknown_node_type.is_some() =>
+                {
+                    unknown_node_type = Some(node_type);
+                    NodeType::Scope
+                }
+                node_type => node_type?,
+            };
             let handle = match node_type {
                 NodeType::DropScopes => self.load_drop_scopes(node_ref),
                 NodeType::PopScopedSymbol => self.load_pop_scoped_symbol(node_ref)?,
@@ -938,8 +1416,27 @@ impl<'a> Builder<'a> {
                 NodeType::PushSymbol => self.load_push_symbol(node_ref)?,
                 NodeType::Scope => self.load_scope(node_ref)?,
             };
+            if let Some(node_type) = unknown_node_type {
+                if let Some(on_unknown_node_type) = &mut self.on_unknown_node_type {
+                    on_unknown_node_type(self.stack_graph, handle, &node_type);
+                }
+            }
             self.load_source_info(node_ref, handle)?;
+            if self.load_flag(&self.graph[node_ref], IS_GENERATED_ATTR)? {
+                self.stack_graph.set_generated(handle, true);
+            }
+            if self.load_flag(&self.graph[node_ref], KEEP_PATH_ATTR)? {
+                self.stack_graph.set_keeps_paths(handle, true);
+            }
             self.load_node_debug_info(node_ref, handle)?;
+            if self.record_tsg_node_index {
+                let key = self.stack_graph.add_string("tsg_node_index");
+                let value = self.stack_graph.add_string(&node_ref.index().to_string());
+                self.stack_graph.node_debug_info_mut(handle).add(key, value);
+            }
+            if let Some(on_node_created) = &mut self.on_node_created {
+                on_node_created(handle, node_ref);
+            }
         }
 
         for node in self.stack_graph.nodes_for_file(self.file) {
@@ -956,14 +1453,28 @@ impl<'a> Builder<'a> {
             let source_handle = self.stack_graph.node_for_id(source_node_id).unwrap();
             for (sink_ref, edge) in source.iter_edges() {
                 cancellation_flag.check("loading graph edges")?;
-                let precedence = match edge.attributes.get(PRECEDENCE_ATTR) {
-                    Some(precedence) => precedence.as_integer()? as i32,
+                // An explicit edge precedence wins over the precedence of the sink node.
+                let precedence = match edge
+                    .attributes
+                    .get(PRECEDENCE_ATTR)
+                    .or_else(|| self.graph[sink_ref].attributes.get(PRECEDENCE_ATTR))
+                {
+                    Some(precedence) => self.load_precedence(precedence)?,
                     None => 0,
                 };
                 let sink_node_id = self.node_id_for_graph_node(sink_ref);
                 let sink_handle = self.stack_graph.node_for_id(sink_node_id).unwrap();
-                self.stack_graph
-                    .add_edge(source_handle, sink_handle, precedence);
+                if self.deduplicate_edges {
+                    self.stack_graph
+                        .add_or_merge_edge(source_handle, sink_handle, precedence);
+                } else {
+                    self.stack_graph
+                        .add_edge(source_handle, sink_handle, precedence);
+                }
+                if let Some(kind) = edge.attributes.get(EDGE_KIND_ATTR) {
+                    self.stack_graph
+                        .set_edge_kind(source_handle, sink_handle, kind.as_integer()?);
+                }
                 Self::load_edge_debug_info(
                     &mut self.stack_graph,
                     source_handle,
@@ -973,6 +1484,23 @@ impl<'a> Builder<'a> {
             }
         }
 
+        if let Some(on_unpopped_symbol) = &mut self.on_unpopped_symbol {
+            let mut popped_symbols = HashSet::new();
+            for node in self.stack_graph.nodes_for_file(self.file) {
+                if let Node::PopSymbol(_) | Node::PopScopedSymbol(_) = &self.stack_graph[node] {
+                    popped_symbols.extend(self.stack_graph[node].symbol());
+                }
+            }
+            for node in self.stack_graph.nodes_for_file(self.file) {
+                if let Node::PushSymbol(_) | Node::PushScopedSymbol(_) = &self.stack_graph[node] {
+                    let symbol = self.stack_graph[node].symbol().unwrap();
+                    if !popped_symbols.contains(&symbol) {
+                        on_unpopped_symbol(self.stack_graph, node);
+                    }
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -1001,7 +1529,15 @@ impl<'a> Builder<'a> {
 
     fn verify_node(&self, node: Handle<Node>) -> Result<(), BuildError> {
         if let Node::PushScopedSymbol(node) = &self.stack_graph[node] {
-            let scope = &self.stack_graph[self.stack_graph.node_for_id(node.scope).unwrap()];
+            let scope = match self.stack_graph.node_for_id(node.scope) {
+                Some(scope) => &self.stack_graph[scope],
+                None => {
+                    return Err(BuildError::UnknownSymbolScope(
+                        format!("{}", node.display(self.stack_graph)),
+                        format!("{}", node.scope.display(self.stack_graph)),
+                    ))
+                }
+            };
             if !scope.is_exported_scope() {
                 return Err(BuildError::SymbolScopeError(
                     format!("{}", node.display(self.stack_graph)),
@@ -1050,7 +1586,7 @@ impl<'a> Builder<'a> {
     ) -> Result<Handle<Node>, BuildError> {
         let node = &self.graph[node_ref];
         let symbol = match node.attributes.get(SYMBOL_ATTR) {
-            Some(symbol) => self.load_symbol(symbol)?,
+            Some(symbol) => self.load_symbol(node_ref, symbol)?,
             None => return Err(BuildError::MissingSymbol(node_ref)),
         };
         let symbol = self.stack_graph.add_symbol(&symbol);
@@ -1063,17 +1599,23 @@ impl<'a> Builder<'a> {
             .unwrap();
         if is_definition {
             self.load_definiens_info(node_ref, node_handle)?;
+            self.load_selection_span(node_ref, node_handle)?;
+            self.load_definition_rank(node_ref, node_handle)?;
         }
         Ok(node_handle)
     }
 
     fn load_pop_symbol(&mut self, node_ref: GraphNodeRef) -> Result<Handle<Node>, BuildError> {
         let node = &self.graph[node_ref];
-        let symbol = match node.attributes.get(SYMBOL_ATTR) {
-            Some(symbol) => self.load_symbol(symbol)?,
-            None => return Err(BuildError::MissingSymbol(node_ref)),
+        let symbol = if self.load_flag(node, IS_WILDCARD_ATTR)? {
+            self.stack_graph.add_wildcard_symbol()
+        } else {
+            let symbol = match node.attributes.get(SYMBOL_ATTR) {
+                Some(symbol) => self.load_symbol(node_ref, symbol)?,
+                None => return Err(BuildError::MissingSymbol(node_ref)),
+            };
+            self.stack_graph.add_symbol(&symbol)
         };
-        let symbol = self.stack_graph.add_symbol(&symbol);
         let id = self.node_id_for_graph_node(node_ref);
         let is_definition = self.load_flag(node, IS_DEFINITION_ATTR)?;
         self.verify_attributes(node, POP_SYMBOL_TYPE, &POP_SYMBOL_ATTRS);
@@ -1083,6 +1625,8 @@ impl<'a> Builder<'a> {
             .unwrap();
         if is_definition {
             self.load_definiens_info(node_ref, node_handle)?;
+            self.load_selection_span(node_ref, node_handle)?;
+            self.load_definition_rank(node_ref, node_handle)?;
         }
         Ok(node_handle)
     }
@@ -1093,12 +1637,13 @@ impl<'a> Builder<'a> {
     ) -> Result<Handle<Node>, BuildError> {
         let node = &self.graph[node_ref];
         let symbol = match node.attributes.get(SYMBOL_ATTR) {
-            Some(symbol) => self.load_symbol(symbol)?,
+            Some(symbol) => self.load_symbol(node_ref, symbol)?,
             None => return Err(BuildError::MissingSymbol(node_ref)),
         };
         let symbol = self.stack_graph.add_symbol(&symbol);
         let id = self.node_id_for_graph_node(node_ref);
         let scope = match node.attributes.get(SCOPE_ATTR) {
+            Some(Value::List(node_id)) => self.load_node_id(node_id)?,
             Some(scope) => self.node_id_for_graph_node(scope.as_graph_node_ref()?),
             None => return Err(BuildError::MissingScope(node_ref)),
         };
@@ -1113,7 +1658,7 @@ impl<'a> Builder<'a> {
     fn load_push_symbol(&mut self, node_ref: GraphNodeRef) -> Result<Handle<Node>, BuildError> {
         let node = &self.graph[node_ref];
         let symbol = match node.attributes.get(SYMBOL_ATTR) {
-            Some(symbol) => self.load_symbol(symbol)?,
+            Some(symbol) => self.load_symbol(node_ref, symbol)?,
             None => return Err(BuildError::MissingSymbol(node_ref)),
         };
         let symbol = self.stack_graph.add_symbol(&symbol);
@@ -1135,12 +1680,50 @@ impl<'a> Builder<'a> {
         Ok(self.stack_graph.add_scope_node(id, is_exported).unwrap())
     }
 
-    fn load_symbol(&self, value: &Value) -> Result<String, BuildError> {
-        match value {
-            Value::Integer(i) => Ok(i.to_string()),
-            Value::String(s) => Ok(s.clone()),
-            _ => Err(BuildError::UnknownSymbolType(format!("{}", value))),
+    /// Loads an edge precedence, given as an integer or as a string containing a possibly negative
+    /// integer, and clamps it to the configured range.
+    fn load_precedence(&self, value: &Value) -> Result<i32, BuildError> {
+        let precedence = match value {
+            Value::Integer(precedence) => i32::try_from(*precedence).ok(),
+            Value::String(precedence) => precedence.parse::<i32>().ok(),
+            _ => None,
         }
+        .ok_or_else(|| BuildError::InvalidPrecedence(format!("{}", value)))?;
+        Ok(match &self.precedence_range {
+            Some(range) => precedence.clamp(*range.start(), *range.end()),
+            None => precedence,
+        })
+    }
+
+    /// Loads an explicit NodeID, given as a list of a file name and a local ID. The file must exist
+    /// in the stack graph, but the node itself is only verified after all nodes are loaded.
+    fn load_node_id(&self, value: &[Value]) -> Result<NodeID, BuildError> {
+        let (file, local_id) = match value {
+            [Value::String(file), Value::Integer(local_id)] => (file, *local_id),
+            _ => {
+                return Err(BuildError::InvalidNodeID(format!(
+                    "{}",
+                    Value::List(value.to_vec())
+                )))
+            }
+        };
+        let file = self
+            .stack_graph
+            .get_file(file)
+            .ok_or_else(|| BuildError::UnknownFile(file.clone()))?;
+        Ok(NodeID::new_in_file(file, local_id))
+    }
+
+    fn load_symbol(&self, node_ref: GraphNodeRef, value: &Value) -> Result<String, BuildError> {
+        let symbol = match value {
+            Value::Integer(i) => i.to_string(),
+            Value::String(s) => s.clone(),
+            _ => return Err(BuildError::UnknownSymbolType(format!("{}", value))),
+        };
+        if self.reject_empty_symbols && symbol.is_empty() {
+            return Err(BuildError::EmptySymbol(node_ref));
+        }
+        Ok(symbol)
     }
 
     fn load_flag(&self, node: &GraphNode, attribute: &str) -> Result<bool, BuildError> {
@@ -1202,6 +1785,42 @@ impl<'a> Builder<'a> {
         Ok(())
     }
 
+    fn load_selection_span(
+        &mut self,
+        node_ref: GraphNodeRef,
+        node_handle: Handle<Node>,
+    ) -> Result<(), BuildError> {
+        let node = &self.graph[node_ref];
+        let selection_node = match node.attributes.get(SELECTION_NODE_ATTR) {
+            Some(Value::Null) => return Ok(()),
+            Some(selection_node) => &self.graph[selection_node.as_syntax_node_ref()?],
+            None => return Ok(()),
+        };
+        let selection_span = self.span_calculator.for_node(selection_node);
+        self.stack_graph
+            .set_selection_span(node_handle, selection_span);
+        Ok(())
+    }
+
+    fn load_definition_rank(
+        &mut self,
+        node_ref: GraphNodeRef,
+        node_handle: Handle<Node>,
+    ) -> Result<(), BuildError> {
+        let node = &self.graph[node_ref];
+        if let Some(value) = node.attributes.get(DEFINITION_RANK_ATTR) {
+            // Like precedences, ranks can be given as strings to allow negative values.
+            let rank = match value {
+                Value::Integer(rank) => i32::try_from(*rank).ok(),
+                Value::String(rank) => rank.parse::<i32>().ok(),
+                _ => None,
+            }
+            .ok_or_else(|| BuildError::InvalidDefinitionRank(format!("{}", value)))?;
+            self.stack_graph.set_definition_rank(node_handle, rank);
+        }
+        Ok(())
+    }
+
     fn load_node_debug_info(
         &mut self,
         node_ref: GraphNodeRef,
@@ -1261,9 +1880,22 @@ impl<'a> Builder<'a> {
             if !allowed_attributes.contains(id)
                 && id != SOURCE_NODE_ATTR
                 && id != EMPTY_SOURCE_SPAN_ATTR
+                && id != IS_GENERATED_ATTR
+                && id != KEEP_PATH_ATTR
                 && !id.starts_with(DEBUG_ATTR_PREFIX)
             {
-                eprintln!("Unexpected attribute {} on node of type {}", id, node_type);
+                // report every attribute once per node type, to avoid flooding the log when the
+                // rules are applied to many files
+                if log::log_enabled!(log::Level::Warn)
+                    && self
+                        .sgl
+                        .reported_attributes
+                        .lock()
+                        .unwrap()
+                        .insert((id.to_string(), node_type.to_string()))
+                {
+                    log::warn!("Unexpected attribute {} on node of type {}", id, node_type);
+                }
             }
         }
     }
@@ -10,24 +10,42 @@
 use ini::Ini;
 use itertools::Itertools;
 use once_cell::sync::Lazy;
+use once_cell::sync::OnceCell;
 use regex::Regex;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::File;
 use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPath;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::Database;
+use stack_graphs::stitching::DatabaseCandidates;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::StitcherConfig;
 use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::ffi::OsStr;
+use std::hash::Hash;
+use std::hash::Hasher;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::Mutex;
 use thiserror::Error;
 use tree_sitter::Language;
 use tree_sitter_graph::ast::File as TsgFile;
+use tree_sitter_graph::parse_error::Excerpt;
+use tree_sitter_graph::Location;
 use tree_sitter_graph::Variables;
 use tree_sitter_loader::Config as TsConfig;
 use tree_sitter_loader::LanguageConfiguration as TSLanguageConfiguration;
 use tree_sitter_loader::Loader as TsLoader;
 
+use crate::CancellationError;
 use crate::CancellationFlag;
 use crate::FileAnalyzer;
+use crate::LanguageError;
 use crate::StackGraphLanguage;
 use crate::FILE_PATH_VAR;
 
@@ -52,6 +70,11 @@ pub struct LanguageConfiguration {
     /// incorrectly set to true, performance of path finding suffers from exponential
     /// blow up.
     pub no_similar_paths_in_file: bool,
+    /// The partial paths of the builtins, which are computed once and reused by
+    /// [`resolve_references_in_file`][Self::resolve_references_in_file].  Use
+    /// `Default::default()` when constructing a configuration, and reset it when changing the
+    /// builtins afterwards.
+    pub builtins_partial_paths: BuiltinsPartialPaths,
 }
 
 impl LanguageConfiguration {
@@ -100,7 +123,9 @@ impl LanguageConfiguration {
                 source: Cow::from(builtins_source),
                 tsg_path: sgl.tsg_path.clone(),
                 tsg: Cow::from(tsg_source),
+                tsg_includes: None,
             })?;
+            Loader::mark_builtins_generated(&mut builtins, file);
         }
         Ok(LanguageConfiguration {
             language,
@@ -111,9 +136,84 @@ impl LanguageConfiguration {
             builtins,
             special_files: FileAnalyzers::new(),
             no_similar_paths_in_file: false,
+            builtins_partial_paths: BuiltinsPartialPaths::default(),
         })
     }
 
+    /// Resolves all references in a file of the given graph, after making sure that the builtins
+    /// of this language are present in the graph.  Builtins files that the graph already contains,
+    /// for example because of an earlier call, are not added again, and are assumed to contain
+    /// the builtins of this language.  References are resolved using the partial paths of every
+    /// file in the graph, where the partial paths of the builtins are cached in
+    /// [`builtins_partial_paths`][Self::builtins_partial_paths], but those of the other files are
+    /// computed on every call.  Returns the complete paths from the references in the file to
+    /// their definitions.
+    pub fn resolve_references_in_file(
+        &self,
+        graph: &mut StackGraph,
+        partials: &mut PartialPaths,
+        file: Handle<File>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<PartialPath>, CancellationError> {
+        let config =
+            StitcherConfig::default().with_detect_similar_paths(!self.no_similar_paths_in_file);
+        let builtins_partial_paths = self.builtins_partial_paths.get_or_compute(
+            &self.builtins,
+            config.clone(),
+            cancellation_flag,
+        )?;
+
+        let mut builtins_files = HashSet::new();
+        for builtins_file in self.builtins.iter_files() {
+            let name = self.builtins[builtins_file].name();
+            let file = match graph.get_file(name) {
+                Some(file) => file,
+                None => self
+                    .builtins
+                    .clone_file_into(builtins_file, graph)
+                    .expect("builtins file is not present"),
+            };
+            builtins_files.insert(file);
+        }
+
+        let mut db = Database::new();
+        builtins_partial_paths
+            .load_into(graph, partials, &mut db)
+            .expect("builtins partial paths refer to builtins nodes");
+        let graph = &*graph;
+        for file in graph.iter_files() {
+            if builtins_files.contains(&file) {
+                continue;
+            }
+            ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+                graph,
+                partials,
+                file,
+                config.clone(),
+                &cancellation_flag,
+                |g, ps, p| {
+                    db.add_partial_path(g, ps, p.clone());
+                },
+            )
+            .map_err(|err| CancellationError(err.0))?;
+        }
+
+        let references = graph
+            .nodes_for_file(file)
+            .filter(|node| graph[*node].is_reference())
+            .collect::<Vec<_>>();
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut DatabaseCandidates::new(graph, partials, &mut db),
+            references,
+            config,
+            &cancellation_flag,
+            |_, _, p| paths.push(p.clone()),
+        )
+        .map_err(|err| CancellationError(err.0))?;
+        Ok(paths)
+    }
+
     // Extracted from tree_sitter_loader::Loader::language_configuration_for_file_name
     fn best_for_file<'a>(
         languages: &'a Vec<LanguageConfiguration>,
@@ -144,6 +244,46 @@ impl LanguageConfiguration {
     }
 }
 
+/// The partial paths of a language's builtins, which are computed the first time they are needed.
+/// The paths are stored in their serializable form, so that they can be loaded into any graph the
+/// builtins have been added to.
+#[derive(Default)]
+pub struct BuiltinsPartialPaths {
+    paths: OnceCell<stack_graphs::serde::Database>,
+}
+
+impl BuiltinsPartialPaths {
+    fn get_or_compute(
+        &self,
+        builtins: &StackGraph,
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<&stack_graphs::serde::Database, CancellationError> {
+        self.paths.get_or_try_init(|| {
+            let mut partials = PartialPaths::new();
+            let mut db = Database::new();
+            for file in builtins.iter_files() {
+                ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+                    builtins,
+                    &mut partials,
+                    file,
+                    config.clone(),
+                    &cancellation_flag,
+                    |g, ps, p| {
+                        db.add_partial_path(g, ps, p.clone());
+                    },
+                )
+                .map_err(|err| CancellationError(err.0))?;
+            }
+            Ok(stack_graphs::serde::Database::from_database(
+                builtins,
+                &mut partials,
+                &db,
+            ))
+        })
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct FileAnalyzers {
     file_analyzers: HashMap<String, Arc<dyn FileAnalyzer + Send + Sync>>,
@@ -177,6 +317,78 @@ impl FileAnalyzers {
     pub fn get(&self, file_name: &str) -> Option<Arc<dyn FileAnalyzer + Send + Sync>> {
         self.file_analyzers.get(file_name).cloned()
     }
+
+    /// Returns the names of the files that have an analyzer.
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.file_analyzers.keys().map(String::as_str)
+    }
+}
+
+/// A cache of compiled stack graph languages, keyed on the tree-sitter language and a hash of the
+/// TSG source. Since a [`StackGraphLanguage`][] is immutable after construction, cached instances
+/// are shared between callers, and threads, via an [`Arc`][].
+///
+/// A cache can be created explicitly, or the process-wide cache returned by [`global`][Self::global]
+/// can be used.
+#[derive(Default)]
+pub struct LanguageCache {
+    languages: Mutex<Vec<(Language, u64, Arc<StackGraphLanguage>)>>,
+}
+
+impl LanguageCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the process-wide language cache.
+    pub fn global() -> &'static LanguageCache {
+        static GLOBAL: Lazy<LanguageCache> = Lazy::new(LanguageCache::new);
+        &GLOBAL
+    }
+
+    /// Returns the cached language for the given tree-sitter language and TSG source, or parses
+    /// the TSG source and caches the result if it was not loaded before. The TSG path is purely
+    /// for informational purposes, see [`StackGraphLanguage::from_source`][]. If the language is
+    /// already cached, the path of the first load is kept.
+    pub fn get_or_load(
+        &self,
+        language: Language,
+        tsg_path: PathBuf,
+        tsg_source: &str,
+    ) -> Result<Arc<StackGraphLanguage>, LanguageError> {
+        let mut hasher = DefaultHasher::new();
+        tsg_source.hash(&mut hasher);
+        let hash = hasher.finish();
+        let mut languages = self.languages.lock().unwrap();
+        if let Some((_, _, sgl)) = languages
+            .iter()
+            .find(|(l, h, _)| *l == language && *h == hash)
+        {
+            return Ok(sgl.clone());
+        }
+        let sgl = Arc::new(StackGraphLanguage::from_source(
+            language.clone(),
+            tsg_path,
+            tsg_source,
+        )?);
+        languages.push((language, hash, sgl.clone()));
+        Ok(sgl)
+    }
+
+    /// Returns the number of cached languages.
+    pub fn len(&self) -> usize {
+        self.languages.lock().unwrap().len()
+    }
+
+    /// Returns whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all cached languages. Instances that are still in use remain valid.
+    pub fn clear(&self) {
+        self.languages.lock().unwrap().clear();
+    }
 }
 
 /// A load path specifies a file to load from, either as a regular path or relative to the grammar location.
@@ -313,18 +525,6 @@ impl Loader {
         Self::load_globals_from_config(&conf, globals)
     }
 
-    fn load_tsg<'a>(
-        language: Language,
-        tsg_source: Cow<'a, str>,
-    ) -> Result<TsgFile, LoadError<'a>> {
-        let tsg = TsgFile::from_str(language, &tsg_source).map_err(|err| LoadError::TsgParse {
-            inner: err,
-            tsg_path: PathBuf::from("<unknown tsg path>"),
-            tsg: Cow::from(tsg_source),
-        })?;
-        Ok(tsg)
-    }
-
     fn load_builtins_into<'a>(
         sgl: &StackGraphLanguage,
         path: &Path,
@@ -350,10 +550,21 @@ impl Loader {
                 source,
                 tsg_path: sgl.tsg_path.to_path_buf(),
                 tsg: sgl.tsg_source.clone(),
+                tsg_includes: sgl.tsg_includes().cloned().map(Box::new),
             })?;
+        Self::mark_builtins_generated(graph, file);
         return Ok(());
     }
 
+    /// Marks all nodes of a builtins file as generated, since they do not originate from user
+    /// source code.
+    fn mark_builtins_generated(graph: &mut StackGraph, file: Handle<File>) {
+        let nodes = graph.nodes_for_file(file).collect::<Vec<_>>();
+        for node in nodes {
+            graph.set_generated(node, true);
+        }
+    }
+
     fn load_globals_from_config(
         conf: &Ini,
         globals: &mut Variables,
@@ -429,6 +640,7 @@ pub enum LoadError<'a> {
         source: Cow<'a, str>,
         tsg_path: PathBuf,
         tsg: Cow<'a, str>,
+        tsg_includes: Option<Box<TsgSource>>,
     },
     #[error("{inner}")]
     TsgParse {
@@ -436,6 +648,21 @@ pub enum LoadError<'a> {
         tsg_path: PathBuf,
         tsg: Cow<'a, str>,
     },
+    #[error("{}", tsg.error_message(inner))]
+    IncludedTsgParse {
+        inner: tree_sitter_graph::ParseError,
+        tsg: Box<TsgSource>,
+    },
+    #[error("Cannot read included TSG file {path}: {inner}")]
+    TsgInclude {
+        path: PathBuf,
+        #[source]
+        inner: std::io::Error,
+    },
+    #[error("Cannot load grammar from {0}: {1}")]
+    GrammarLibrary(PathBuf, Box<dyn std::error::Error + Send + Sync>),
+    #[error("Cyclic include of TSG file {0}")]
+    TsgIncludeCycle(PathBuf),
     #[error(transparent)]
     TreeSitter(anyhow::Error),
 }
@@ -459,10 +686,17 @@ impl std::fmt::Display for DisplayLoadErrorPretty<'_> {
                 source,
                 tsg_path,
                 tsg,
+                tsg_includes,
             } => write!(
                 f,
                 "{}",
-                inner.display_pretty(source_path, source, tsg_path, tsg)
+                inner.display_pretty_with_includes(
+                    source_path,
+                    source,
+                    tsg_path,
+                    tsg,
+                    tsg_includes.as_deref()
+                )
             )?,
             LoadError::SglParse {
                 inner,
@@ -474,12 +708,441 @@ impl std::fmt::Display for DisplayLoadErrorPretty<'_> {
                 tsg_path,
                 tsg,
             } => write!(f, "{}", inner.display_pretty(tsg_path, tsg))?,
+            LoadError::IncludedTsgParse { inner, tsg } => {
+                writeln!(f, "{}", tsg.error_message(inner))?;
+                if let Some(location) = TsgSource::error_location(inner) {
+                    let (path, source, row) = tsg.locate(location.row);
+                    write!(
+                        f,
+                        "{}",
+                        Excerpt::from_source(
+                            path,
+                            source,
+                            row,
+                            location.column..location.column + 1,
+                            0
+                        )
+                    )?;
+                }
+            }
             err => writeln!(f, "{}", err)?,
         }
         Ok(())
     }
 }
 
+// ------------------------------------------------------------------------------------------------
+// TSG includes
+
+pub(crate) static INCLUDE_DIRECTIVE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"^\s*;#include\s+"([^"]*)"\s*$"#).unwrap());
+
+/// A TSG source in which `;#include "<path>"` directives are replaced by the content of the
+/// included file. Included paths are resolved relative to the directory of the including file.
+/// Because the directive is a TSG comment, files using it are still valid TSG on their own.
+///
+/// The expanded source tracks which file every line came from, so that errors reported while
+/// parsing it refer to the file and line where the error originated. Locations in errors that
+/// are reported while executing the rules refer to the [expanded source][Self::source], unless
+/// they are displayed for a language whose [TSG source][StackGraphLanguage::set_tsg_source] is
+/// this one.
+#[derive(Clone, Debug)]
+pub struct TsgSource {
+    source: String,
+    rows: usize,
+    files: Vec<(PathBuf, String)>,
+    segments: Vec<TsgSegment>,
+}
+
+/// A run of consecutive rows in the expanded source, all coming from the same file.
+#[derive(Clone, Debug)]
+struct TsgSegment {
+    row: usize,
+    file: usize,
+    file_row: usize,
+}
+
+impl TsgSource {
+    /// Reads the TSG file at the given path, and expands the includes it contains.
+    pub fn read(path: &Path) -> Result<Self, LoadError<'static>> {
+        let source = std::fs::read_to_string(path)?;
+        Self::expand(path, source, &mut |path| std::fs::read_to_string(path))
+    }
+
+    /// Expands the includes in the given TSG source, which was read from the given path. The
+    /// `read` function is used to get the content of included files.
+    pub fn expand(
+        path: &Path,
+        source: String,
+        read: &mut dyn FnMut(&Path) -> std::io::Result<String>,
+    ) -> Result<Self, LoadError<'static>> {
+        let mut result = Self {
+            source: String::new(),
+            rows: 0,
+            files: Vec::new(),
+            segments: Vec::new(),
+        };
+        result.include(path.to_path_buf(), source, read, &mut Vec::new())?;
+        Ok(result)
+    }
+
+    fn include(
+        &mut self,
+        path: PathBuf,
+        source: String,
+        read: &mut dyn FnMut(&Path) -> std::io::Result<String>,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), LoadError<'static>> {
+        if stack.contains(&path) {
+            return Err(LoadError::TsgIncludeCycle(path));
+        }
+        let file = self.files.len();
+        self.files.push((path.clone(), source.clone()));
+        self.start_segment(file, 0);
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        stack.push(path);
+        for (file_row, line) in source.lines().enumerate() {
+            if let Some(captures) = INCLUDE_DIRECTIVE.captures(line) {
+                let included_path = dir.join(&captures[1]);
+                let included_source =
+                    read(&included_path).map_err(|inner| LoadError::TsgInclude {
+                        path: included_path.clone(),
+                        inner,
+                    })?;
+                self.include(included_path, included_source, read, stack)?;
+                self.start_segment(file, file_row + 1);
+            } else {
+                self.source.push_str(line);
+                self.source.push('\n');
+                self.rows += 1;
+            }
+        }
+        stack.pop();
+        Ok(())
+    }
+
+    fn start_segment(&mut self, file: usize, file_row: usize) {
+        if self.segments.last().is_some_and(|s| s.row == self.rows) {
+            self.segments.pop();
+        }
+        self.segments.push(TsgSegment {
+            row: self.rows,
+            file,
+            file_row,
+        });
+    }
+
+    /// Returns the path of the main file, which includes the other files.
+    pub fn path(&self) -> &Path {
+        &self.files[0].0
+    }
+
+    /// Returns the expanded source.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the path and source of the file that the given row of the expanded source came
+    /// from, together with the corresponding row in that file.
+    pub fn locate(&self, row: usize) -> (&Path, &str, usize) {
+        let segment = self
+            .segments
+            .iter()
+            .rev()
+            .find(|s| s.row <= row)
+            .expect("expanded source has no segments");
+        let (path, source) = &self.files[segment.file];
+        (path, source, segment.file_row + row - segment.row)
+    }
+
+    /// Parses the expanded source. Locations in parse errors are reported relative to the file
+    /// they occur in.
+    pub fn parse(&self, language: Language) -> Result<TsgFile, LoadError<'static>> {
+        TsgFile::from_str(language, &self.source).map_err(|inner| LoadError::IncludedTsgParse {
+            inner,
+            tsg: Box::new(self.clone()),
+        })
+    }
+
+    /// Returns the location in the expanded source of the given parse error.
+    fn error_location(error: &tree_sitter_graph::ParseError) -> Option<Location> {
+        use tree_sitter_graph::ParseError::*;
+        match error {
+            ExpectedQuantifier(location)
+            | ExpectedToken(_, location)
+            | ExpectedVariable(location)
+            | ExpectedUnscopedVariable(location)
+            | InvalidRegex(_, location)
+            | InvalidRegexCapture(location)
+            | UnexpectedCharacter(_, _, location)
+            | UnexpectedEOF(location)
+            | UnexpectedKeyword(_, location)
+            | UnexpectedLiteral(_, location)
+            | UnexpectedQueryPatterns(location) => Some(*location),
+            QueryError(err) => Some(Location {
+                row: err.row,
+                column: err.column,
+            }),
+            Check(err) => {
+                // tree-sitter-graph does not export its check error type, so the location is
+                // recovered from the message, where it is the last `at (row, column)`
+                let message = err.to_string();
+                let start = message.rfind(" at (")? + " at ".len();
+                parse_location(&message[start..]).map(|(location, _)| location)
+            }
+        }
+    }
+
+    /// Returns the message of the given parse error, with its location relative to the file it
+    /// occurs in.
+    fn error_message(&self, error: &tree_sitter_graph::ParseError) -> String {
+        let message = error.to_string();
+        let location = match Self::error_location(error) {
+            Some(location) => location,
+            None => return message,
+        };
+        let file_location = self.file_location(location);
+        // query errors do not mention their location, all other errors end with it
+        let expanded_location = format!("at {}", location);
+        match message.rfind(&expanded_location) {
+            Some(start) => format!(
+                "{}{}{}",
+                &message[..start],
+                file_location,
+                &message[start + expanded_location.len()..]
+            ),
+            None => format!("{} {}", message, file_location),
+        }
+    }
+
+    /// Returns the given location in the expanded source as `at <location> in <path>`, where the
+    /// location is relative to the file it occurs in.
+    fn file_location(&self, location: Location) -> String {
+        let (path, _, row) = self.locate(location.row);
+        format!(
+            "at {} in {}",
+            Location {
+                row,
+                column: location.column
+            },
+            path.display()
+        )
+    }
+
+    /// Returns a pretty printer for the given execution error, in the format of
+    /// [`ExecutionError::display_pretty`][tree_sitter_graph::ExecutionError::display_pretty],
+    /// but with excerpts of the TSG rules taken from the file they occur in.
+    pub(crate) fn display_execution_error<'a>(
+        &'a self,
+        error: &'a tree_sitter_graph::ExecutionError,
+        source_path: &'a Path,
+        source: &'a str,
+    ) -> impl std::fmt::Display + 'a {
+        DisplayExecutionErrorPretty {
+            tsg: self,
+            error,
+            source_path,
+            source,
+        }
+    }
+}
+
+struct DisplayExecutionErrorPretty<'a> {
+    tsg: &'a TsgSource,
+    error: &'a tree_sitter_graph::ExecutionError,
+    source_path: &'a Path,
+    source: &'a str,
+}
+
+impl std::fmt::Display for DisplayExecutionErrorPretty<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_entry(f, 0, self.error)
+    }
+}
+
+impl DisplayExecutionErrorPretty<'_> {
+    fn fmt_entry(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        index: usize,
+        error: &tree_sitter_graph::ExecutionError,
+    ) -> std::fmt::Result {
+        use tree_sitter_graph::ExecutionError;
+        match error {
+            ExecutionError::InContext(context, cause) => {
+                let message = context.to_string();
+                match StatementContext::parse_all(&message) {
+                    Some(stmts) => {
+                        for (i, stmt) in stmts.iter().enumerate() {
+                            self.fmt_statement(f, index, i == 0, stmt)?;
+                        }
+                    }
+                    None => writeln!(f, "{:>5}: {}", index, message)?,
+                }
+                self.fmt_entry(f, index + 1, cause)
+            }
+            other => writeln!(f, "{:>5}: {}", index, other),
+        }
+    }
+
+    fn fmt_statement(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        index: usize,
+        first: bool,
+        stmt: &StatementContext,
+    ) -> std::fmt::Result {
+        let statement_location = self.tsg.file_location(stmt.statement_location);
+        if first {
+            writeln!(
+                f,
+                "{:>5}: Error executing statement {} {}",
+                index, stmt.statement, statement_location
+            )?;
+        } else {
+            writeln!(
+                f,
+                "     > and executing statement {} {}",
+                stmt.statement, statement_location
+            )?;
+        }
+        self.fmt_tsg_excerpt(f, stmt.statement_location)?;
+        writeln!(f, "{}in stanza", " ".repeat(7))?;
+        self.fmt_tsg_excerpt(f, stmt.stanza_location)?;
+        writeln!(f, "{}matching ({}) node", " ".repeat(7), stmt.node_kind)?;
+        write!(
+            f,
+            "{}",
+            Excerpt::from_source(
+                self.source_path,
+                self.source,
+                stmt.source_location.row,
+                stmt.source_location.column..stmt.source_location.column + 1,
+                7
+            )
+        )
+    }
+
+    /// Writes an excerpt of the TSG file that the given location of the expanded source is in.
+    fn fmt_tsg_excerpt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        location: Location,
+    ) -> std::fmt::Result {
+        let (path, source, row) = self.tsg.locate(location.row);
+        write!(
+            f,
+            "{}",
+            Excerpt::from_source(path, source, row, location.column..location.column + 1, 7)
+        )
+    }
+}
+
+/// The statement context of an execution error. tree-sitter-graph does not export its context
+/// type, so this is recovered from the message, which has the form `Error executing <statement>
+/// at <location> in stanza at <location> matching (<kind>) node at <location>`, followed by
+/// `and executing ...` for every further statement.
+struct StatementContext<'a> {
+    statement: &'a str,
+    statement_location: Location,
+    stanza_location: Location,
+    source_location: Location,
+    node_kind: &'a str,
+}
+
+impl<'a> StatementContext<'a> {
+    fn parse_all(message: &'a str) -> Option<Vec<Self>> {
+        let mut rest = message.strip_prefix("Error executing ")?;
+        let mut result = Vec::new();
+        loop {
+            let (statement, tail) = rest.split_once(" in stanza at ")?;
+            let (statement, statement_location) = statement.rsplit_once(" at ")?;
+            let (statement_location, _) = parse_location(statement_location)?;
+            let (stanza_location, tail) = parse_location(tail)?;
+            let tail = tail.strip_prefix(" matching (")?;
+            let (node_kind, tail) = tail.split_once(") node at ")?;
+            let (source_location, tail) = parse_location(tail)?;
+            result.push(Self {
+                statement,
+                statement_location,
+                stanza_location,
+                source_location,
+                node_kind,
+            });
+            if tail.is_empty() {
+                return Some(result);
+            }
+            rest = tail.strip_prefix(" and executing ")?;
+        }
+    }
+}
+
+/// Parses a location as it is displayed by tree-sitter-graph, i.e., `(row, column)` counting
+/// from one, at the start of the given string. Returns the location and the rest of the string.
+fn parse_location(s: &str) -> Option<(Location, &str)> {
+    let (location, rest) = s.strip_prefix('(')?.split_once(')')?;
+    let (row, column) = location.split_once(", ")?;
+    let location = Location {
+        row: row.parse::<usize>().ok()?.checked_sub(1)?,
+        column: column.parse::<usize>().ok()?.checked_sub(1)?,
+    };
+    Some((location, rest))
+}
+
+// ------------------------------------------------------------------------------------------------
+// grammar libraries
+
+/// Loads a tree-sitter grammar from the shared library at `path`. The `symbol_name` is the name of
+/// the grammar's language function, which is typically `tree_sitter_` followed by the name of the
+/// language, e.g., `tree_sitter_python`. The resulting language can be used to create a
+/// [`StackGraphLanguage`][]. The library is never unloaded, because the language refers to data
+/// inside it.
+///
+/// Returns an error if the library cannot be loaded, if it does not contain the symbol, or if the
+/// grammar was generated for an ABI version that the linked tree-sitter version does not support.
+///
+/// # Safety
+///
+/// Loading a shared library runs its initialization code, and calling the language function runs
+/// arbitrary code from the library. The caller must ensure that the library is a tree-sitter
+/// grammar that can be trusted, and that `symbol_name` refers to a function with the signature
+/// `const TSLanguage *(void)`. Calling a symbol with a different signature is undefined behavior.
+///
+/// Only available with the `dynamic-grammars` feature.
+#[cfg(feature = "dynamic-grammars")]
+pub unsafe fn load_grammar_from_library(
+    path: &Path,
+    symbol_name: &str,
+) -> Result<Language, LoadError<'static>> {
+    let error = |err: Box<dyn std::error::Error + Send + Sync>| {
+        LoadError::GrammarLibrary(path.to_path_buf(), err)
+    };
+    let library = libloading::Library::new(path).map_err(|err| error(err.into()))?;
+    let language = {
+        let language_fn = library
+            .get::<libloading::Symbol<unsafe extern "C" fn() -> Language>>(symbol_name.as_bytes())
+            .map_err(|err| error(err.into()))?;
+        language_fn()
+    };
+    // the language refers to static data in the library, so it must stay loaded
+    std::mem::forget(library);
+    let version = language.version();
+    let supported_versions =
+        tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION..=tree_sitter::LANGUAGE_VERSION;
+    if !supported_versions.contains(&version) {
+        return Err(error(
+            format!(
+                "incompatible language version {}, expected {} to {}",
+                version,
+                tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+                tree_sitter::LANGUAGE_VERSION
+            )
+            .into(),
+        ));
+    }
+    Ok(language)
+}
+
 // ------------------------------------------------------------------------------------------------
 // provided languages loader
 
@@ -540,11 +1203,12 @@ impl PathLoader {
     // Adopted from tree_sitter_loader::Loader::load
     fn config_paths(config: &TsConfig) -> Result<Vec<PathBuf>, LoadError<'static>> {
         if config.parser_directories.is_empty() {
-            eprintln!("Warning: You have not configured any parser directories!");
-            eprintln!("Please run `tree-sitter init-config` and edit the resulting");
-            eprintln!("configuration file to indicate where we should look for");
-            eprintln!("language grammars.");
-            eprintln!("");
+            log::warn!(
+                "You have not configured any parser directories! \
+                 Please run `tree-sitter init-config` and edit the resulting \
+                 configuration file to indicate where we should look for \
+                 language grammars."
+            );
         }
         let mut paths = Vec::new();
         for parser_container_dir in &config.parser_directories {
@@ -589,8 +1253,7 @@ impl PathLoader {
         let index = match index {
             Some(index) => index,
             None => {
-                let tsg = self.load_tsg_from_paths(&language)?;
-                let sgl = StackGraphLanguage::new(language.language.clone(), tsg);
+                let sgl = self.load_sgl_from_paths(&language)?;
 
                 let mut builtins = StackGraph::new();
                 self.load_builtins_from_paths_into(
@@ -610,6 +1273,7 @@ impl PathLoader {
                     special_files: FileAnalyzers::new(),
                     // always detect similar paths, we don't know the language configuration when loading from the file system
                     no_similar_paths_in_file: false,
+                    builtins_partial_paths: BuiltinsPartialPaths::default(),
                 };
                 self.cache.push((language.language, lc));
 
@@ -683,19 +1347,22 @@ impl PathLoader {
         Ok(None)
     }
 
-    // Load the TSG file for the given language and path
-    fn load_tsg_from_paths(
+    // Load the TSG file for the given language and path, keeping its path and expanded source
+    fn load_sgl_from_paths(
         &self,
         language: &SupplementedLanguage,
-    ) -> Result<TsgFile, LoadError<'static>> {
+    ) -> Result<StackGraphLanguage, LoadError<'static>> {
         for tsg_path in &self.tsg_paths {
             let mut tsg_path = tsg_path.get_for_grammar(&language.root_path);
             if tsg_path.extension().is_none() {
                 tsg_path.set_extension("tsg");
             }
             if tsg_path.exists() {
-                let tsg_source = std::fs::read_to_string(tsg_path)?;
-                return Loader::load_tsg(language.language.clone(), Cow::from(tsg_source));
+                let tsg_source = TsgSource::read(&tsg_path)?;
+                let tsg = tsg_source.parse(language.language.clone())?;
+                let mut sgl = StackGraphLanguage::new(language.language.clone(), tsg);
+                sgl.set_tsg_source(tsg_source);
+                return Ok(sgl);
             }
         }
         return Err(LoadError::NoTsgFound);
@@ -892,8 +1559,9 @@ pub trait ContentProvider {
 }
 
 /// FileReader reads files from the filesystem and caches the most recently read file.
+/// Files that are not valid UTF-8 are converted lossily, see [`read_source_lossy`][].
 pub struct FileReader {
-    cache: Option<(PathBuf, String)>,
+    cache: Option<(PathBuf, String, Option<InvalidUtf8>)>,
 }
 
 impl FileReader {
@@ -902,11 +1570,21 @@ impl FileReader {
     }
 
     pub fn get(&mut self, path: &Path) -> std::io::Result<&str> {
-        if self.cache.as_ref().map_or(true, |(p, _)| p != path) {
-            let content = std::fs::read_to_string(path)?;
-            self.cache = Some((path.to_path_buf(), content));
+        self.get_with_diagnostic(path).map(|(content, _)| content)
+    }
+
+    /// Returns the content of the file, together with a diagnostic if the file was not valid
+    /// UTF-8 and had to be converted lossily.
+    pub fn get_with_diagnostic(
+        &mut self,
+        path: &Path,
+    ) -> std::io::Result<(&str, Option<&InvalidUtf8>)> {
+        if self.cache.as_ref().map_or(true, |(p, _, _)| p != path) {
+            let (content, diagnostic) = read_source_lossy(path)?;
+            self.cache = Some((path.to_path_buf(), content, diagnostic));
         }
-        Ok(&self.cache.as_ref().unwrap().1)
+        let (_, content, diagnostic) = self.cache.as_ref().unwrap();
+        Ok((content, diagnostic.as_ref()))
     }
 }
 
@@ -916,6 +1594,37 @@ impl ContentProvider for FileReader {
     }
 }
 
+/// Diagnostic recorded when source content is not valid UTF-8.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("invalid UTF-8 after byte {valid_up_to}, invalid sequences were replaced")]
+pub struct InvalidUtf8 {
+    /// The number of leading bytes that were valid UTF-8.
+    pub valid_up_to: usize,
+}
+
+/// Reads the content of a file as a string. If the file is not valid UTF-8, invalid sequences
+/// are replaced by U+FFFD REPLACEMENT CHARACTER and a diagnostic is returned. All spans computed
+/// for the file refer to the converted string.
+pub fn read_source_lossy(path: &Path) -> std::io::Result<(String, Option<InvalidUtf8>)> {
+    let bytes = std::fs::read(path)?;
+    Ok(source_from_bytes_lossy(bytes))
+}
+
+/// Converts source bytes to a string. If the bytes are not valid UTF-8, invalid sequences are
+/// replaced by U+FFFD REPLACEMENT CHARACTER and a diagnostic is returned.
+pub fn source_from_bytes_lossy(bytes: Vec<u8>) -> (String, Option<InvalidUtf8>) {
+    match String::from_utf8(bytes) {
+        Ok(content) => (content, None),
+        Err(err) => {
+            let diagnostic = InvalidUtf8 {
+                valid_up_to: err.utf8_error().valid_up_to(),
+            };
+            let content = String::from_utf8_lossy(err.as_bytes()).into_owned();
+            (content, Some(diagnostic))
+        }
+    }
+}
+
 impl ContentProvider for Option<&str> {
     fn get(&mut self, _path: &Path) -> std::io::Result<Option<&str>> {
         Ok(self.clone())
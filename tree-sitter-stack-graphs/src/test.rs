@@ -76,6 +76,7 @@ use stack_graphs::partial::PartialPaths;
 use stack_graphs::stitching::Database;
 use stack_graphs::stitching::StitcherConfig;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::path::Path;
 use std::path::PathBuf;
 use thiserror::Error;
@@ -170,7 +171,20 @@ impl Test {
         source: &str,
         default_fragment_path: &Path,
     ) -> Result<Self, TestError> {
-        let mut graph = StackGraph::new();
+        Self::from_source_with_graph(path, source, default_fragment_path, StackGraph::new())
+    }
+
+    /// Creates a test from source, like [`from_source`][Self::from_source], using the given graph
+    /// for the test. Fragments whose path matches a file that is already present in the graph reuse
+    /// that file, including any nodes and edges in it. This allows a previously built graph for the
+    /// same test source to be reused, instead of building the fragments again.
+    pub fn from_source_with_graph(
+        path: &Path,
+        source: &str,
+        default_fragment_path: &Path,
+        mut graph: StackGraph,
+    ) -> Result<Self, TestError> {
+        let mut fragment_paths = HashSet::new();
         let mut fragments = Vec::new();
         let mut have_fragments = false;
         let mut current_path = default_fragment_path.to_path_buf();
@@ -189,14 +203,13 @@ impl Test {
                 // ignored, so that the file name of the test does not interfere with
                 // the file names of the fragments
                 if have_fragments {
-                    let file = graph
-                        .add_file(&current_path.to_string_lossy())
-                        .map_err(|_| {
-                            TestError::DuplicatePath(
-                                line_files.len(),
-                                format!("{}", current_path.display()),
-                            )
-                        })?;
+                    if !fragment_paths.insert(current_path.clone()) {
+                        return Err(TestError::DuplicatePath(
+                            line_files.len(),
+                            format!("{}", current_path.display()),
+                        ));
+                    }
+                    let file = graph.get_or_create_file(&current_path.to_string_lossy());
                     (line_files.len()..current_line_number)
                         .for_each(|_| line_files.push(Some(file)));
                     fragments.push(TestFragment {
@@ -242,14 +255,13 @@ impl Test {
             prev_source.push_str("\n");
         }
         {
-            let file = graph
-                .add_file(&current_path.to_string_lossy())
-                .map_err(|_| {
-                    TestError::DuplicatePath(
-                        line_files.len(),
-                        format!("{}", current_path.display()),
-                    )
-                })?;
+            if !fragment_paths.insert(current_path.clone()) {
+                return Err(TestError::DuplicatePath(
+                    line_files.len(),
+                    format!("{}", current_path.display()),
+                ));
+            }
+            let file = graph.get_or_create_file(&current_path.to_string_lossy());
             (line_files.len()..line_count).for_each(|_| line_files.push(Some(file)));
             fragments.push(TestFragment {
                 file,
@@ -398,7 +410,7 @@ impl TestFragment {
 /// Result of running a stack graph test.
 #[derive(Debug, Clone)]
 pub struct TestResult {
-    success_count: usize,
+    successes: Vec<TestSuccess>,
     failures: Vec<TestFailure>,
 }
 
@@ -406,12 +418,12 @@ impl TestResult {
     pub fn new() -> Self {
         Self {
             failures: Vec::new(),
-            success_count: 0,
+            successes: Vec::new(),
         }
     }
 
-    fn add_success(&mut self) {
-        self.success_count += 1;
+    fn add_success(&mut self, success: TestSuccess) {
+        self.successes.push(success);
     }
 
     fn add_failure(&mut self, reason: TestFailure) {
@@ -420,7 +432,7 @@ impl TestResult {
 
     /// Number of successfull assertions.
     pub fn success_count(&self) -> usize {
-        self.success_count
+        self.successes.len()
     }
 
     /// Number of failed assertions.
@@ -428,6 +440,10 @@ impl TestResult {
         self.failures.len()
     }
 
+    pub fn successes_iter(&self) -> std::slice::Iter<'_, TestSuccess> {
+        self.successes.iter()
+    }
+
     pub fn failures_iter(&self) -> std::slice::Iter<'_, TestFailure> {
         self.failures.iter()
     }
@@ -442,10 +458,101 @@ impl TestResult {
     }
 
     pub fn absorb(&mut self, other: TestResult) {
-        self.success_count += other.success_count;
+        let mut successes = other.successes;
+        self.successes.append(&mut successes);
         let mut failures = other.failures;
         self.failures.append(&mut failures);
     }
+
+    /// Writes this result as a JUnit XML report, with a single test suite of the given name,
+    /// and a test case for every assertion.
+    pub fn write_junit_xml<W: std::io::Write>(
+        &self,
+        suite_name: &str,
+        w: &mut W,
+    ) -> std::io::Result<()> {
+        writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(w, "<testsuites>")?;
+        writeln!(
+            w,
+            r#"  <testsuite name="{}" tests="{}" failures="{}">"#,
+            xml_escape(suite_name),
+            self.count(),
+            self.failure_count()
+        )?;
+        let cases = self
+            .successes
+            .iter()
+            .map(|s| (s.path.as_path(), Some(&s.position), s.assertion, None))
+            .chain(
+                self.failures
+                    .iter()
+                    .map(|f| (f.path(), f.position(), f.assertion(), Some(f))),
+            )
+            .sorted_by_key(|(path, position, _, _)| (*path, position.cloned()));
+        for (path, position, assertion, failure) in cases {
+            let name = match position {
+                Some(position) => format!(
+                    "{}:{}:{}: {}",
+                    path.display(),
+                    position.line + 1,
+                    position.column.grapheme_offset + 1,
+                    assertion
+                ),
+                None => format!("{}: {}", path.display(), assertion),
+            };
+            let classname = xml_escape(&path.display().to_string());
+            let name = xml_escape(&name);
+            match failure {
+                None => writeln!(
+                    w,
+                    r#"    <testcase classname="{}" name="{}"/>"#,
+                    classname, name
+                )?,
+                Some(failure) => {
+                    let message = xml_escape(&failure.to_string());
+                    writeln!(
+                        w,
+                        r#"    <testcase classname="{}" name="{}">"#,
+                        classname, name
+                    )?;
+                    writeln!(
+                        w,
+                        r#"      <failure message="{}">{}</failure>"#,
+                        message, message
+                    )?;
+                    writeln!(w, "    </testcase>")?;
+                }
+            }
+        }
+        writeln!(w, "  </testsuite>")?;
+        writeln!(w, "</testsuites>")?;
+        Ok(())
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Description of a successful assertion.
+#[derive(Debug, Clone)]
+pub struct TestSuccess {
+    pub path: PathBuf,
+    pub position: Position,
+    /// The kind of assertion, one of `defined`, `defines`, or `refers`.
+    pub assertion: &'static str,
 }
 
 impl std::fmt::Display for TestResult {
@@ -494,6 +601,40 @@ pub enum TestFailure {
     Cancelled(stack_graphs::CancellationError),
 }
 
+impl TestFailure {
+    /// Returns the path of the test file in which the failing assertion appears.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::NoReferences { path, .. }
+            | Self::IncorrectResolutions { path, .. }
+            | Self::IncorrectDefinitions { path, .. }
+            | Self::IncorrectReferences { path, .. } => path,
+            Self::Cancelled(_) => Path::new(""),
+        }
+    }
+
+    /// Returns the position of the failing assertion, if it is known.
+    pub fn position(&self) -> Option<&Position> {
+        match self {
+            Self::NoReferences { position, .. }
+            | Self::IncorrectResolutions { position, .. }
+            | Self::IncorrectDefinitions { position, .. }
+            | Self::IncorrectReferences { position, .. } => Some(position),
+            Self::Cancelled(_) => None,
+        }
+    }
+
+    /// Returns the kind of the failing assertion.
+    pub fn assertion(&self) -> &'static str {
+        match self {
+            Self::NoReferences { .. } | Self::IncorrectResolutions { .. } => DEFINED,
+            Self::IncorrectDefinitions { .. } => DEFINES,
+            Self::IncorrectReferences { .. } => REFERS,
+            Self::Cancelled(_) => "cancelled",
+        }
+    }
+}
+
 impl std::fmt::Display for TestFailure {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -635,12 +776,12 @@ impl Test {
                         &self.graph,
                         partials,
                         db,
-                        stitcher_config,
+                        stitcher_config.clone(),
                         &cancellation_flag,
                     )
                     .map_or_else(|e| self.from_error(e), |v| Ok(v))
                 {
-                    Ok(_) => result.add_success(),
+                    Ok(_) => result.add_success(self.success_for(assertion)),
                     Err(f) => result.add_failure(f),
                 }
             }
@@ -648,6 +789,20 @@ impl Test {
         Ok(result)
     }
 
+    /// Construct a TestSuccess for an Assertion.
+    fn success_for(&self, assertion: &Assertion) -> TestSuccess {
+        let (source, assertion) = match assertion {
+            Assertion::Defined { source, .. } => (source, DEFINED),
+            Assertion::Defines { source, .. } => (source, DEFINES),
+            Assertion::Refers { source, .. } => (source, REFERS),
+        };
+        TestSuccess {
+            path: self.path.clone(),
+            position: source.position.clone(),
+            assertion,
+        }
+    }
+
     /// Construct a TestFailure from an AssertionError.
     fn from_error(&self, err: AssertionError) -> Result<(), TestFailure> {
         match err {
@@ -6,7 +6,10 @@
 // ------------------------------------------------------------------------------------------------
 
 use stack_graphs::graph::StackGraph;
+use stack_graphs::stats::PhaseTimings;
+use std::time::Duration;
 use tree_sitter_graph::Variables;
+use tree_sitter_stack_graphs::BuildError;
 use tree_sitter_stack_graphs::NoCancellation;
 use tree_sitter_stack_graphs::StackGraphLanguage;
 use tree_sitter_stack_graphs::FILE_PATH_VAR;
@@ -85,3 +88,662 @@ fn can_support_injected_nodes() {
         &["[test.py(1) scope] -0-> [test.py(0) exported scope]"],
     );
 }
+
+#[test]
+fn can_observe_created_nodes() {
+    let tsg = r#"
+    (module)@mod {
+      node @mod.lexical_scope
+      node @mod.definition
+      attr (@mod.definition) type = "pop_symbol", symbol = "x"
+    }
+    "#;
+    let python = "pass";
+
+    let file_name = "test.py";
+
+    let mut created_nodes = Vec::new();
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    let mut builder = language.builder_into_stack_graph(&mut graph, file, python);
+    builder.on_node_created(Box::new(|handle, _node_ref| created_nodes.push(handle)));
+
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+
+    builder
+        .build(&globals, &NoCancellation)
+        .expect("Failed to build graph");
+
+    assert_eq!(2, created_nodes.len());
+    assert_eq!(
+        graph.nodes_for_file(file).collect::<Vec<_>>(),
+        created_nodes
+    );
+}
+
+#[test]
+fn can_record_tsg_node_index_in_debug_info() {
+    let tsg = r#"
+    (module)@mod {
+      node @mod.lexical_scope
+    }
+    "#;
+    let python = "pass";
+
+    let file_name = "test.py";
+
+    let mut created_nodes = Vec::new();
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+    let node_id = graph.new_node_id(file);
+    let _preexisting_node = graph.add_scope_node(node_id, true).unwrap();
+
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    let mut builder = language.builder_into_stack_graph(&mut graph, file, python);
+    builder.record_tsg_node_index(true);
+    builder.on_node_created(Box::new(|handle, node_ref| {
+        created_nodes.push((handle, node_ref.index()))
+    }));
+
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+
+    builder
+        .build(&globals, &NoCancellation)
+        .expect("Failed to build graph");
+
+    assert_eq!(1, created_nodes.len());
+    let (handle, index) = created_nodes[0];
+    assert_ne!(index as u32, graph[handle].id().local_id());
+    let debug_info = graph.node_debug_info(handle).expect("Expected debug info");
+    let entry = debug_info
+        .iter()
+        .find(|e| &graph[e.key] == "tsg_node_index")
+        .map(|e| graph[e.value].to_string());
+    assert_eq!(Some(index.to_string()), entry);
+}
+
+#[test]
+fn can_report_unpopped_symbols() {
+    let tsg = r#"
+    (module)@mod {
+      node @mod.lexical_scope
+    }
+    (module (_)@stmt)@mod {
+      edge @stmt.lexical_scope -> @mod.lexical_scope
+    }
+    (expression_statement)@stmt {
+      node @stmt.lexical_scope
+    }
+    (expression_statement (assignment left:(identifier)@name))@stmt {
+      node def
+      attr (def) type = "pop_symbol", symbol = (source-text @name), is_definition
+      edge @stmt.lexical_scope -> def
+    }
+    (expression_statement (identifier)@name)@stmt {
+      node ref
+      attr (ref) type = "push_symbol", symbol = (source-text @name), is_reference
+      edge ref -> @stmt.lexical_scope
+    }
+    "#;
+    let python = "x = 1\nx\ny\n";
+
+    let file_name = "test.py";
+
+    let mut unpopped = Vec::new();
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    let mut builder = language.builder_into_stack_graph(&mut graph, file, python);
+    builder.on_unpopped_symbol(Box::new(|graph, node| {
+        unpopped.push(graph[graph[node].symbol().unwrap()].to_string())
+    }));
+
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+
+    builder
+        .build(&globals, &NoCancellation)
+        .expect("Failed to build graph");
+
+    assert_eq!(vec!["y".to_string()], unpopped);
+}
+
+#[test]
+fn missing_globals_are_reported_by_name() {
+    let tsg = r#"
+    global PACKAGE_NAME
+    (module)@mod {
+      node @mod.lexical_scope
+      attr (@mod.lexical_scope) debug_package = PACKAGE_NAME
+    }
+    "#;
+    let python = "pass";
+
+    let file_name = "test.py";
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    let err = language
+        .build_stack_graph_into(&mut graph, file, python, &globals, &NoCancellation)
+        .expect_err("Expected missing global to fail the build");
+    assert!(
+        matches!(&err, BuildError::MissingGlobal(name) if name == "PACKAGE_NAME"),
+        "unexpected error {}",
+        err
+    );
+}
+
+#[test]
+fn can_reject_empty_symbols() {
+    let tsg = r#"
+    (module) {
+      node ref
+      attr (ref) type = "push_symbol", symbol = "", is_reference
+    }
+    "#;
+    let python = "pass";
+
+    let file_name = "test.py";
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+    language
+        .builder_into_stack_graph(&mut graph, file, python)
+        .build(&globals, &NoCancellation)
+        .expect("Expected empty symbols to be allowed by default");
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+    let mut builder = language.builder_into_stack_graph(&mut graph, file, python);
+    builder.reject_empty_symbols(true);
+    let result = builder.build(&globals, &NoCancellation);
+    assert!(
+        matches!(result, Err(BuildError::EmptySymbol(_))),
+        "Expected empty symbol error"
+    );
+}
+
+#[test]
+fn can_collect_phase_timings() {
+    let tsg = r#"
+    (identifier) @id {
+      node ref
+      attr (ref) type = "push_symbol", symbol = (source-text @id), source_node = @id, is_reference
+    }
+    "#;
+    let python = (0..1000)
+        .map(|i| format!("def f{i}(x):\n    return x + {i}\n"))
+        .collect::<String>();
+
+    let file_name = "test.py";
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+    let mut timings = PhaseTimings::default();
+    let mut builder = language.builder_into_stack_graph(&mut graph, file, &python);
+    builder.collect_timings(&mut timings);
+    builder
+        .build(&globals, &NoCancellation)
+        .expect("Failed to build graph");
+
+    assert!(timings.parsing > Duration::ZERO);
+    assert!(timings.execution > Duration::ZERO);
+    assert_eq!(Duration::ZERO, timings.partial_paths);
+    assert_eq!(Duration::ZERO, timings.stitching);
+    assert_eq!(timings.parsing + timings.execution, timings.total());
+}
+
+#[test]
+fn can_mark_nodes_to_keep_paths() {
+    let tsg = r#"
+    (module) {
+      node kept
+      attr (kept) type = "scope", keep_path
+      node other
+      attr (other) type = "scope"
+    }
+    "#;
+    let python = "pass";
+
+    let file_name = "test.py";
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+    language
+        .build_stack_graph_into(&mut graph, file, python, &globals, &NoCancellation)
+        .expect("Failed to build graph");
+
+    let kept = graph
+        .nodes_for_file(file)
+        .filter(|node| graph.keeps_paths(*node))
+        .count();
+    assert_eq!(1, kept);
+    assert!(graph.has_kept_nodes());
+}
+
+#[test]
+fn can_read_definition_site_spans() {
+    let tsg = r#"
+    (function_definition name: (identifier) @id) @func {
+      node def
+      attr (def) type = "pop_symbol", symbol = (source-text @id), source_node = @func, is_definition
+      attr (def) selection_node = @id
+    }
+    (call function: (identifier) @id) {
+      node ref
+      attr (ref) type = "push_symbol", symbol = (source-text @id), source_node = @id, is_reference
+    }
+    "#;
+    let python = "def foo():\n  pass\nfoo()\n";
+
+    let file_name = "test.py";
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+    language
+        .build_stack_graph_into(&mut graph, file, python, &globals, &NoCancellation)
+        .expect("Failed to build graph");
+
+    let lines_and_columns = |span: &lsp_positions::Span| {
+        (
+            (span.start.line, span.start.column.utf8_offset),
+            (span.end.line, span.end.column.utf8_offset),
+        )
+    };
+
+    let def = graph
+        .nodes_for_file(file)
+        .find(|node| graph[*node].is_definition())
+        .expect("Expected definition node");
+    let site = graph
+        .definition_site(def)
+        .expect("Expected definition site");
+    assert_eq!(((0, 0), (1, 6)), lines_and_columns(&site.span));
+    assert_eq!(((0, 4), (0, 7)), lines_and_columns(&site.selection_span));
+
+    // without a selection node, the selection span is the full span
+    let reference = graph
+        .nodes_for_file(file)
+        .find(|node| graph[*node].is_reference())
+        .expect("Expected reference node");
+    let site = graph
+        .definition_site(reference)
+        .expect("Expected definition site");
+    assert_eq!(((2, 0), (2, 3)), lines_and_columns(&site.span));
+    assert_eq!(site.span, site.selection_span);
+}
+
+#[test]
+fn can_create_language_from_grammar_constants() {
+    let tsg = r#"
+    (module) {
+      node n
+    }
+    "#;
+    let language: tree_sitter::Language = tree_sitter_python::LANGUAGE.into();
+
+    let from_str = StackGraphLanguage::from_grammar_str(tree_sitter_python::LANGUAGE, tsg).unwrap();
+    let from_value = StackGraphLanguage::from_grammar_str(language.clone(), tsg).unwrap();
+    let from_source = StackGraphLanguage::from_grammar_source(
+        tree_sitter_python::LANGUAGE,
+        "test.tsg".into(),
+        tsg,
+    )
+    .unwrap();
+    let tsg_file = tree_sitter_graph::ast::File::from_str(language.clone(), tsg).unwrap();
+    let from_file = StackGraphLanguage::from_grammar(tree_sitter_python::LANGUAGE, tsg_file);
+
+    for sgl in [&from_str, &from_value, &from_source, &from_file] {
+        assert_eq!(&language, sgl.language());
+    }
+    assert_eq!(std::path::Path::new("test.tsg"), from_source.tsg_path());
+}
+
+#[test]
+fn can_override_file_path() {
+    let tsg = r#"
+    global FILE_PATH
+    (module) {
+      node def
+      attr (def) type = "pop_symbol", symbol = (path-filestem FILE_PATH), is_definition
+    }
+    "#;
+    let python = "pass";
+
+    let file_name = "storage/1234.py";
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+
+    let build = |file_path: Option<&str>| {
+        let mut graph = StackGraph::new();
+        let file = graph.get_or_create_file(file_name);
+        let mut builder = language.builder_into_stack_graph(&mut graph, file, python);
+        if let Some(file_path) = file_path {
+            builder.set_file_path(file_path.to_string());
+        }
+        let globals = Variables::new();
+        builder
+            .build(&globals, &NoCancellation)
+            .expect("Failed to build graph");
+        (graph, file)
+    };
+
+    // by default, the file name is used
+    let (graph, file) = build(None);
+    check_stack_graph_nodes(&graph, file, &["[storage/1234.py(0) definition 1234]"]);
+    // the overridden path determines the module name, but not the file name
+    let (graph, file) = build(Some("src/mymodule.py"));
+    check_stack_graph_nodes(&graph, file, &["[storage/1234.py(0) definition mymodule]"]);
+}
+
+#[test]
+fn can_deduplicate_edges() {
+    let tsg = r#"
+    global EXT_NODE
+    global ROOT_NODE
+    (module) {
+      edge ROOT_NODE -> EXT_NODE
+      attr (ROOT_NODE -> EXT_NODE) precedence = 1
+    }
+    "#;
+    let python = "pass";
+
+    let file_name = "test.py";
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+
+    let build = |deduplicate: bool| {
+        let mut graph = StackGraph::new();
+        let file = graph.get_or_create_file(file_name);
+        let node_id = graph.new_node_id(file);
+        let preexisting_node = graph.add_scope_node(node_id, true).unwrap();
+        graph.add_edge(StackGraph::root_node(), preexisting_node, 0);
+
+        let mut builder = language.builder_into_stack_graph(&mut graph, file, python);
+        builder.deduplicate_edges(deduplicate);
+        let mut globals = Variables::new();
+        globals
+            .add(FILE_PATH_VAR.into(), file_name.into())
+            .expect("failed to add file path variable");
+        globals
+            .add("EXT_NODE".into(), builder.inject_node(node_id).into())
+            .expect("Failed to add EXT_NODE variable");
+        builder
+            .build(&globals, &NoCancellation)
+            .expect("Failed to build graph");
+        graph
+    };
+
+    // by default, the existing edge is kept
+    check_stack_graph_edges(&build(false), &["[root] -0-> [test.py(0) exported scope]"]);
+    // with deduplication, the duplicate edge's higher precedence is kept
+    check_stack_graph_edges(&build(true), &["[root] -1-> [test.py(0) exported scope]"]);
+}
+
+#[test]
+fn can_clamp_edge_precedences() {
+    let tsg = r#"
+    global ROOT_NODE
+    (module) {
+      node low
+      node high
+      edge ROOT_NODE -> low
+      attr (ROOT_NODE -> low) precedence = "-17"
+      edge ROOT_NODE -> high
+      attr (ROOT_NODE -> high) precedence = 17
+    }
+    "#;
+    let python = "pass";
+
+    let file_name = "test.py";
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+    let mut builder = language.builder_into_stack_graph(&mut graph, file, python);
+    builder.clamp_precedence(-1..=1);
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+    builder
+        .build(&globals, &NoCancellation)
+        .expect("Failed to build graph");
+
+    check_stack_graph_edges(
+        &graph,
+        &[
+            "[root] --1-> [test.py(0) scope]",
+            "[root] -1-> [test.py(1) scope]",
+        ],
+    );
+}
+
+#[test]
+fn can_load_unknown_node_types_as_scopes() {
+    let tsg = r#"
+    (module) {
+      node n
+      attr (n) type = "future_node"
+    }
+    "#;
+    let python = "pass";
+
+    let file_name = "test.py";
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+
+    // by default, unknown node types are rejected
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+    let builder = language.builder_into_stack_graph(&mut graph, file, python);
+    match builder.build(&globals, &NoCancellation) {
+        Err(BuildError::UnknownNodeType(node_type)) => assert_eq!("future_node", node_type),
+        result => panic!("Expected unknown node type error, got {:?}", result),
+    }
+
+    // in lenient mode, they are loaded as scopes and reported
+    let mut unknown = Vec::new();
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+    let mut builder = language.builder_into_stack_graph(&mut graph, file, python);
+    builder.on_unknown_node_type(Box::new(|graph, node, node_type| {
+        unknown.push((
+            graph[node].display(graph).to_string(),
+            node_type.to_string(),
+        ))
+    }));
+    builder
+        .build(&globals, &NoCancellation)
+        .expect("Failed to build graph");
+    assert_eq!(
+        vec![("[test.py(0) scope]".to_string(), "future_node".to_string())],
+        unknown
+    );
+    check_stack_graph_nodes(&graph, file, &["[test.py(0) scope]"]);
+}
+
+#[test]
+fn can_rebuild_with_edited_tree() {
+    let tsg = r#"
+    (function_definition name: (identifier) @id) @func {
+      node def
+      attr (def) type = "pop_symbol", symbol = (source-text @id), source_node = @func, is_definition
+    }
+    (call function: (identifier) @id) {
+      node ref
+      attr (ref) type = "push_symbol", symbol = (source-text @id), source_node = @id, is_reference
+    }
+    "#;
+    let old_python = "def foo():\n  pass\nfoo()\n";
+    let new_python = "x = 1\ndef foo():\n  pass\nfoo()\n";
+
+    let file_name = "test.py";
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+
+    let nodes_and_spans = |graph: &StackGraph| {
+        graph
+            .iter_nodes()
+            .filter_map(|node| {
+                let site = graph.definition_site(node)?;
+                Some((
+                    graph[node].display(graph).to_string(),
+                    (site.span.start.line, site.span.end.line),
+                ))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut tree = None;
+    let mut old_graph = StackGraph::new();
+    let file = old_graph.get_or_create_file(file_name);
+    let mut builder = language.builder_into_stack_graph(&mut old_graph, file, old_python);
+    builder.save_tree(&mut tree);
+    builder
+        .build(&globals, &NoCancellation)
+        .expect("Failed to build graph");
+    let mut tree = tree.expect("Expected saved tree");
+    assert_eq!(
+        vec![
+            ("[test.py(0) definition foo]".to_string(), (0, 1)),
+            ("[test.py(1) reference foo]".to_string(), (2, 2)),
+        ],
+        nodes_and_spans(&old_graph)
+    );
+
+    tree.edit(&tree_sitter::InputEdit {
+        start_byte: 0,
+        old_end_byte: 0,
+        new_end_byte: 6,
+        start_position: tree_sitter::Point::new(0, 0),
+        old_end_position: tree_sitter::Point::new(0, 0),
+        new_end_position: tree_sitter::Point::new(1, 0),
+    });
+    let mut incremental_graph = StackGraph::new();
+    let file = incremental_graph.get_or_create_file(file_name);
+    let mut builder = language.builder_into_stack_graph(&mut incremental_graph, file, new_python);
+    builder.set_old_tree(&tree);
+    builder
+        .build(&globals, &NoCancellation)
+        .expect("Failed to build graph");
+
+    let mut full_graph = StackGraph::new();
+    let file = full_graph.get_or_create_file(file_name);
+    language
+        .build_stack_graph_into(&mut full_graph, file, new_python, &globals, &NoCancellation)
+        .expect("Failed to build graph");
+
+    assert_eq!(
+        vec![
+            ("[test.py(0) definition foo]".to_string(), (1, 2)),
+            ("[test.py(1) reference foo]".to_string(), (3, 3)),
+        ],
+        nodes_and_spans(&incremental_graph)
+    );
+    assert_eq!(
+        nodes_and_spans(&full_graph),
+        nodes_and_spans(&incremental_graph)
+    );
+}
+
+thread_local! {
+    static LOGGED: std::cell::RefCell<Vec<String>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+struct ThreadLocalLogger;
+
+impl log::Log for ThreadLocalLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        LOGGED.with(|logged| logged.borrow_mut().push(record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: ThreadLocalLogger = ThreadLocalLogger;
+
+#[test]
+fn can_control_diagnostics_with_log_level() {
+    let tsg = r#"
+    (module) {
+      node n
+      attr (n) type = "scope", unknown = "x"
+    }
+    "#;
+    let python = "pass";
+
+    let file_name = "test.py";
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    let globals = Variables::new();
+    let build = || {
+        let mut graph = StackGraph::new();
+        let file = graph.get_or_create_file(file_name);
+        language
+            .build_stack_graph_into(&mut graph, file, python, &globals, &NoCancellation)
+            .expect("Failed to build graph");
+        LOGGED.with(|logged| logged.take())
+    };
+
+    // the logger is global, but only records messages of the current thread, so that tests
+    // running concurrently do not interfere
+    log::set_logger(&LOGGER).expect("Failed to set logger");
+
+    log::set_max_level(log::LevelFilter::Off);
+    assert!(build().is_empty());
+
+    log::set_max_level(log::LevelFilter::Warn);
+    assert_eq!(
+        vec!["Unexpected attribute unknown on node of type scope".to_string()],
+        build()
+    );
+
+    // the same unexpected attribute is reported only once
+    assert!(build().is_empty());
+}
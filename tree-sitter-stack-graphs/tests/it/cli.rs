@@ -0,0 +1,232 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2026, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use stack_graphs::graph::StackGraph;
+use tree_sitter::Language;
+use tree_sitter_stack_graphs::cli::test::TestArgs;
+use tree_sitter_stack_graphs::cli::visualize::VisualizeArgs;
+use tree_sitter_stack_graphs::loader::FileAnalyzers;
+use tree_sitter_stack_graphs::loader::LanguageConfiguration;
+use tree_sitter_stack_graphs::loader::Loader;
+use tree_sitter_stack_graphs::StackGraphLanguage;
+
+static TSG: &str = r#"
+  global ROOT_NODE
+
+  (module) @mod {
+    node @mod.scope
+  }
+
+  (module (_) @stmt) @mod {
+    edge @stmt.scope -> @mod.scope
+  }
+
+  (expression_statement) @stmt {
+    node @stmt.scope
+  }
+
+  (expression_statement (assignment left:(identifier) @name)) @stmt {
+    node def
+    attr (def) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+    edge @stmt.scope -> def
+  }
+
+  (expression_statement (identifier) @name) @stmt {
+    node ref
+    attr (ref) type = "push_symbol", symbol = (source-text @name), source_node = @name, is_reference
+    edge ref -> @stmt.scope
+  }
+"#;
+
+fn python_loader() -> Loader {
+    python_loader_with_tsg(TSG)
+}
+
+fn python_loader_with_tsg(tsg: &str) -> Loader {
+    Loader::from_language_configurations(vec![python_language_configuration(tsg)], None)
+        .expect("Expected loader to succeed")
+}
+
+fn python_language_configuration(tsg: &str) -> LanguageConfiguration {
+    let language: Language = tree_sitter_python::LANGUAGE.into();
+    let sgl = StackGraphLanguage::from_str(language.clone(), tsg).unwrap();
+    LanguageConfiguration {
+        language,
+        scope: Some("source.py".into()),
+        content_regex: None,
+        file_types: vec!["py".into()],
+        sgl,
+        builtins: StackGraph::new(),
+        special_files: FileAnalyzers::new(),
+        no_similar_paths_in_file: false,
+        builtins_partial_paths: Default::default(),
+    }
+}
+
+#[test]
+fn can_visualize_file_from_source() {
+    let loader = python_loader();
+
+    let dir = std::env::temp_dir().join(format!("visualize-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("Expected creating directory to succeed");
+    let source_path = dir.join("test.py");
+    std::fs::write(&source_path, "x = 1\nx\n").expect("Expected writing fixture to succeed");
+    let output = dir.join("out.html");
+
+    VisualizeArgs {
+        source_paths: vec![source_path],
+        output: output.clone(),
+        from_source: true,
+        stdin: false,
+        path: None,
+    }
+    .run_from_source(loader)
+    .expect("Expected visualization to succeed");
+
+    let html = std::fs::read_to_string(&output).expect("Expected reading output to succeed");
+    assert!(html.contains("<html"));
+    assert!(html.contains("test.py"));
+
+    std::fs::remove_dir_all(&dir).expect("Expected removing directory to succeed");
+}
+
+#[test]
+fn can_visualize_source_from_stdin() {
+    let loader = python_loader();
+
+    let dir = std::env::temp_dir().join(format!("visualize-stdin-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("Expected creating directory to succeed");
+    let output = dir.join("out.html");
+
+    let mut stdin = "x = 1\nx\n".as_bytes();
+    VisualizeArgs {
+        source_paths: vec![],
+        output: output.clone(),
+        from_source: true,
+        stdin: true,
+        path: Some("virtual/stdin_module.py".into()),
+    }
+    .run_from_source_with_stdin(loader, &mut stdin)
+    .expect("Expected visualization to succeed");
+
+    let html = std::fs::read_to_string(&output).expect("Expected reading output to succeed");
+    assert!(html.contains("virtual/stdin_module.py"));
+    assert!(html.contains("definition"));
+
+    std::fs::remove_dir_all(&dir).expect("Expected removing directory to succeed");
+}
+
+#[test]
+fn can_reuse_cached_test_graphs() {
+    let tsg = r#"
+      (assignment left:(identifier) @name) {
+        node def
+        attr (def) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+      }
+    "#;
+
+    let dir = std::env::temp_dir().join(format!("graph-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Expected creating directory to succeed");
+    let test_path = dir.join("test.py");
+    std::fs::write(&test_path, "xyz = 1\n# ^ defines: xyz\n")
+        .expect("Expected writing fixture to succeed");
+    let cache_dir = dir.join("cache");
+    let run_with = |lc: LanguageConfiguration| {
+        let mut args = TestArgs::new(vec![test_path.clone()]);
+        args.quiet = true;
+        args.graph_cache = Some(cache_dir.clone());
+        args.run(Loader::from_language_configurations(vec![lc], None).unwrap())
+    };
+    let run = |tsg: &str| run_with(python_language_configuration(tsg));
+    let cache_entries = || {
+        std::fs::read_dir(&cache_dir)
+            .expect("Expected reading cache directory to succeed")
+            .map(|e| e.unwrap().path())
+            .collect::<Vec<_>>()
+    };
+
+    run(tsg).expect("Expected test to succeed");
+    let entries = cache_entries();
+    assert_eq!(1, entries.len());
+
+    // replace the cached graph with one without any nodes, which fails the assertion if reused
+    let mut empty = StackGraph::new();
+    empty.get_or_create_file("test.py");
+    let json = serde_json::to_string(&empty.to_serializable()).unwrap();
+    std::fs::write(&entries[0], json).expect("Expected writing cache entry to succeed");
+    assert!(run(tsg).is_err());
+    assert_eq!(1, cache_entries().len());
+
+    // changing the rules invalidates the cached graph
+    let changed_tsg = format!("{}\n; changed\n", tsg);
+    run(&changed_tsg).expect("Expected test to succeed");
+    assert_eq!(2, cache_entries().len());
+
+    // so do changing the language scope and the builtins
+    let mut lc = python_language_configuration(tsg);
+    lc.scope = Some("source.python".into());
+    run_with(lc).expect("Expected test to succeed");
+    assert_eq!(3, cache_entries().len());
+    let mut lc = python_language_configuration(tsg);
+    lc.builtins.get_or_create_file("builtins.py");
+    run_with(lc).expect("Expected test to succeed");
+    assert_eq!(4, cache_entries().len());
+
+    std::fs::remove_dir_all(&dir).expect("Expected removing directory to succeed");
+}
+
+#[test]
+fn does_not_cache_test_graphs_without_tsg_source() {
+    let tsg = r#"
+      (assignment left:(identifier) @name) {
+        node def
+        attr (def) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+      }
+    "#;
+
+    let dir = std::env::temp_dir().join(format!("graph-cache-no-source-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Expected creating directory to succeed");
+    let test_path = dir.join("test.py");
+    std::fs::write(&test_path, "xyz = 1\n# ^ defines: xyz\n")
+        .expect("Expected writing fixture to succeed");
+    let cache_dir = dir.join("cache");
+    std::fs::create_dir_all(&cache_dir).expect("Expected creating directory to succeed");
+
+    // a language created from a parsed TSG file does not know its source
+    let language: Language = tree_sitter_python::LANGUAGE.into();
+    let tsg = tree_sitter_graph::ast::File::from_str(language.clone(), tsg).unwrap();
+    let sgl = StackGraphLanguage::new(language.clone(), tsg);
+    assert!(sgl.tsg_source().is_empty());
+    let lc = LanguageConfiguration {
+        language,
+        scope: Some("source.py".into()),
+        content_regex: None,
+        file_types: vec!["py".into()],
+        sgl,
+        builtins: StackGraph::new(),
+        special_files: FileAnalyzers::new(),
+        no_similar_paths_in_file: false,
+        builtins_partial_paths: Default::default(),
+    };
+    let loader =
+        Loader::from_language_configurations(vec![lc], None).expect("Expected loader to succeed");
+
+    let mut args = TestArgs::new(vec![test_path.clone()]);
+    args.quiet = true;
+    args.graph_cache = Some(cache_dir.clone());
+    args.run(loader).expect("Expected test to succeed");
+    assert_eq!(
+        0,
+        std::fs::read_dir(&cache_dir)
+            .expect("Expected reading cache directory to succeed")
+            .count()
+    );
+
+    std::fs::remove_dir_all(&dir).expect("Expected removing directory to succeed");
+}
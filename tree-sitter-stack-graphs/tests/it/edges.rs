@@ -7,7 +7,13 @@
 
 use pretty_assertions::assert_eq;
 use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::unshadowed_paths;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
 use std::collections::BTreeSet;
+use tree_sitter_stack_graphs::BuildError;
 
 use super::build_stack_graph;
 
@@ -83,6 +89,73 @@ fn can_create_edges_with_precedence() {
     );
 }
 
+#[test]
+fn can_create_edges_with_negative_precedence() {
+    let tsg = r#"
+      (identifier) @id {
+         node source
+         attr (source) type = "pop_symbol", symbol = (source-text @id), is_definition
+         node sink
+         attr (sink) type = "push_symbol", symbol = (source-text @id), is_reference
+         edge source -> sink
+         attr (source -> sink) precedence = "-3"
+      }
+    "#;
+    let python = "a";
+    build_and_check_stack_graph_edges(
+        python,
+        tsg,
+        &[
+            "[test.py(0) definition a] --3-> [test.py(1) reference a]", //
+        ],
+    );
+}
+
+#[test]
+fn cannot_create_edges_with_out_of_range_precedence() {
+    let tsg = r#"
+      (identifier) {
+         node source
+         node sink
+         edge source -> sink
+         attr (source -> sink) precedence = 4294967295
+      }
+    "#;
+    let python = "a";
+    match build_stack_graph(python, tsg) {
+        Err(BuildError::InvalidPrecedence(precedence)) => assert_eq!("4294967295", precedence),
+        Err(err) => panic!("Expected invalid precedence error, got {}", err),
+        Ok(_) => panic!("Expected invalid precedence error"),
+    }
+}
+
+#[test]
+fn can_create_edges_with_kind() {
+    let tsg = r#"
+      (identifier) @id {
+         node source
+         attr (source) type = "pop_symbol", symbol = (source-text @id), is_definition
+         node sink
+         attr (sink) type = "push_symbol", symbol = (source-text @id), is_reference
+         node other
+         edge source -> sink
+         attr (source -> sink) edge_kind = 3
+         edge source -> other
+      }
+    "#;
+    let python = "a";
+    let (graph, _) = build_stack_graph(python, tsg).expect("Could not load stack graph");
+    let source = graph
+        .iter_nodes()
+        .find(|n| graph[*n].is_definition())
+        .unwrap();
+    let kinds = graph
+        .outgoing_edges(source)
+        .map(|edge| graph.edge_kind(edge.source, edge.sink))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![Some(3), Some(0)], kinds);
+}
+
 #[test]
 fn can_create_edges_to_singleton_nodes() {
     let tsg = r#"
@@ -119,3 +192,127 @@ fn can_create_edges_to_singleton_nodes() {
         ],
     );
 }
+
+#[test]
+fn can_inherit_edge_precedence_from_sink_node() {
+    let tsg = r#"
+      (identifier) @id {
+         node source
+         attr (source) type = "push_symbol", symbol = (source-text @id), is_reference
+         node sink
+         attr (sink) type = "pop_symbol", symbol = (source-text @id), is_definition
+         attr (sink) precedence = 3
+         node other
+         edge source -> sink
+         edge other -> sink
+         attr (other -> sink) precedence = 5
+      }
+    "#;
+    let python = "a";
+    build_and_check_stack_graph_edges(
+        python,
+        tsg,
+        &[
+            "[test.py(0) reference a] -3-> [test.py(1) definition a]",
+            "[test.py(2) scope] -5-> [test.py(1) definition a]",
+        ],
+    );
+}
+
+#[test]
+fn definitions_with_higher_node_precedence_win() {
+    let tsg = r#"
+      (module) @mod {
+         node @mod.scope
+      }
+
+      (module (function_definition name:(identifier) @name)) @mod {
+         node def
+         attr (def) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+         edge @mod.scope -> def
+      }
+
+      (module (expression_statement (assignment left:(identifier) @name))) @mod {
+         node def
+         attr (def) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+         attr (def) precedence = 1
+         edge @mod.scope -> def
+      }
+
+      (module (expression_statement (identifier) @name)) @mod {
+         node ref
+         attr (ref) type = "push_symbol", symbol = (source-text @name), source_node = @name, is_reference
+         edge ref -> @mod.scope
+      }
+    "#;
+    let python = "def x():\n  pass\nx = 1\nx\n";
+
+    let (graph, _) = build_stack_graph(python, tsg).expect("Could not load stack graph");
+    let references = graph
+        .iter_nodes()
+        .filter(|n| graph[*n].is_reference())
+        .collect::<Vec<_>>();
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        references,
+        StitcherConfig::default(),
+        &stack_graphs::NoCancellation,
+        |_, _, path| paths.push(path.clone()),
+    )
+    .expect("should never be cancelled");
+    assert_eq!(2, paths.len());
+
+    let lines = unshadowed_paths(&graph, &mut partials, &paths)
+        .iter()
+        .map(|path| graph.source_info(path.end_node).unwrap().span.start.line)
+        .collect::<Vec<_>>();
+    assert_eq!(vec![2], lines);
+}
+
+#[test]
+fn can_resolve_references_in_comments() {
+    let tsg = r#"
+      global ROOT_NODE
+
+      (class_definition name:(identifier) @name) @class {
+         node def
+         attr (def) type = "pop_symbol", symbol = (source-text @name), source_node = @class, is_definition
+         edge ROOT_NODE -> def
+      }
+
+      (comment) @comment {
+         node ref
+         attr (ref) type = "push_symbol", symbol = (replace (source-text @comment) "^#\\s*@see\\s+" ""), source_node = @comment, is_reference
+         edge ref -> ROOT_NODE
+      }
+    "#;
+    let python = "class Foo:\n  pass\n\n# @see Foo\n";
+
+    let (graph, _) = build_stack_graph(python, tsg).expect("Could not load stack graph");
+    let references = graph
+        .iter_nodes()
+        .filter(|n| graph[*n].is_reference())
+        .collect::<Vec<_>>();
+    assert_eq!(1, references.len());
+    let reference_span = &graph.source_info(references[0]).unwrap().span;
+    assert_eq!(3, reference_span.start.line);
+    assert_eq!(10, reference_span.end.column.utf8_offset);
+
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        references,
+        StitcherConfig::default(),
+        &stack_graphs::NoCancellation,
+        |_, _, path| paths.push(path.clone()),
+    )
+    .expect("should never be cancelled");
+    let definitions = paths
+        .iter()
+        .map(|path| graph[path.end_node].display(&graph).to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["[test.py(0) definition Foo]"], definitions);
+}
@@ -0,0 +1,125 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2026, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use pretty_assertions::assert_eq;
+use tree_sitter_stack_graphs::functions::string::dedent;
+use tree_sitter_stack_graphs::functions::string::url_decode;
+use tree_sitter_stack_graphs::functions::string::url_encode;
+
+use crate::build_stack_graph;
+
+#[test]
+fn can_dedent_multi_line_strings() {
+    assert_eq!(dedent("    foo\n      bar\n    baz"), "foo\n  bar\nbaz");
+    assert_eq!(dedent("\n\tfoo\n\n\t\tbar\n"), "\nfoo\n\n\tbar\n");
+    assert_eq!(dedent("  foo\n    \n  bar"), "foo\n\nbar");
+    assert_eq!(dedent("foo\n  bar"), "foo\n  bar");
+    assert_eq!(dedent(" \t foo\n \tbar"), " foo\nbar");
+}
+
+#[test]
+fn can_trim_and_dedent_source_text() {
+    let python = r#"
+def f():
+    """
+    Summary.
+
+      Details.
+    """
+"#;
+    let tsg = r#"
+      (function_definition name:(_)@name body:(block . (expression_statement (string (string_content)@doc)))) {
+        node def
+        attr (def) type = "pop_symbol", symbol = (source-text @name), is_definition
+        attr (def) debug_doc = (trim (dedent (source-text @doc)))
+        attr (def) debug_trimmed = (trim "  \n foo \t\n ")
+      }
+    "#;
+    let (graph, file) = build_stack_graph(python, tsg).unwrap();
+    let def = graph
+        .nodes_for_file(file)
+        .find(|n| graph[*n].is_definition())
+        .expect("Expected a definition");
+    let debug_info = graph.node_debug_info(def).expect("Expected debug info");
+    let entry = |key: &str| {
+        debug_info
+            .iter()
+            .find(|e| &graph[e.key] == key)
+            .map(|e| graph[e.value].to_string())
+    };
+    assert_eq!(entry("doc"), Some("Summary.\n\n  Details.".to_string()));
+    assert_eq!(entry("trimmed"), Some("foo".to_string()));
+}
+
+#[test]
+fn can_url_decode_and_encode() {
+    assert_eq!(url_decode("my%20module"), "my module");
+    assert_eq!(url_decode("caf%C3%A9%2fx"), "café/x");
+    assert_eq!(url_decode("100%"), "100%");
+    assert_eq!(url_decode("%zz%4"), "%zz%4");
+    assert_eq!(url_encode("my module/café~"), "my%20module%2Fcaf%C3%A9~");
+    for text in ["", "plain", "with space", "a/b?c=d&e", "ünïcödé", "%20"] {
+        assert_eq!(url_decode(&url_encode(text)), text);
+    }
+}
+
+#[test]
+fn can_use_url_functions_in_tsg() {
+    let python = "pass";
+    let tsg = r#"
+      (module) {
+        node n
+        attr (n) debug_decoded = (url-decode "src%2Fmy%20pkg")
+        attr (n) debug_encoded = (url-encode "src/my pkg")
+      }
+    "#;
+    let (graph, file) = build_stack_graph(python, tsg).unwrap();
+    let node = graph.nodes_for_file(file).next().expect("Expected a node");
+    let debug_info = graph.node_debug_info(node).expect("Expected debug info");
+    let entry = |key: &str| {
+        debug_info
+            .iter()
+            .find(|e| &graph[e.key] == key)
+            .map(|e| graph[e.value].to_string())
+    };
+    assert_eq!(entry("decoded"), Some("src/my pkg".to_string()));
+    assert_eq!(entry("encoded"), Some("src%2Fmy%20pkg".to_string()));
+}
+
+#[test]
+fn path_functions_propagate_null() {
+    let python = "pass";
+    let tsg = r#"
+      global FILE_PATH
+
+      (module) {
+        node n
+        ; (path-dir "test.py") is "", which has no parent
+        attr (n) debug_dir = (path-dir (path-dir FILE_PATH))
+        attr (n) debug_filename = (path-filename (path-dir (path-dir FILE_PATH)))
+        attr (n) debug_joined = (path-join "src" (path-fileext "src") "mod")
+        attr (n) debug_normalized = (path-normalize (path-join (path-dir FILE_PATH) (path-filestem FILE_PATH)))
+        attr (n) debug_split = (path-split (path-fileext FILE_PATH))
+        attr (n) debug_unsplit = (path-split (path-fileext "dir"))
+      }
+    "#;
+    let (graph, file) = build_stack_graph(python, tsg).unwrap();
+    let node = graph.nodes_for_file(file).next().expect("Expected a node");
+    let debug_info = graph.node_debug_info(node).expect("Expected debug info");
+    let entry = |key: &str| {
+        debug_info
+            .iter()
+            .find(|e| &graph[e.key] == key)
+            .map(|e| graph[e.value].to_string())
+    };
+    assert_eq!(entry("dir"), Some("#null".to_string()));
+    assert_eq!(entry("filename"), Some("#null".to_string()));
+    assert_eq!(entry("joined"), Some("#null".to_string()));
+    assert_eq!(entry("normalized"), Some("test".to_string()));
+    assert_eq!(entry("split"), Some("[py]".to_string()));
+    assert_eq!(entry("unsplit"), Some("#null".to_string()));
+}
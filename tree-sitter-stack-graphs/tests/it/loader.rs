@@ -8,13 +8,28 @@
 use once_cell::sync::Lazy;
 use pretty_assertions::assert_eq;
 use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use std::collections::HashMap;
+use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tree_sitter::Language;
+use tree_sitter_graph::Variables;
+#[cfg(feature = "dynamic-grammars")]
+use tree_sitter_stack_graphs::loader::load_grammar_from_library;
 use tree_sitter_stack_graphs::loader::FileAnalyzers;
+use tree_sitter_stack_graphs::loader::FileReader;
+use tree_sitter_stack_graphs::loader::InvalidUtf8;
+use tree_sitter_stack_graphs::loader::LanguageCache;
 use tree_sitter_stack_graphs::loader::LanguageConfiguration;
+use tree_sitter_stack_graphs::loader::LoadError;
 use tree_sitter_stack_graphs::loader::Loader;
+use tree_sitter_stack_graphs::loader::TsgSource;
+use tree_sitter_stack_graphs::LanguageError;
 use tree_sitter_stack_graphs::NoCancellation;
 use tree_sitter_stack_graphs::StackGraphLanguage;
+#[cfg(feature = "dynamic-grammars")]
+use tree_sitter_stack_graphs::FILE_PATH_VAR;
 
 static PATH: Lazy<PathBuf> = Lazy::new(|| PathBuf::from("test.py"));
 static TSG: Lazy<String> = Lazy::new(|| {
@@ -37,6 +52,7 @@ fn can_load_from_provided_language_configuration() {
         builtins: StackGraph::new(),
         special_files: FileAnalyzers::new(),
         no_similar_paths_in_file: false,
+        builtins_partial_paths: Default::default(),
     };
     let mut loader =
         Loader::from_language_configurations(vec![lc], None).expect("Expected loader to succeed");
@@ -51,3 +67,336 @@ fn can_load_from_provided_language_configuration() {
         .expect("Expected loading stack graph language to succeed");
     assert_eq!(lc.primary.map(|lc| &lc.language), Some(&language));
 }
+
+#[test]
+fn can_index_invalid_utf8_source_lossily() {
+    // `x = "caf\xe9"` in Latin-1, which is not valid UTF-8
+    let fixture = b"x = \"caf\xe9\"\n";
+    let path = std::env::temp_dir().join(format!("invalid-utf8-{}.py", std::process::id()));
+    std::fs::write(&path, fixture).expect("Expected writing fixture to succeed");
+
+    let mut file_reader = FileReader::new();
+    let (source, diagnostic) = file_reader
+        .get_with_diagnostic(&path)
+        .expect("Expected reading fixture to succeed");
+    assert_eq!(diagnostic, Some(&InvalidUtf8 { valid_up_to: 8 }));
+    assert_eq!(source, "x = \"caf\u{FFFD}\"\n");
+
+    let tsg = r#"
+      (string) @str {
+        node n
+        attr (n) type = "pop_symbol", symbol = (source-text @str), source_node = @str, is_definition
+      }
+    "#;
+    let sgl = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    sgl.build_stack_graph_into(&mut graph, file, source, &Variables::new(), &NoCancellation)
+        .expect("Expected indexing to succeed");
+    let definition = graph
+        .nodes_for_file(file)
+        .find(|n| graph[*n].is_definition())
+        .expect("Expected a definition");
+    let span = &graph.source_info(definition).unwrap().span;
+    assert_eq!(
+        &source[span.start.column.utf8_offset..span.end.column.utf8_offset],
+        "\"caf\u{FFFD}\""
+    );
+
+    std::fs::remove_file(&path).expect("Expected removing fixture to succeed");
+}
+
+#[test]
+fn can_reuse_cached_languages() {
+    let language: Language = tree_sitter_python::LANGUAGE.into();
+    let cache = LanguageCache::new();
+    assert!(cache.is_empty());
+
+    let first = cache
+        .get_or_load(language.clone(), PathBuf::from("first.tsg"), &TSG)
+        .expect("Expected loading language to succeed");
+    let second = cache
+        .get_or_load(language.clone(), PathBuf::from("second.tsg"), &TSG)
+        .expect("Expected loading language to succeed");
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(second.tsg_path(), PathBuf::from("first.tsg"));
+    assert_eq!(cache.len(), 1);
+
+    let other = cache
+        .get_or_load(
+            language.clone(),
+            PathBuf::from("other.tsg"),
+            "(module) {}\n(identifier) {}",
+        )
+        .expect("Expected loading language to succeed");
+    assert!(!Arc::ptr_eq(&first, &other));
+    assert_eq!(cache.len(), 2);
+
+    let shared = std::thread::spawn(move || {
+        LanguageCache::global()
+            .get_or_load(language, PathBuf::from("global.tsg"), &TSG)
+            .expect("Expected loading language to succeed")
+    })
+    .join()
+    .unwrap();
+    assert!(Arc::ptr_eq(
+        &shared,
+        &LanguageCache::global()
+            .get_or_load(
+                tree_sitter_python::LANGUAGE.into(),
+                PathBuf::from("global.tsg"),
+                &TSG,
+            )
+            .unwrap()
+    ));
+}
+
+#[test]
+fn can_resolve_references_to_builtins() {
+    let tsg = r#"
+      global ROOT_NODE
+
+      (expression_statement (assignment left:(identifier) @name)) {
+        node def
+        attr (def) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+        edge ROOT_NODE -> def
+      }
+
+      (expression_statement (identifier) @name) {
+        node ref
+        attr (ref) type = "push_symbol", symbol = (source-text @name), source_node = @name, is_reference
+        edge ref -> ROOT_NODE
+      }
+    "#;
+    let lc = LanguageConfiguration::from_sources(
+        tree_sitter_python::LANGUAGE.into(),
+        Some("source.py".into()),
+        None,
+        vec!["py".into()],
+        PathBuf::from("test.tsg"),
+        tsg,
+        Some((PathBuf::from("builtins.py"), "print = None\n")),
+        None,
+        &NoCancellation,
+    )
+    .expect("Expected loading language configuration to succeed");
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    lc.sgl
+        .build_stack_graph_into(
+            &mut graph,
+            file,
+            "print\nx\n",
+            &Variables::new(),
+            &NoCancellation,
+        )
+        .expect("Expected building stack graph to succeed");
+    let other_file = graph.get_or_create_file("other.py");
+    lc.sgl
+        .build_stack_graph_into(
+            &mut graph,
+            other_file,
+            "x = 1\n",
+            &Variables::new(),
+            &NoCancellation,
+        )
+        .expect("Expected building stack graph to succeed");
+
+    let mut partials = PartialPaths::new();
+    for _ in 0..2 {
+        let mut paths = lc
+            .resolve_references_in_file(&mut graph, &mut partials, file, &NoCancellation)
+            .expect("Expected resolution to succeed");
+        paths.sort_by_key(|p| graph.file_name_for_node(p.end_node));
+        assert_eq!(2, paths.len());
+        assert!(paths
+            .iter()
+            .all(|p| graph.file_name_for_node(p.start_node) == Some("test.py")));
+        assert_eq!(
+            Some("<builtins>"),
+            graph.file_name_for_node(paths[0].end_node)
+        );
+        assert!(!graph.is_generated(paths[0].start_node));
+        assert!(graph.is_generated(paths[0].end_node));
+        assert_eq!(
+            Some("other.py"),
+            graph.file_name_for_node(paths[1].end_node)
+        );
+        assert_eq!(3, graph.iter_files().count());
+    }
+}
+
+#[test]
+fn can_report_errors_in_included_tsg_files() {
+    let main_tsg = r#"
+global ROOT_NODE
+;#include "rules/module.tsg"
+(identifier) {}
+"#;
+    let module_tsg = r#"(module) {
+  edge ROOT_NODE -> UNDEFINED_NODE
+}
+"#;
+    let mut files = HashMap::new();
+    files.insert(PathBuf::from("queries/rules/module.tsg"), module_tsg);
+    let mut read = |path: &Path| {
+        files
+            .get(path)
+            .map(|source| source.to_string())
+            .ok_or_else(|| std::io::ErrorKind::NotFound.into())
+    };
+
+    let tsg = TsgSource::expand(
+        Path::new("queries/stack-graphs.tsg"),
+        main_tsg.to_string(),
+        &mut read,
+    )
+    .expect("Expected includes to be expanded");
+    assert_eq!(
+        "\nglobal ROOT_NODE\n(module) {\n  edge ROOT_NODE -> UNDEFINED_NODE\n}\n(identifier) {}\n",
+        tsg.source()
+    );
+    assert_eq!(Path::new("queries/rules/module.tsg"), tsg.locate(3).0);
+    assert_eq!(1, tsg.locate(3).2);
+    assert_eq!(Path::new("queries/stack-graphs.tsg"), tsg.locate(5).0);
+    assert_eq!(3, tsg.locate(5).2);
+
+    let err = match tsg.parse(tree_sitter_python::LANGUAGE.into()) {
+        Ok(_) => panic!("Expected parse error"),
+        Err(err) => err,
+    };
+    assert!(matches!(err, LoadError::IncludedTsgParse { .. }));
+    assert_eq!(
+        "Undefined variable UNDEFINED_NODE at (2, 21) in queries/rules/module.tsg",
+        err.to_string()
+    );
+    let pretty = err.display_pretty().to_string();
+    assert!(pretty.contains("queries/rules/module.tsg"), "{}", pretty);
+    assert!(
+        pretty.contains("edge ROOT_NODE -> UNDEFINED_NODE"),
+        "{}",
+        pretty
+    );
+}
+
+#[test]
+fn can_report_syntax_errors_in_included_tsg_files() {
+    let main_tsg = "global ROOT_NODE\n;#include \"module.tsg\"\n";
+    let mut read = |_: &Path| Ok("(module) {\n  edge ROOT_NODE -> !\n}\n".to_string());
+    let tsg = TsgSource::expand(Path::new("main.tsg"), main_tsg.to_string(), &mut read)
+        .expect("Expected includes to be expanded");
+    let err = match tsg.parse(tree_sitter_python::LANGUAGE.into()) {
+        Ok(_) => panic!("Expected parse error"),
+        Err(err) => err,
+    };
+    assert_eq!(
+        "Unexpected character '!' in expression at (2, 21) in module.tsg",
+        err.to_string()
+    );
+}
+
+#[test]
+fn can_report_execution_errors_in_included_tsg_files() {
+    let main_tsg = "(identifier) {}\n\n;#include \"module.tsg\"\n";
+    let mut read =
+        |_: &Path| Ok("(module) {\n  node n\n  attr (n) x = 1\n  attr (n) x = 2\n}\n".to_string());
+    let tsg = TsgSource::expand(Path::new("main.tsg"), main_tsg.to_string(), &mut read)
+        .expect("Expected includes to be expanded");
+    let file = tsg
+        .parse(tree_sitter_python::LANGUAGE.into())
+        .expect("Expected parsing to succeed");
+    let mut sgl = StackGraphLanguage::new(tree_sitter_python::LANGUAGE.into(), file);
+    sgl.set_tsg_source(tsg);
+
+    let source = "x = 1\n";
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let err = sgl
+        .build_stack_graph_into(&mut graph, file, source, &Variables::new(), &NoCancellation)
+        .expect_err("Expected building stack graph to fail");
+    let pretty = err
+        .display_pretty_for_language(Path::new("test.py"), source, &sgl)
+        .to_string();
+    assert!(
+        pretty.contains("attr (n) x = 2 at (4, 3) in module.tsg"),
+        "{}",
+        pretty
+    );
+    assert!(pretty.contains("module.tsg:4:3:"), "{}", pretty);
+    assert!(pretty.contains("4 |   attr (n) x = 2"), "{}", pretty);
+    assert!(pretty.contains("module.tsg:1:1:"), "{}", pretty);
+    assert!(!pretty.contains("main.tsg"), "{}", pretty);
+    assert!(!pretty.contains("(6, 3)"), "{}", pretty);
+}
+
+#[test]
+fn cannot_load_tsg_with_includes_from_str() {
+    let language: Language = tree_sitter_python::LANGUAGE.into();
+    let tsg = "global ROOT_NODE\n;#include \"module.tsg\"\n";
+    let err = match StackGraphLanguage::from_str(language.clone(), tsg) {
+        Ok(_) => panic!("Expected include to be rejected"),
+        Err(err) => err,
+    };
+    assert!(matches!(err, LanguageError::UnsupportedInclude(2)));
+
+    let result = LanguageConfiguration::from_sources(
+        language,
+        None,
+        None,
+        vec!["py".into()],
+        PathBuf::from("test.tsg"),
+        tsg,
+        None,
+        None,
+        &NoCancellation,
+    );
+    assert!(matches!(
+        result,
+        Err(LoadError::SglParse {
+            inner: LanguageError::UnsupportedInclude(2),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn cannot_include_tsg_files_cyclically() {
+    let mut read = |_: &Path| Ok(";#include \"main.tsg\"\n".to_string());
+    let result = TsgSource::expand(
+        Path::new("main.tsg"),
+        ";#include \"main.tsg\"\n".to_string(),
+        &mut read,
+    );
+    assert!(matches!(result, Err(LoadError::TsgIncludeCycle(_))));
+}
+
+#[cfg(feature = "dynamic-grammars")]
+#[test]
+fn cannot_load_grammar_from_missing_library() {
+    let result =
+        unsafe { load_grammar_from_library(Path::new("missing/libgrammar.so"), "tree_sitter_x") };
+    assert!(matches!(result, Err(LoadError::GrammarLibrary(..))));
+}
+
+/// Requires a shared library build of the Python grammar, whose path must be given in the
+/// `TREE_SITTER_PYTHON_LIBRARY` environment variable.
+#[cfg(feature = "dynamic-grammars")]
+#[test]
+#[ignore]
+fn can_load_grammar_from_library() {
+    let path = std::env::var("TREE_SITTER_PYTHON_LIBRARY")
+        .expect("TREE_SITTER_PYTHON_LIBRARY must be set to a Python grammar library");
+    let language = unsafe { load_grammar_from_library(Path::new(&path), "tree_sitter_python") }
+        .expect("Expected grammar to load");
+    let sgl = StackGraphLanguage::from_str(language, &TSG).unwrap();
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), "test.py".into())
+        .expect("failed to add file path variable");
+    sgl.build_stack_graph_into(&mut graph, file, "pass", &globals, &NoCancellation)
+        .expect("Expected graph to build");
+}
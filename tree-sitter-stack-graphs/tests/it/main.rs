@@ -17,7 +17,10 @@ use tree_sitter_stack_graphs::StackGraphLanguage;
 use tree_sitter_stack_graphs::FILE_PATH_VAR;
 
 mod builder;
+#[cfg(feature = "cli")]
+mod cli;
 mod edges;
+mod functions;
 mod loader;
 mod nodes;
 mod test;
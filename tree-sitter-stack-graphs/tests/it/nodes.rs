@@ -9,7 +9,10 @@ use pretty_assertions::assert_eq;
 use stack_graphs::arena::Handle;
 use stack_graphs::graph::File;
 use stack_graphs::graph::StackGraph;
+use tree_sitter_graph::Variables;
 use tree_sitter_stack_graphs::BuildError;
+use tree_sitter_stack_graphs::NoCancellation;
+use tree_sitter_stack_graphs::StackGraphLanguage;
 
 use super::build_stack_graph;
 
@@ -215,6 +218,64 @@ fn can_create_push_scoped_node() {
     );
 }
 
+#[test]
+fn can_create_push_scoped_symbol_node_with_scope_in_other_file() {
+    let builtins_tsg = r#"
+      (module) {
+         node scope
+         attr (scope) is_exported
+      }
+    "#;
+    let tsg = r#"
+      (identifier) @id {
+         node result
+         attr (result) type = "push_scoped_symbol", symbol = (source-text @id), scope = ["builtins.py", 0]
+      }
+    "#;
+    let python = "a";
+
+    let mut graph = StackGraph::new();
+    let globals = Variables::new();
+    let builtins = graph.get_or_create_file("builtins.py");
+    StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), builtins_tsg)
+        .unwrap()
+        .build_stack_graph_into(&mut graph, builtins, "pass", &globals, &NoCancellation)
+        .expect("Could not load builtins stack graph");
+    let file = graph.get_or_create_file("test.py");
+    StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg)
+        .unwrap()
+        .build_stack_graph_into(&mut graph, file, python, &globals, &NoCancellation)
+        .expect("Could not load stack graph");
+
+    check_stack_graph_nodes(&graph, file, &["[test.py(0) push scoped a builtins.py(0)]"]);
+}
+
+#[test]
+fn cannot_create_push_scoped_symbol_node_with_scope_in_unknown_file() {
+    let tsg = r#"
+      (identifier) @id {
+         node result
+         attr (result) type = "push_scoped_symbol", symbol = (source-text @id), scope = ["builtins.py", 0]
+      }
+    "#;
+    let python = "a";
+    let result = build_stack_graph(python, tsg);
+    assert!(matches!(result, Err(BuildError::UnknownFile(_))));
+}
+
+#[test]
+fn cannot_create_push_scoped_symbol_node_with_unknown_scope_in_other_file() {
+    let tsg = r#"
+      (identifier) @id {
+         node result
+         attr (result) type = "push_scoped_symbol", symbol = (source-text @id), scope = ["test.py", 42]
+      }
+    "#;
+    let python = "a";
+    let result = build_stack_graph(python, tsg);
+    assert!(matches!(result, Err(BuildError::UnknownSymbolScope(_, _))));
+}
+
 #[test]
 fn cannot_create_push_scoped_symbol_node_without_symbol() {
     let tsg = r#"
@@ -333,6 +394,105 @@ fn can_set_null_definiens() {
     assert_eq!(lsp_positions::Span::default(), source_info.definiens_span)
 }
 
+#[test]
+fn can_set_definition_rank() {
+    let tsg = r#"
+      (function_definition name:(_)@name) {
+         node result
+         attr (result) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+         attr (result) definition_rank = 2
+      }
+    "#;
+    let python = r#"
+      def foo():
+        pass
+    "#;
+
+    let (graph, file) = build_stack_graph(python, tsg).unwrap();
+    let node_handle = graph.nodes_for_file(file).next().unwrap();
+    assert_eq!(2, graph.definition_rank(node_handle));
+}
+
+#[test]
+fn can_set_negative_definition_rank() {
+    let tsg = r#"
+      (function_definition name:(_)@name) {
+         node result
+         attr (result) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+         attr (result) definition_rank = "-1"
+      }
+    "#;
+    let python = r#"
+      def foo():
+        pass
+    "#;
+
+    let (graph, file) = build_stack_graph(python, tsg).unwrap();
+    let node_handle = graph.nodes_for_file(file).next().unwrap();
+    assert_eq!(-1, graph.definition_rank(node_handle));
+}
+
+#[test]
+fn cannot_set_out_of_range_definition_rank() {
+    let tsg = r#"
+      (function_definition name:(_)@name) {
+         node result
+         attr (result) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+         attr (result) definition_rank = 4294967295
+      }
+    "#;
+    let python = r#"
+      def foo():
+        pass
+    "#;
+
+    match build_stack_graph(python, tsg) {
+        Err(BuildError::InvalidDefinitionRank(rank)) => assert_eq!("4294967295", rank),
+        Err(err) => panic!("Expected invalid definition rank error, got {}", err),
+        Ok(_) => panic!("Expected invalid definition rank error"),
+    }
+}
+
+#[test]
+fn can_mark_generated_nodes() {
+    let tsg = r#"
+      (module) {
+         node builtin
+         attr (builtin) type = "pop_symbol", symbol = "print", is_definition, is_generated
+         node user
+         attr (user) type = "pop_symbol", symbol = "print", is_definition
+      }
+    "#;
+    let python = "pass";
+
+    let (graph, file) = build_stack_graph(python, tsg).unwrap();
+    let generated = graph
+        .nodes_for_file(file)
+        .map(|n| graph.is_generated(n))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![true, false], generated);
+}
+
+#[test]
+fn can_create_wildcard_pop_symbol_node() {
+    let tsg = r#"
+      (module) {
+         node wildcard
+         attr (wildcard) type = "pop_symbol", is_wildcard
+         node star
+         attr (star) type = "pop_symbol", symbol = "*"
+      }
+    "#;
+    let python = "pass";
+
+    let (graph, file) = build_stack_graph(python, tsg).unwrap();
+    let wildcards = graph
+        .nodes_for_file(file)
+        .map(|n| graph.is_wildcard_symbol(graph[n].symbol().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![true, false], wildcards);
+}
+
 #[test]
 fn can_set_syntax_type() {
     let tsg = r#"
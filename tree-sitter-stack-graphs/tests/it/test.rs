@@ -19,6 +19,7 @@ use std::path::Path;
 use std::path::PathBuf;
 use tree_sitter_graph::Variables;
 use tree_sitter_stack_graphs::test::Test;
+use tree_sitter_stack_graphs::test::TestResult;
 use tree_sitter_stack_graphs::BuildError;
 use tree_sitter_stack_graphs::NoCancellation;
 use tree_sitter_stack_graphs::StackGraphLanguage;
@@ -84,7 +85,7 @@ fn check_test(
     tsg_source: &str,
     expected_successes: usize,
     expected_failures: usize,
-) {
+) -> TestResult {
     let mut test =
         Test::from_source(python_path, python_source, python_path).expect("Could not parse test");
     let assertion_count: usize = test.fragments.iter().map(|f| f.assertions.len()).sum();
@@ -156,6 +157,7 @@ fn check_test(
         expected_failures,
         results.failure_count()
     );
+    results
 }
 
 #[test]
@@ -345,3 +347,30 @@ fn test_cannot_set_global_before_first_fragment() {
         panic!("Parsing test unexpectedly succeeded.");
     }
 }
+
+#[test]
+fn can_write_junit_xml_report() {
+    let python = r#"
+      x = 1;
+        x;
+      # ^ defined: 2
+        y;
+      # ^ refers: x
+    "#;
+    let results = check_test(&PATH, python, &TSG, 1, 1);
+    let mut report = Vec::new();
+    results
+        .write_junit_xml("tests", &mut report)
+        .expect("writing to a vector should not fail");
+    let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="tests" tests="2" failures="1">
+    <testcase classname="test.py" name="test.py:3:9: defined"/>
+    <testcase classname="test.py" name="test.py:5:9: refers">
+      <failure message="test.py:5:9: references missing expected x found unexpected y">test.py:5:9: references missing expected x found unexpected y</failure>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#;
+    assert_eq!(expected, String::from_utf8(report).unwrap());
+}